@@ -1,4 +1,10 @@
-use std::io::{Read, Result};
+use std::fs::File;
+use std::io::{Error, ErrorKind, Read, Result};
+use std::path::Path;
+
+/// Largest accepted `ChunkParams::max`. Readers cap a stored chunk at 2 MiB,
+/// so leave headroom for the AEAD tag and codec framing.
+pub const MAX_CHUNK_LIMIT: usize = 2 * 1024 * 1024 - 4096;
 
 #[derive(Clone, Copy, Debug)]
 pub struct ChunkParams {
@@ -6,7 +12,7 @@ pub struct ChunkParams {
     pub min: usize,
     /// Target/average size (power-of-two-ish, e.g. 256 KiB)
     pub avg: usize,
-    /// Maximum chunk size (must be > avg)
+    /// Maximum chunk size (must be >= avg)
     pub max: usize,
 }
 
@@ -21,6 +27,34 @@ impl Default for ChunkParams {
     }
 }
 
+impl ChunkParams {
+    /// Derive bounds from a target average, FastCDC-style: min = avg/4, max = avg*4,
+    /// with max capped at `MAX_CHUNK_LIMIT` (but never below avg).
+    pub fn from_avg(avg: usize) -> Self {
+        Self {
+            min: avg / 4,
+            avg,
+            max: avg.saturating_mul(4).min(MAX_CHUNK_LIMIT).max(avg),
+        }
+    }
+
+    /// Check `64 <= min <= avg <= max <= MAX_CHUNK_LIMIT`.
+    pub fn validate(&self) -> Result<()> {
+        if self.min < 64 || self.min > self.avg || self.avg > self.max || self.max > MAX_CHUNK_LIMIT
+        {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "invalid chunk sizes min={} avg={} max={} \
+                     (need 64 <= min <= avg <= max <= {MAX_CHUNK_LIMIT})",
+                    self.min, self.avg, self.max
+                ),
+            ));
+        }
+        Ok(())
+    }
+}
+
 /// Streaming CDC chunker using a Gear rolling hash.
 /// Keeps a small stash buffer to avoid over-reading beyond a boundary.
 pub struct StreamingChunker {
@@ -38,7 +72,10 @@ pub struct StreamingChunker {
 impl StreamingChunker {
     pub fn new(p: ChunkParams) -> Self {
         assert!(p.min >= 64, "min too small");
-        assert!(p.max > p.avg && p.avg >= p.min, "min <= avg < max required");
+        assert!(
+            p.max >= p.avg && p.avg >= p.min,
+            "min <= avg <= max required"
+        );
         let k = (usize::BITS - (p.avg.saturating_sub(1)).leading_zeros()) as u32 - 1;
         let mask = (1u64 << k).saturating_sub(1);
         Self {
@@ -211,3 +248,37 @@ const fn build_full_gear() -> [u64; 256] {
     }
     t
 }
+
+/// One cut found by [`chunk_boundaries`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Boundary {
+    /// Offset just past the chunk: the sum of this and all earlier chunk lengths.
+    pub end: u64,
+    /// blake3 of the chunk, as the chunk table would record it.
+    pub blake3: [u8; 32],
+}
+
+/// Run [`StreamingChunker`] over the file at `path` and return where it cuts,
+/// for tuning `params` or feeding an external dedup index. Nothing is packed.
+/// The whole file is chunked as one stream; `pack` restarts the chunker at
+/// each data run of a sparse file, so its cuts can differ there.
+pub fn chunk_boundaries(path: &Path, params: ChunkParams) -> Result<Vec<Boundary>> {
+    params.validate()?;
+    let mut f = File::open(path)?;
+    let mut chunker = StreamingChunker::new(params);
+    let mut buf = Vec::with_capacity(params.max);
+    let mut cuts = Vec::new();
+    let mut end = 0u64;
+    loop {
+        let n = chunker.next_chunk(&mut f, &mut buf)?;
+        if n == 0 {
+            break;
+        }
+        end += n as u64;
+        cuts.push(Boundary {
+            end,
+            blake3: *blake3::hash(&buf[..n]).as_bytes(),
+        });
+    }
+    Ok(cuts)
+}
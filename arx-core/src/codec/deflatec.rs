@@ -0,0 +1,29 @@
+use super::{CodecId, Compressor};
+use crate::error::Result;
+use flate2::Compression;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use std::io::{Read, Write};
+
+/// Raw DEFLATE (RFC 1951, no zlib/gzip header). A chunk payload can be
+/// inflated by stock zlib with `windowBits = -15`.
+pub struct DeflateCompressor;
+
+impl Compressor for DeflateCompressor {
+    fn id(&self) -> CodecId {
+        CodecId::Deflate
+    }
+
+    fn compress(&self, src: &mut dyn Read, dst: &mut dyn Write, level: i32) -> Result<u64> {
+        let mut enc = DeflateEncoder::new(dst, Compression::new(level.clamp(1, 9) as u32));
+        let written_uncompressed = std::io::copy(src, &mut enc)?;
+        enc.finish()?;
+        Ok(written_uncompressed)
+    }
+
+    fn decompress(&self, src: &mut dyn Read, dst: &mut dyn Write) -> Result<u64> {
+        let mut dec = DeflateDecoder::new(src);
+        let written_uncompressed = std::io::copy(&mut dec, dst)?;
+        Ok(written_uncompressed)
+    }
+}
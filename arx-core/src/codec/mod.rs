@@ -7,6 +7,59 @@ use std::io::{Read, Write};
 pub enum CodecId {
     Store = 0,
     Zstd = 1,
+    /// Zstd against the dictionary stored in the archive's manifest.
+    ZstdDict = 2,
+    Deflate = 3,
+}
+
+impl CodecId {
+    /// Map an on-disk codec byte back to its id.
+    pub fn from_u8(x: u8) -> Option<Self> {
+        match x {
+            0 => Some(CodecId::Store),
+            1 => Some(CodecId::Zstd),
+            2 => Some(CodecId::ZstdDict),
+            3 => Some(CodecId::Deflate),
+            _ => None,
+        }
+    }
+
+    /// Lower-case name, as the CLI prints it.
+    pub const fn name(self) -> &'static str {
+        match self {
+            CodecId::Store => "store",
+            CodecId::Zstd => "zstd",
+            CodecId::ZstdDict => "zstd+dict",
+            CodecId::Deflate => "deflate",
+        }
+    }
+
+    /// Codec `pack` tries when none is requested: Zstd, or Store in builds
+    /// without the `zstd` feature.
+    pub const fn default_for_pack() -> Self {
+        if cfg!(feature = "zstd") {
+            CodecId::Zstd
+        } else {
+            CodecId::Store
+        }
+    }
+}
+
+/// How `pack` chooses each chunk's codec. Whatever wins, a chunk that does not
+/// shrink by `min_gain` is stored as is.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CodecStrategy {
+    /// Try this one codec.
+    Fixed(CodecId),
+    /// Try every listed codec and keep the smallest output; slower to pack,
+    /// smaller archives on mixed content.
+    BestOf(Vec<CodecId>),
+}
+
+impl Default for CodecStrategy {
+    fn default() -> Self {
+        CodecStrategy::Fixed(CodecId::default_for_pack())
+    }
 }
 
 pub trait Compressor: Send + Sync {
@@ -15,13 +68,103 @@ pub trait Compressor: Send + Sync {
     fn decompress(&self, src: &mut dyn Read, dst: &mut dyn Write) -> Result<u64>;
 }
 
+pub mod deflatec;
 pub mod store;
+#[cfg(feature = "zstd")]
 pub mod zstdc;
 
 pub fn get_decoder_u8(codec: u8) -> Result<&'static dyn Compressor> {
     match codec {
         val if val == CodecId::Store as u8 => Ok(&store::Store),
+        #[cfg(feature = "zstd")]
         val if val == CodecId::Zstd as u8 => Ok(&zstdc::ZstdCompressor),
-        _ => Err(std::io::Error::new(std::io::ErrorKind::Other, "unknown codec id").into()),
+        #[cfg(not(feature = "zstd"))]
+        val if val == CodecId::Zstd as u8 => Err(zstd_unavailable()),
+        val if val == CodecId::Deflate as u8 => Ok(&deflatec::DeflateCompressor),
+        val if val == CodecId::ZstdDict as u8 => Err(crate::error::ArxError::Format(
+            "zstd+dict codec needs the archive's dictionary; see decompress_u8".into(),
+        )),
+        other => Err(crate::error::ArxError::UnsupportedCodec(other)),
     }
 }
+
+/// Compress `src` as codec `codec`. `ZstdDict` compresses against `dict`.
+pub fn compress_u8(codec: u8, dict: Option<&[u8]>, src: &[u8], level: i32) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(src.len());
+    compress_to(codec, dict, &mut &src[..], &mut out, level)?;
+    Ok(out)
+}
+
+/// Streaming form of [`compress_u8`]: compress `src` into `dst` as codec
+/// `codec`; returns the uncompressed bytes read.
+pub fn compress_to(
+    codec: u8,
+    dict: Option<&[u8]>,
+    src: &mut dyn Read,
+    dst: &mut dyn Write,
+    level: i32,
+) -> Result<u64> {
+    if codec != CodecId::ZstdDict as u8 {
+        return get_decoder_u8(codec)?.compress(src, dst, level);
+    }
+    let dict = dict.ok_or_else(|| {
+        crate::error::ArxError::Format("zstd+dict codec without a dictionary".into())
+    })?;
+    #[cfg(feature = "zstd")]
+    {
+        zstdc::compress_with_dict(src, dst, level, dict)
+    }
+    #[cfg(not(feature = "zstd"))]
+    {
+        let _ = (src, dst, level, dict);
+        Err(zstd_unavailable())
+    }
+}
+
+/// Decompress `src` of codec `codec` into `dst`; returns the bytes written.
+/// `ZstdDict` data needs the dictionary of the archive it came from.
+pub fn decompress_u8(
+    codec: u8,
+    dict: Option<&[u8]>,
+    src: &mut dyn Read,
+    dst: &mut dyn Write,
+) -> Result<u64> {
+    if codec != CodecId::ZstdDict as u8 {
+        return get_decoder_u8(codec)?.decompress(src, dst);
+    }
+    let dict = dict.ok_or_else(|| {
+        crate::error::ArxError::Format(
+            "zstd+dict chunk, but the archive carries no zstd dictionary".into(),
+        )
+    })?;
+    #[cfg(feature = "zstd")]
+    {
+        zstdc::decompress_with_dict(src, dst, dict)
+    }
+    #[cfg(not(feature = "zstd"))]
+    {
+        let _ = dict;
+        Err(zstd_unavailable())
+    }
+}
+
+/// Content size `src`'s headers declare, for codecs whose frames record it
+/// (zstd, see `zstdc::declared_content_size`); 0 for the rest. Lets a reader
+/// refuse an oversized chunk before decoding any of it.
+pub fn declared_size(codec: u8, src: &[u8]) -> u64 {
+    #[cfg(feature = "zstd")]
+    {
+        if codec == CodecId::Zstd as u8 || codec == CodecId::ZstdDict as u8 {
+            return zstdc::declared_content_size(src);
+        }
+    }
+    let _ = (codec, src);
+    0
+}
+
+#[cfg(not(feature = "zstd"))]
+fn zstd_unavailable() -> crate::error::ArxError {
+    crate::error::ArxError::Format(
+        "zstd codec unavailable: arx-core was built without the `zstd` feature".into(),
+    )
+}
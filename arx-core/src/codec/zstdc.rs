@@ -2,6 +2,12 @@ use super::{CodecId, Compressor};
 use crate::error::Result;
 use std::io::{Read, Write};
 
+/// Largest window (as a log2) a zstd frame may ask the decoder for: 8 MiB.
+/// Chunks are at most `MAX_CHUNK_SIZE` and arx writes them at level 3 (a
+/// 2 MiB window); the library default of 128 MiB would let a crafted frame
+/// make every decode allocate that much.
+pub const MAX_WINDOW_LOG: u32 = 23;
+
 pub struct ZstdCompressor;
 
 impl Compressor for ZstdCompressor {
@@ -22,7 +28,53 @@ impl Compressor for ZstdCompressor {
 
     fn decompress(&self, src: &mut dyn Read, dst: &mut dyn Write) -> Result<u64> {
         let mut dec = zstd::stream::Decoder::new(src)?;
+        dec.window_log_max(MAX_WINDOW_LOG)?;
         let written_uncompressed = std::io::copy(&mut dec, dst)?;
         Ok(written_uncompressed)
     }
 }
+
+/// Zstd-compress `src` into `dst` against a dictionary.
+pub fn compress_with_dict(
+    src: &mut dyn Read,
+    dst: &mut dyn Write,
+    level: i32,
+    dict: &[u8],
+) -> Result<u64> {
+    let mut w = zstd::stream::Encoder::with_dictionary(dst, level.max(1), dict)?.auto_finish();
+    Ok(std::io::copy(src, &mut w)?)
+}
+
+/// Inverse of [`compress_with_dict`]; needs the same dictionary.
+pub fn decompress_with_dict(src: &mut dyn Read, dst: &mut dyn Write, dict: &[u8]) -> Result<u64> {
+    let mut dec = zstd::stream::Decoder::with_dictionary(std::io::BufReader::new(src), dict)?;
+    dec.window_log_max(MAX_WINDOW_LOG)?;
+    Ok(std::io::copy(&mut dec, dst)?)
+}
+
+/// Content size the frames of `src` declare in their headers, summed. Frames
+/// that leave it out count as 0, and the scan stops at the first frame that
+/// does not parse, so this is a lower bound on what decoding would produce.
+pub fn declared_content_size(mut src: &[u8]) -> u64 {
+    let mut total = 0u64;
+    while !src.is_empty() {
+        let Ok(len) = zstd::zstd_safe::find_frame_compressed_size(src) else {
+            break;
+        };
+        if let Ok(Some(n)) = zstd::zstd_safe::get_frame_content_size(src) {
+            total = total.saturating_add(n);
+        }
+        match src.get(len..) {
+            Some(rest) if len > 0 => src = rest,
+            _ => break,
+        }
+    }
+    total
+}
+
+/// Train a zstd dictionary of at most `max_size` bytes on `samples`, for
+/// `PackOptions::zstd_dict`. Pays off for many small, similar files (JSON
+/// documents, logs); zstd wants a few hundred samples or more.
+pub fn train_dict<S: AsRef<[u8]>>(samples: &[S], max_size: usize) -> Result<Vec<u8>> {
+    Ok(zstd::dict::from_samples(samples, max_size)?)
+}
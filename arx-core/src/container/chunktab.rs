@@ -1,3 +1,4 @@
+use crate::container::superblock::{FLAG_CHUNK_AEAD, FLAG_ENCRYPTED};
 use crate::error::Result;
 use std::env;
 use std::io::{self, Write};
@@ -16,6 +17,27 @@ pub struct ChunkEntry {
     /// BLAKE3 hash of the uncompressed chunk data.
     /// All-zeros for entries read from v3 archives (no hash was stored).
     pub blake3: [u8; 32],
+    /// Chunk is sealed on its own in a `FLAG_CHUNK_AEAD` archive (bit 0 of
+    /// byte 1). Always false otherwise; see [`ChunkEntry::is_sealed`].
+    pub encrypted: bool,
+    /// Chunk data lives in the parent archive of a differential pack (bit 1 of
+    /// byte 1), found there by `blake3`. Such entries store no bytes here:
+    /// `c_size` is 0.
+    pub parent: bool,
+}
+
+impl ChunkEntry {
+    /// Whether this chunk's data is AEAD-sealed in an archive with superblock `flags`.
+    /// Parent chunks are never sealed here; the parent archive seals its own.
+    pub fn is_sealed(&self, flags: u64) -> bool {
+        if self.parent {
+            false
+        } else if flags & FLAG_CHUNK_AEAD != 0 {
+            self.encrypted
+        } else {
+            flags & FLAG_ENCRYPTED != 0
+        }
+    }
 }
 
 /// Serialize a chunk table in v4 format (64 bytes per entry).
@@ -23,7 +45,8 @@ pub fn write_table(mut w: impl Write, entries: &[ChunkEntry]) -> Result<()> {
     let mut buf = [0u8; ENTRY_SIZE];
     for e in entries {
         buf[0] = e.codec;
-        for b in &mut buf[1..8] {
+        buf[1] = e.encrypted as u8 | (e.parent as u8) << 1;
+        for b in &mut buf[2..8] {
             *b = 0;
         } // padding
         buf[8..16].copy_from_slice(&e.u_size.to_le_bytes());
@@ -71,6 +94,8 @@ pub fn read_table_from_slice(buf: &[u8], count: u64) -> io::Result<Vec<ChunkEntr
     for _ in 0..count {
         let e = &buf[off..off + entry_size];
         let codec = e[0];
+        let encrypted = e[1] & 1 != 0;
+        let parent = e[1] & 2 != 0;
         let u_size = le64(&e[8..16]);
         let c_size = le64(&e[16..24]);
         let data_off = le64(&e[24..32]);
@@ -85,6 +110,8 @@ pub fn read_table_from_slice(buf: &[u8], count: u64) -> io::Result<Vec<ChunkEntr
             c_size,
             data_off,
             blake3,
+            encrypted,
+            parent,
         });
         off += entry_size;
     }
@@ -122,6 +149,8 @@ pub fn read_table(r: &mut &[u8], count: u64) -> Result<Vec<ChunkEntry>> {
 
     for i in 0..(count as usize) {
         let codec = slice[off];
+        let encrypted = slice[off + 1] & 1 != 0;
+        let parent = slice[off + 1] & 2 != 0;
         let u_size = le64(&slice[off + 8..off + 16]);
         let c_size = le64(&slice[off + 16..off + 24]);
         let data_off = le64(&slice[off + 24..off + 32]);
@@ -141,6 +170,8 @@ pub fn read_table(r: &mut &[u8], count: u64) -> Result<Vec<ChunkEntry>> {
             c_size,
             data_off,
             blake3,
+            encrypted,
+            parent,
         });
         off += entry_size;
     }
@@ -161,6 +192,8 @@ mod tests {
                 c_size: 65536,
                 data_off: 1000,
                 blake3: [0x11; 32],
+                encrypted: false,
+                parent: false,
             },
             ChunkEntry {
                 codec: 1,
@@ -168,6 +201,8 @@ mod tests {
                 c_size: 98304,
                 data_off: 66536,
                 blake3: [0x22; 32],
+                encrypted: true,
+                parent: false,
             },
         ]
     }
@@ -189,6 +224,8 @@ mod tests {
         assert_eq!(back[0].blake3, [0x11; 32]);
         assert_eq!(back[1].codec, 1);
         assert_eq!(back[1].blake3, [0x22; 32]);
+        assert!(!back[0].encrypted);
+        assert!(back[1].encrypted);
     }
 
     #[test]
@@ -43,6 +43,56 @@ impl DeltaStore {
         })
     }
 
+    /// Flush appended frames to stable storage (see [`Journal::set_durable`]).
+    ///
+    /// [`Journal::set_durable`]: crate::container::journal::Journal::set_durable
+    pub fn sync(&self) -> Result<()> {
+        Ok(self.f.sync_data()?)
+    }
+
+    /// Write the frames at `live` (`(off, len)` pairs, as returned by
+    /// [`append_frame`](Self::append_frame)) to a new store at `out`, returning
+    /// it and each frame's `(old_off, new_off, new_len)`.
+    ///
+    /// Sealed frames take their nonce from their offset, so under the same key
+    /// they are re-sealed past this store's end rather than from zero; the
+    /// unused prefix is left as a hole. Plain stores are packed from zero.
+    #[allow(clippy::type_complexity)]
+    pub fn compact_into(
+        &self,
+        live: &[(u64, u64)],
+        out: &Path,
+    ) -> Result<(DeltaStore, Vec<(u64, u64, u64)>)> {
+        let f = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(out)?;
+        let start = match self.enc {
+            EncMode::Plain => 0,
+            EncMode::Aead { .. } => self.next_off,
+        };
+        f.set_len(start)?;
+        let mut store = DeltaStore {
+            f,
+            path: out.to_path_buf(),
+            next_off: start,
+            enc: self.enc,
+            salt: self.salt,
+        };
+        store.f.seek(SeekFrom::Start(start))?;
+        let mut moves = Vec::with_capacity(live.len());
+        for &(off, len) in live {
+            let mut plain = Vec::new();
+            self.read_frame(off, len)?.read_to_end(&mut plain)?;
+            let (new_off, new_len) = store.append_frame(&plain)?;
+            moves.push((off, new_off, new_len));
+        }
+        store.sync()?;
+        Ok((store, moves))
+    }
+
     pub fn append_frame(&mut self, frame_plain: &[u8]) -> Result<(u64, u64)> {
         match self.enc {
             EncMode::Plain => {
@@ -4,7 +4,7 @@ use std::io::{Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
 
 use crate::codec::CodecId;
-use crate::error::Result;
+use crate::error::{ArxError, Result};
 use crate::policy::Policy;
 use crate::util::varint::{read_uvarint, uvarint_len, write_uvarint};
 
@@ -12,7 +12,12 @@ use chacha20poly1305::aead::{Aead, KeyInit};
 use chacha20poly1305::{XChaCha20Poly1305, XNonce};
 
 const MAGIC: &[u8; 8] = b"ARXLOG\0\0";
-const VERSION: u8 = 1;
+/// v2 appends a CRC32 (LE) of the length prefix and payload to every record;
+/// v1 logs have none and are still read and appended to as v1.
+const VERSION: u8 = 2;
+const CRC_LEN: u64 = 4;
+/// Magic, version, flags and salt.
+const HEADER_LEN: u64 = (MAGIC.len() + 1 + 1 + 32) as u64;
 const FLAG_AEAD: u8 = 0b0000_0001;
 
 #[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
@@ -50,6 +55,14 @@ pub enum LogRecord {
     Note {
         text: String,
     },
+    /// The delta was rewritten with only its live frames: each `(old_off,
+    /// new_off, new_len)` moves one frame, and frames not listed were dropped.
+    /// `delta_blake3` hashes the rewritten file, so an interrupted compaction
+    /// can tell whether its file is the one this record committed.
+    CompactDelta {
+        moves: Vec<(u64, u64, u64)>,
+        delta_blake3: [u8; 32],
+    },
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -58,24 +71,45 @@ pub enum EncMode {
     Aead { key: [u8; 32], salt: [u8; 32] },
 }
 
+/// How a durable journal forces appended records to stable storage.
+/// A seam for tests, which count calls instead of hitting the disk.
+pub(crate) trait Syncer: Send + Sync {
+    fn sync(&self, f: &File) -> std::io::Result<()>;
+}
+
+/// `fdatasync`: the record and the file length, without other metadata.
+struct DataSync;
+
+impl Syncer for DataSync {
+    fn sync(&self, f: &File) -> std::io::Result<()> {
+        f.sync_data()
+    }
+}
+
 pub struct Journal {
     f: File,
     path: PathBuf,
     enc: EncMode,
     flags: u8,
     salt: [u8; 32],
+    version: u8,
+    /// End of the last record known to be complete; appends scan only past it.
+    valid_end: u64,
+    /// Set by [`Journal::set_durable`]: sync after every append.
+    sync: Option<Box<dyn Syncer>>,
 }
 
 pub struct JournalIter<'a> {
     f: &'a mut File,
     enc: EncMode,
     salt: [u8; 32],
+    crc: bool,
 }
 
 impl<'a> Iterator for JournalIter<'a> {
     type Item = Result<LogRecord>;
     fn next(&mut self) -> Option<Self::Item> {
-        match read_next_record(self.f, self.enc, self.salt) {
+        match read_next_record(self.f, self.enc, self.salt, self.crc) {
             Ok(Some(r)) => Some(Ok(r)),
             Ok(None) => None,
             Err(e) => Some(Err(e)),
@@ -83,26 +117,31 @@ impl<'a> Iterator for JournalIter<'a> {
     }
 }
 
-fn read_next_record(f: &mut File, enc: EncMode, salt: [u8; 32]) -> Result<Option<LogRecord>> {
+/// A record cut short by EOF is a half-written tail and ends the log cleanly;
+/// with `crc`, a complete record whose checksum does not match is corruption
+/// and an error, and so is a length running past EOF with complete records
+/// after it: only the last record can be torn.
+fn read_next_record(
+    f: &mut File,
+    enc: EncMode,
+    salt: [u8; 32],
+    crc: bool,
+) -> Result<Option<LogRecord>> {
     let start = f.stream_position()?;
-    let len = match read_uvarint(f) {
-        Ok(Some(n)) => n,
-        Ok(None) => return Ok(None), // Clean EOF
-        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
-            // Partial varint — process crashed mid-write; stop replay gracefully
-            return Ok(None);
-        }
-        Err(e) => return Err(e.into()),
+    let Some((len, payload_off)) = read_record_head(f, crc)? else {
+        return Ok(None);
     };
-    let payload_off = start + uvarint_len(len) as u64;
 
     let mut buf = vec![0u8; len as usize];
-    if let Err(e) = f.read_exact(&mut buf) {
-        if e.kind() == std::io::ErrorKind::UnexpectedEof {
-            // Partial record body — stop replay gracefully
-            return Ok(None);
+    f.read_exact(&mut buf)?;
+    if crc {
+        let mut stored = [0u8; CRC_LEN as usize];
+        f.read_exact(&mut stored)?;
+        if record_crc(len, &buf) != u32::from_le_bytes(stored) {
+            return Err(ArxError::Format(format!(
+                "journal record at offset {start} is corrupt (crc mismatch)"
+            )));
         }
-        return Err(e.into());
     }
 
     let plain = match enc {
@@ -134,6 +173,68 @@ fn read_next_record(f: &mut File, enc: EncMode, salt: [u8; 32]) -> Result<Option
     Ok(Some(rec))
 }
 
+/// Read the length prefix of the record at the current position and return
+/// it with the payload offset, leaving `f` there. `None` is a clean EOF or a
+/// record the log ends partway through.
+fn read_record_head(f: &mut File, crc: bool) -> Result<Option<(u64, u64)>> {
+    let start = f.stream_position()?;
+    let len = match read_uvarint(f) {
+        Ok(Some(n)) => n,
+        Ok(None) => return Ok(None), // Clean EOF
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+            // Partial varint — process crashed mid-write; stop replay gracefully
+            return Ok(None);
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let payload_off = start + uvarint_len(len) as u64;
+    let trailer = if crc { CRC_LEN } else { 0 };
+    if payload_off.saturating_add(len).saturating_add(trailer) > f.metadata()?.len() {
+        if crc {
+            let mut rest = Vec::new();
+            f.seek(SeekFrom::Start(start + 1))?;
+            f.read_to_end(&mut rest)?;
+            if holds_complete_record(&rest) {
+                return Err(ArxError::Corrupt {
+                    region: "journal",
+                    offset: start,
+                    detail: format!("record length {len} runs past the end of the log"),
+                });
+            }
+        }
+        // Partial record body — stop replay gracefully
+        return Ok(None);
+    }
+    Ok(Some((len, payload_off)))
+}
+
+/// Whether `rest` ends in a complete record with a matching CRC. One backward
+/// pass: a candidate's CRC is only computed when its length lands exactly on
+/// the end of `rest`.
+fn holds_complete_record(rest: &[u8]) -> bool {
+    (0..rest.len()).rev().any(|p| {
+        let mut r = &rest[p..];
+        let Ok(Some(len)) = read_uvarint(&mut r) else {
+            return false;
+        };
+        if len.checked_add(CRC_LEN) != Some(r.len() as u64) {
+            return false;
+        }
+        let (payload, stored) = r.split_at(r.len() - CRC_LEN as usize);
+        record_crc(len, payload).to_le_bytes() == stored
+    })
+}
+
+/// CRC32 of a record's varint length prefix followed by its payload.
+fn record_crc(len: u64, payload: &[u8]) -> u32 {
+    let mut lenv = Vec::with_capacity(10);
+    write_uvarint(&mut lenv, len).expect("write to Vec never fails");
+    let mut h = crc32fast::Hasher::new();
+    h.update(&lenv);
+    h.update(payload);
+    h.finalize()
+}
+
 impl Journal {
     pub fn open(path: &Path, enc: EncMode) -> Result<Self> {
         let existed = path.exists();
@@ -142,7 +243,7 @@ impl Journal {
             .write(true)
             .create(true)
             .open(path)?;
-        let (flags, salt) = if !existed {
+        let (flags, salt, version) = if !existed {
             let (flags, salt) = match enc {
                 EncMode::Plain => (0u8, [0u8; 32]),
                 EncMode::Aead { salt, .. } => (FLAG_AEAD, salt),
@@ -152,7 +253,7 @@ impl Journal {
             f.write_all(&[flags])?;
             f.write_all(&salt)?;
             f.flush()?;
-            (flags, salt)
+            (flags, salt, VERSION)
         } else {
             let mut magic = [0u8; 8];
             f.read_exact(&mut magic)?;
@@ -176,19 +277,26 @@ impl Journal {
                 f.write_all(&[flags])?;
                 f.write_all(&salt)?;
                 f.flush()?;
-                (flags, salt)
+                (flags, salt, VERSION)
             } else {
                 let mut ver = [0u8; 1];
                 f.read_exact(&mut ver)?;
-                let _ = ver[0];
+                if ver[0] > VERSION {
+                    return Err(ArxError::Format(format!(
+                        "journal version {} is newer than supported ({VERSION})",
+                        ver[0]
+                    )));
+                }
                 let mut flags = [0u8; 1];
                 let mut salt = [0u8; 32];
                 match f.read_exact(&mut flags) {
                     Ok(_) => {
                         f.read_exact(&mut salt)?;
-                        (flags[0], salt)
+                        (flags[0], salt, ver[0])
+                    }
+                    Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                        (0, [0u8; 32], ver[0])
                     }
-                    Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => (0, [0u8; 32]),
                     Err(e) => return Err(e.into()),
                 }
             }
@@ -211,26 +319,53 @@ impl Journal {
             enc,
             flags,
             salt,
+            version,
+            valid_end: HEADER_LEN,
+            sync: None,
         })
     }
 
+    /// Sync the file after every append, plain or sealed, so a record that
+    /// `append` returned for survives a power loss. Off by default: each
+    /// append then waits for the disk, which costs orders of magnitude in
+    /// throughput for small records.
+    pub fn set_durable(&mut self, durable: bool) {
+        self.sync = durable.then(|| Box::new(DataSync) as Box<dyn Syncer>);
+    }
+
+    pub fn is_durable(&self) -> bool {
+        self.sync.is_some()
+    }
+
+    /// Force every record appended so far to stable storage, whether or not
+    /// the journal is durable.
+    pub fn sync(&self) -> Result<()> {
+        Ok(self.f.sync_data()?)
+    }
+
     /// Append a single record (length-delimited, optionally AEAD-sealed).
+    ///
+    /// Holds an advisory exclusive lock on the journal for the whole
+    /// seek-to-EOF, nonce derivation and write, so concurrent appenders (other
+    /// processes, or other `Journal`s on the same path) never interleave.
     pub fn append(&mut self, rec: &LogRecord) -> Result<()> {
         let mut plain = Vec::with_capacity(256);
         serde_cbor::to_writer(&mut plain, rec)
             .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
 
-        match self.enc {
-            EncMode::Plain => {
-                let mut lenv = Vec::with_capacity(10);
-                write_uvarint(&mut lenv, plain.len() as u64).expect("write to Vec never fails");
-                self.f.write_all(&lenv)?;
-                self.f.write_all(&plain)?;
-                self.f.flush()?;
-                Ok(())
-            }
+        fs2::FileExt::lock_exclusive(&self.f)?;
+        let res = self.append_locked(&plain);
+        let unlocked = fs2::FileExt::unlock(&self.f);
+        res?;
+        Ok(unlocked?)
+    }
+
+    fn append_locked(&mut self, plain: &[u8]) -> Result<()> {
+        // Another writer may have extended the file since we last wrote
+        let pos = self.trim_torn_tail()?;
+        let payload = match self.enc {
+            EncMode::Plain => plain.to_vec(),
             EncMode::Aead { key, .. } => {
-                let pos = self.f.stream_position()?;
                 let cipher_len = (plain.len() as u64) + 16;
                 let vlen = uvarint_len(cipher_len);
                 let payload_off = pos + vlen as u64;
@@ -245,31 +380,239 @@ impl Journal {
                 nonce.copy_from_slice(&hb.as_bytes()[..24]);
 
                 let cipher = XChaCha20Poly1305::new((&key).into());
-                let ct = cipher
-                    .encrypt(&XNonce::from(nonce), plain.as_ref())
-                    .map_err(|_| {
-                        std::io::Error::new(std::io::ErrorKind::Other, "journal aead encrypt")
-                    })?;
-
-                let mut lenv = Vec::with_capacity(10);
-                write_uvarint(&mut lenv, ct.len() as u64).expect("write to Vec never fails");
-                self.f.write_all(&lenv)?;
-                self.f.write_all(&ct)?;
-                self.f.flush()?;
-                Ok(())
+                cipher
+                    .encrypt(&XNonce::from(nonce), plain)
+                    .map_err(|_| std::io::Error::other("journal aead encrypt"))?
             }
+        };
+
+        // One write per record, so a crash leaves at most a short tail
+        let len = payload.len() as u64;
+        let mut rec = Vec::with_capacity(10 + payload.len() + CRC_LEN as usize);
+        write_uvarint(&mut rec, len).expect("write to Vec never fails");
+        rec.extend_from_slice(&payload);
+        if self.version >= 2 {
+            rec.extend_from_slice(&record_crc(len, &payload).to_le_bytes());
         }
+        self.f.seek(SeekFrom::Start(pos))?;
+        self.f.write_all(&rec)?;
+        self.f.flush()?;
+        if let Some(sync) = &self.sync {
+            sync.sync(&self.f)?;
+        }
+        self.valid_end = pos + rec.len() as u64;
+        Ok(())
+    }
+
+    /// Walk the records past `valid_end` and cut off a record a crash left
+    /// half-written, so the next append does not land after it. Returns the
+    /// new end of the log. Must hold the lock.
+    fn trim_torn_tail(&mut self) -> Result<u64> {
+        let crc = self.version >= 2;
+        let file_len = self.f.metadata()?.len();
+        let mut pos = self.valid_end.min(file_len);
+        self.f.seek(SeekFrom::Start(pos))?;
+        while let Some((len, payload_off)) = read_record_head(&mut self.f, crc)? {
+            pos = payload_off + len + if crc { CRC_LEN } else { 0 };
+            self.f.seek(SeekFrom::Start(pos))?;
+        }
+        if pos < file_len {
+            self.f.set_len(pos)?;
+        }
+        self.valid_end = pos;
+        Ok(pos)
     }
 
     /// Create an iterator starting after the journal header.
     pub fn iter(&mut self) -> Result<JournalIter<'_>> {
         self.f.flush()?;
-        self.f
-            .seek(SeekFrom::Start((MAGIC.len() + 1 + 1 + 32) as u64))?;
+        self.f.seek(SeekFrom::Start(HEADER_LEN))?;
         Ok(JournalIter {
             f: &mut self.f,
             enc: self.enc,
             salt: self.salt,
+            crc: self.version >= 2,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_concurrent_appends_do_not_interleave() {
+        const PER_THREAD: usize = 200;
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("a.log");
+        let enc = EncMode::Aead {
+            key: [3u8; 32],
+            salt: [5u8; 32],
+        };
+        Journal::open(&path, enc).unwrap();
+
+        std::thread::scope(|s| {
+            for t in 0..2 {
+                let path = &path;
+                s.spawn(move || {
+                    let mut j = Journal::open(path, enc).unwrap();
+                    for i in 0..PER_THREAD {
+                        j.append(&LogRecord::Note {
+                            text: format!("writer {t} record {i}"),
+                        })
+                        .unwrap();
+                    }
+                });
+            }
+        });
+
+        let mut j = Journal::open(&path, enc).unwrap();
+        let recs: Vec<LogRecord> = j.iter().unwrap().collect::<Result<_>>().unwrap();
+        assert_eq!(recs.len(), 2 * PER_THREAD);
+        for t in 0..2 {
+            let mine = recs
+                .iter()
+                .filter(|r| matches!(r, LogRecord::Note { text } if text.starts_with(&format!("writer {t} "))))
+                .count();
+            assert_eq!(mine, PER_THREAD);
+        }
+    }
+
+    #[test]
+    fn test_durable_journal_syncs_every_append() {
+        use std::sync::Arc;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        struct Counting(Arc<AtomicUsize>);
+        impl Syncer for Counting {
+            fn sync(&self, _: &File) -> std::io::Result<()> {
+                self.0.fetch_add(1, Ordering::SeqCst);
+                Ok(())
+            }
+        }
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("d.log");
+        let mut j = Journal::open(&path, EncMode::Plain).unwrap();
+        j.append(&note(0)).unwrap();
+        assert!(!j.is_durable());
+
+        let syncs = Arc::new(AtomicUsize::new(0));
+        j.sync = Some(Box::new(Counting(syncs.clone())));
+        for i in 1..4 {
+            j.append(&note(i)).unwrap();
+        }
+        assert_eq!(syncs.load(Ordering::SeqCst), 3);
+
+        // The real syncer on a sealed journal
+        let enc = EncMode::Aead {
+            key: [1u8; 32],
+            salt: [2u8; 32],
+        };
+        let mut j = Journal::open(&dir.path().join("e.log"), enc).unwrap();
+        j.set_durable(true);
+        assert!(j.is_durable());
+        j.append(&note(0)).unwrap();
+        assert_eq!(j.iter().unwrap().count(), 1);
+    }
+
+    fn note(i: usize) -> LogRecord {
+        LogRecord::Note {
+            text: format!("record {i}"),
+        }
+    }
+
+    #[test]
+    fn test_crc_tells_torn_tail_from_mid_log_corruption() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("c.log");
+        let mut ends = Vec::new();
+        let mut j = Journal::open(&path, EncMode::Plain).unwrap();
+        for i in 0..3 {
+            j.append(&note(i)).unwrap();
+            ends.push(std::fs::metadata(&path).unwrap().len());
+        }
+        drop(j);
+        let clean = std::fs::read(&path).unwrap();
+
+        // Torn last record: the first two replay, then clean EOF
+        std::fs::write(&path, &clean[..clean.len() - 3]).unwrap();
+        let mut j = Journal::open(&path, EncMode::Plain).unwrap();
+        let recs: Vec<_> = j.iter().unwrap().collect::<Result<_>>().unwrap();
+        assert_eq!(recs.len(), 2);
+
+        // A flipped byte in the middle record is an error, not a silent stop
+        let mut bad = clean.clone();
+        bad[(ends[0] + ends[1]) as usize / 2] ^= 0x40;
+        std::fs::write(&path, &bad).unwrap();
+        let mut j = Journal::open(&path, EncMode::Plain).unwrap();
+        let got: Vec<Result<LogRecord>> = j.iter().unwrap().collect();
+        assert!(got[0].is_ok());
+        let err = got[1].as_ref().unwrap_err().to_string();
+        assert!(err.contains("crc mismatch"), "{err}");
+
+        // So is a middle record whose length now points past EOF
+        let mut bad = clean.clone();
+        bad[ends[0] as usize] = 0xFF;
+        bad.insert(ends[0] as usize + 1, 0x7F);
+        std::fs::write(&path, &bad).unwrap();
+        let mut j = Journal::open(&path, EncMode::Plain).unwrap();
+        let got: Vec<Result<LogRecord>> = j.iter().unwrap().collect();
+        assert!(got[0].is_ok());
+        assert!(
+            matches!(got[1], Err(ArxError::Corrupt { offset, .. }) if offset == ends[0]),
+            "{:?}",
+            got[1]
+        );
+    }
+
+    #[test]
+    fn test_append_after_torn_tail_replaces_it() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("t.log");
+        let mut j = Journal::open(&path, EncMode::Plain).unwrap();
+        for i in 0..3 {
+            j.append(&note(i)).unwrap();
+        }
+        drop(j);
+
+        // Crash partway through the third record, then append one more
+        let clean = std::fs::read(&path).unwrap();
+        std::fs::write(&path, &clean[..clean.len() - 3]).unwrap();
+        let mut j = Journal::open(&path, EncMode::Plain).unwrap();
+        j.append(&note(9)).unwrap();
+        drop(j);
+
+        let mut j = Journal::open(&path, EncMode::Plain).unwrap();
+        let recs: Vec<_> = j.iter().unwrap().collect::<Result<_>>().unwrap();
+        let texts: Vec<_> = recs
+            .iter()
+            .map(|r| match r {
+                LogRecord::Note { text } => text.as_str(),
+                other => panic!("unexpected {other:?}"),
+            })
+            .collect();
+        assert_eq!(texts, ["record 0", "record 1", "record 9"]);
+    }
+
+    #[test]
+    fn test_legacy_v1_log_reads_and_appends_without_crc() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("v1.log");
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&[1, 0]);
+        bytes.extend_from_slice(&[0u8; 32]);
+        let mut plain = Vec::new();
+        serde_cbor::to_writer(&mut plain, &note(0)).unwrap();
+        write_uvarint(&mut bytes, plain.len() as u64).unwrap();
+        bytes.extend_from_slice(&plain);
+        std::fs::write(&path, &bytes).unwrap();
+
+        let mut j = Journal::open(&path, EncMode::Plain).unwrap();
+        j.append(&note(1)).unwrap();
+        let want = bytes.len() as u64 + 1 + plain.len() as u64;
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), want, "no crc");
+        let recs: Vec<_> = j.iter().unwrap().collect::<Result<_>>().unwrap();
+        assert_eq!(recs.len(), 2);
+    }
+}
@@ -1,4 +1,18 @@
+use crate::container::superblock::FLAG_MANIFEST_ZSTD;
+use crate::error::ArxError;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+#[cfg(feature = "zstd")]
+use std::io::Read;
+
+/// Current CBOR manifest schema version. Bumped when manifest fields are added;
+/// independent of the binary container `Superblock::version`. Version 4 added
+/// entry owners, Windows attributes, source roots, hostname and the zstd
+/// dictionary.
+pub const MANIFEST_VERSION: u32 = 4;
+
+/// Upper bound on an inflated manifest, guarding against decompression bombs.
+const MAX_INFLATED_MANIFEST: u64 = 1024 * 1024 * 1024; // 1 GiB
 
 /// Reference to a chunk in the chunk table.
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -7,6 +21,18 @@ pub struct ChunkRef {
     pub u_size: u64, // uncompressed size of this chunk
 }
 
+/// Windows `FILE_ATTRIBUTE_READONLY`, as kept in [`FileEntry::attrs`].
+pub const ATTR_READONLY: u32 = 0x1;
+/// Windows `FILE_ATTRIBUTE_HIDDEN`, as kept in [`FileEntry::attrs`].
+pub const ATTR_HIDDEN: u32 = 0x2;
+
+/// Numeric owner of an entry when it was packed.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Owner {
+    pub uid: u32,
+    pub gid: u32,
+}
+
 /// A regular file stored in the archive.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct FileEntry {
@@ -15,6 +41,44 @@ pub struct FileEntry {
     pub mtime: i64,
     pub u_size: u64,
     pub chunk_refs: Vec<ChunkRef>,
+    /// Sparse holes as `(offset, len)` in ascending order; `chunk_refs` cover only
+    /// the data between them. Empty for dense files and before manifest version 3.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub holes: Vec<(u64, u64)>,
+    /// Numeric uid/gid at pack time. `None` for deterministic packs, non-Unix
+    /// sources and before manifest version 4.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<Owner>,
+    /// [`ATTR_READONLY`]/[`ATTR_HIDDEN`] bits of a file packed on Windows,
+    /// reapplied when extracting there. `None` when neither was set and for
+    /// other platforms' packs; ignored when extracting elsewhere, where `mode`
+    /// carries the permissions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attrs: Option<u32>,
+}
+
+impl FileEntry {
+    /// Logical file offset at which each chunk in `chunk_refs` starts, with the
+    /// chunks laid out sequentially and skipping over `holes`.
+    pub fn chunk_offsets(&self) -> Vec<u64> {
+        let mut holes = self.holes.iter().peekable();
+        let mut pos = 0u64;
+        self.chunk_refs
+            .iter()
+            .map(|r| {
+                while let Some(&&(off, len)) = holes.peek() {
+                    if off > pos {
+                        break;
+                    }
+                    pos = pos.max(off.saturating_add(len));
+                    holes.next();
+                }
+                let start = pos;
+                pos = pos.saturating_add(r.u_size);
+                start
+            })
+            .collect()
+    }
 }
 
 /// A directory stored in the archive.
@@ -23,6 +87,9 @@ pub struct DirEntry {
     pub path: String,
     pub mode: u32,
     pub mtime: i64,
+    /// As [`FileEntry::owner`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<Owner>,
 }
 
 /// A symbolic link stored in the archive.
@@ -35,6 +102,14 @@ pub struct SymlinkEntry {
     pub mtime: i64,
 }
 
+/// An additional path for a file already stored in `files` (same inode at pack time).
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HardlinkEntry {
+    pub path: String,
+    /// Path of the primary `FileEntry` this link shares content with.
+    pub target: String,
+}
+
 /// Archive-level metadata.
 #[derive(Serialize, Deserialize, Debug, Default, Clone)]
 pub struct Meta {
@@ -49,6 +124,20 @@ pub struct Meta {
     /// Free-form notes embedded at creation time.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub notes: Option<String>,
+    /// Manifest schema version (0 for manifests written before the field existed).
+    #[serde(default)]
+    pub manifest_version: u32,
+    /// Absolute source roots given to `pack`, for auditing where the archive
+    /// came from. Empty for deterministic packs and non-filesystem sources.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub source_roots: Vec<String>,
+    /// Host that ran `pack`; `None` for deterministic packs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
+    /// Zstd dictionary that `ZstdDict` chunks were compressed against. Kept
+    /// here so it is sealed with the manifest and survives rewrites that keep it.
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "serde_bytes")]
+    pub zstd_dict: Option<Vec<u8>>,
 }
 
 /// The root manifest — CBOR-serialized and stored in the manifest region.
@@ -60,6 +149,122 @@ pub struct Manifest {
     /// Symbolic links (empty for archives created before v4).
     #[serde(default)]
     pub symlinks: Vec<SymlinkEntry>,
+    /// Hardlinks to entries in `files` (empty before manifest version 2).
+    #[serde(default)]
+    pub hardlinks: Vec<HardlinkEntry>,
+}
+
+#[cfg(feature = "zstd")]
+fn inflate_manifest(bytes: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut cbor = Vec::new();
+    zstd::stream::read::Decoder::new(bytes)?
+        .take(MAX_INFLATED_MANIFEST + 1)
+        .read_to_end(&mut cbor)?;
+    Ok(cbor)
+}
+
+#[cfg(not(feature = "zstd"))]
+fn inflate_manifest(_bytes: &[u8]) -> std::io::Result<Vec<u8>> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "manifest is zstd-compressed but arx-core was built without the `zstd` feature",
+    ))
+}
+
+impl Manifest {
+    /// Sum of the files' uncompressed sizes; hardlinks are not counted again.
+    pub fn total_logical(&self) -> u64 {
+        self.files.iter().map(|f| f.u_size).sum()
+    }
+
+    /// Decode the manifest region's plaintext (after any AEAD open), inflating
+    /// it first when the superblock `flags` carry `FLAG_MANIFEST_ZSTD`.
+    pub fn from_region(bytes: &[u8], flags: u64) -> std::io::Result<Self> {
+        if flags & FLAG_MANIFEST_ZSTD == 0 {
+            return Self::from_cbor(bytes);
+        }
+        let cbor = inflate_manifest(bytes)?;
+        if cbor.len() as u64 > MAX_INFLATED_MANIFEST {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("inflated manifest exceeds maximum {MAX_INFLATED_MANIFEST}"),
+            ));
+        }
+        Self::from_cbor(&cbor)
+    }
+
+    /// Map a hardlink path to its primary file's path; other paths pass through.
+    pub fn resolve_hardlink<'a>(&'a self, path: &'a str) -> &'a str {
+        self.hardlinks
+            .iter()
+            .find(|h| h.path == path)
+            .map_or(path, |h| h.target.as_str())
+    }
+
+    /// Check that every chunk reference indexes into a table of `chunk_count`
+    /// entries, so readers can index the table without panicking on corrupt input.
+    pub fn validate_chunk_refs(&self, chunk_count: usize) -> crate::error::Result<()> {
+        for fe in &self.files {
+            if let Some(r) = fe.chunk_refs.iter().find(|r| r.id >= chunk_count as u64) {
+                return Err(ArxError::Format(format!(
+                    "{}: chunk id {} out of range (table has {chunk_count} entries)",
+                    fe.path, r.id
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Reject manifests where one path names two entries of which at least one is
+    /// not a directory, or where a non-directory would have to contain another
+    /// entry. Extraction would otherwise fail half-way through the tree.
+    pub fn check_path_conflicts(&self) -> crate::error::Result<()> {
+        let mut kinds: HashMap<&str, &'static str> = HashMap::new();
+        let entries = self
+            .dirs
+            .iter()
+            .map(|d| (d.path.as_str(), "directory"))
+            .chain(self.files.iter().map(|f| (f.path.as_str(), "file")))
+            .chain(self.symlinks.iter().map(|l| (l.path.as_str(), "symlink")))
+            .chain(self.hardlinks.iter().map(|h| (h.path.as_str(), "hardlink")));
+        for (path, kind) in entries {
+            if let Some(prev) = kinds.insert(path, kind)
+                && (prev != "directory" || kind != "directory")
+            {
+                return Err(ArxError::Format(format!(
+                    "path conflict: {path} is both a {prev} and a {kind}"
+                )));
+            }
+        }
+        for path in kinds.keys() {
+            let mut parent = *path;
+            while let Some((p, _)) = parent.rsplit_once('/') {
+                parent = p;
+                if let Some(&kind) = kinds.get(parent)
+                    && kind != "directory"
+                {
+                    return Err(ArxError::Format(format!(
+                        "path conflict: {parent} is a {kind} but contains {path}"
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Decode a plaintext CBOR manifest, branching on its schema version.
+    /// Older schemas rely on serde defaults for fields added since; newer
+    /// schemas are rejected rather than silently misread.
+    pub fn from_cbor(bytes: &[u8]) -> std::io::Result<Self> {
+        let m: Manifest = ciborium::de::from_reader(bytes).map_err(std::io::Error::other)?;
+        match m.meta.manifest_version {
+            v if v <= MANIFEST_VERSION => Ok(m),
+            v => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("unsupported manifest version: {v} (supported: <= {MANIFEST_VERSION})"),
+            )),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -81,11 +286,15 @@ mod tests {
                 mtime: 1_700_000_000,
                 u_size: 12,
                 chunk_refs: vec![ChunkRef { id: 0, u_size: 12 }],
+                holes: vec![],
+                owner: None,
+                attrs: Some(ATTR_READONLY | ATTR_HIDDEN),
             }],
             dirs: vec![DirEntry {
                 path: "subdir".into(),
                 mode: 0o755,
                 mtime: 0,
+                owner: None,
             }],
             meta: Meta {
                 created: 1_700_000_000,
@@ -93,6 +302,10 @@ mod tests {
                 label: Some("test archive".into()),
                 owner: Some("alice".into()),
                 notes: Some("integration test".into()),
+                manifest_version: MANIFEST_VERSION,
+                source_roots: vec!["/home/alice/project".into()],
+                hostname: Some("build01".into()),
+                zstd_dict: Some(vec![0x37, 0xa4, 0x30, 0xec]),
             },
             symlinks: vec![SymlinkEntry {
                 path: "link".into(),
@@ -100,12 +313,25 @@ mod tests {
                 mode: 0o777,
                 mtime: 0,
             }],
+            hardlinks: vec![HardlinkEntry {
+                path: "hello-again.txt".into(),
+                target: "hello.txt".into(),
+            }],
         };
         let back = roundtrip(&m);
         assert_eq!(back.files[0].path, "hello.txt");
+        assert_eq!(back.files[0].attrs, Some(ATTR_READONLY | ATTR_HIDDEN));
         assert_eq!(back.meta.label.as_deref(), Some("test archive"));
         assert_eq!(back.meta.owner.as_deref(), Some("alice"));
         assert_eq!(back.symlinks[0].target, "hello.txt");
+        assert_eq!(back.hardlinks[0].path, "hello-again.txt");
+        assert_eq!(back.meta.manifest_version, MANIFEST_VERSION);
+        assert_eq!(back.meta.source_roots, ["/home/alice/project"]);
+        assert_eq!(back.meta.hostname.as_deref(), Some("build01"));
+        assert_eq!(
+            back.meta.zstd_dict.as_deref(),
+            Some(&[0x37, 0xa4, 0x30, 0xec][..])
+        );
     }
 
     #[test]
@@ -116,6 +342,7 @@ mod tests {
             dirs: vec![],
             meta: Meta::default(),
             symlinks: vec![],
+            hardlinks: vec![],
         };
         let back = roundtrip(&m);
         assert!(
@@ -123,4 +350,173 @@ mod tests {
             "missing symlinks field should default to empty"
         );
     }
+
+    #[test]
+    fn test_unversioned_manifest_gets_defaults() {
+        // Shape of a manifest written before `manifest_version` and `symlinks` existed
+        #[derive(Serialize)]
+        struct OldMeta {
+            created: i64,
+            tool: String,
+        }
+        #[derive(Serialize)]
+        struct OldManifest {
+            files: Vec<FileEntry>,
+            dirs: Vec<DirEntry>,
+            meta: OldMeta,
+        }
+        let old = OldManifest {
+            files: vec![],
+            dirs: vec![],
+            meta: OldMeta {
+                created: 1,
+                tool: "arx 0.0.1".into(),
+            },
+        };
+        let mut buf = Vec::new();
+        ciborium::ser::into_writer(&old, &mut buf).unwrap();
+
+        let m = Manifest::from_cbor(&buf).unwrap();
+        assert_eq!(m.meta.manifest_version, 0);
+        assert!(m.symlinks.is_empty());
+        assert!(m.hardlinks.is_empty());
+        assert!(m.meta.label.is_none());
+        assert!(m.meta.source_roots.is_empty());
+    }
+
+    #[test]
+    fn test_previous_manifest_version_decodes() {
+        let m = Manifest {
+            meta: Meta {
+                manifest_version: 3,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut buf = Vec::new();
+        ciborium::ser::into_writer(&m, &mut buf).unwrap();
+        let back = Manifest::from_cbor(&buf).unwrap();
+        assert_eq!(back.meta.manifest_version, 3);
+        assert!(back.meta.zstd_dict.is_none());
+    }
+
+    #[test]
+    fn test_future_manifest_version_rejected() {
+        let m = Manifest {
+            meta: Meta {
+                manifest_version: MANIFEST_VERSION + 1,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut buf = Vec::new();
+        ciborium::ser::into_writer(&m, &mut buf).unwrap();
+        let err = Manifest::from_cbor(&buf).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_compressed_region_roundtrip_and_savings() {
+        // Synthetic manifest for a tree of 100k tiny files
+        let m = Manifest {
+            files: (0..100_000u64)
+                .map(|i| FileEntry {
+                    path: format!("src/module_{:03}/file_{i:06}.txt", i / 1000),
+                    mode: 0o100644,
+                    mtime: 1_700_000_000 + i as i64,
+                    u_size: 100 + i % 50,
+                    chunk_refs: vec![ChunkRef {
+                        id: i,
+                        u_size: 100 + i % 50,
+                    }],
+                    holes: vec![],
+                    owner: None,
+                    attrs: None,
+                })
+                .collect(),
+            ..Default::default()
+        };
+        let mut cbor = Vec::new();
+        ciborium::ser::into_writer(&m, &mut cbor).unwrap();
+        let packed = zstd::stream::encode_all(cbor.as_slice(), 3).unwrap();
+        assert!(
+            packed.len() * 4 < cbor.len(),
+            "expected at least 4x savings: cbor={} zstd={}",
+            cbor.len(),
+            packed.len()
+        );
+
+        let back = Manifest::from_region(&packed, FLAG_MANIFEST_ZSTD).unwrap();
+        assert_eq!(back.files.len(), 100_000);
+        assert_eq!(back.files[99_999].path, m.files[99_999].path);
+        assert!(Manifest::from_region(&cbor, 0).is_ok());
+    }
+
+    #[test]
+    fn test_path_conflicts_rejected() {
+        let file = |path: &str| FileEntry {
+            path: path.into(),
+            mode: 0o100644,
+            mtime: 0,
+            u_size: 0,
+            chunk_refs: vec![],
+            holes: vec![],
+            owner: None,
+            attrs: None,
+        };
+        let dir = |path: &str| DirEntry {
+            path: path.into(),
+            mode: 0o040755,
+            mtime: 0,
+            owner: None,
+        };
+
+        let ok = Manifest {
+            files: vec![file("foo/a.txt"), file("bar")],
+            dirs: vec![dir(""), dir("foo"), dir("")],
+            ..Default::default()
+        };
+        assert!(ok.check_path_conflicts().is_ok());
+
+        let both = Manifest {
+            files: vec![file("foo")],
+            dirs: vec![dir("foo")],
+            ..Default::default()
+        };
+        let err = both.check_path_conflicts().unwrap_err().to_string();
+        assert!(err.contains("foo is both a directory and a file"), "{err}");
+
+        let nested = Manifest {
+            files: vec![file("foo"), file("foo/bar/baz.txt")],
+            ..Default::default()
+        };
+        let err = nested.check_path_conflicts().unwrap_err().to_string();
+        assert!(err.contains("foo is a file but contains"), "{err}");
+    }
+
+    #[test]
+    fn test_chunk_offsets_skip_holes() {
+        let refs = |sizes: &[u64]| {
+            sizes
+                .iter()
+                .map(|&u_size| ChunkRef { id: 0, u_size })
+                .collect()
+        };
+        let mut fe = FileEntry {
+            path: "disk.img".into(),
+            mode: 0o100644,
+            mtime: 0,
+            u_size: 1000,
+            chunk_refs: refs(&[100, 50, 200]),
+            holes: vec![],
+            owner: None,
+            attrs: None,
+        };
+        assert_eq!(fe.chunk_offsets(), vec![0, 100, 150]);
+
+        // Leading hole, hole between data runs, trailing hole
+        fe.holes = vec![(0, 200), (350, 400), (950, 50)];
+        assert_eq!(fe.chunk_offsets(), vec![200, 300, 750]);
+    }
 }
@@ -1,8 +1,8 @@
-use std::io::{Read, Write};
+use std::io::{Read, Seek, SeekFrom, Write};
 
 // ! ARX Superblock Layout
 // !
-// ! Version 4 (80 bytes, little-endian):
+// ! Version 7 (100 bytes, little-endian) — v6 plus a checksum:
 // !   0-5   (6B)  Magic "ARXALP"
 // !   6-7   (2B)  Version: u16
 // !   8-15  (8B)  Manifest length: u64
@@ -11,24 +11,62 @@ use std::io::{Read, Write};
 // !   32-39 (8B)  Data offset: u64
 // !   40-47 (8B)  Flags: u64
 // !   48-79 (32B) KDF salt: [u8; 32]   ← added in v4
+// !   80-87 (8B)  File count: u64      ← added in v5
+// !   88-95 (8B)  Total logical bytes: u64 ← added in v5
+// !   96-99 (4B)  CRC32 of bytes 0-95: u32 ← added in v7
 // !
+// ! From v6 a 12-byte region guard `[magic:4][region_len:u64]` sits right
+// ! before the manifest (`ARXM`, len = manifest_len) and right before the chunk
+// ! table (`ARXT`, len = data_off - chunk_table_off). `header_len()` counts the
+// ! manifest guard, so regions are still located the same way in every version.
+// !
+// ! With FLAG_KEYRING (v7) a keyring region follows the superblock, before the
+// ! manifest guard: its own guard `[ARXK][len:u64]`, then `len` bytes of
+// ! wrapped data keys (see `crypto::keyring`). `header_len()` counts it too.
+// !
+// ! Version 6 (96 bytes): no checksum.
+// ! Version 5 (96 bytes): no region guards.
+// ! Version 4 (80 bytes): same layout without the file count and total.
 // ! Version 3 (48 bytes): same layout without kdf_salt.
-// ! v3 archives are readable; all new archives are written as v4.
+// ! v3–v6 archives are readable; all new archives are written as v7.
 // !
 // ! Flags:
 // !   Bit 0: FLAG_ENCRYPTED     — archive uses per-region AEAD encryption
 // !   Bit 1: FLAG_KDF_PASSWORD  — key was derived via Argon2id from a password
+// !   Bit 2: FLAG_MANIFEST_ZSTD — manifest CBOR is zstd-compressed (before AEAD)
+// !   Bit 3: FLAG_AES_GCM       — regions use AES-256-GCM instead of XChaCha20-Poly1305
+// !   Bit 4: FLAG_CHUNK_AEAD    — only chunks marked `encrypted` in the table are sealed;
+// !                               with bit 0 the manifest and chunk table are sealed too,
+// !                               without it they stay plaintext
+// !   Bit 5: FLAG_PARENT        — some chunks are stored in a parent archive (table entries
+// !                               marked `parent`); reading them needs that archive
+// !   Bit 6: FLAG_KEYRING       — regions are sealed under a random data key, wrapped once
+// !                               per recipient key in the keyring region (with bit 0)
 
 pub const MAGIC: &[u8; 6] = b"ARXALP";
-pub const VERSION: u16 = 4;
+pub const VERSION: u16 = 7;
 
-/// Header length for v4+ archives.
-pub const HEADER_LEN: u64 = 80; // 6+2+8+8+8+8+8+32
+/// Superblock length for v7+ archives.
+pub const HEADER_LEN: u64 = 100; // 6+2+8+8+8+8+8+32+8+8+4
+/// Header length for v5 and v6 archives (no checksum).
+pub const HEADER_LEN_V5: u64 = 96;
+/// Region guard `[magic:4][region_len:8]` before the manifest and chunk table (v6+).
+pub const REGION_GUARD_LEN: u64 = 12;
+pub const MANIFEST_MAGIC: &[u8; 4] = b"ARXM";
+pub const TABLE_MAGIC: &[u8; 4] = b"ARXT";
+pub const KEYRING_MAGIC: &[u8; 4] = b"ARXK";
+/// Header length for v4 archives (backward compatibility).
+pub const HEADER_LEN_V4: u64 = 80;
 /// Header length for v3 archives (backward compatibility).
 pub const HEADER_LEN_V3: u64 = 48;
 
 pub const FLAG_ENCRYPTED: u64 = 1 << 0;
 pub const FLAG_KDF_PASSWORD: u64 = 1 << 1;
+pub const FLAG_MANIFEST_ZSTD: u64 = 1 << 2;
+pub const FLAG_AES_GCM: u64 = 1 << 3;
+pub const FLAG_CHUNK_AEAD: u64 = 1 << 4;
+pub const FLAG_PARENT: u64 = 1 << 5;
+pub const FLAG_KEYRING: u64 = 1 << 6;
 
 #[derive(Debug, Clone, Copy)]
 pub struct Superblock {
@@ -43,100 +81,248 @@ pub struct Superblock {
     /// Per-archive random salt used for nonce derivation and password KDF.
     /// All-zeros for v3 archives (no KDF salt in older format).
     pub kdf_salt: [u8; 32],
+    /// Regular files in the manifest (hardlinks not counted). Zero before v5.
+    pub file_count: u64,
+    /// Sum of the files' uncompressed sizes. Zero before v5.
+    pub total_logical: u64,
+    /// Byte length of the keyring region after its guard; 0 without
+    /// `FLAG_KEYRING`. Stored in the keyring guard, not the superblock proper.
+    pub keyring_len: u64,
 }
 
 impl Superblock {
-    /// The actual header length based on this archive's version.
+    /// Offset of the manifest region based on this archive's version: the
+    /// superblock plus, from v6, the manifest's region guard.
     pub fn header_len(&self) -> u64 {
-        if self.version >= 4 {
-            HEADER_LEN
+        self.keyring_off() + self.keyring_len + self.guard_len()
+    }
+
+    /// Offset of the keyring region (just past its guard), for `FLAG_KEYRING`
+    /// archives; otherwise where the manifest guard starts.
+    pub fn keyring_off(&self) -> u64 {
+        superblock_len(self.version)
+            + if self.flags & FLAG_KEYRING != 0 {
+                REGION_GUARD_LEN
+            } else {
+                0
+            }
+    }
+
+    /// Bytes of region guard in front of each of the manifest and chunk table.
+    pub fn guard_len(&self) -> u64 {
+        if self.version >= 6 {
+            REGION_GUARD_LEN
         } else {
-            HEADER_LEN_V3
+            0
         }
     }
 
-    pub fn write_to(&self, mut w: impl Write) -> std::io::Result<()> {
-        w.write_all(MAGIC)?;
-        w.write_all(&self.version.to_le_bytes())?;
-        w.write_all(&self.manifest_len.to_le_bytes())?;
-        w.write_all(&self.chunk_table_off.to_le_bytes())?;
-        w.write_all(&self.chunk_count.to_le_bytes())?;
-        w.write_all(&self.data_off.to_le_bytes())?;
-        w.write_all(&self.flags.to_le_bytes())?;
-        w.write_all(&self.kdf_salt)?;
+    /// Write the manifest and chunk table guards for this superblock's offsets
+    /// and lengths. A no-op before v6.
+    pub fn write_guards<W: Write + Seek>(&self, mut w: W) -> std::io::Result<()> {
+        if self.guard_len() == 0 {
+            return Ok(());
+        }
+        for (off, magic, len) in self.guards()? {
+            w.seek(SeekFrom::Start(off))?;
+            w.write_all(magic)?;
+            w.write_all(&len.to_le_bytes())?;
+        }
         Ok(())
     }
 
+    /// Check that both region guards are where the superblock's offsets put
+    /// them and agree on the region lengths, so a bad offset or length is
+    /// reported as such instead of as a decode error. A no-op before v6.
+    pub fn check_guards<R: Read + Seek>(&self, mut r: R) -> std::io::Result<()> {
+        if self.guard_len() == 0 {
+            return Ok(());
+        }
+        for (off, magic, len) in self.guards()? {
+            let what = if magic == MANIFEST_MAGIC {
+                "manifest"
+            } else {
+                "chunk table"
+            };
+            let mut guard = [0u8; REGION_GUARD_LEN as usize];
+            r.seek(SeekFrom::Start(off))?;
+            r.read_exact(&mut guard).map_err(|e| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("{what} region guard at offset {off} unreadable: {e}"),
+                )
+            })?;
+            if &guard[..4] != magic {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!(
+                        "{what} region magic mismatch at offset {off}: found {:?}",
+                        &guard[..4]
+                    ),
+                ));
+            }
+            let stored = u64::from_le_bytes(guard[4..].try_into().unwrap());
+            if stored != len {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!(
+                        "{what} region length mismatch: guard records {stored} bytes, \
+                         superblock implies {len}"
+                    ),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// `(offset, magic, region length)` of each guard.
+    fn guards(&self) -> std::io::Result<[(u64, &'static [u8; 4], u64); 2]> {
+        let table_len = self.data_off.checked_sub(self.chunk_table_off);
+        let table_guard = self.chunk_table_off.checked_sub(REGION_GUARD_LEN);
+        let (Some(table_len), Some(table_guard)) = (table_len, table_guard) else {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!(
+                    "bad region offsets: chunk_table_off {} data_off {}",
+                    self.chunk_table_off, self.data_off
+                ),
+            ));
+        };
+        Ok([
+            (
+                self.keyring_off() + self.keyring_len,
+                MANIFEST_MAGIC,
+                self.manifest_len,
+            ),
+            (table_guard, TABLE_MAGIC, table_len),
+        ])
+    }
+
+    pub fn write_to(&self, mut w: impl Write) -> std::io::Result<()> {
+        let mut buf = Vec::with_capacity(HEADER_LEN as usize);
+        buf.extend_from_slice(MAGIC);
+        buf.extend_from_slice(&self.version.to_le_bytes());
+        buf.extend_from_slice(&self.manifest_len.to_le_bytes());
+        buf.extend_from_slice(&self.chunk_table_off.to_le_bytes());
+        buf.extend_from_slice(&self.chunk_count.to_le_bytes());
+        buf.extend_from_slice(&self.data_off.to_le_bytes());
+        buf.extend_from_slice(&self.flags.to_le_bytes());
+        buf.extend_from_slice(&self.kdf_salt);
+        // Rewriting an older archive's header in place must keep its length
+        if self.version >= 5 {
+            buf.extend_from_slice(&self.file_count.to_le_bytes());
+            buf.extend_from_slice(&self.total_logical.to_le_bytes());
+        }
+        if self.version >= 7 {
+            let crc = crc32fast::hash(&buf);
+            buf.extend_from_slice(&crc.to_le_bytes());
+        }
+        if self.flags & FLAG_KEYRING != 0 {
+            buf.extend_from_slice(KEYRING_MAGIC);
+            buf.extend_from_slice(&self.keyring_len.to_le_bytes());
+        }
+        w.write_all(&buf)
+    }
+
     pub fn read_from(mut r: impl Read) -> std::io::Result<Self> {
-        let mut magic = [0u8; 6];
-        r.read_exact(&mut magic)?;
-        if &magic != MAGIC {
+        let mut head = [0u8; 8];
+        r.read_exact(&mut head)?;
+        if &head[..6] != MAGIC {
             return Err(std::io::Error::new(
                 std::io::ErrorKind::InvalidData,
-                format!("bad archive magic: {:?}", &magic),
+                format!("bad archive magic: {:?}", &head[..6]),
             ));
         }
 
-        let mut v = [0u8; 2];
-        r.read_exact(&mut v)?;
-        let version = u16::from_le_bytes(v);
+        let version = u16::from_le_bytes([head[6], head[7]]);
         match version {
-            3 | 4 => {}
+            3..=7 => {}
             v => {
                 return Err(std::io::Error::new(
                     std::io::ErrorKind::InvalidData,
-                    format!("unsupported archive version: {v} (supported: 3, 4)"),
+                    format!("unsupported archive version: {v} (supported: 3 to 7)"),
                 ));
             }
         }
 
-        let mut ml = [0u8; 8];
-        r.read_exact(&mut ml)?;
-        let manifest_len = u64::from_le_bytes(ml);
+        let mut buf = vec![0u8; superblock_len(version) as usize];
+        buf[..8].copy_from_slice(&head);
+        r.read_exact(&mut buf[8..])?;
 
-        let mut cto = [0u8; 8];
-        r.read_exact(&mut cto)?;
-        let chunk_table_off = u64::from_le_bytes(cto);
-
-        let mut cc = [0u8; 8];
-        r.read_exact(&mut cc)?;
-        let chunk_count = u64::from_le_bytes(cc);
-
-        let mut doff = [0u8; 8];
-        r.read_exact(&mut doff)?;
-        let data_off = u64::from_le_bytes(doff);
-
-        let mut flags_buf = [0u8; 8];
-        r.read_exact(&mut flags_buf)?;
-        let flags = u64::from_le_bytes(flags_buf);
+        // Checked before any field is trusted: a flipped offset bit would
+        // otherwise send readers seeking anywhere in the file
+        if version >= 7 {
+            let (fields, stored) = buf.split_at(HEADER_LEN_V5 as usize);
+            let stored = u32::from_le_bytes(stored.try_into().unwrap());
+            let computed = crc32fast::hash(fields);
+            if stored != computed {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!(
+                        "superblock checksum mismatch: stored {stored:08x}, computed {computed:08x}"
+                    ),
+                ));
+            }
+        }
 
+        let u64_at = |off: usize| u64::from_le_bytes(buf[off..off + 8].try_into().unwrap());
         // v4+ stores a 32-byte KDF salt; v3 does not — default to all-zeros
         let kdf_salt = if version >= 4 {
-            let mut salt = [0u8; 32];
-            r.read_exact(&mut salt)?;
-            salt
+            buf[48..80].try_into().unwrap()
         } else {
             [0u8; 32]
         };
+        let (file_count, total_logical) = if version >= 5 {
+            (u64_at(80), u64_at(88))
+        } else {
+            (0, 0)
+        };
+        let flags = u64_at(40);
+        let keyring_len = if flags & FLAG_KEYRING != 0 {
+            let mut guard = [0u8; REGION_GUARD_LEN as usize];
+            r.read_exact(&mut guard)?;
+            if &guard[..4] != KEYRING_MAGIC {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("keyring region magic mismatch: found {:?}", &guard[..4]),
+                ));
+            }
+            u64::from_le_bytes(guard[4..].try_into().unwrap())
+        } else {
+            0
+        };
 
         Ok(Self {
             version,
-            manifest_len,
-            chunk_table_off,
-            chunk_count,
-            data_off,
+            manifest_len: u64_at(8),
+            chunk_table_off: u64_at(16),
+            chunk_count: u64_at(24),
+            data_off: u64_at(32),
             flags,
             kdf_salt,
+            file_count,
+            total_logical,
+            keyring_len,
         })
     }
 }
 
+/// Superblock length for `version`, without the manifest guard.
+fn superblock_len(version: u16) -> u64 {
+    match version {
+        7.. => HEADER_LEN,
+        5 | 6 => HEADER_LEN_V5,
+        4 => HEADER_LEN_V4,
+        _ => HEADER_LEN_V3,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::io::Cursor;
 
-    fn sample_v4() -> Superblock {
+    fn sample() -> Superblock {
         Superblock {
             version: VERSION,
             manifest_len: 512,
@@ -145,15 +331,22 @@ mod tests {
             data_off: 696,
             flags: FLAG_ENCRYPTED | FLAG_KDF_PASSWORD,
             kdf_salt: [0xABu8; 32],
+            file_count: 7,
+            total_logical: 123_456,
+            keyring_len: 0,
         }
     }
 
     #[test]
-    fn test_v4_roundtrip() {
-        let sb = sample_v4();
+    fn test_roundtrip() {
+        let sb = sample();
         let mut buf = Vec::new();
         sb.write_to(&mut buf).unwrap();
-        assert_eq!(buf.len(), HEADER_LEN as usize, "v4 header must be 80 bytes");
+        assert_eq!(
+            buf.len(),
+            HEADER_LEN as usize,
+            "superblock must be 100 bytes"
+        );
 
         let sb2 = Superblock::read_from(Cursor::new(&buf)).unwrap();
         assert_eq!(sb2.version, VERSION);
@@ -163,6 +356,104 @@ mod tests {
         assert_eq!(sb2.data_off, 696);
         assert_eq!(sb2.flags, FLAG_ENCRYPTED | FLAG_KDF_PASSWORD);
         assert_eq!(sb2.kdf_salt, [0xABu8; 32]);
+        assert_eq!(sb2.file_count, 7);
+        assert_eq!(sb2.total_logical, 123_456);
+    }
+
+    #[test]
+    fn test_region_guards_catch_bad_lengths_and_offsets() {
+        let sb = sample();
+        assert_eq!(sb.header_len(), HEADER_LEN + REGION_GUARD_LEN);
+        let mut buf = Cursor::new(vec![0u8; sb.data_off as usize]);
+        sb.write_guards(&mut buf).unwrap();
+        sb.check_guards(&mut buf).unwrap();
+
+        let bad_len = Superblock {
+            manifest_len: 513,
+            ..sb
+        };
+        let err = bad_len.check_guards(&mut buf).unwrap_err().to_string();
+        assert!(err.contains("manifest region length mismatch"), "{err}");
+
+        let bad_off = Superblock {
+            chunk_table_off: 590,
+            ..sb
+        };
+        let err = bad_off.check_guards(&mut buf).unwrap_err().to_string();
+        assert!(err.contains("chunk table region magic mismatch"), "{err}");
+
+        // Pre-v6 archives have no guards to check
+        let v5 = Superblock { version: 5, ..sb };
+        v5.check_guards(Cursor::new(Vec::new())).unwrap();
+        assert_eq!(v5.header_len(), HEADER_LEN_V5);
+    }
+
+    #[test]
+    fn test_keyring_guard_follows_superblock() {
+        let sb = Superblock {
+            flags: FLAG_ENCRYPTED | FLAG_KEYRING,
+            keyring_len: 388,
+            ..sample()
+        };
+        let mut buf = Vec::new();
+        sb.write_to(&mut buf).unwrap();
+        assert_eq!(buf.len(), (HEADER_LEN + REGION_GUARD_LEN) as usize);
+        assert_eq!(&buf[HEADER_LEN as usize..][..4], KEYRING_MAGIC);
+
+        let sb2 = Superblock::read_from(Cursor::new(&buf)).unwrap();
+        assert_eq!(sb2.keyring_len, 388);
+        assert_eq!(sb2.keyring_off(), HEADER_LEN + REGION_GUARD_LEN);
+        assert_eq!(sb2.header_len(), HEADER_LEN + 2 * REGION_GUARD_LEN + 388);
+
+        buf[HEADER_LEN as usize] ^= 0xFF;
+        let err = Superblock::read_from(Cursor::new(&buf)).unwrap_err();
+        assert!(err.to_string().contains("keyring region magic"), "{err}");
+    }
+
+    #[test]
+    fn test_checksum_catches_flipped_field() {
+        let mut buf = Vec::new();
+        sample().write_to(&mut buf).unwrap();
+        buf[33] ^= 0x01; // data_off
+        let err = Superblock::read_from(Cursor::new(&buf)).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert!(
+            err.to_string().contains("superblock checksum mismatch"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_v6_backward_compat() {
+        let sb = Superblock {
+            version: 6,
+            ..sample()
+        };
+        let mut buf = Vec::new();
+        sb.write_to(&mut buf).unwrap();
+        assert_eq!(buf.len(), HEADER_LEN_V5 as usize, "v6 has no checksum");
+
+        let sb2 = Superblock::read_from(Cursor::new(&buf)).unwrap();
+        assert_eq!(sb2.version, 6);
+        assert_eq!(sb2.data_off, 696);
+        assert_eq!(sb2.header_len(), HEADER_LEN_V5 + REGION_GUARD_LEN);
+    }
+
+    #[test]
+    fn test_v4_backward_compat() {
+        let sb = Superblock {
+            version: 4,
+            ..sample()
+        };
+        let mut buf = Vec::new();
+        sb.write_to(&mut buf).unwrap();
+        assert_eq!(buf.len(), HEADER_LEN_V4 as usize);
+
+        let sb2 = Superblock::read_from(Cursor::new(&buf)).unwrap();
+        assert_eq!(sb2.version, 4);
+        assert_eq!(sb2.kdf_salt, [0xABu8; 32]);
+        assert_eq!((sb2.file_count, sb2.total_logical), (0, 0));
+        assert_eq!(sb2.header_len(), HEADER_LEN_V4);
     }
 
     #[test]
@@ -187,7 +478,7 @@ mod tests {
     #[test]
     fn test_bad_magic_rejected() {
         let mut buf = b"BADMAG".to_vec();
-        buf.extend_from_slice(&[0u8; 74]);
+        buf.extend_from_slice(&[0u8; 90]);
         let err = Superblock::read_from(Cursor::new(&buf)).unwrap_err();
         assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
     }
@@ -8,6 +8,8 @@ pub const TAIL_LEN: u64 = 120;
 pub struct TailSummary {
     pub manifest_blake3: [u8; 32],
     pub chunktab_blake3: [u8; 32],
+    /// BLAKE3 over each chunk's compressed plaintext (post-AEAD, pre-decompression),
+    /// in chunk table order.
     pub data_blake3: [u8; 32],
     pub total_u: u64,
     pub total_c: u64,
@@ -1,16 +1,20 @@
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
 use std::io::{Cursor, Read};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 use crate::chunking::fastcdc::{ChunkParams, StreamingChunker};
-use crate::codec::zstdc::ZstdCompressor;
-use crate::codec::{CodecId, Compressor, get_decoder_u8};
+use crate::codec::{CodecId, get_decoder_u8};
 use crate::container::delta::DeltaStore;
 use crate::container::journal::{ChunkRef, EncMode, Journal, Loc, LogRecord};
 use crate::error::{ArxError, Result};
-use crate::index::inmem::InMemIndex;
+use crate::index::inmem::{Entry, InMemIndex};
+use crate::policy::Policy;
 use crate::read::opened::Opened;
+use crate::util::sanitize::sanitize_entry_path;
+use crate::util::varint::uvarint_len;
 use crate::{PackOptions, pack};
 
 pub struct DiffEntry {
@@ -31,6 +35,8 @@ pub struct CrudArchive {
     /// Opened view of the base archive (for reading Base-located chunks).
     base_opened: Arc<Opened>,
     min_gain: f32,
+    /// Codec new delta frames are trial-compressed with; see [`Self::set_codec`].
+    codec: CodecId,
 }
 
 impl CrudArchive {
@@ -59,10 +65,29 @@ impl CrudArchive {
 
         // Replay journal on top
         let mut journal = Journal::open(&log_path, enc)?;
+        let mut last_compaction = None;
         {
             let mut it = journal.iter()?;
             for rec in &mut it {
-                index.apply(&rec?);
+                let rec = rec?;
+                index.apply(&rec);
+                last_compaction = match rec {
+                    LogRecord::CompactDelta { delta_blake3, .. } => Some(delta_blake3),
+                    _ => None,
+                };
+            }
+        }
+
+        // An interrupted `compact_delta` committed only if its record is the
+        // last one and describes this very file; otherwise it never happened.
+        let gc_path = with_ext(base, "arx.delta.gc");
+        if gc_path.exists() {
+            let mut h = blake3::Hasher::new();
+            std::io::copy(&mut File::open(&gc_path)?, &mut h)?;
+            if last_compaction == Some(*h.finalize().as_bytes()) {
+                std::fs::rename(&gc_path, &delta_path)?;
+            } else {
+                std::fs::remove_file(&gc_path)?;
             }
         }
 
@@ -77,6 +102,7 @@ impl CrudArchive {
             delta,
             base_opened: Arc::new(base_opened),
             min_gain: 0.05,
+            codec: CodecId::default_for_pack(),
         })
     }
 
@@ -84,7 +110,32 @@ impl CrudArchive {
         Self::open_with_crypto(base, None, [0u8; 32])
     }
 
-    /// Add a file to the overlay using FastCDC chunking + Zstd compression.
+    /// Compress the frames of later puts with `codec` instead of the default
+    /// (Zstd, or Store in builds without the `zstd` feature). Each frame keeps
+    /// the codec it was written with in its `ChunkRef`, so frames of different
+    /// codecs mix freely. `ZstdDict` is refused: the overlay has no dictionary.
+    pub fn set_codec(&mut self, codec: CodecId) -> Result<()> {
+        if codec == CodecId::ZstdDict {
+            return Err(ArxError::Format(
+                "the overlay cannot use zstd+dict: it has no dictionary".into(),
+            ));
+        }
+        get_decoder_u8(codec as u8)?;
+        self.codec = codec;
+        Ok(())
+    }
+
+    /// Add a file to the overlay using FastCDC chunking + compression with the
+    /// overlay's codec ([`Self::set_codec`]; Zstd by default).
+    ///
+    /// Chunks already known to the index (from the base archive or an earlier
+    /// PUT) are referenced in place instead of being appended to the delta, so
+    /// re-putting a lightly edited file only stores the chunks that changed.
+    ///
+    /// The overlay's [`Policy`] is enforced before the PUT is journaled: a put
+    /// that would break a limit fails with `ArxError::Format`. Frames it
+    /// already appended stay in the delta unreferenced until
+    /// [`Self::compact_delta`] drops them.
     pub fn put_file<P: AsRef<Path>>(
         &mut self,
         src: P,
@@ -92,14 +143,34 @@ impl CrudArchive {
         mode: u32,
         mtime: u64,
     ) -> Result<()> {
+        let dst_path = normalize_path(dst_path)?;
+        if dst_path.is_empty() {
+            return Err(ArxError::Format("put: destination path is empty".into()));
+        }
+        let policy = self.index.policy.clone();
+        let replaced = self.index.by_path.get(&dst_path).map(|e| e.size);
+        if let Some(max) = policy.max_entries
+            && replaced.is_none()
+            && self.index.by_path.len() as u64 >= max
+        {
+            return Err(ArxError::Format(format!(
+                "put {dst_path}: overlay already has {} entries, policy max_entries is {max}",
+                self.index.by_path.len()
+            )));
+        }
         let src = src.as_ref();
         let mut f = File::open(src)?;
         let mut chunker = StreamingChunker::new(ChunkParams::default());
-        let zstd = ZstdCompressor;
+        let codec_id = self.codec;
+        let compressor = get_decoder_u8(codec_id as u8)?;
         let min_gain = self.min_gain;
 
         let mut chunk_refs: Vec<ChunkRef> = Vec::new();
         let mut total = 0u64;
+        // Frames this put appended, by hash, with the refs that use them; they
+        // join the index only once the policy has passed.
+        let mut added: HashMap<[u8; 32], Vec<usize>> = HashMap::new();
+        let (mut new_u, mut new_stored) = (0u64, 0u64);
 
         loop {
             let mut buf = Vec::new();
@@ -112,18 +183,38 @@ impl CrudArchive {
             // Hash uncompressed chunk
             let hash = *blake3::hash(&buf).as_bytes();
 
+            if let Some(&(loc, off, len, codec)) = self.index.by_chunk.get(&hash) {
+                chunk_refs.push(ChunkRef {
+                    loc,
+                    off,
+                    len,
+                    codec,
+                    blake3: hash,
+                });
+                continue;
+            }
+            // Repeats within this same file share the first copy's frame
+            if let Some(refs) = added.get_mut(&hash) {
+                chunk_refs.push(chunk_refs[refs[0]].clone());
+                refs.push(chunk_refs.len() - 1);
+                continue;
+            }
+
             // Trial compress
             let mut compressed = Vec::with_capacity(n);
-            zstd.compress(&mut buf.as_slice(), &mut compressed, 3)?;
+            compressor.compress(&mut buf.as_slice(), &mut compressed, 3)?;
 
             let (payload, codec) =
                 if (n as f64 - compressed.len() as f64) >= n as f64 * min_gain as f64 {
-                    (compressed, CodecId::Zstd)
+                    (compressed, codec_id)
                 } else {
                     (buf, CodecId::Store)
                 };
 
             let (off, len) = self.delta.append_frame(&payload)?;
+            new_u += n as u64;
+            new_stored += payload.len() as u64;
+            added.insert(hash, vec![chunk_refs.len()]);
             chunk_refs.push(ChunkRef {
                 loc: Loc::Delta,
                 off,
@@ -131,15 +222,96 @@ impl CrudArchive {
                 codec,
                 blake3: hash,
             });
+            // No point reading on once the delta is past its limit
+            if policy
+                .max_delta_bytes
+                .is_some_and(|max| self.delta.next_off > max)
+            {
+                break;
+            }
+        }
+
+        if let Some(msg) = policy_violation(
+            &policy,
+            &dst_path,
+            &self.index,
+            replaced,
+            total,
+            (new_u, new_stored),
+            self.delta.next_off,
+        ) {
+            return Err(ArxError::Format(msg));
+        }
+        for (hash, refs) in &added {
+            let r = &chunk_refs[refs[0]];
+            self.index
+                .by_chunk
+                .insert(*hash, (Loc::Delta, r.off, r.len, r.codec));
         }
 
         let rec = LogRecord::Put {
-            path: dst_path.to_string(),
+            path: dst_path,
             mode,
             mtime,
             size: total,
             chunks: chunk_refs.clone(),
         };
+        // A durable record must not point at delta frames still in the page cache
+        if self.journal.is_durable() {
+            self.delta.sync()?;
+        }
+        self.journal.append(&rec)?;
+        self.index.apply(&rec);
+        Ok(())
+    }
+
+    /// Rewrite the delta with only the frames live entries still reference,
+    /// dropping overwritten and deleted content. Returns the delta's length
+    /// before and the bytes its live frames take after.
+    ///
+    /// The rewritten delta is built beside the old one, committed by a synced
+    /// `CompactDelta` journal record, then renamed into place; reopening after
+    /// a crash finishes or discards the compaction (see `open_with_crypto`).
+    /// Sealed frames are re-sealed at fresh offsets, so their delta keeps its
+    /// size on disk but only the live frames take up space.
+    pub fn compact_delta(&mut self) -> Result<(u64, u64)> {
+        let mut live: Vec<(u64, u64)> = self
+            .index
+            .by_path
+            .values()
+            .flat_map(|e| &e.chunks)
+            .filter(|c| c.loc == Loc::Delta)
+            .map(|c| (c.off, c.len))
+            .collect();
+        live.sort_unstable();
+        live.dedup();
+
+        let before = self.delta.next_off;
+        let gc_path = with_ext(&self.base_path, "arx.delta.gc");
+        let (mut store, moves) = self.delta.compact_into(&live, &gc_path)?;
+        let after = moves
+            .iter()
+            .map(|&(_, _, len)| uvarint_len(len) as u64 + len)
+            .sum();
+        let mut h = blake3::Hasher::new();
+        std::io::copy(&mut File::open(&gc_path)?, &mut h)?;
+        let rec = LogRecord::CompactDelta {
+            moves,
+            delta_blake3: *h.finalize().as_bytes(),
+        };
+        self.journal.append(&rec)?;
+        self.journal.sync()?;
+        std::fs::rename(&gc_path, &self.delta_path)?;
+        store.path = self.delta_path.clone();
+        self.delta = store;
+        self.index.apply(&rec);
+
+        Ok((before, after))
+    }
+
+    /// Journal `policy` as the overlay's limits for subsequent puts.
+    pub fn set_policy(&mut self, policy: Policy) -> Result<()> {
+        let rec = LogRecord::SetPolicy(policy);
         self.journal.append(&rec)?;
         self.index.apply(&rec);
         Ok(())
@@ -147,7 +319,7 @@ impl CrudArchive {
 
     pub fn delete_path(&mut self, path: &str) -> Result<()> {
         let rec = LogRecord::Delete {
-            path: path.to_string(),
+            path: normalize_path(path)?,
         };
         self.journal.append(&rec)?;
         self.index.apply(&rec);
@@ -155,9 +327,11 @@ impl CrudArchive {
     }
 
     pub fn delete_path_recursive(&mut self, path: &str) -> Result<()> {
-        // Collect all overlay paths that are under `path`
-        let prefix = if path.ends_with('/') {
-            path.to_string()
+        // Collect all overlay paths that are under `path` ("" is the root)
+        let path = normalize_path(path)?;
+        let path = path.as_str();
+        let prefix = if path.is_empty() {
+            String::new()
         } else {
             format!("{path}/")
         };
@@ -178,71 +352,146 @@ impl CrudArchive {
     }
 
     pub fn rename(&mut self, from: &str, to: &str) -> Result<()> {
-        if !self.index.by_path.contains_key(from) {
+        let (from, to) = (normalize_path(from)?, normalize_path(to)?);
+        if to.is_empty() {
+            return Err(ArxError::Format("rename: target path is empty".into()));
+        }
+        if !self.index.by_path.contains_key(&from) {
             return Err(ArxError::Format(format!(
                 "rename: source not found: {from}"
             )));
         }
-        if self.index.by_path.contains_key(to) {
+        if self.index.by_path.contains_key(&to) {
             return Err(ArxError::Format(format!(
                 "rename: target already exists: {to}"
             )));
         }
-        let rec = LogRecord::Rename {
-            from: from.to_string(),
-            to: to.to_string(),
-        };
+        let rec = LogRecord::Rename { from, to };
         self.journal.append(&rec)?;
         self.index.apply(&rec);
         Ok(())
     }
 
+    /// Every record in the journal, oldest first, as replayed on open: puts,
+    /// deletes, renames, policy changes, notes and delta compactions. Read-only
+    /// (it takes `&mut self` to seek the journal's handle); later appends land
+    /// at the end as usual. Only `Put` carries a time, the file's `mtime`.
+    pub fn journal_records(&mut self) -> Result<Vec<LogRecord>> {
+        self.journal.iter()?.collect()
+    }
+
+    /// The overlay index as it stood after the first `n` journal records:
+    /// the base replayed with only `records[..n]` (`n` past the end means all
+    /// of them). Delta frames are addressed by offset and never rewritten in
+    /// place, so older versions stay readable — except across a later
+    /// `CompactDelta`, which moves live frames (remapped here) and drops the
+    /// rest; a snapshot still referencing a dropped frame is an error.
+    pub fn index_at(&mut self, n: usize) -> Result<InMemIndex> {
+        let records = self.journal_records()?;
+        let n = n.min(records.len());
+        let mut index = InMemIndex::from_base(&self.base_opened)?;
+        for rec in &records[..n] {
+            index.apply(rec);
+        }
+        for (i, rec) in records.iter().enumerate().skip(n) {
+            let LogRecord::CompactDelta { moves, .. } = rec else {
+                continue;
+            };
+            let live: HashSet<u64> = moves.iter().map(|&(old, _, _)| old).collect();
+            for (path, entry) in &index.by_path {
+                if entry
+                    .chunks
+                    .iter()
+                    .any(|c| c.loc == Loc::Delta && !live.contains(&c.off))
+                {
+                    return Err(ArxError::Format(format!(
+                        "history at record {n} is gone: {path} was dropped by the compaction at record {i}"
+                    )));
+                }
+            }
+            index.apply(rec);
+        }
+        Ok(index)
+    }
+
     /// Open a reader for a path in the merged overlay (base + journal).
     ///
-    /// - Files that exist only in the base: delegates to `Opened::open_reader` (streaming, no buffer).
-    /// - Files that exist only in delta (added via `put_file`): decompresses and buffers.
-    /// - Mixed base+delta files: not yet supported; returns an error suggesting `sync` first.
+    /// - Files unchanged from the base: delegates to `Opened::open_reader` (streaming, no buffer).
+    /// - Anything else: a [`ChainReader`] over the file's chunks, reading delta
+    ///   frames from the delta store and base chunks via `Opened::read_chunk` one
+    ///   chunk at a time as the caller reads.
     pub fn open_reader(&self, path: &str) -> Result<Box<dyn Read + Send + '_>> {
-        let entry = self.index.by_path.get(path).ok_or_else(|| {
-            std::io::Error::new(
-                std::io::ErrorKind::NotFound,
-                format!("path not found: {path}"),
-            )
-        })?;
+        let path = &normalize_path(path)?;
+        let entry = self
+            .index
+            .by_path
+            .get(path)
+            .ok_or_else(|| not_found(path))?;
+        self.entry_reader(path, Cow::Borrowed(entry))
+    }
+
+    /// [`open_reader`](Self::open_reader) against the snapshot from
+    /// [`index_at(n)`](Self::index_at): `path` as it read after the first `n`
+    /// journal records.
+    pub fn open_reader_at(&mut self, path: &str, n: usize) -> Result<Box<dyn Read + Send + '_>> {
+        let path = &normalize_path(path)?;
+        let entry = self
+            .index_at(n)?
+            .by_path
+            .remove(path)
+            .ok_or_else(|| not_found(path))?;
+        self.entry_reader(path, Cow::Owned(entry))
+    }
 
+    fn entry_reader<'a>(
+        &'a self,
+        path: &str,
+        entry: Cow<'a, Entry>,
+    ) -> Result<Box<dyn Read + Send + 'a>> {
         // Empty file (0 bytes) has no chunks — return an empty reader directly.
         // Without this guard, Iterator::all() returns true vacuously for both
         // all_base and all_delta, causing all_base to win and incorrectly
         // delegate to the base archive (which doesn't have the file).
+        // A base file that is all sparse hole also has no chunks but a nonzero size.
         if entry.chunks.is_empty() {
+            if entry.size > 0 {
+                return Ok(Box::new(self.base_opened.open_reader(path)?));
+            }
             return Ok(Box::new(Cursor::new(Vec::<u8>::new())));
         }
 
-        let all_base = entry.chunks.iter().all(|c| c.loc == Loc::Base);
-        let all_delta = entry.chunks.iter().all(|c| c.loc == Loc::Delta);
-
-        if all_base {
+        if self.matches_base(path, &entry) {
             // Delegate to the base archive reader — handles decrypt+decompress efficiently
             let r = self.base_opened.open_reader(path)?;
             return Ok(Box::new(r));
         }
 
-        if all_delta {
-            let mut out = Vec::with_capacity(entry.size as usize);
-            for c in &entry.chunks {
-                let mut r = self.delta.read_frame(c.off, c.len)?;
-                let mut compressed = Vec::new();
-                std::io::copy(&mut r, &mut compressed)?;
-                // Decompress based on stored codec
-                let plain = decompress_bytes(&compressed, c.codec)?;
-                out.extend_from_slice(&plain);
-            }
-            return Ok(Box::new(Cursor::new(out)));
-        }
+        let chunks = match entry {
+            Cow::Borrowed(e) => Cow::Borrowed(e.chunks.as_slice()),
+            Cow::Owned(e) => Cow::Owned(e.chunks),
+        };
+        Ok(Box::new(ChainReader {
+            base: &self.base_opened,
+            delta: &self.delta,
+            chunks,
+            next: 0,
+            cur: Cursor::new(Vec::new()),
+        }))
+    }
 
-        Err(ArxError::Format(
-            "mixed Base/Delta files are not yet supported in open_reader — run `crud sync` first to compact the overlay".into()
-        ))
+    /// Whether `entry` is exactly the base archive's file at `path`. A PUT can
+    /// dedup entirely against base chunks, so "all Base" alone does not mean the
+    /// base file at this path has the same content.
+    fn matches_base(&self, path: &str, entry: &Entry) -> bool {
+        entry.chunks.iter().all(|c| c.loc == Loc::Base)
+            && self.base_opened.file_entry(path).is_ok_and(|fe| {
+                fe.u_size == entry.size
+                    && fe
+                        .chunk_refs
+                        .iter()
+                        .map(|r| r.id)
+                        .eq(entry.chunks.iter().map(|c| c.off))
+            })
     }
 
     /// Return a diff between the current overlay state and the original base.
@@ -279,17 +528,16 @@ impl CrudArchive {
             });
         }
 
-        // Modified (in both, but overlay has delta chunks)
+        // Modified (in both, but overlay content differs from the base file)
         for path in overlay_paths.intersection(&base_paths) {
-            if let Some(entry) = self.index.by_path.get(*path) {
-                let has_delta = entry.chunks.iter().any(|c| c.loc == Loc::Delta);
-                if has_delta {
-                    entries.push(DiffEntry {
-                        kind: "M",
-                        path: path.to_string(),
-                        from: None,
-                    });
-                }
+            if let Some(entry) = self.index.by_path.get(*path)
+                && !self.matches_base(path, entry)
+            {
+                entries.push(DiffEntry {
+                    kind: "M",
+                    path: path.to_string(),
+                    from: None,
+                });
             }
         }
 
@@ -312,6 +560,7 @@ impl CrudArchive {
 
         let tmp = tempfile::tempdir()?;
         for (path, _) in arc.index.by_path.iter() {
+            // Journals written before paths were normalized may still hold "/a"
             let abs = tmp.path().join(path.trim_start_matches('/'));
             if let Some(parent) = abs.parent() {
                 std::fs::create_dir_all(parent)?;
@@ -335,11 +584,8 @@ impl CrudArchive {
         let final_out = out
             .map(|p| p.to_path_buf())
             .unwrap_or_else(|| archive.to_path_buf());
-        let tmp_out = final_out.with_extension("arx.tmp");
-        pack(&refs, &tmp_out, Some(&opts))?;
-
-        // Atomic rename if writing in-place
-        std::fs::rename(&tmp_out, &final_out)?;
+        // pack replaces the base atomically when writing in place
+        pack(&refs, &final_out, Some(&opts))?;
 
         // Clean up sidecar files — all changes are now in the new base archive.
         let _ = std::fs::remove_file(&arc.log_path);
@@ -387,14 +633,119 @@ impl CrudArchive {
     }
 }
 
+/// Streams an overlay file's chunks in order, each decoded from whichever
+/// store holds it only once the previous one has been read, so at most one
+/// chunk is buffered.
+pub struct ChainReader<'a> {
+    base: &'a Opened,
+    delta: &'a DeltaStore,
+    chunks: Cow<'a, [ChunkRef]>,
+    next: usize,
+    cur: Cursor<Vec<u8>>,
+}
+
+impl ChainReader<'_> {
+    fn load(&self, c: &ChunkRef) -> Result<Vec<u8>> {
+        match c.loc {
+            Loc::Base => self.base.read_chunk(c.off),
+            Loc::Delta => {
+                let mut compressed = Vec::new();
+                self.delta
+                    .read_frame(c.off, c.len)?
+                    .read_to_end(&mut compressed)?;
+                // Decompress based on stored codec
+                decompress_bytes(&compressed, c.codec)
+            }
+        }
+    }
+}
+
+impl Read for ChainReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            let n = self.cur.read(buf)?;
+            if n > 0 || buf.is_empty() {
+                return Ok(n);
+            }
+            let Some(c) = self.chunks.get(self.next) else {
+                return Ok(0);
+            };
+            let plain = self.load(c).map_err(|e| match e {
+                ArxError::Io(e) => e,
+                e => std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string()),
+            })?;
+            self.cur = Cursor::new(plain);
+            self.next += 1;
+        }
+    }
+}
+
 // ── Private helpers ───────────────────────────────────────────────────────────
 
+/// The limit a put of `total` bytes to `path` breaks, if any. `new` is the
+/// uncompressed and stored size of the chunks it adds to the delta, which
+/// would then be `delta_len` bytes; a put made entirely of known chunks has no
+/// ratio to check.
+fn policy_violation(
+    policy: &Policy,
+    path: &str,
+    index: &InMemIndex,
+    replaced: Option<u64>,
+    total: u64,
+    (new_u, new_stored): (u64, u64),
+    delta_len: u64,
+) -> Option<String> {
+    if let Some(max) = policy.max_delta_bytes
+        && delta_len > max
+    {
+        return Some(format!(
+            "put {path}: delta would grow to {delta_len} bytes, policy max_delta_bytes is {max}"
+        ));
+    }
+    if let Some(max) = policy.max_uncompressed {
+        let logical = (index.stats.logical_bytes)
+            .saturating_sub(replaced.unwrap_or(0))
+            .saturating_add(total);
+        if logical > max {
+            return Some(format!(
+                "put {path}: overlay would hold {logical} bytes, policy max_uncompressed is {max}"
+            ));
+        }
+    }
+    if let Some(min) = policy.min_compression_ratio
+        && new_stored > 0
+    {
+        let ratio = new_u as f64 / new_stored as f64;
+        if ratio < min as f64 {
+            return Some(format!(
+                "put {path}: compression ratio {ratio:.2} is below policy min_compression_ratio {min}"
+            ));
+        }
+    }
+    None
+}
+
+fn not_found(path: &str) -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::NotFound,
+        format!("path not found: {path}"),
+    )
+}
+
 fn decompress_bytes(compressed: &[u8], codec: CodecId) -> Result<Vec<u8>> {
     let mut out = Vec::new();
-    get_decoder_u8(codec as u8)?.decompress(&mut compressed.as_ref(), &mut out)?;
+    get_decoder_u8(codec as u8)?.decompress(&mut &compressed[..], &mut out)?;
     Ok(out)
 }
 
+/// Canonical overlay path, matching the base manifest's convention: relative,
+/// `/`-separated, no leading slash, `.` and empty segments dropped. `..` is
+/// rejected rather than resolved. `"/a/b"`, `"a//b"` and `"./a/b"` all become
+/// `"a/b"`; the root itself is `""`.
+pub fn normalize_path(path: &str) -> Result<String> {
+    sanitize_entry_path(path.trim_start_matches('/'))
+}
+
 fn with_ext(base: &Path, ext: &str) -> PathBuf {
     let mut p = PathBuf::from(base);
     if let Some(os) = p.file_name() {
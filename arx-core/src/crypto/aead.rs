@@ -1,9 +1,11 @@
+use aes_gcm::Aes256Gcm;
 use blake3::Hasher;
 use chacha20poly1305::{
     Key, XChaCha20Poly1305, XNonce,
-    aead::{Aead, KeyInit},
+    aead::{Aead, AeadInPlace, KeyInit, Payload},
 };
 
+use crate::container::superblock::FLAG_AES_GCM;
 use crate::error::{ArxError, Result};
 
 pub const TAG_LEN: usize = 16;
@@ -12,49 +14,127 @@ pub const TAG_LEN: usize = 16;
 #[derive(Clone)]
 pub struct AeadKey(pub [u8; 32]);
 
+/// Cipher used for every encrypted region of an archive.
+/// Both variants have a 16-byte tag, so region sizes do not depend on the choice.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AeadAlg {
+    #[default]
+    XChaCha20Poly1305,
+    /// Faster on CPUs with AES-NI; uses a 12-byte nonce.
+    Aes256Gcm,
+}
+
+impl AeadAlg {
+    /// The algorithm recorded in superblock `flags`.
+    pub fn from_flags(flags: u64) -> Self {
+        if flags & FLAG_AES_GCM != 0 {
+            AeadAlg::Aes256Gcm
+        } else {
+            AeadAlg::XChaCha20Poly1305
+        }
+    }
+
+    /// Superblock flag bits to set for this algorithm.
+    pub fn flag(self) -> u64 {
+        match self {
+            AeadAlg::XChaCha20Poly1305 => 0,
+            AeadAlg::Aes256Gcm => FLAG_AES_GCM,
+        }
+    }
+}
+
+/// Per-region nonce, sized for the algorithm it was derived for.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AeadNonce {
+    XChaCha20Poly1305(XNonce),
+    Aes256Gcm(aes_gcm::Nonce<aes_gcm::aead::consts::U12>),
+}
+
 /// Region IDs for domain separation — each region gets a unique nonce.
+#[derive(Clone, Copy)]
 pub enum Region {
     Manifest = 1,
     ChunkTable = 2,
     ChunkData = 3, // per-chunk: chunk_id included in nonce
+    KeyWrap = 4,   // per-recipient: slot index included in nonce
 }
 
-/// Derive a 24-byte XChaCha20 nonce.
-/// nonce = blake3(key_salt || region_byte || chunk_id_le).take(24)
-pub fn derive_nonce(key_salt: &[u8; 32], region: Region, chunk_id: u64) -> XNonce {
+/// Derive the nonce for one region.
+/// nonce = blake3(key_salt || region_byte || chunk_id_le), truncated to 24
+/// bytes for XChaCha20 or 12 bytes for AES-GCM.
+pub fn derive_nonce(alg: AeadAlg, key_salt: &[u8; 32], region: Region, chunk_id: u64) -> AeadNonce {
     let mut h = Hasher::new();
     h.update(key_salt);
     h.update(&[region as u8]);
     h.update(&chunk_id.to_le_bytes());
     let out = h.finalize();
-    XNonce::from_slice(&out.as_bytes()[..24]).to_owned()
+    match alg {
+        AeadAlg::XChaCha20Poly1305 => {
+            AeadNonce::XChaCha20Poly1305(XNonce::from_slice(&out.as_bytes()[..24]).to_owned())
+        }
+        AeadAlg::Aes256Gcm => {
+            AeadNonce::Aes256Gcm(aes_gcm::Nonce::from_slice(&out.as_bytes()[..12]).to_owned())
+        }
+    }
+}
+
+/// Encrypt `plaintext` with the nonce's algorithm. Returns ciphertext + 16-byte tag,
+/// or `ArxError::Format` if the cipher rejects the input (e.g. it exceeds the
+/// algorithm's maximum message length).
+pub fn seal_whole(
+    key: &AeadKey,
+    nonce: &AeadNonce,
+    ad: &[u8],
+    plaintext: &[u8],
+) -> Result<Vec<u8>> {
+    let payload = Payload {
+        msg: plaintext,
+        aad: ad,
+    };
+    match nonce {
+        AeadNonce::XChaCha20Poly1305(n) => {
+            XChaCha20Poly1305::new(Key::from_slice(&key.0)).encrypt(n, payload)
+        }
+        AeadNonce::Aes256Gcm(n) => {
+            Aes256Gcm::new(aes_gcm::Key::<Aes256Gcm>::from_slice(&key.0)).encrypt(n, payload)
+        }
+    }
+    .map_err(|_| ArxError::Format("aead encrypt failed".into()))
 }
 
-/// Encrypt `plaintext` with XChaCha20-Poly1305. Returns ciphertext + 16-byte tag.
-/// Panics only if the cipher itself fails (never happens with valid key/nonce).
-pub fn seal_whole(key: &AeadKey, nonce: &XNonce, ad: &[u8], plaintext: &[u8]) -> Vec<u8> {
-    let aead = XChaCha20Poly1305::new(Key::from_slice(&key.0));
-    aead.encrypt(
-        nonce,
-        chacha20poly1305::aead::Payload {
-            msg: plaintext,
-            aad: ad,
-        },
-    )
-    .expect("XChaCha20 encrypt should never fail for valid key/nonce")
+/// [`seal_whole`] without the second buffer: encrypts `buf` in place and
+/// appends the 16-byte tag, leaving exactly the bytes `seal_whole` returns.
+pub fn seal_in_place(key: &AeadKey, nonce: &AeadNonce, ad: &[u8], buf: &mut Vec<u8>) -> Result<()> {
+    match nonce {
+        AeadNonce::XChaCha20Poly1305(n) => {
+            XChaCha20Poly1305::new(Key::from_slice(&key.0)).encrypt_in_place(n, ad, buf)
+        }
+        AeadNonce::Aes256Gcm(n) => Aes256Gcm::new(aes_gcm::Key::<Aes256Gcm>::from_slice(&key.0))
+            .encrypt_in_place(n, ad, buf),
+    }
+    .map_err(|_| ArxError::Format("aead encrypt failed".into()))
 }
 
 /// Decrypt and authenticate `ciphertext`. Returns `Err(AeadError)` if the tag
-/// does not match — indicating a wrong key or tampered ciphertext.
-pub fn open_whole(key: &AeadKey, nonce: &XNonce, ad: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>> {
-    let aead = XChaCha20Poly1305::new(Key::from_slice(&key.0));
-    aead.decrypt(
-        nonce,
-        chacha20poly1305::aead::Payload {
-            msg: ciphertext,
-            aad: ad,
-        },
-    )
+/// does not match — indicating a wrong key, wrong algorithm, or tampered ciphertext.
+pub fn open_whole(
+    key: &AeadKey,
+    nonce: &AeadNonce,
+    ad: &[u8],
+    ciphertext: &[u8],
+) -> Result<Vec<u8>> {
+    let payload = Payload {
+        msg: ciphertext,
+        aad: ad,
+    };
+    match nonce {
+        AeadNonce::XChaCha20Poly1305(n) => {
+            XChaCha20Poly1305::new(Key::from_slice(&key.0)).decrypt(n, payload)
+        }
+        AeadNonce::Aes256Gcm(n) => {
+            Aes256Gcm::new(aes_gcm::Key::<Aes256Gcm>::from_slice(&key.0)).decrypt(n, payload)
+        }
+    }
     .map_err(|_| ArxError::AeadError)
 }
 
@@ -72,9 +152,14 @@ mod tests {
     #[test]
     fn test_seal_open_roundtrip() {
         let key = test_key();
-        let nonce = derive_nonce(&test_salt(), Region::Manifest, 0);
+        let nonce = derive_nonce(
+            AeadAlg::XChaCha20Poly1305,
+            &test_salt(),
+            Region::Manifest,
+            0,
+        );
         let plaintext = b"hello arx";
-        let ct = seal_whole(&key, &nonce, b"manifest", plaintext);
+        let ct = seal_whole(&key, &nonce, b"manifest", plaintext).unwrap();
         let pt = open_whole(&key, &nonce, b"manifest", &ct).unwrap();
         assert_eq!(pt, plaintext);
     }
@@ -83,8 +168,13 @@ mod tests {
     fn test_wrong_key_returns_aead_error() {
         let key = test_key();
         let wrong_key = AeadKey([0xFFu8; 32]);
-        let nonce = derive_nonce(&test_salt(), Region::ChunkData, 7);
-        let ct = seal_whole(&key, &nonce, b"chunk", b"secret data");
+        let nonce = derive_nonce(
+            AeadAlg::XChaCha20Poly1305,
+            &test_salt(),
+            Region::ChunkData,
+            7,
+        );
+        let ct = seal_whole(&key, &nonce, b"chunk", b"secret data").unwrap();
         let err = open_whole(&wrong_key, &nonce, b"chunk", &ct).unwrap_err();
         assert!(matches!(err, ArxError::AeadError));
     }
@@ -92,8 +182,13 @@ mod tests {
     #[test]
     fn test_tampered_ciphertext_returns_aead_error() {
         let key = test_key();
-        let nonce = derive_nonce(&test_salt(), Region::ChunkTable, 0);
-        let mut ct = seal_whole(&key, &nonce, b"chunktab", b"table data");
+        let nonce = derive_nonce(
+            AeadAlg::XChaCha20Poly1305,
+            &test_salt(),
+            Region::ChunkTable,
+            0,
+        );
+        let mut ct = seal_whole(&key, &nonce, b"chunktab", b"table data").unwrap();
         ct[0] ^= 0x01; // flip one bit
         let err = open_whole(&key, &nonce, b"chunktab", &ct).unwrap_err();
         assert!(matches!(err, ArxError::AeadError));
@@ -102,16 +197,59 @@ mod tests {
     #[test]
     fn test_nonce_determinism() {
         let salt = test_salt();
-        let n1 = derive_nonce(&salt, Region::ChunkData, 42);
-        let n2 = derive_nonce(&salt, Region::ChunkData, 42);
+        let n1 = derive_nonce(AeadAlg::XChaCha20Poly1305, &salt, Region::ChunkData, 42);
+        let n2 = derive_nonce(AeadAlg::XChaCha20Poly1305, &salt, Region::ChunkData, 42);
         assert_eq!(n1, n2);
     }
 
     #[test]
     fn test_different_regions_different_nonces() {
         let salt = test_salt();
-        let n_manifest = derive_nonce(&salt, Region::Manifest, 0);
-        let n_table = derive_nonce(&salt, Region::ChunkTable, 0);
+        let n_manifest = derive_nonce(AeadAlg::XChaCha20Poly1305, &salt, Region::Manifest, 0);
+        let n_table = derive_nonce(AeadAlg::XChaCha20Poly1305, &salt, Region::ChunkTable, 0);
         assert_ne!(n_manifest, n_table);
     }
+
+    #[test]
+    fn test_seal_open_roundtrip_both_algs() {
+        let key = test_key();
+        for alg in [AeadAlg::XChaCha20Poly1305, AeadAlg::Aes256Gcm] {
+            let nonce = derive_nonce(alg, &test_salt(), Region::ChunkData, 3);
+            let ct = seal_whole(&key, &nonce, b"chunk", b"payload").unwrap();
+            assert_eq!(ct.len(), b"payload".len() + TAG_LEN);
+            assert_eq!(open_whole(&key, &nonce, b"chunk", &ct).unwrap(), b"payload");
+        }
+    }
+
+    #[test]
+    fn test_gcm_nonce_is_truncated_hash() {
+        let salt = test_salt();
+        let x = derive_nonce(AeadAlg::XChaCha20Poly1305, &salt, Region::Manifest, 0);
+        let g = derive_nonce(AeadAlg::Aes256Gcm, &salt, Region::Manifest, 0);
+        match (x, g) {
+            (AeadNonce::XChaCha20Poly1305(x), AeadNonce::Aes256Gcm(g)) => {
+                assert_eq!(g.len(), 12);
+                assert_eq!(&x[..12], &g[..]);
+            }
+            _ => panic!("nonce variant does not match algorithm"),
+        }
+    }
+
+    #[test]
+    fn test_alg_mismatch_returns_aead_error() {
+        let key = test_key();
+        let salt = test_salt();
+        let x = derive_nonce(AeadAlg::XChaCha20Poly1305, &salt, Region::ChunkTable, 0);
+        let g = derive_nonce(AeadAlg::Aes256Gcm, &salt, Region::ChunkTable, 0);
+        let ct = seal_whole(&key, &x, b"chunktab", b"table data").unwrap();
+        let err = open_whole(&key, &g, b"chunktab", &ct).unwrap_err();
+        assert!(matches!(err, ArxError::AeadError));
+    }
+
+    #[test]
+    fn test_alg_flag_round_trip() {
+        for alg in [AeadAlg::XChaCha20Poly1305, AeadAlg::Aes256Gcm] {
+            assert_eq!(AeadAlg::from_flags(alg.flag() | 1), alg);
+        }
+    }
 }
@@ -0,0 +1,179 @@
+//! Per-recipient key wrapping for `FLAG_KEYRING` archives.
+//!
+//! Such an archive's regions are sealed under a random data key instead of a
+//! user's key. The data key is sealed once per recipient key into the keyring
+//! region after the superblock; any one recipient key unwraps it, and
+//! [`rekey_recipients`](crate::crypto::rekey::rekey_recipients) changes who
+//! can without repacking.
+//!
+//! Region layout: `count: u32` then `slots` wrapped keys of [`WRAPPED_KEY_LEN`]
+//! bytes, the first `count` in use and the rest zero. Slot `i` is the data key
+//! sealed under recipient `i`'s key with the `KeyWrap` nonce for `i`. Spare
+//! slots let recipients be added in place later.
+
+use std::io::{Read, Seek, SeekFrom};
+
+use crate::container::superblock::{FLAG_KEYRING, Superblock};
+use crate::crypto::aead::{
+    AeadAlg, AeadKey, Region, TAG_LEN, derive_nonce, open_whole, seal_whole,
+};
+use crate::error::{ArxError, Result};
+
+/// One wrapped data key: 32 bytes of ciphertext and the tag.
+pub const WRAPPED_KEY_LEN: usize = 32 + TAG_LEN;
+/// Slots a new keyring gets at least, so recipients can be added by rekey.
+pub const MIN_SLOTS: usize = 8;
+/// Cap on slots read from an archive, bounding what a bad length can allocate.
+const MAX_SLOTS: usize = 4096;
+
+/// Byte length of a keyring region with `slots` slots.
+pub fn region_len(slots: usize) -> u64 {
+    (4 + slots * WRAPPED_KEY_LEN) as u64
+}
+
+/// Seal `data_key` for each of `recipients` into a keyring region with room
+/// for `slots` keys (at least [`MIN_SLOTS`] and the recipient count).
+pub fn seal_keyring(
+    alg: AeadAlg,
+    salt: &[u8; 32],
+    data_key: &AeadKey,
+    recipients: &[[u8; 32]],
+    slots: usize,
+) -> Result<Vec<u8>> {
+    if recipients.is_empty() {
+        return Err(ArxError::Format(
+            "a keyring needs at least one recipient".into(),
+        ));
+    }
+    let slots = slots.max(MIN_SLOTS).max(recipients.len());
+    let mut out = Vec::with_capacity(region_len(slots) as usize);
+    out.extend_from_slice(&(recipients.len() as u32).to_le_bytes());
+    for (i, key) in recipients.iter().enumerate() {
+        let nonce = derive_nonce(alg, salt, Region::KeyWrap, i as u64);
+        out.extend(seal_whole(&AeadKey(*key), &nonce, b"keywrap", &data_key.0)?);
+    }
+    out.resize(region_len(slots) as usize, 0);
+    Ok(out)
+}
+
+/// Slots in a keyring region of `len` bytes.
+pub fn slots(len: u64) -> Result<usize> {
+    let slots = (len.saturating_sub(4) / WRAPPED_KEY_LEN as u64) as usize;
+    if len < 4 || region_len(slots) != len || slots > MAX_SLOTS {
+        return Err(ArxError::Format(format!("bad keyring region length {len}")));
+    }
+    Ok(slots)
+}
+
+/// The data key `key` unwraps from keyring region `ring`, trying each slot in
+/// use in turn. `ArxError::AeadError` if `key` is not a recipient.
+pub fn unwrap_key(alg: AeadAlg, salt: &[u8; 32], ring: &[u8], key: &AeadKey) -> Result<AeadKey> {
+    let slots = slots(ring.len() as u64)?;
+    let count = u32::from_le_bytes(ring[..4].try_into().unwrap()) as usize;
+    if count > slots {
+        return Err(ArxError::Format(format!(
+            "keyring lists {count} recipients but has {slots} slots"
+        )));
+    }
+    for (i, wrapped) in ring[4..]
+        .chunks_exact(WRAPPED_KEY_LEN)
+        .take(count)
+        .enumerate()
+    {
+        let nonce = derive_nonce(alg, salt, Region::KeyWrap, i as u64);
+        if let Ok(pt) = open_whole(key, &nonce, b"keywrap", wrapped) {
+            let data_key: [u8; 32] = pt
+                .try_into()
+                .map_err(|_| ArxError::Format("wrapped key is not 32 bytes".into()))?;
+            return Ok(AeadKey(data_key));
+        }
+    }
+    Err(ArxError::AeadError)
+}
+
+/// Read `sb`'s keyring region from `r`. The length comes from the keyring
+/// guard, which the superblock checksum does not cover, so it is checked
+/// against [`MAX_SLOTS`] and the end of `r` before anything is allocated.
+pub fn read_keyring<R: Read + Seek>(mut r: R, sb: &Superblock) -> Result<Vec<u8>> {
+    slots(sb.keyring_len)?;
+    let end = r.seek(SeekFrom::End(0))?;
+    if sb.keyring_off().saturating_add(sb.keyring_len) > end {
+        return Err(ArxError::Format(format!(
+            "keyring region of {} bytes at offset {} runs past the end of the archive ({end} bytes)",
+            sb.keyring_len,
+            sb.keyring_off()
+        )));
+    }
+    let mut ring = vec![0u8; sb.keyring_len as usize];
+    r.seek(SeekFrom::Start(sb.keyring_off()))?;
+    r.read_exact(&mut ring)
+        .map_err(|e| ArxError::corrupt("keyring", sb.keyring_off(), e))?;
+    Ok(ring)
+}
+
+/// The key `sb`'s regions are sealed under, given a user's `key`: unwrapped
+/// from the keyring in a `FLAG_KEYRING` archive, `key` itself otherwise.
+pub fn region_key<R: Read + Seek>(r: R, sb: &Superblock, key: AeadKey) -> Result<AeadKey> {
+    if sb.flags & FLAG_KEYRING == 0 {
+        return Ok(key);
+    }
+    let ring = read_keyring(r, sb)?;
+    unwrap_key(AeadAlg::from_flags(sb.flags), &sb.kdf_salt, &ring, &key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_each_recipient_unwraps_the_data_key() {
+        let salt = [3u8; 32];
+        let data = AeadKey([0x5A; 32]);
+        let (alice, bob) = ([1u8; 32], [2u8; 32]);
+        let ring = seal_keyring(AeadAlg::default(), &salt, &data, &[alice, bob], 0).unwrap();
+        assert_eq!(ring.len() as u64, region_len(MIN_SLOTS));
+
+        for key in [alice, bob] {
+            let got = unwrap_key(AeadAlg::default(), &salt, &ring, &AeadKey(key)).unwrap();
+            assert_eq!(got.0, data.0);
+        }
+        // AeadKey has no Debug (keys stay out of panic messages), so no unwrap_err
+        let Err(err) = unwrap_key(AeadAlg::default(), &salt, &ring, &AeadKey([9; 32])) else {
+            panic!("a key with no slot unwrapped the keyring");
+        };
+        assert!(matches!(err, ArxError::AeadError), "{err}");
+    }
+
+    /// A keyring length that is well formed but longer than the file is
+    /// reported as such rather than read.
+    #[test]
+    fn test_keyring_past_end_of_file_is_rejected() {
+        use crate::container::superblock::VERSION;
+
+        let sb = Superblock {
+            version: VERSION,
+            manifest_len: 0,
+            chunk_table_off: 0,
+            chunk_count: 0,
+            data_off: 0,
+            flags: FLAG_KEYRING,
+            kdf_salt: [0u8; 32],
+            file_count: 0,
+            total_logical: 0,
+            keyring_len: region_len(MAX_SLOTS),
+        };
+        let file = vec![0u8; (sb.keyring_off() + region_len(MIN_SLOTS)) as usize];
+        let err = read_keyring(std::io::Cursor::new(&file), &sb).unwrap_err();
+        assert!(err.to_string().contains("runs past the end"), "{err}");
+
+        let sb = Superblock {
+            keyring_len: region_len(MAX_SLOTS + 1),
+            ..sb
+        };
+        let err = read_keyring(std::io::Cursor::new(&file), &sb).unwrap_err();
+        assert!(
+            err.to_string().contains("bad keyring region length"),
+            "{err}"
+        );
+    }
+}
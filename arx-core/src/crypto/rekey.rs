@@ -0,0 +1,200 @@
+//! Rotate the key of an encrypted archive without unpacking it.
+//!
+//! Every region is sealed under a nonce derived from the superblock's
+//! `kdf_salt`, and AEAD tags have a fixed length, so each region can be opened
+//! with the old key and resealed with the new key and salt in place: offsets,
+//! sizes and the (plaintext) tail hashes are unchanged.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use crate::container::chunktab::read_table;
+use crate::container::superblock::{
+    FLAG_CHUNK_AEAD, FLAG_ENCRYPTED, FLAG_KDF_PASSWORD, FLAG_KEYRING, Superblock,
+};
+use crate::crypto::aead::{AeadAlg, AeadKey, Region, derive_nonce, open_whole, seal_whole};
+use crate::crypto::nonce::random_salt;
+use crate::crypto::{kdf, keyring};
+use crate::error::{ArxError, Result};
+
+/// Write a copy of `archive` to `out` with every region resealed under
+/// `new_key` and `new_salt`. `old_key` is checked against the manifest before
+/// anything is written; the old salt is read from the superblock. `out` may
+/// be `archive` itself: the copy is built beside it and renamed into place.
+///
+/// Only the base archive is rekeyed; sync any crud overlay first.
+pub fn rekey(
+    archive: &Path,
+    old_key: [u8; 32],
+    new_key: [u8; 32],
+    new_salt: [u8; 32],
+    out: &Path,
+) -> Result<()> {
+    rekey_with(archive, old_key, new_key, new_salt, false, None, out)
+}
+
+/// Like [`rekey`], but derives the new key from `new_password` under a fresh
+/// salt and marks the archive as password-keyed.
+pub fn rekey_password(
+    archive: &Path,
+    old_key: [u8; 32],
+    new_password: &str,
+    out: &Path,
+) -> Result<()> {
+    let salt = random_salt();
+    let key = kdf::derive_key(new_password, &salt);
+    rekey_with(archive, old_key, key, salt, true, None, out)
+}
+
+/// Replace the recipients of an archive packed with `PackOptions::recipients`.
+/// `key` is any current recipient's key. Everything is resealed under a fresh
+/// data key and salt, so a removed recipient's key, or a data key it saw
+/// before, opens nothing in the copy. The new recipients must fit the slots
+/// the keyring was packed with.
+pub fn rekey_recipients(
+    archive: &Path,
+    key: [u8; 32],
+    recipients: &[[u8; 32]],
+    out: &Path,
+) -> Result<()> {
+    let mut src = File::open(archive)?;
+    let sb = Superblock::read_from(&mut src)?;
+    if sb.flags & FLAG_KEYRING == 0 {
+        return Err(ArxError::Format(
+            "rekey_recipients: archive has no recipient keyring".into(),
+        ));
+    }
+    let alg = AeadAlg::from_flags(sb.flags);
+    let ring = keyring::read_keyring(&mut src, &sb)?;
+    let old = keyring::unwrap_key(alg, &sb.kdf_salt, &ring, &AeadKey(key))?;
+    let slots = keyring::slots(sb.keyring_len)?;
+    if recipients.len() > slots {
+        return Err(ArxError::Format(format!(
+            "rekey_recipients: {} recipients do not fit the keyring's {slots} slots; repack instead",
+            recipients.len()
+        )));
+    }
+    let (new_salt, new) = (random_salt(), AeadKey(random_salt()));
+    let ring = keyring::seal_keyring(alg, &new_salt, &new, recipients, slots)?;
+    if ring.len() as u64 != sb.keyring_len {
+        return Err(ArxError::Format(format!(
+            "rekey_recipients: keyring would grow from {} to {} bytes",
+            sb.keyring_len,
+            ring.len()
+        )));
+    }
+    drop(src);
+    rekey_with(archive, old.0, new.0, new_salt, false, Some(ring), out)
+}
+
+fn rekey_with(
+    archive: &Path,
+    old_key: [u8; 32],
+    new_key: [u8; 32],
+    new_salt: [u8; 32],
+    password_derived: bool,
+    keyring: Option<Vec<u8>>,
+    out: &Path,
+) -> Result<()> {
+    let mut src = File::open(archive)?;
+    let sb = Superblock::read_from(&mut src)?;
+    // The keyring wraps the old data key; resealing without a new one would strand it
+    if sb.flags & FLAG_KEYRING != 0 && keyring.is_none() {
+        return Err(ArxError::Format(
+            "rekey: archive has a recipient keyring; use rekey_recipients".into(),
+        ));
+    }
+    if sb.flags & FLAG_CHUNK_AEAD != 0 {
+        return Err(ArxError::Format(
+            "rekey: archives with per-file encryption are not supported".into(),
+        ));
+    }
+    if sb.flags & FLAG_ENCRYPTED == 0 {
+        return Err(ArxError::Format("rekey: archive is not encrypted".into()));
+    }
+    if sb.chunk_table_off > sb.data_off {
+        return Err(ArxError::Format(format!(
+            "rekey: chunk_table_off {} > data_off {}",
+            sb.chunk_table_off, sb.data_off
+        )));
+    }
+    let alg = AeadAlg::from_flags(sb.flags);
+    let (old, new) = (AeadKey(old_key), AeadKey(new_key));
+    let reseal = |region: Region, id: u64, aad: &[u8], ct: &[u8]| -> Result<Vec<u8>> {
+        let pt = open_whole(&old, &derive_nonce(alg, &sb.kdf_salt, region, id), aad, ct)?;
+        seal_whole(&new, &derive_nonce(alg, &new_salt, region, id), aad, &pt)
+    };
+
+    // Front regions first: a wrong old key fails here, before any output exists
+    let manifest = read_at(&mut src, sb.header_len(), sb.manifest_len)?;
+    let manifest = reseal(Region::Manifest, 0, b"manifest", &manifest)?;
+    let table_ct = read_at(
+        &mut src,
+        sb.chunk_table_off,
+        sb.data_off - sb.chunk_table_off,
+    )?;
+    let table_pt = open_whole(
+        &old,
+        &derive_nonce(alg, &sb.kdf_salt, Region::ChunkTable, 0),
+        b"chunktab",
+        &table_ct,
+    )?;
+    let table = read_table(&mut &table_pt[..], sb.chunk_count)?;
+    let table_ct = seal_whole(
+        &new,
+        &derive_nonce(alg, &new_salt, Region::ChunkTable, 0),
+        b"chunktab",
+        &table_pt,
+    )?;
+
+    // Copy (keeping gaps and the tail), then overwrite each sealed region
+    let tmp = out.with_extension("arx.tmp");
+    fs::copy(archive, &tmp)?;
+    let result = (|| -> Result<()> {
+        let mut dst = OpenOptions::new().read(true).write(true).open(&tmp)?;
+        // Parent chunks (differential archives) have no bytes here to reseal
+        for (id, ce) in table.iter().enumerate().filter(|(_, ce)| !ce.parent) {
+            let ct = read_at(&mut src, ce.data_off, ce.c_size)?;
+            let ct = reseal(Region::ChunkData, id as u64, b"chunk", &ct)?;
+            dst.seek(SeekFrom::Start(ce.data_off))?;
+            dst.write_all(&ct)?;
+        }
+        dst.seek(SeekFrom::Start(sb.header_len()))?;
+        dst.write_all(&manifest)?;
+        dst.seek(SeekFrom::Start(sb.chunk_table_off))?;
+        dst.write_all(&table_ct)?;
+        dst.seek(SeekFrom::Start(0))?;
+        let kdf_flag = if password_derived {
+            FLAG_KDF_PASSWORD
+        } else {
+            0
+        };
+        Superblock {
+            flags: (sb.flags & !FLAG_KDF_PASSWORD) | kdf_flag,
+            kdf_salt: new_salt,
+            ..sb
+        }
+        .write_to(&mut dst)?;
+        if let Some(ring) = &keyring {
+            dst.seek(SeekFrom::Start(sb.keyring_off()))?;
+            dst.write_all(ring)?;
+        }
+        dst.sync_all()?;
+        Ok(())
+    })();
+    if let Err(e) = result {
+        let _ = fs::remove_file(&tmp);
+        return Err(e);
+    }
+    drop(src);
+    fs::rename(&tmp, out)?;
+    Ok(())
+}
+
+fn read_at(f: &mut File, off: u64, len: u64) -> Result<Vec<u8>> {
+    f.seek(SeekFrom::Start(off))?;
+    let mut buf = vec![0u8; len as usize];
+    f.read_exact(&mut buf)?;
+    Ok(buf)
+}
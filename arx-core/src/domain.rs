@@ -17,4 +17,5 @@ pub struct ChunkRow {
     pub c_len: u64,
     pub data_off: u64,
     pub pct_end: f32,
+    pub refcount: u64,
 }
@@ -10,6 +10,29 @@ pub enum ArxError {
 
     #[error("AEAD authentication failed — wrong key or tampered data")]
     AeadError,
+
+    /// A region of the archive could not be read in full; `offset` is the
+    /// absolute file offset the read started at.
+    #[error("corrupt {region} at offset {offset}: {detail}")]
+    Corrupt {
+        region: &'static str,
+        offset: u64,
+        detail: String,
+    },
+
+    #[error("unsupported codec id {0}")]
+    UnsupportedCodec(u8),
+}
+
+impl ArxError {
+    /// Wrap a failed region read with the region name and its file offset.
+    pub(crate) fn corrupt(region: &'static str, offset: u64, err: std::io::Error) -> Self {
+        ArxError::Corrupt {
+            region,
+            offset,
+            detail: err.to_string(),
+        }
+    }
 }
 
 // Convenient crate-wide result type
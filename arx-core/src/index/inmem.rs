@@ -2,6 +2,7 @@ use std::collections::{BTreeMap, HashMap};
 
 use crate::codec::CodecId;
 use crate::container::journal::{ChunkRef, Loc, LogRecord};
+use crate::crud::normalize_path;
 use crate::error::Result;
 use crate::policy::Policy;
 use crate::read::opened::Opened;
@@ -33,22 +34,13 @@ impl InMemIndex {
         for fe in &opened.manifest.files {
             let mut chunks: Vec<ChunkRef> = Vec::with_capacity(fe.chunk_refs.len());
             for cr in &fe.chunk_refs {
-                if (cr.id as usize) >= opened.table.len() {
-                    return Err(std::io::Error::new(
+                let ce = opened.chunk_entry(cr.id)?;
+                let codec = CodecId::from_u8(ce.codec).ok_or_else(|| {
+                    std::io::Error::new(
                         std::io::ErrorKind::InvalidData,
-                        format!(
-                            "manifest chunk id {} out of bounds (table has {} entries)",
-                            cr.id,
-                            opened.table.len()
-                        ),
+                        format!("chunk {} has unknown codec id {}", cr.id, ce.codec),
                     )
-                    .into());
-                }
-                let ce = &opened.table[cr.id as usize];
-                let codec = match ce.codec {
-                    0 => CodecId::Store,
-                    _ => CodecId::Zstd,
-                };
+                })?;
                 chunks.push(ChunkRef {
                     loc: Loc::Base,
                     // For Base chunks, off stores the chunk TABLE index so we
@@ -72,6 +64,27 @@ impl InMemIndex {
             );
         }
 
+        // Seed chunk dedup with the base's table so a PUT can reference base
+        // chunks directly. Entries without a hash (v3, or sealed in per-file
+        // mode) can never match and are skipped.
+        for (id, ce) in opened.table.iter().enumerate() {
+            if ce.blake3 == [0u8; 32] {
+                continue;
+            }
+            if let Some(codec) = CodecId::from_u8(ce.codec) {
+                idx.by_chunk
+                    .entry(ce.blake3)
+                    .or_insert((Loc::Base, id as u64, ce.c_size, codec));
+            }
+        }
+
+        // Hardlinks become independent entries sharing the primary's chunks
+        for hl in &opened.manifest.hardlinks {
+            if let Some(e) = idx.by_path.get(&hl.target).cloned() {
+                idx.by_path.insert(hl.path.clone(), e);
+            }
+        }
+
         idx.stats.files = opened.manifest.files.len() as u64;
         idx.stats.dirs = opened.manifest.dirs.len() as u64;
         idx.stats.chunks = opened.table.len() as u64;
@@ -95,7 +108,11 @@ impl InMemIndex {
                     size: *size,
                     chunks: chunks.clone(),
                 };
-                self.by_path.insert(path.clone(), e);
+                // Overwriting a path replaces its size rather than adding a file
+                if let Some(old) = self.by_path.insert(replay_key(path), e) {
+                    self.stats.files = self.stats.files.saturating_sub(1);
+                    self.stats.logical_bytes = self.stats.logical_bytes.saturating_sub(old.size);
+                }
                 for c in chunks {
                     self.by_chunk
                         .insert(c.blake3, (c.loc, c.off, c.len, c.codec));
@@ -104,20 +121,54 @@ impl InMemIndex {
                 self.stats.logical_bytes = self.stats.logical_bytes.saturating_add(*size);
             }
             LogRecord::Delete { path } => {
-                if let Some(e) = self.by_path.remove(path) {
+                if let Some(e) = self.by_path.remove(&replay_key(path)) {
                     self.stats.files = self.stats.files.saturating_sub(1);
                     self.stats.logical_bytes = self.stats.logical_bytes.saturating_sub(e.size);
                 }
             }
             LogRecord::Rename { from, to } => {
-                if let Some(e) = self.by_path.remove(from) {
-                    self.by_path.insert(to.clone(), e);
+                if let Some(e) = self.by_path.remove(&replay_key(from)) {
+                    self.by_path.insert(replay_key(to), e);
                 }
             }
             LogRecord::SetPolicy(p) => {
                 self.policy = p.clone();
             }
             LogRecord::Note { .. } => {}
+            LogRecord::CompactDelta { moves, .. } => {
+                let moved: HashMap<u64, (u64, u64)> = moves
+                    .iter()
+                    .map(|&(old, off, len)| (old, (off, len)))
+                    .collect();
+                for c in self.by_path.values_mut().flat_map(|e| &mut e.chunks) {
+                    if c.loc == Loc::Delta
+                        && let Some(&(off, len)) = moved.get(&c.off)
+                    {
+                        (c.off, c.len) = (off, len);
+                    }
+                }
+                // Dropped frames can no longer be deduplicated against
+                self.by_chunk.retain(|_, (loc, off, len, _)| {
+                    if *loc != Loc::Delta {
+                        return true;
+                    }
+                    match moved.get(off) {
+                        Some(&(new_off, new_len)) => {
+                            (*off, *len) = (new_off, new_len);
+                            true
+                        }
+                        None => false,
+                    }
+                });
+            }
         }
     }
 }
+
+/// Index key for a journaled path. Journals written before the CRUD calls
+/// normalized their paths can hold keys like `/a` or `a//b`; replay files
+/// them under the canonical path those calls now look up. A path that
+/// doesn't normalize (one with `..`) is kept as written.
+fn replay_key(path: &str) -> String {
+    normalize_path(path).unwrap_or_else(|_| path.to_string())
+}
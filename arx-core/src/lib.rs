@@ -8,6 +8,7 @@ pub mod stats;
 
 pub mod util {
     pub mod buf;
+    pub mod cancel;
     pub mod hash_forward;
     pub mod sanitize;
     pub mod varint;
@@ -27,7 +28,9 @@ pub mod crypto {
     pub mod aead;
     pub mod hex;
     pub mod kdf;
+    pub mod keyring;
     pub mod nonce;
+    pub mod rekey;
 }
 
 pub mod container {
@@ -40,12 +43,21 @@ pub mod container {
 }
 
 pub mod pack {
+    pub mod append;
+    pub mod append_log;
+    pub(crate) mod dedup;
+    pub mod tar_import;
     pub mod walker;
     pub mod writer;
 }
 
 pub mod read {
+    pub(crate) mod cache;
+    pub mod checksums;
+    pub mod diff;
+    pub mod export;
     pub mod extract;
+    pub mod layout;
     pub mod opened;
     pub mod reader;
     pub mod stats;
@@ -59,15 +71,23 @@ pub mod index {
 pub mod list;
 pub use crate::error::Result;
 
-pub use pack::writer::{PackOptions, pack};
+pub use pack::append::pack_append;
+pub use pack::append_log::{LogOptions, append_records, create_log};
+pub use pack::tar_import::pack_from_tar;
+pub use pack::writer::{
+    PackOptions, PackPlan, pack, pack_boundaries, pack_list, pack_stream, pack_to_writer, plan_pack,
+};
 
-pub use read::extract::{ExtractOptions, extract};
+pub use read::extract::{ExtractOptions, extract, extract_from};
 
-pub use list::{ListOptions, list};
+pub use list::{ChunkStats, ListOptions, ListRow, SortKey, chunk_stats, list, list_entries};
 
 pub mod repo;
 pub mod repo_factory;
 pub mod repo_fs;
+#[cfg(feature = "http")]
+pub mod repo_http;
+pub mod repo_mem;
 
 pub mod domain;
 
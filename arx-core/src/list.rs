@@ -1,22 +1,320 @@
-use crate::container::chunktab::{ENTRY_SIZE, ENTRY_SIZE_V3, read_table_from_slice};
+use crate::codec::CodecId;
+use crate::container::chunktab::{ChunkEntry, ENTRY_SIZE, ENTRY_SIZE_V3, read_table_from_slice};
 use crate::container::manifest::Manifest;
 use crate::container::superblock::{FLAG_ENCRYPTED, Superblock};
 use crate::container::tail::{TAIL_LEN, TAIL_MAGIC};
-use crate::crypto::aead::{AeadKey, Region, derive_nonce};
-use crate::error::Result;
+use crate::crypto::aead::{AeadAlg, AeadKey, Region, derive_nonce};
+use crate::crypto::keyring::region_key;
+use crate::error::{ArxError, Result};
+use crate::read::opened::Opened;
 
+use std::collections::{BTreeMap, HashSet};
 use std::env;
 use std::fs::File;
-use std::io::{Read, Seek, SeekFrom};
+use std::io::{Read, Seek, SeekFrom, Write};
 use std::path::Path;
 
 #[derive(Clone, Default)]
 pub struct ListOptions {
     pub aead_key: Option<[u8; 32]>,
     pub key_salt: [u8; 32],
+    /// Print sizes as KiB/MiB/GiB rather than raw byte counts.
+    pub human: bool,
+    /// Print an indented directory tree instead of flat paths; each directory
+    /// line carries the summed sizes of the files below it.
+    pub tree: bool,
+    /// After each file, print how many of its chunks use each codec and the
+    /// spread of their sizes; end with the archive-wide codec histogram.
+    /// Ignored with `tree`.
+    pub verbose: bool,
+    /// `archive` is an index sidecar (`PackOptions::emit_index`) rather than
+    /// a whole archive; see [`Opened::open_index`].
+    pub index: bool,
+    /// Order the file lines by this key instead of manifest order; ties keep
+    /// manifest order. Ignored with `tree`.
+    pub sort: Option<SortKey>,
+    /// Sort descending (largest or last name first). Ignored without `sort`.
+    pub reverse: bool,
+}
+
+/// What `list` can order its file lines by.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortKey {
+    Name,
+    /// Uncompressed size.
+    USize,
+    /// Stored size.
+    CSize,
+    Chunks,
+}
+
+impl SortKey {
+    fn cmp(self, a: &ListRow, b: &ListRow) -> std::cmp::Ordering {
+        match self {
+            SortKey::Name => a.path.cmp(&b.path),
+            SortKey::USize => a.u_size.cmp(&b.u_size),
+            SortKey::CSize => a.c_size.cmp(&b.c_size),
+            SortKey::Chunks => a.chunks.cmp(&b.chunks),
+        }
+    }
+}
+
+/// Indices of `rows` in the order `opts` asks to print them.
+fn row_order(rows: &[ListRow], opts: Option<&ListOptions>) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..rows.len()).collect();
+    if let Some(key) = opts.and_then(|o| o.sort) {
+        let reverse = opts.is_some_and(|o| o.reverse);
+        // Stable, and reversed by key only, so ties stay in manifest order
+        order.sort_by(|&a, &b| {
+            let ord = key.cmp(&rows[a], &rows[b]);
+            if reverse { ord.reverse() } else { ord }
+        });
+    }
+    order
 }
 
 pub fn list(archive: &Path, opts: Option<&ListOptions>) -> Result<()> {
+    list_to(archive, opts, &mut std::io::stdout().lock())
+}
+
+/// One `list` line; also used for the pack-time listing sidecar.
+pub(crate) fn entry_line(
+    path: &str,
+    encrypted: bool,
+    u_size: u64,
+    c_size: u64,
+    chunks: usize,
+    human: bool,
+) -> String {
+    let enc_mark = if encrypted { " [E]" } else { "" };
+    let (u, c) = (fmt_size(u_size, human), fmt_size(c_size, human));
+    format!("{path}{enc_mark}  u={u}  c={c}  chunks={chunks}")
+}
+
+fn fmt_size(n: u64, human: bool) -> String {
+    if human { human_size(n) } else { n.to_string() }
+}
+
+/// `n` bytes in binary units with one decimal, e.g. `1.5KiB`; below 1 KiB, `512B`.
+pub(crate) fn human_size(n: u64) -> String {
+    const UNITS: [&str; 5] = ["KiB", "MiB", "GiB", "TiB", "PiB"];
+    if n < 1024 {
+        return format!("{n}B");
+    }
+    let mut v = n as f64 / 1024.0;
+    let mut unit = 0;
+    while v >= 1024.0 && unit < UNITS.len() - 1 {
+        v /= 1024.0;
+        unit += 1;
+    }
+    format!("{v:.1}{}", UNITS[unit])
+}
+
+/// One file of a [`list_entries`] listing.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ListRow {
+    pub path: String,
+    /// Uncompressed size.
+    pub u_size: u64,
+    /// Stored size of the file's chunks, AEAD tags included; a chunk shared
+    /// with other files counts toward each of them.
+    pub c_size: u64,
+    pub chunks: usize,
+    /// Reading the file needs the key.
+    pub encrypted: bool,
+}
+
+/// Codec histogram and chunk size spread of a file, or of a whole archive, as
+/// `list --verbose` prints it. Sizes are uncompressed.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ChunkStats {
+    /// Chunk count per on-disk codec byte.
+    pub codecs: BTreeMap<u8, u64>,
+    pub chunks: u64,
+    pub min_size: u64,
+    pub max_size: u64,
+    pub total_size: u64,
+}
+
+impl ChunkStats {
+    fn add(&mut self, codec: u8, size: u64) {
+        *self.codecs.entry(codec).or_default() += 1;
+        self.min_size = if self.chunks == 0 {
+            size
+        } else {
+            self.min_size.min(size)
+        };
+        self.max_size = self.max_size.max(size);
+        self.total_size += size;
+        self.chunks += 1;
+    }
+
+    /// Chunks stored with `codec`.
+    pub fn count(&self, codec: CodecId) -> u64 {
+        self.codecs.get(&(codec as u8)).copied().unwrap_or(0)
+    }
+
+    pub fn avg_size(&self) -> u64 {
+        self.total_size.checked_div(self.chunks).unwrap_or(0)
+    }
+
+    /// `zstd=3 store=1`; codec bytes this build doesn't know print as `codec7`.
+    fn histogram(&self) -> String {
+        let parts: Vec<String> = self
+            .codecs
+            .iter()
+            .map(|(&c, n)| match CodecId::from_u8(c) {
+                Some(id) => format!("{}={n}", id.name()),
+                None => format!("codec{c}={n}"),
+            })
+            .collect();
+        parts.join(" ")
+    }
+}
+
+/// Per-file chunk stats in manifest order (the order [`list_entries`] yields),
+/// plus the archive-wide stats, where a chunk shared by several files counts once.
+pub fn chunk_stats(
+    archive: &Path,
+    opts: Option<&ListOptions>,
+) -> Result<(Vec<ChunkStats>, ChunkStats)> {
+    let (key, salt) = opts.map_or((None, [0u8; 32]), |o| (o.aead_key, o.key_salt));
+    let opened = if opts.is_some_and(|o| o.index) {
+        Opened::open_index(archive, key)?
+    } else {
+        Opened::open(archive, key, salt)?
+    };
+    let mut files = Vec::new();
+    let mut total = ChunkStats::default();
+    let mut seen = HashSet::new();
+    for i in 0..opened.manifest.files.len() {
+        let mut st = ChunkStats::default();
+        for c in opened.chunk_map_by_index(i)? {
+            st.add(c.codec, c.u_len);
+            if seen.insert(c.id) {
+                total.add(c.codec, c.u_len);
+            }
+        }
+        files.push(st);
+    }
+    Ok((files, total))
+}
+
+fn write_stats<W: Write>(
+    out: &mut W,
+    label: &str,
+    st: &ChunkStats,
+    human: bool,
+) -> std::io::Result<()> {
+    let (min, avg, max) = (
+        fmt_size(st.min_size, human),
+        fmt_size(st.avg_size(), human),
+        fmt_size(st.max_size, human),
+    );
+    writeln!(
+        out,
+        "{label}chunks={}  {}  size min={min} avg={avg} max={max}",
+        st.chunks,
+        st.histogram()
+    )
+}
+
+/// A directory (or, with `file` set, a file) in the `tree` listing.
+#[derive(Default)]
+struct TreeNode<'a> {
+    u_size: u64,
+    c_size: u64,
+    file: Option<&'a ListRow>,
+    children: BTreeMap<&'a str, TreeNode<'a>>,
+}
+
+/// Rebuild the hierarchy from the flat manifest paths and print it, two
+/// spaces of indent per level. Directories roll up their files' sizes; since
+/// `c` counts a shared chunk once per file, rollups can exceed the data region.
+fn write_tree<'a, W: Write>(
+    out: &mut W,
+    rows: &'a [ListRow],
+    dirs: impl Iterator<Item = &'a str>,
+    human: bool,
+) -> std::io::Result<()> {
+    let mut root = TreeNode::default();
+    for d in dirs {
+        let mut node = &mut root;
+        for seg in d.split('/').filter(|s| !s.is_empty()) {
+            node = node.children.entry(seg).or_default();
+        }
+    }
+    for row in rows {
+        let mut node = &mut root;
+        for seg in row.path.split('/').filter(|s| !s.is_empty()) {
+            node.u_size += row.u_size;
+            node.c_size += row.c_size;
+            node = node.children.entry(seg).or_default();
+        }
+        node.file = Some(row);
+    }
+    // A single-file archive stores its file at the root (empty path)
+    if let Some(r) = root.file {
+        let line = entry_line(&r.path, r.encrypted, r.u_size, r.c_size, r.chunks, human);
+        writeln!(out, "{line}")?;
+    }
+    write_tree_node(out, &root, 0, human)
+}
+
+fn write_tree_node<W: Write>(
+    out: &mut W,
+    node: &TreeNode<'_>,
+    depth: usize,
+    human: bool,
+) -> std::io::Result<()> {
+    let indent = "  ".repeat(depth);
+    for (name, child) in &node.children {
+        if let Some(r) = child.file {
+            let line = entry_line(name, r.encrypted, r.u_size, r.c_size, r.chunks, human);
+            writeln!(out, "{indent}{line}")?;
+        } else {
+            let (u, c) = (fmt_size(child.u_size, human), fmt_size(child.c_size, human));
+            writeln!(out, "{indent}{name}/  u={u}  c={c}")?;
+            write_tree_node(out, child, depth + 1, human)?;
+        }
+    }
+    Ok(())
+}
+
+/// Like [`list`], but writes the file lines to `out` instead of stdout.
+pub fn list_to<W: Write>(archive: &Path, opts: Option<&ListOptions>, out: &mut W) -> Result<()> {
+    let (rows, dirs) = read_listing(archive, opts)?;
+    let human = opts.is_some_and(|o| o.human);
+    if opts.is_some_and(|o| o.tree) {
+        write_tree(out, &rows, dirs.iter().map(String::as_str), human)?;
+    } else if opts.is_some_and(|o| o.verbose) {
+        let (stats, total) = chunk_stats(archive, opts)?;
+        for i in row_order(&rows, opts) {
+            let (r, st) = (&rows[i], &stats[i]);
+            let line = entry_line(&r.path, r.encrypted, r.u_size, r.c_size, r.chunks, human);
+            writeln!(out, "{line}")?;
+            write_stats(out, "    ", st, human)?;
+        }
+        write_stats(out, "total: ", &total, human)?;
+    } else {
+        for i in row_order(&rows, opts) {
+            let r = &rows[i];
+            let line = entry_line(&r.path, r.encrypted, r.u_size, r.c_size, r.chunks, human);
+            writeln!(out, "{line}")?;
+        }
+    }
+    Ok(())
+}
+
+/// The rows [`list`] prints, one per manifest file in manifest order, with
+/// stored sizes summed from the chunk table. Display options in `opts` are ignored.
+pub fn list_entries(archive: &Path, opts: Option<&ListOptions>) -> Result<Vec<ListRow>> {
+    read_listing(archive, opts).map(|(rows, _)| rows)
+}
+
+/// File rows and directory paths of `archive`, after the same layout checks
+/// `list` has always made.
+fn read_listing(archive: &Path, opts: Option<&ListOptions>) -> Result<(Vec<ListRow>, Vec<String>)> {
     let mut f = File::open(archive)?;
     let file_len = f.metadata()?.len();
     let dbg = env::var_os("ARX_DEBUG_LIST").is_some();
@@ -24,10 +322,18 @@ pub fn list(archive: &Path, opts: Option<&ListOptions>) -> Result<()> {
     let sb = Superblock::read_from(&mut f)?;
     let header_len = sb.header_len();
     let enc_enabled = (sb.flags & FLAG_ENCRYPTED) != 0;
+    let alg = AeadAlg::from_flags(sb.flags);
+    let index = opts.is_some_and(|o| o.index);
+    if index && file_len != sb.data_off {
+        return Err(ArxError::Format(format!(
+            "not an archive index: {file_len} bytes, expected data_off {}",
+            sb.data_off
+        )));
+    }
 
     if dbg {
         eprintln!(
-            "[DBG] SB: ver={} flags=0x{:x}\n      manifest_len={}  header_len={}  manifest_end={}\n      chunk_table_off={}  data_off={}  chunk_count={}\n      file_len={}",
+            "[DBG] SB: ver={} flags=0x{:x}\n      manifest_len={}  header_len={}  manifest_end={}\n      chunk_table_off={}  data_off={}  chunk_count={}\n      file_count={}  total_logical={}\n      file_len={}",
             sb.version,
             sb.flags,
             sb.manifest_len,
@@ -36,12 +342,14 @@ pub fn list(archive: &Path, opts: Option<&ListOptions>) -> Result<()> {
             sb.chunk_table_off,
             sb.data_off,
             sb.chunk_count,
+            sb.file_count,
+            sb.total_logical,
             file_len
         );
     }
 
     let mut file_end_for_data = file_len;
-    if file_len >= TAIL_LEN {
+    if !index && file_len >= TAIL_LEN {
         f.seek(SeekFrom::End(-(TAIL_LEN as i64)))?;
         let mut magic = [0u8; 8];
         if f.read_exact(&mut magic).is_ok() && magic == TAIL_MAGIC {
@@ -90,6 +398,7 @@ pub fn list(archive: &Path, opts: Option<&ListOptions>) -> Result<()> {
         )
         .into());
     }
+    sb.check_guards(&mut f)?;
     let table_ct_len = sb.data_off - sb.chunk_table_off;
     if dbg {
         eprintln!(
@@ -118,7 +427,7 @@ pub fn list(archive: &Path, opts: Option<&ListOptions>) -> Result<()> {
             );
         }
         // Use the archive's stored kdf_salt for nonce derivation
-        Some((AeadKey(key), sb.kdf_salt))
+        Some((region_key(&mut f, &sb, AeadKey(key))?, sb.kdf_salt))
     } else {
         if dbg {
             eprintln!("[DBG] AEAD: disabled");
@@ -134,13 +443,14 @@ pub fn list(archive: &Path, opts: Option<&ListOptions>) -> Result<()> {
         );
     }
     let mut mbytes = vec![0u8; sb.manifest_len as usize];
-    f.read_exact(&mut mbytes)?;
+    f.read_exact(&mut mbytes)
+        .map_err(|e| ArxError::corrupt("manifest", header_len, e))?;
     if dbg {
         eprintln!("[DBG] Manifest bytes: ct_len={}", mbytes.len());
     }
 
     let manifest_bytes = if let Some((ref key, salt)) = enc {
-        let nonce = derive_nonce(&salt, Region::Manifest, 0);
+        let nonce = derive_nonce(alg, &salt, Region::Manifest, 0);
         let pt = crate::crypto::aead::open_whole(key, &nonce, b"manifest", &mbytes)?;
         if dbg {
             eprintln!("[DBG] Manifest decrypted: pt_len={}", pt.len());
@@ -150,7 +460,7 @@ pub fn list(archive: &Path, opts: Option<&ListOptions>) -> Result<()> {
         mbytes
     };
 
-    let manifest: Manifest = match ciborium::de::from_reader(&manifest_bytes[..]) {
+    let manifest = match Manifest::from_region(&manifest_bytes, sb.flags) {
         Ok(m) => m,
         Err(e) => {
             if dbg {
@@ -160,7 +470,7 @@ pub fn list(archive: &Path, opts: Option<&ListOptions>) -> Result<()> {
                     manifest_bytes.len()
                 );
             }
-            return Err(std::io::Error::new(std::io::ErrorKind::Other, e).into());
+            return Err(e.into());
         }
     };
     if dbg {
@@ -179,7 +489,8 @@ pub fn list(archive: &Path, opts: Option<&ListOptions>) -> Result<()> {
         );
     }
     let mut tbytes = vec![0u8; table_ct_len as usize];
-    f.read_exact(&mut tbytes)?;
+    f.read_exact(&mut tbytes)
+        .map_err(|e| ArxError::corrupt("chunk table", sb.chunk_table_off, e))?;
     if dbg {
         eprintln!(
             "[DBG] Chunk table bytes: ct_len={} data_off={} chunk_table_off={} table_len={}",
@@ -191,7 +502,7 @@ pub fn list(archive: &Path, opts: Option<&ListOptions>) -> Result<()> {
     }
 
     let raw_table = if let Some((ref key, salt)) = enc {
-        let nonce = derive_nonce(&salt, Region::ChunkTable, 0);
+        let nonce = derive_nonce(alg, &salt, Region::ChunkTable, 0);
         let pt = crate::crypto::aead::open_whole(key, &nonce, b"chunktab", &tbytes)?;
         if dbg {
             eprintln!("[DBG] Chunk table decrypted: pt_len={}", pt.len());
@@ -231,6 +542,7 @@ pub fn list(archive: &Path, opts: Option<&ListOptions>) -> Result<()> {
             return Err(e.into());
         }
     };
+    manifest.validate_chunk_refs(table.len())?;
 
     if dbg {
         eprintln!("[DBG] Chunk table parsed: entries={}", table.len());
@@ -252,6 +564,7 @@ pub fn list(archive: &Path, opts: Option<&ListOptions>) -> Result<()> {
     }
 
     // Hard bound check (fail fast if any entry overlaps tail or precedes data_off)
+    let has_tail = file_end_for_data != file_len;
     for (i, ce) in table.iter().enumerate() {
         if ce.data_off < sb.data_off {
             return Err(std::io::Error::new(
@@ -264,11 +577,19 @@ pub fn list(archive: &Path, opts: Option<&ListOptions>) -> Result<()> {
             .into());
         }
         let end = ce.data_off.saturating_add(ce.c_size);
-        if end > file_end_for_data {
+        // An index has no data region to bound the chunks by
+        if end > file_end_for_data && !index {
+            if !has_tail && let Some(expected) = packed_data_end(&table, sb.data_off) {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::UnexpectedEof,
+                    format!("archive truncated (EOF at {file_len}, expected >= {expected})"),
+                )
+                .into());
+            }
             return Err(std::io::Error::new(
-                std::io::ErrorKind::UnexpectedEof,
+                std::io::ErrorKind::InvalidData,
                 format!(
-                    "chunk[{}] end {} exceeds file_end_for_data {}",
+                    "chunk[{}] end {} exceeds file_end_for_data {} (corrupt offset)",
                     i, end, file_end_for_data
                 ),
             )
@@ -276,33 +597,57 @@ pub fn list(archive: &Path, opts: Option<&ListOptions>) -> Result<()> {
         }
     }
 
-    let enc_mark = if enc_enabled { " [E]" } else { "" };
+    let mut rows = Vec::with_capacity(manifest.files.len());
     for fe in &manifest.files {
         let mut c_sum = 0u64;
+        let mut sealed = enc_enabled;
         for c in &fe.chunk_refs {
-            let id = c.id as usize;
-            if id >= table.len() {
-                return Err(std::io::Error::new(
-                    std::io::ErrorKind::InvalidData,
-                    format!(
-                        "chunk id {} out of bounds (table has {} entries)",
-                        c.id,
-                        table.len()
-                    ),
-                )
-                .into());
-            }
-            c_sum += table[id].c_size;
+            let ce = table.get(c.id as usize).ok_or_else(|| {
+                ArxError::Format(format!(
+                    "chunk id {} out of range (table has {} entries)",
+                    c.id,
+                    table.len()
+                ))
+            })?;
+            c_sum += ce.c_size;
+            sealed |= ce.is_sealed(sb.flags);
         }
-        println!(
-            "{}{}  u={}  c={}  chunks={}",
-            fe.path,
-            enc_mark,
-            fe.u_size,
-            c_sum,
-            fe.chunk_refs.len()
-        );
+        rows.push(ListRow {
+            path: fe.path.clone(),
+            u_size: fe.u_size,
+            c_size: c_sum,
+            chunks: fe.chunk_refs.len(),
+            encrypted: sealed,
+        });
     }
+    let dirs = manifest.dirs.into_iter().map(|d| d.path).collect();
+    Ok((rows, dirs))
+}
 
-    Ok(())
+/// End of the data region when the chunks are laid out back to back from
+/// `data_off`, as `pack` writes them. A tailless archive whose chunks are laid
+/// out this way but run past EOF was cut short rather than given bad offsets.
+fn packed_data_end(table: &[ChunkEntry], data_off: u64) -> Option<u64> {
+    let mut end = data_off;
+    for ce in table {
+        if ce.data_off != end {
+            return None;
+        }
+        end = end.checked_add(ce.c_size)?;
+    }
+    Some(end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_human_size_units() {
+        assert_eq!(human_size(0), "0B");
+        assert_eq!(human_size(1023), "1023B");
+        assert_eq!(human_size(1536), "1.5KiB");
+        assert_eq!(human_size(5 * 1024 * 1024), "5.0MiB");
+        assert_eq!(human_size(3 << 30), "3.0GiB");
+    }
 }
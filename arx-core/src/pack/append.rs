@@ -0,0 +1,289 @@
+//! Add files to an existing archive without re-chunking or recompressing what
+//! it already holds.
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{Seek, SeekFrom, Write};
+use std::path::Path;
+
+use crate::container::chunktab::{ChunkEntry, ENTRY_SIZE, write_table};
+use crate::container::manifest::{DirEntry, FileEntry, MANIFEST_VERSION};
+use crate::container::superblock::{
+    FLAG_CHUNK_AEAD, FLAG_ENCRYPTED, FLAG_KEYRING, FLAG_MANIFEST_ZSTD, Superblock,
+};
+use crate::crypto::aead::{AeadAlg, AeadKey, Region, derive_nonce, open_whole};
+use crate::crypto::kdf;
+use crate::error::{ArxError, Result};
+use crate::pack::writer::{
+    PackOptions, StreamPacker, TempArchive, attrs_from, mode_from, mtime_from, owner_from,
+    rel_display, sort_parents_first, walk_inputs,
+};
+use crate::read::extract::fix_tail;
+use crate::read::opened::Opened;
+
+/// Add the files and directories under `new_inputs` to the archive `existing`.
+///
+/// New content is chunked and deduplicated against the chunks already in the
+/// archive, so only genuinely new chunks are stored. A path that already
+/// exists is replaced; its old chunks stay in the table, unreferenced.
+///
+/// Unencrypted archives are updated in place when the space in front of the
+/// data region can hold the grown manifest and chunk table: new chunks are
+/// appended after the old data, every existing `data_off` stays put, and the
+/// superblock and tail are backpatched. Otherwise the archive is rewritten
+/// once, copying chunk bytes verbatim and leaving headroom so that later
+/// appends fit in place. Either way the new archive is built beside the old
+/// one and renamed over it only once complete, so a failure partway leaves
+/// the old archive as it was.
+///
+/// Encrypted archives (key or password in `opts`) are always rewritten and
+/// resealed under a fresh salt: the manifest and table nonces are fixed per
+/// salt, so sealing new contents in place would reuse them.
+///
+/// `emit_listing` and `emit_index` are ignored, and so is `zstd_dict`: new chunks use the
+/// dictionary the archive already has. Symlinks under `new_inputs` are
+/// skipped, as in [`pack`](crate::pack::writer::pack). Archives encrypted
+/// for `recipients` (a keyring) are refused.
+pub fn pack_append(
+    existing: &Path,
+    new_inputs: &[&Path],
+    opts: Option<&PackOptions>,
+) -> Result<()> {
+    append_with(existing, new_inputs, opts, || Ok(()))
+}
+
+/// [`pack_append`], running `before_tail` once an in-place update has written
+/// everything but the tail: a seam for failure-injection tests.
+fn append_with(
+    existing: &Path,
+    new_inputs: &[&Path],
+    opts: Option<&PackOptions>,
+    before_tail: impl FnOnce() -> Result<()>,
+) -> Result<()> {
+    let sb = Superblock::read_from(File::open(existing)?)?;
+    if sb.flags & FLAG_CHUNK_AEAD != 0 {
+        return Err(ArxError::Format(
+            "pack_append: archives with per-file encryption cannot be appended to".into(),
+        ));
+    }
+    if sb.flags & FLAG_KEYRING != 0 {
+        return Err(ArxError::Format(
+            "pack_append: archives with a recipient keyring cannot be appended to".into(),
+        ));
+    }
+    let encrypted = sb.flags & FLAG_ENCRYPTED != 0;
+    let has_key = opts.is_some_and(|o| o.aead_key.is_some() || o.password.is_some());
+    if encrypted && !has_key {
+        return Err(ArxError::Format(
+            "pack_append: archive is encrypted; key or password required".into(),
+        ));
+    }
+    if !encrypted && has_key {
+        return Err(ArxError::Format(
+            "pack_append: cannot encrypt while appending to an unencrypted archive".into(),
+        ));
+    }
+    if sb.version < 4 {
+        return Err(ArxError::Format(format!(
+            "pack_append: v{} archive has no chunk hashes to dedup against; repack it first",
+            sb.version
+        )));
+    }
+    let old_key = if encrypted {
+        opts.and_then(|o| {
+            o.aead_key.or_else(|| {
+                o.password
+                    .as_deref()
+                    .map(|pw| kdf::derive_key(pw, &sb.kdf_salt))
+            })
+        })
+    } else {
+        None
+    };
+    let mut arx = Opened::open(existing, old_key, [0u8; 32])?;
+    // New chunks compress against the archive's own dictionary, if it has one
+    let opts = Some(PackOptions {
+        emit_listing: None,
+        emit_index: None,
+        zstd_dict: arx.manifest.meta.zstd_dict.clone(),
+        ..opts.cloned().unwrap_or_default()
+    });
+    let opts = opts.as_ref();
+
+    // ── Chunk the new inputs, dedup against the existing table ───────────────
+    let mut sp = StreamPacker::new(opts)?;
+    sp.seed(&arx.table);
+    let deterministic = sp.deterministic;
+
+    let (files, dirs) = walk_inputs(new_inputs, opts)?;
+
+    let mut manifest = std::mem::take(&mut arx.manifest);
+    manifest.meta.manifest_version = MANIFEST_VERSION;
+    let mut file_idx: HashMap<String, usize> = manifest
+        .files
+        .iter()
+        .enumerate()
+        .map(|(i, f)| (f.path.clone(), i))
+        .collect();
+    for p in &files {
+        let md = fs::metadata(p)?;
+        let (chunk_refs, u_size, _) = sp.add(File::open(p)?)?;
+        let fe = FileEntry {
+            path: rel_display(p, new_inputs)?,
+            mode: mode_from(&md),
+            mtime: if deterministic { 0 } else { mtime_from(&md) },
+            u_size,
+            chunk_refs,
+            holes: vec![],
+            owner: if deterministic { None } else { owner_from(&md) },
+            attrs: attrs_from(&md),
+        };
+        manifest.hardlinks.retain(|h| h.path != fe.path);
+        manifest.symlinks.retain(|s| s.path != fe.path);
+        match file_idx.get(&fe.path) {
+            Some(&i) => manifest.files[i] = fe,
+            None => {
+                file_idx.insert(fe.path.clone(), manifest.files.len());
+                manifest.files.push(fe);
+            }
+        }
+    }
+    for d in &dirs {
+        let path = rel_display(d, new_inputs)?;
+        if manifest.dirs.iter().any(|e| e.path == path) {
+            continue;
+        }
+        let md = fs::metadata(d)?;
+        manifest.dirs.push(DirEntry {
+            path,
+            mode: mode_from(&md),
+            mtime: if deterministic { 0 } else { mtime_from(&md) },
+            owner: if deterministic { None } else { owner_from(&md) },
+        });
+    }
+    sort_parents_first(&mut manifest.dirs);
+    manifest.check_path_conflicts()?;
+
+    let base = std::mem::take(&mut arx.table);
+    let tmp = existing.with_extension("arx.tmp");
+
+    if encrypted {
+        let old_key = AeadKey(old_key.expect("checked above"));
+        let alg = AeadAlg::from_flags(sb.flags);
+        sp.finish_with_base(&tmp, opts, &manifest, &[], &base, 0, |i| {
+            let ce = &base[i];
+            let ct = arx.read_chunk_bytes(ce.data_off, ce.c_size)?;
+            let nonce = derive_nonce(alg, &sb.kdf_salt, Region::ChunkData, i as u64);
+            open_whole(&old_key, &nonce, b"chunk", &ct)
+        })?;
+        drop(arx);
+        fs::rename(&tmp, existing)?;
+        return Ok(());
+    }
+
+    // ── Unencrypted: grow the front regions in place if they fit ─────────────
+    let old_end = base
+        .iter()
+        .map(|e| e.data_off + e.c_size)
+        .fold(sb.data_off, u64::max);
+    let mut table = base.clone();
+    let mut off = old_end;
+    for ce in sp.new_chunks() {
+        table.push(ChunkEntry {
+            data_off: off,
+            ..*ce
+        });
+        off += ce.c_size;
+    }
+    let mut manifest_plain = Vec::new();
+    ciborium::ser::into_writer(&manifest, &mut manifest_plain).map_err(std::io::Error::other)?;
+    let table_len = (table.len() * ENTRY_SIZE) as u64;
+    let table_off = sb.data_off.checked_sub(table_len);
+    let min_table_off = sb.header_len() + manifest_plain.len() as u64 + sb.guard_len();
+    let Some(table_off) = table_off.filter(|&t| t >= min_table_off) else {
+        // No room: rewrite once, with headroom for the next appends
+        let headroom = manifest_plain.len() as u64 + table_len;
+        sp.finish_with_base(&tmp, opts, &manifest, &[], &base, headroom, |i| {
+            let ce = &base[i];
+            Ok(arx.read_chunk_bytes(ce.data_off, ce.c_size)?)
+        })?;
+        drop(arx);
+        fs::rename(&tmp, existing)?;
+        return Ok(());
+    };
+    drop(arx);
+
+    // New data past the old, then the table and manifest, then the superblock
+    // that points at them; the tail is rewritten last. All of it goes to a
+    // copy of the archive, which replaces it only once complete.
+    let mut tmp = TempArchive::copy_of(existing)?;
+    let f = tmp.file();
+    f.seek(SeekFrom::Start(old_end))?;
+    for i in 0..sp.new_chunks().len() {
+        f.write_all(&sp.new_chunk_data(i)?)?;
+    }
+    let mut table_bytes = Vec::with_capacity(table_len as usize);
+    write_table(&mut table_bytes, &table)?;
+    f.seek(SeekFrom::Start(table_off))?;
+    f.write_all(&table_bytes)?;
+    // The header keeps its version (and so its length) when updated in place
+    f.seek(SeekFrom::Start(sb.header_len()))?;
+    f.write_all(&manifest_plain)?;
+    f.seek(SeekFrom::Start(0))?;
+    let sb = Superblock {
+        manifest_len: manifest_plain.len() as u64,
+        chunk_table_off: table_off,
+        chunk_count: table.len() as u64,
+        flags: sb.flags & !FLAG_MANIFEST_ZSTD,
+        file_count: manifest.files.len() as u64,
+        total_logical: manifest.total_logical(),
+        ..sb
+    };
+    sb.write_to(&mut *f)?;
+    sb.write_guards(&mut *f)?;
+
+    before_tail()?;
+    fix_tail(tmp.path(), None)?;
+    tmp.persist(opts.is_some_and(|o| o.durable))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pack::writer::pack;
+    use crate::read::extract::verify;
+
+    #[test]
+    fn test_failed_in_place_append_leaves_archive_intact() {
+        let dir = tempfile::tempdir().unwrap();
+        let (old, new, more) = (
+            dir.path().join("old"),
+            dir.path().join("new"),
+            dir.path().join("more"),
+        );
+        for (d, name) in [(&old, "a.txt"), (&new, "b.txt"), (&more, "c.txt")] {
+            fs::create_dir_all(d).unwrap();
+            fs::write(d.join(name), name.repeat(1000)).unwrap();
+        }
+        let archive = dir.path().join("a.arx");
+        pack(&[old.as_path()], &archive, None).unwrap();
+        // The first append rewrites with headroom, so the next one is in place
+        pack_append(&archive, &[new.as_path()], None).unwrap();
+        let before = fs::read(&archive).unwrap();
+
+        let err = append_with(&archive, &[more.as_path()], None, || {
+            Err(std::io::Error::other("injected").into())
+        })
+        .unwrap_err();
+        assert!(err.to_string().contains("injected"), "{err}");
+        assert_eq!(fs::read(&archive).unwrap(), before);
+        assert!(!archive.with_extension("arx.tmp").exists());
+        verify(&archive, None).unwrap();
+
+        // Without the fault the same append goes through
+        pack_append(&archive, &[more.as_path()], None).unwrap();
+        verify(&archive, None).unwrap();
+        let opened = Opened::open(&archive, None, [0u8; 32]).unwrap();
+        assert!(opened.manifest.files.iter().any(|f| f.path == "c.txt"));
+    }
+}
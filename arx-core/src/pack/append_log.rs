@@ -0,0 +1,355 @@
+//! Append-only log archives: one logical stream that grows in place.
+//!
+//! [`create_log`] lays the archive out with spare room: the manifest region is
+//! reserved up to `manifest_reserve` bytes and the chunk table holds `slots`
+//! entries, the unused ones being empty placeholders (`u_size == 0`, pointing
+//! at `data_off`) that every reader already accepts. [`append_records`] chunks
+//! the new bytes, writes the new chunks where the tail used to be, fills the
+//! next free table slots, rewrites the manifest inside its reservation and
+//! backpatches the superblock and tail. Chunks already in the archive are never
+//! moved; only when the slots or the manifest room run out is the archive
+//! rebuilt, with the reservation doubled.
+//!
+//! Log archives are unencrypted: rewriting the manifest and table in place
+//! would reuse their AEAD nonces.
+
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use time::OffsetDateTime;
+
+use crate::chunking::fastcdc::{ChunkParams, StreamingChunker};
+use crate::codec::{CodecId, get_decoder_u8};
+use crate::container::chunktab::{ChunkEntry, ENTRY_SIZE, read_table_from_slice, write_table};
+use crate::container::manifest::{ChunkRef, FileEntry, Manifest};
+use crate::container::superblock::{
+    FLAG_CHUNK_AEAD, FLAG_ENCRYPTED, FLAG_MANIFEST_ZSTD, HEADER_LEN, REGION_GUARD_LEN, Superblock,
+    VERSION,
+};
+use crate::error::{ArxError, Result};
+use crate::pack::writer::{PackOptions, effective_min_gain, new_meta, should_compress};
+use crate::read::extract::fix_tail;
+
+/// Chunk-table slots reserved by [`LogOptions::default`].
+pub const DEFAULT_LOG_SLOTS: u64 = 1024;
+/// Manifest bytes reserved by [`LogOptions::default`].
+pub const DEFAULT_MANIFEST_RESERVE: u64 = 64 * 1024;
+
+/// Space a log archive reserves for growth.
+#[derive(Clone, Copy, Debug)]
+pub struct LogOptions {
+    /// Chunk-table entries, used or not.
+    pub slots: u64,
+    /// Bytes set aside for the manifest region.
+    pub manifest_reserve: u64,
+}
+
+impl Default for LogOptions {
+    fn default() -> Self {
+        Self {
+            slots: DEFAULT_LOG_SLOTS,
+            manifest_reserve: DEFAULT_MANIFEST_RESERVE,
+        }
+    }
+}
+
+/// Create an empty log archive at `out` holding a single file `name`.
+/// Only the metadata and `deterministic` fields of `opts` are used; asking
+/// for encryption is an error.
+pub fn create_log(
+    out: &Path,
+    name: &str,
+    log: &LogOptions,
+    opts: Option<&PackOptions>,
+) -> Result<()> {
+    if opts.is_some_and(|o| o.aead_key.is_some() || o.password.is_some()) {
+        return Err(ArxError::Format("log archives cannot be encrypted".into()));
+    }
+    if opts.is_some_and(|o| o.zstd_dict.is_some()) {
+        return Err(ArxError::Format(
+            "log archives do not support zstd_dict".into(),
+        ));
+    }
+    if name.is_empty()
+        || name
+            .split('/')
+            .any(|s| s.is_empty() || s == "." || s == "..")
+    {
+        return Err(ArxError::Format(format!("invalid log file name: {name:?}")));
+    }
+    let deterministic = opts.map(|o| o.deterministic).unwrap_or(false);
+    let meta = new_meta(opts, deterministic);
+    let manifest = Manifest {
+        files: vec![FileEntry {
+            path: name.to_string(),
+            mode: 0o100644,
+            mtime: meta.created,
+            u_size: 0,
+            chunk_refs: vec![],
+            holes: vec![],
+            owner: None,
+            attrs: None,
+        }],
+        dirs: vec![],
+        symlinks: vec![],
+        hardlinks: vec![],
+        meta,
+    };
+    write_layout(out, &manifest, &[], log, |_| Ok(Vec::new()))
+}
+
+/// Append `bytes` to the single file of the log archive `archive`.
+///
+/// New chunks are deduplicated against the existing ones and written after
+/// the current data; earlier chunks stay where they are. The update is not
+/// atomic: a crash part-way leaves an archive that `verify` rejects.
+pub fn append_records(archive: &Path, bytes: &[u8]) -> Result<()> {
+    if bytes.is_empty() {
+        return Ok(());
+    }
+    let mut f = OpenOptions::new().read(true).write(true).open(archive)?;
+    let mut sb = Superblock::read_from(&mut f)?;
+    if sb.flags & (FLAG_ENCRYPTED | FLAG_CHUNK_AEAD) != 0 {
+        return Err(ArxError::Format(
+            "cannot append records to an encrypted archive".into(),
+        ));
+    }
+    if sb.version < 4 {
+        return Err(ArxError::Format(format!(
+            "cannot append records to a v{} archive",
+            sb.version
+        )));
+    }
+    let header_len = sb.header_len();
+    sb.check_guards(&mut f)?;
+
+    f.seek(SeekFrom::Start(header_len))?;
+    let mut mbytes = vec![0u8; sb.manifest_len as usize];
+    f.read_exact(&mut mbytes)?;
+    let mut manifest = Manifest::from_region(&mbytes, sb.flags)?;
+    if manifest.files.len() != 1
+        || !manifest.dirs.is_empty()
+        || !manifest.symlinks.is_empty()
+        || !manifest.hardlinks.is_empty()
+    {
+        return Err(ArxError::Format(
+            "not a log archive: expected exactly one file and nothing else".into(),
+        ));
+    }
+
+    let table_len = sb.data_off.checked_sub(sb.chunk_table_off).ok_or_else(|| {
+        ArxError::Format(format!(
+            "chunk_table_off {} > data_off {}",
+            sb.chunk_table_off, sb.data_off
+        ))
+    })?;
+    f.seek(SeekFrom::Start(sb.chunk_table_off))?;
+    let mut tbytes = vec![0u8; table_len as usize];
+    f.read_exact(&mut tbytes)?;
+    let table = read_table_from_slice(&tbytes, sb.chunk_count)?;
+
+    // Used slots come first; the rest are placeholders
+    let used = table.iter().take_while(|e| e.u_size > 0).count();
+    if table[used..].iter().any(|e| e.u_size > 0) {
+        return Err(ArxError::Format(
+            "not a log archive: chunk table has unused slots between chunks".into(),
+        ));
+    }
+    let data_end = table[..used]
+        .iter()
+        .map(|e| e.data_off + e.c_size)
+        .fold(sb.data_off, u64::max);
+
+    // Chunk → dedup → compress, exactly as pack does
+    let codec_id = CodecId::default_for_pack();
+    let compressor = get_decoder_u8(codec_id as u8)?;
+    let min_gain = effective_min_gain(None);
+    let mut by_hash: HashMap<[u8; 32], u64> = table[..used]
+        .iter()
+        .enumerate()
+        .map(|(i, e)| (e.blake3, i as u64))
+        .collect();
+    let mut new_entries: Vec<ChunkEntry> = Vec::new();
+    let mut new_data: Vec<Vec<u8>> = Vec::new();
+    let mut refs = Vec::new();
+    let mut chunker = StreamingChunker::new(ChunkParams::default());
+    let mut src = bytes;
+    let mut buf = Vec::new();
+    let mut off = data_end;
+    loop {
+        let n = chunker.next_chunk(&mut src, &mut buf)?;
+        if n == 0 {
+            break;
+        }
+        let chunk = &buf[..n];
+        let hash = *blake3::hash(chunk).as_bytes();
+        let id = match by_hash.get(&hash) {
+            Some(&id) => id,
+            None => {
+                let mut comp = Vec::with_capacity(n);
+                compressor.compress(&mut &chunk[..], &mut comp, 3)?;
+                let (codec, stored) = if should_compress(n, comp.len(), min_gain) {
+                    (codec_id as u8, comp)
+                } else {
+                    (CodecId::Store as u8, chunk.to_vec())
+                };
+                new_entries.push(ChunkEntry {
+                    codec,
+                    u_size: n as u64,
+                    c_size: stored.len() as u64,
+                    data_off: off,
+                    blake3: hash,
+                    encrypted: false,
+                    parent: false,
+                });
+                off += stored.len() as u64;
+                new_data.push(stored);
+                let id = (used + new_entries.len() - 1) as u64;
+                by_hash.insert(hash, id);
+                id
+            }
+        };
+        refs.push(ChunkRef {
+            id,
+            u_size: n as u64,
+        });
+    }
+
+    let file = &mut manifest.files[0];
+    file.chunk_refs.extend(refs);
+    file.u_size += bytes.len() as u64;
+    if manifest.meta.created != 0 {
+        file.mtime = OffsetDateTime::now_utc().unix_timestamp();
+    }
+    let cbor = manifest_cbor(&manifest)?;
+
+    let manifest_room = sb.chunk_table_off - header_len - sb.guard_len();
+    let slots_needed = (used + new_entries.len()) as u64;
+    if slots_needed > sb.chunk_count || cbor.len() as u64 > manifest_room {
+        // Out of reservation: rebuild once with twice the room
+        let log = LogOptions {
+            slots: (sb.chunk_count * 2).max(slots_needed * 2),
+            manifest_reserve: (manifest_room * 2).max(cbor.len() as u64 * 2),
+        };
+        let mut entries = table[..used].to_vec();
+        entries.extend_from_slice(&new_entries);
+        let tmp = archive.with_extension("arx.tmp");
+        write_layout(&tmp, &manifest, &entries, &log, |i| {
+            if i >= used {
+                return Ok(std::mem::take(&mut new_data[i - used]));
+            }
+            let e = &entries[i];
+            let mut data = vec![0u8; e.c_size as usize];
+            f.seek(SeekFrom::Start(e.data_off))?;
+            f.read_exact(&mut data)?;
+            Ok(data)
+        })?;
+        drop(f);
+        std::fs::rename(&tmp, archive)?;
+        return Ok(());
+    }
+
+    // Data first, then the table slots, then the manifest and superblock that
+    // make them reachable, and finally the tail.
+    f.seek(SeekFrom::Start(data_end))?;
+    for d in &new_data {
+        f.write_all(d)?;
+    }
+    let mut tab = Vec::with_capacity(new_entries.len() * ENTRY_SIZE);
+    write_table(&mut tab, &new_entries)?;
+    f.seek(SeekFrom::Start(
+        sb.chunk_table_off + (used * ENTRY_SIZE) as u64,
+    ))?;
+    f.write_all(&tab)?;
+
+    f.seek(SeekFrom::Start(header_len))?;
+    f.write_all(&cbor)?;
+    sb.manifest_len = cbor.len() as u64;
+    sb.flags &= !FLAG_MANIFEST_ZSTD;
+    sb.file_count = manifest.files.len() as u64;
+    sb.total_logical = manifest.total_logical();
+    f.seek(SeekFrom::Start(0))?;
+    sb.write_to(&mut f)?;
+    sb.write_guards(&mut f)?;
+    drop(f);
+
+    fix_tail(archive, None)?;
+    Ok(())
+}
+
+/// Write a log archive from scratch: superblock, reserved manifest region,
+/// `log.slots` table entries, the chunks of `used` in order, and the tail.
+/// `chunk_data(i)` returns the stored bytes of `used[i]`.
+fn write_layout(
+    out: &Path,
+    manifest: &Manifest,
+    used: &[ChunkEntry],
+    log: &LogOptions,
+    mut chunk_data: impl FnMut(usize) -> Result<Vec<u8>>,
+) -> Result<()> {
+    let cbor = manifest_cbor(manifest)?;
+    let manifest_cap = log.manifest_reserve.max(cbor.len() as u64);
+    let slots = log.slots.max(used.len() as u64);
+    let chunk_table_off = HEADER_LEN + REGION_GUARD_LEN + manifest_cap + REGION_GUARD_LEN;
+    let data_off = chunk_table_off + slots * ENTRY_SIZE as u64;
+
+    let mut f = File::create(out)?;
+    f.seek(SeekFrom::Start(data_off))?;
+    let mut entries = Vec::with_capacity(slots as usize);
+    let mut off = data_off;
+    for (i, e) in used.iter().enumerate() {
+        let data = chunk_data(i)?;
+        f.write_all(&data)?;
+        entries.push(ChunkEntry {
+            data_off: off,
+            ..*e
+        });
+        off += data.len() as u64;
+    }
+    entries.resize(
+        slots as usize,
+        ChunkEntry {
+            codec: CodecId::Store as u8,
+            u_size: 0,
+            c_size: 0,
+            data_off,
+            blake3: *blake3::hash(b"").as_bytes(),
+            encrypted: false,
+            parent: false,
+        },
+    );
+
+    let sb = Superblock {
+        version: VERSION,
+        manifest_len: cbor.len() as u64,
+        chunk_table_off,
+        chunk_count: slots,
+        data_off,
+        flags: 0,
+        kdf_salt: [0u8; 32],
+        file_count: manifest.files.len() as u64,
+        total_logical: manifest.total_logical(),
+        keyring_len: 0,
+    };
+    f.seek(SeekFrom::Start(0))?;
+    sb.write_to(&mut f)?;
+    f.seek(SeekFrom::Start(sb.header_len()))?;
+    f.write_all(&cbor)?;
+    // Zero the unused manifest reservation explicitly rather than leaving a hole
+    f.write_all(&vec![0u8; (manifest_cap - cbor.len() as u64) as usize])?;
+    f.seek(SeekFrom::Start(chunk_table_off))?;
+    write_table(&mut f, &entries)?;
+    sb.write_guards(&mut f)?;
+    drop(f);
+
+    fix_tail(out, None)?;
+    Ok(())
+}
+
+fn manifest_cbor(manifest: &Manifest) -> Result<Vec<u8>> {
+    let mut cbor = Vec::new();
+    ciborium::ser::into_writer(manifest, &mut cbor).map_err(std::io::Error::other)?;
+    Ok(cbor)
+}
@@ -0,0 +1,121 @@
+//! Chunk-hash → chunk-id index used for dedup while packing.
+//!
+//! Unbounded, it remembers every unique chunk: ~48 bytes of map entry per
+//! chunk, i.e. close to 200 MiB per TB of input at the default 256 KiB average
+//! chunk size. With a window it keeps only the most recently used `window`
+//! hashes, and a chunk whose earlier copy has aged out is simply stored again.
+//! Duplicates that recur close together (the common case: copies within a
+//! directory, re-appended logs) are still caught.
+//!
+//! The window bounds this index only. The writer's per-file chunk plans still
+//! hold every chunk's hash until the pack is written, so a pack's memory keeps
+//! growing with its chunk count either way.
+
+use std::collections::{HashMap, VecDeque};
+
+pub(crate) struct DedupIndex {
+    /// hash → (chunk id, tick of last use)
+    map: HashMap<[u8; 32], (u64, u64)>,
+    /// Uses in tick order; entries whose tick is older than the map's are stale.
+    recency: VecDeque<([u8; 32], u64)>,
+    tick: u64,
+    /// Maximum hashes remembered; 0 means unbounded.
+    window: usize,
+}
+
+impl DedupIndex {
+    pub(crate) fn new(window: usize) -> Self {
+        Self {
+            map: HashMap::new(),
+            recency: VecDeque::new(),
+            tick: 0,
+            window,
+        }
+    }
+
+    /// Id of the chunk with this hash, if remembered; marks it recently used.
+    pub(crate) fn get(&mut self, hash: &[u8; 32]) -> Option<u64> {
+        let id = self.map.get(hash)?.0;
+        self.touch(*hash, id);
+        Some(id)
+    }
+
+    /// Remember `hash` as chunk `id`, evicting the least recently used hash
+    /// when the window is full.
+    pub(crate) fn insert(&mut self, hash: [u8; 32], id: u64) {
+        self.touch(hash, id);
+        if self.window == 0 {
+            return;
+        }
+        while self.map.len() > self.window {
+            let Some((old, t)) = self.recency.pop_front() else {
+                break;
+            };
+            if self.map.get(&old).is_some_and(|&(_, last)| last == t) {
+                self.map.remove(&old);
+            }
+        }
+    }
+
+    fn touch(&mut self, hash: [u8; 32], id: u64) {
+        self.tick += 1;
+        self.map.insert(hash, (id, self.tick));
+        if self.window == 0 {
+            return;
+        }
+        self.recency.push_back((hash, self.tick));
+        // Re-used hashes leave stale queue entries behind, on hits as well as
+        // inserts; drop them before the queue outgrows the window by much.
+        if self.recency.len() > 2 * self.window {
+            let map = &self.map;
+            self.recency
+                .retain(|(h, t)| map.get(h).is_some_and(|&(_, last)| last == *t));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn h(b: u8) -> [u8; 32] {
+        [b; 32]
+    }
+
+    #[test]
+    fn test_unbounded_remembers_everything() {
+        let mut idx = DedupIndex::new(0);
+        for i in 0..1000u64 {
+            idx.insert(h((i % 256) as u8), i);
+        }
+        assert!(idx.recency.is_empty());
+        assert_eq!(idx.get(&h(0)), Some(768));
+    }
+
+    #[test]
+    fn test_window_evicts_least_recently_used() {
+        let mut idx = DedupIndex::new(2);
+        idx.insert(h(1), 0);
+        idx.insert(h(2), 1);
+        assert_eq!(idx.get(&h(1)), Some(0)); // 1 is now most recent
+        idx.insert(h(3), 2); // evicts 2
+        assert_eq!(idx.get(&h(2)), None);
+        assert_eq!(idx.get(&h(1)), Some(0));
+        assert_eq!(idx.get(&h(3)), Some(2));
+        assert_eq!(idx.map.len(), 2);
+    }
+
+    #[test]
+    fn test_recency_queue_stays_bounded() {
+        let mut idx = DedupIndex::new(4);
+        for i in 0..100u64 {
+            idx.insert(h((i % 8) as u8), i);
+            // A run of repeated chunks: many hits, no inserts
+            for _ in 0..1000 {
+                idx.get(&h((i % 8) as u8));
+                assert!(idx.recency.len() <= 2 * 4 + 1);
+            }
+        }
+        assert!(idx.map.len() <= 4);
+    }
+}
@@ -0,0 +1,155 @@
+//! Pack a `.tar` stream straight into an archive, without unpacking it to disk.
+
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::Path;
+
+use crate::container::manifest::{
+    DirEntry, FileEntry, HardlinkEntry, Manifest, Owner, SymlinkEntry,
+};
+use crate::error::{ArxError, Result};
+use crate::pack::writer::{PackOptions, StreamPacker, new_meta};
+use crate::util::sanitize::sanitize_entry_path;
+
+const S_IFREG: u32 = 0o100000;
+const S_IFDIR: u32 = 0o040000;
+const S_IFLNK: u32 = 0o120000;
+
+/// Pack the entries of the tar stream `reader` into `out`. Regular files go
+/// through the same chunk → dedup → codec → AEAD pipeline as [`pack_stream`];
+/// directories, symlinks and hardlinks are recorded with their header metadata.
+/// GNU long names and pax `path`/`linkpath`/`mtime` records are honoured.
+/// Contiguous files count as regular files, and GNU sparse files are packed
+/// with their holes expanded to zeros. Device nodes and FIFOs are skipped. A path repeated later in the stream
+/// replaces the earlier entry, as `tar -x` would.
+///
+/// [`pack_stream`]: crate::pack::writer::pack_stream
+pub fn pack_from_tar(reader: impl Read, out: &Path, opts: Option<&PackOptions>) -> Result<()> {
+    let mut sp = StreamPacker::new(opts)?;
+    let mut files: Vec<FileEntry> = Vec::new();
+    let mut hashes: Vec<[u8; 32]> = Vec::new();
+    let mut file_idx: HashMap<String, usize> = HashMap::new();
+    let mut dirs: Vec<DirEntry> = Vec::new();
+    let mut symlinks: Vec<SymlinkEntry> = Vec::new();
+    let mut hardlinks: Vec<HardlinkEntry> = Vec::new();
+
+    let mut archive = tar::Archive::new(reader);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = normalize(&entry.path()?)?;
+        if path.is_empty() {
+            continue; // "./" itself
+        }
+        let header = entry.header();
+        let kind = header.entry_type();
+        let perm = header.mode()? & 0o7777;
+        // Some writers leave uid/gid blank; that is an unknown owner, not an error
+        let owner = match (sp.deterministic, header.uid(), header.gid()) {
+            (false, Ok(uid), Ok(gid)) => Some(Owner {
+                uid: uid as u32,
+                gid: gid as u32,
+            }),
+            _ => None,
+        };
+        let mtime = if sp.deterministic {
+            0
+        } else {
+            pax_mtime(&mut entry)?.unwrap_or(entry.header().mtime()? as i64)
+        };
+
+        // The tar reader expands a GNU sparse member's holes as it is read
+        if kind.is_file() || kind.is_contiguous() || kind.is_gnu_sparse() {
+            let (chunk_refs, u_size, hash) = sp.add(&mut entry)?;
+            let fe = FileEntry {
+                path: path.clone(),
+                mode: S_IFREG | perm,
+                mtime,
+                u_size,
+                chunk_refs,
+                holes: vec![],
+                owner,
+                attrs: None,
+            };
+            match file_idx.get(&path) {
+                Some(&i) => {
+                    files[i] = fe;
+                    hashes[i] = hash;
+                }
+                None => {
+                    file_idx.insert(path, files.len());
+                    files.push(fe);
+                    hashes.push(hash);
+                }
+            }
+        } else if kind.is_dir() {
+            dirs.push(DirEntry {
+                path,
+                mode: S_IFDIR | perm,
+                mtime,
+                owner,
+            });
+        } else if kind.is_symlink() {
+            let target = link_target(&entry, &path)?;
+            symlinks.push(SymlinkEntry {
+                path,
+                target,
+                mode: S_IFLNK | perm,
+                mtime,
+            });
+        } else if kind.is_hard_link() {
+            let target = normalize(Path::new(&link_target(&entry, &path)?))?;
+            if !file_idx.contains_key(&target) {
+                return Err(ArxError::Format(format!(
+                    "tar hardlink {path} points at {target}, which is not an earlier regular file"
+                )));
+            }
+            hardlinks.push(HardlinkEntry { path, target });
+        }
+        // pax/GNU extension headers are consumed by the tar reader itself;
+        // character/block devices and FIFOs are not representable and skipped.
+    }
+
+    // Directories may be listed more than once (e.g. by appended tars)
+    dirs.sort_by(|a, b| a.path.cmp(&b.path));
+    dirs.dedup_by(|a, b| a.path == b.path);
+
+    let manifest = Manifest {
+        files,
+        dirs,
+        symlinks,
+        hardlinks,
+        meta: new_meta(opts, sp.deterministic),
+    };
+    manifest.check_path_conflicts()?;
+    sp.finish(out, opts, &manifest, &hashes)
+}
+
+/// Archive-relative form of a tar member path: `./` and leading `/` dropped,
+/// `..` rejected.
+fn normalize(path: &Path) -> Result<String> {
+    sanitize_entry_path(path.to_string_lossy().trim_start_matches('/'))
+}
+
+fn link_target<R: Read>(entry: &tar::Entry<'_, R>, path: &str) -> Result<String> {
+    let target = entry
+        .link_name()?
+        .ok_or_else(|| ArxError::Format(format!("tar link {path} has no target")))?;
+    Ok(target.to_string_lossy().into_owned())
+}
+
+/// `mtime` from the entry's pax header, which can exceed the ustar field's range.
+fn pax_mtime<R: Read>(entry: &mut tar::Entry<'_, R>) -> Result<Option<i64>> {
+    let Some(exts) = entry.pax_extensions()? else {
+        return Ok(None);
+    };
+    for ext in exts {
+        let ext = ext?;
+        if ext.key() == Ok("mtime")
+            && let Ok(v) = ext.value()
+            && let Some(secs) = v.split('.').next().and_then(|s| s.parse::<i64>().ok())
+        {
+            return Ok(Some(secs));
+        }
+    }
+    Ok(None)
+}
@@ -1,29 +1,49 @@
-use crate::chunking::fastcdc::{ChunkParams, StreamingChunker};
-use crate::codec::zstdc::ZstdCompressor;
-use crate::codec::{CodecId, Compressor};
+use crate::chunking::fastcdc::{
+    Boundary, ChunkParams, MAX_CHUNK_LIMIT, StreamingChunker, chunk_boundaries,
+};
+use crate::codec::{CodecId, CodecStrategy, Compressor, compress_to, compress_u8, get_decoder_u8};
 use crate::container::chunktab::{ChunkEntry, ENTRY_SIZE, write_table};
-use crate::container::manifest::{ChunkRef, DirEntry, FileEntry, Manifest, Meta};
+use crate::container::manifest::{
+    ChunkRef, DirEntry, FileEntry, HardlinkEntry, MANIFEST_VERSION, Manifest, Meta, Owner,
+};
 use crate::container::superblock::{
-    FLAG_ENCRYPTED, FLAG_KDF_PASSWORD, HEADER_LEN, Superblock, VERSION,
+    FLAG_CHUNK_AEAD, FLAG_ENCRYPTED, FLAG_KDF_PASSWORD, FLAG_KEYRING, FLAG_MANIFEST_ZSTD,
+    FLAG_PARENT, HEADER_LEN, REGION_GUARD_LEN, Superblock, VERSION,
+};
+use crate::container::tail::{TAIL_LEN, TailSummary};
+use crate::crypto::aead::{
+    AeadAlg, AeadKey, Region, TAG_LEN, derive_nonce, seal_in_place, seal_whole,
 };
-use crate::container::tail::TailSummary;
-use crate::crypto::aead::{AeadKey, Region, TAG_LEN, derive_nonce, seal_whole};
 use crate::crypto::kdf;
+use crate::crypto::keyring::seal_keyring;
 use crate::crypto::nonce::random_salt;
-use crate::error::Result;
+use crate::error::{ArxError, Result};
+use crate::list::entry_line;
+use crate::pack::dedup::DedupIndex;
+use crate::read::extract::{ExtractOptions, open_archive};
+use crate::util::cancel::check_cancel;
+use crate::util::hash_forward::HashingForward;
+use crate::util::sanitize::sanitize_entry_path;
 
 use blake3;
 use rayon::prelude::*;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs::{self, File};
-use std::io::{Read, Seek, SeekFrom, Write};
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, Mutex};
 use time::OffsetDateTime;
 use walkdir::WalkDir;
 
 #[derive(Clone, Default)]
 pub struct PackOptions {
     /// When true, zero timestamps in manifest for deterministic output.
+    /// Two such packs are byte-identical, encrypted or not, when the inputs'
+    /// relative paths, contents, modes and links match and the options, key
+    /// and `key_salt` are the same; where the inputs live, their mtimes and
+    /// owners, the host and the thread count don't matter. Compressed chunks
+    /// also depend on the zstd library version.
     pub deterministic: bool,
     /// Only accept compression if it saves at least this fraction (e.g. 0.05 = 5%).
     pub min_gain: f32, // default 0.05 if left as 0.0
@@ -32,39 +52,145 @@ pub struct PackOptions {
     /// Derive the encryption key from this password via Argon2id.
     /// If both `aead_key` and `password` are set, `aead_key` takes precedence.
     pub password: Option<String>,
-    /// KDF salt override (used for deterministic builds); leave as zeros to auto-generate.
+    /// Encrypt for these raw 32-byte recipient keys instead of `aead_key` or
+    /// `password` (sets `FLAG_KEYRING`): regions are sealed under a random
+    /// data key, wrapped once per recipient, and any one recipient key opens
+    /// the archive as `aead_key` would. Change recipients with
+    /// [`rekey_recipients`](crate::crypto::rekey::rekey_recipients). Not with
+    /// `encrypt_paths`; the random data key makes such packs non-reproducible.
+    pub recipients: Vec<[u8; 32]>,
+    /// KDF/nonce salt override (used for deterministic builds); leave as zeros to
+    /// auto-generate a random one per archive. Deterministic packs keep it zero,
+    /// which encryption refuses unless `allow_zero_salt` is set.
     pub key_salt: [u8; 32],
     /// Optional metadata embedded in the manifest (label, owner, notes).
     pub meta_label: Option<String>,
     pub meta_owner: Option<String>,
     pub meta_notes: Option<String>,
+    /// Also record where the pack came from in `Meta`: the absolute input
+    /// roots and the local hostname (`arx info` shows them). Off by default,
+    /// as both reveal the machine and directories the archive was built from;
+    /// ignored for deterministic packs.
+    pub record_origin: bool,
+    /// FastCDC chunk size bounds in bytes. Zero falls back to the defaults;
+    /// when only `chunk_avg` is set, min/max are derived as avg/4 and avg*4
+    /// (max capped at `MAX_CHUNK_LIMIT`).
+    pub chunk_min: usize,
+    pub chunk_avg: usize,
+    pub chunk_max: usize,
+    /// Codec tried for each chunk (kept only if it meets `min_gain`). Defaults to Zstd,
+    /// or Store in builds without the `zstd` feature. Overrides a `Fixed` strategy.
+    pub codec: Option<CodecId>,
+    /// Which codecs each chunk is trial-compressed with; the winner is recorded
+    /// per chunk, so readers need nothing extra.
+    pub codec_strategy: CodecStrategy,
+    /// Zstd-compress the manifest CBOR before sealing it (sets `FLAG_MANIFEST_ZSTD`).
+    /// Ignored in builds without the `zstd` feature.
+    /// Pays off for trees of many small files, where paths dominate the archive.
+    pub compress_manifest: bool,
+    /// Also write a text catalog of the packed files here: one `list`-style line per
+    /// file followed by the blake3 of its contents.
+    pub emit_listing: Option<PathBuf>,
+    /// Also write a metadata index here (conventionally `<out>.arxidx`): the
+    /// archive's bytes up to `data_off`, i.e. superblock, manifest and chunk
+    /// table without the data region, so a remote client can list and map
+    /// chunks without fetching the archive. Read it with [`Opened::open_index`]
+    /// or `ListOptions::index`.
+    ///
+    /// [`Opened::open_index`]: crate::read::opened::Opened::open_index
+    pub emit_index: Option<PathBuf>,
+    /// Cipher for the encrypted regions; recorded in the superblock flags.
+    pub aead_alg: AeadAlg,
+    /// Remember at most this many distinct chunk hashes for dedup, evicting the
+    /// least recently used; 0 (the default) remembers all of them. Bounds the
+    /// dedup index for very large inputs, at the cost of storing a chunk again
+    /// when its previous copy has aged out of the window. The rest of the
+    /// pack's bookkeeping still grows with the number of chunks.
+    pub dedup_window: usize,
+    /// Seal only the chunks of files whose archive path matches one of these
+    /// globs (e.g. `secrets/**`), storing the rest in plaintext (sets
+    /// `FLAG_CHUNK_AEAD`). The manifest and chunk table are still sealed, so
+    /// reading anything needs the key unless `public_manifest` is set. Empty
+    /// seals the whole archive as usual when a key or password is given. Only
+    /// [`pack`] and [`pack_to_writer`] support this.
+    pub encrypt_paths: Vec<String>,
+    /// With `encrypt_paths`, leave the manifest and chunk table unsealed so
+    /// the file list and the unmatched files read without a key. Every path
+    /// and size, and the contents of unmatched files, are then public.
+    pub public_manifest: bool,
+    /// Seal even when the salt resolves to all zeros (a deterministic pack
+    /// without `key_salt`). Nonces then repeat across every archive sealed
+    /// under the same key, so only use this for throwaway fixtures.
+    pub allow_zero_salt: bool,
+    /// Skip input paths matching any of these globs. A pattern containing `/`
+    /// is matched against the path relative to its input root; one without
+    /// (e.g. `.git`, `*.tmp`) against each entry's file name. An excluded
+    /// directory prunes its whole subtree.
+    pub exclude: Vec<String>,
+    /// Also skip whatever `.gitignore`/`.ignore` files inside the inputs ignore,
+    /// applying nested ones per directory as git does.
+    pub respect_gitignore: bool,
+    /// Follow symlinks under the inputs and archive what they point to, as
+    /// regular files and directories under the link's path. A directory reached
+    /// a second time (a link cycle, or two links to one place) is walked only
+    /// the first time. A dangling link is an error. Off, symlinks are skipped.
+    pub follow_symlinks: bool,
+    /// Walk and chunk the inputs but write nothing; see [`plan_pack`] for the
+    /// resulting summary. Only [`pack`] and [`pack_to_writer`] support this.
+    pub dry_run: bool,
+    /// Differential pack: chunks already stored in this parent archive are not
+    /// stored again, only referenced (sets `FLAG_PARENT`), so reading the new
+    /// archive needs the parent too (`ExtractOptions::parent`). The parent is
+    /// opened with `aead_key`/`password`. Only [`pack`] and [`pack_to_writer`]
+    /// support this.
+    pub parent: Option<PathBuf>,
+    /// Also fsync `out`'s directory after the archive is renamed into place,
+    /// so the new archive (not just its data) survives a power loss once
+    /// [`pack`] returns. The archive file itself is always synced before the
+    /// rename; this adds one directory sync per pack. No effect on Windows or
+    /// for [`pack_to_writer`].
+    pub durable: bool,
+    /// Compress zstd chunks against this dictionary (see
+    /// [`train_dict`](crate::codec::zstdc::train_dict)); they are recorded as
+    /// `CodecId::ZstdDict` and the dictionary is stored in the manifest, so
+    /// readers need nothing extra. Pays off for many small, similar files.
+    /// Ignored in builds without the `zstd` feature; refused together with
+    /// `public_manifest`, which leaves the manifest readable.
+    pub zstd_dict: Option<Vec<u8>>,
+    /// Store each file smaller than this many bytes whole, as one chunk,
+    /// instead of running it through FastCDC; 0 (the default) chunks every
+    /// file. Raising it past `chunk_min` trades dedup inside mid-sized files
+    /// for fewer chunk table entries. Sparse-file holes are not detected in
+    /// such files. At most `MAX_CHUNK_LIMIT`. Only [`pack`] and
+    /// [`pack_to_writer`] support this.
+    pub small_file_threshold: u64,
+    /// Abort with `ArxError::Format("cancelled")` once this is set, checked
+    /// before each input file is chunked and each chunk is written. [`pack`]
+    /// then leaves no archive behind; [`pack_to_writer`]'s writer is left with
+    /// whatever was written so far.
+    pub cancel: Option<Arc<AtomicBool>>,
 }
 
-struct CountingWriter<'a, W: Write> {
-    inner: &'a mut W,
-    n: u64,
-}
-impl<'a, W: Write> CountingWriter<'a, W> {
-    fn new(inner: &'a mut W) -> Self {
-        Self { inner, n: 0 }
-    }
-    #[allow(dead_code)]
-    fn bytes(&self) -> u64 {
-        self.n
-    }
-}
-impl<'a, W: Write> Write for CountingWriter<'a, W> {
-    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-        let k = self.inner.write(buf)?;
-        self.n += k as u64;
-        Ok(k)
-    }
-    fn flush(&mut self) -> std::io::Result<()> {
-        self.inner.flush()
-    }
+/// What [`pack`] would write, as computed by [`plan_pack`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PackPlan {
+    /// Regular files (hardlinks excluded).
+    pub files: u64,
+    pub dirs: u64,
+    /// Chunk references across all files, before dedup.
+    pub chunk_refs: u64,
+    /// Distinct chunks stored, i.e. the archive's `chunk_count`.
+    pub unique_chunks: u64,
+    /// Total uncompressed size of the files.
+    pub u_bytes: u64,
+    /// Data region size: the unique chunks after compression (and AEAD tags).
+    pub c_bytes: u64,
+    /// Estimated archive size: header, manifest (uncompressed, unsealed),
+    /// chunk table, data region and tail.
+    pub est_bytes: u64,
 }
 
-fn mode_from(md: &std::fs::Metadata) -> u32 {
+pub(crate) fn mode_from(md: &std::fs::Metadata) -> u32 {
     #[cfg(unix)]
     {
         use std::os::unix::fs::PermissionsExt;
@@ -75,27 +201,235 @@ fn mode_from(md: &std::fs::Metadata) -> u32 {
         0o100644
     }
 }
-fn mtime_from(md: &std::fs::Metadata) -> i64 {
+/// The `FileEntry::attrs` bits set on a Windows file; `None` elsewhere.
+pub(crate) fn attrs_from(md: &std::fs::Metadata) -> Option<u32> {
+    #[cfg(windows)]
+    {
+        use crate::container::manifest::{ATTR_HIDDEN, ATTR_READONLY};
+        use std::os::windows::fs::MetadataExt;
+        Some(md.file_attributes() & (ATTR_READONLY | ATTR_HIDDEN)).filter(|&a| a != 0)
+    }
+    #[cfg(not(windows))]
+    {
+        let _ = md;
+        None
+    }
+}
+pub(crate) fn mtime_from(md: &std::fs::Metadata) -> i64 {
     md.modified()
         .ok()
         .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
         .map(|d| d.as_secs() as i64)
         .unwrap_or(0)
 }
-fn rel_display(path: &Path, roots: &[&Path]) -> Result<String> {
+/// Numeric uid/gid of the entry `md` describes; `None` off Unix.
+pub(crate) fn owner_from(md: &std::fs::Metadata) -> Option<Owner> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        Some(Owner {
+            uid: md.uid(),
+            gid: md.gid(),
+        })
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = md;
+        None
+    }
+}
+/// Archive path of `path` relative to the first root containing it, normalized
+/// and checked by [`sanitize_entry_path`].
+pub(crate) fn rel_display(path: &Path, roots: &[&Path]) -> Result<String> {
     for r in roots {
         if let Ok(p) = path.strip_prefix(r) {
-            return Ok(p.to_string_lossy().to_string());
+            return sanitize_entry_path(&p.to_string_lossy());
+        }
+    }
+    sanitize_entry_path(&path.to_string_lossy())
+}
+
+/// Split sorted `files` into primaries and `(link, primary)` pairs for paths that
+/// share an inode with an earlier path. Primaries keep the first path in sort order.
+#[allow(clippy::type_complexity)]
+fn split_hardlinks(files: Vec<PathBuf>) -> Result<(Vec<PathBuf>, Vec<(PathBuf, PathBuf)>)> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        let mut seen: HashMap<(u64, u64), PathBuf> = HashMap::new();
+        let mut primaries = Vec::with_capacity(files.len());
+        let mut links = Vec::new();
+        for p in files {
+            let md = fs::symlink_metadata(&p)?;
+            if md.nlink() > 1 {
+                if let Some(primary) = seen.get(&(md.dev(), md.ino())) {
+                    links.push((p, primary.clone()));
+                    continue;
+                }
+                seen.insert((md.dev(), md.ino()), p.clone());
+            }
+            primaries.push(p);
+        }
+        Ok((primaries, links))
+    }
+    #[cfg(not(unix))]
+    {
+        Ok((files, Vec::new()))
+    }
+}
+
+/// Data runs of `f` as `(offset, len)`, found with `SEEK_DATA`/`SEEK_HOLE`.
+/// `None` when the file has no holes or the filesystem cannot report them.
+#[cfg(target_os = "linux")]
+fn data_segments(f: &File, len: u64) -> Option<Vec<(u64, u64)>> {
+    use rustix::fs::{SeekFrom, seek};
+    use rustix::io::Errno;
+    let mut runs = Vec::new();
+    let mut off = 0u64;
+    while off < len {
+        let data = match seek(f, SeekFrom::Data(off)) {
+            Ok(data) => data,
+            // ENXIO: only a hole remains past `off`
+            Err(Errno::NXIO) => break,
+            Err(_) => return None,
+        };
+        if data >= len {
+            break;
         }
+        let hole = seek(f, SeekFrom::Hole(data)).ok()?.min(len);
+        runs.push((data, hole - data));
+        off = hole;
     }
-    Ok(path.to_string_lossy().to_string())
+    let data_len: u64 = runs.iter().map(|r| r.1).sum();
+    (data_len < len).then_some(runs)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn data_segments(_f: &File, _len: u64) -> Option<Vec<(u64, u64)>> {
+    None
+}
+
+/// Complement of ascending `runs` within `0..len`.
+fn holes_between(runs: &[(u64, u64)], len: u64) -> Vec<(u64, u64)> {
+    let mut holes = Vec::new();
+    let mut pos = 0u64;
+    for &(off, run_len) in runs {
+        if off > pos {
+            holes.push((pos, off - pos));
+        }
+        pos = off + run_len;
+    }
+    if len > pos {
+        holes.push((pos, len - pos));
+    }
+    holes
+}
+
+fn hash_zeros(h: &mut blake3::Hasher, mut n: u64) {
+    static ZEROS: [u8; 64 * 1024] = [0u8; 64 * 1024];
+    while n > 0 {
+        let k = n.min(ZEROS.len() as u64) as usize;
+        h.update(&ZEROS[..k]);
+        n -= k as u64;
+    }
+}
+
+/// The codecs to trial-compress with, from `codec_strategy` and `codec`.
+fn effective_codecs(opts: Option<&PackOptions>) -> Result<Vec<(CodecId, &'static dyn Compressor)>> {
+    let ids = match opts.map(|o| (&o.codec_strategy, o.codec)) {
+        Some((CodecStrategy::BestOf(ids), _)) if ids.is_empty() => {
+            return Err(ArxError::Format(
+                "codec_strategy BestOf needs at least one codec".into(),
+            ));
+        }
+        Some((CodecStrategy::BestOf(ids), _)) => ids.clone(),
+        Some((CodecStrategy::Fixed(id), codec)) => vec![codec.unwrap_or(*id)],
+        None => vec![CodecId::default_for_pack()],
+    };
+    ids.into_iter()
+        .map(|id| Ok((id, get_decoder_u8(id as u8)?)))
+        .collect()
+}
+
+/// The dictionary zstd chunks are compressed against, if any.
+fn effective_zstd_dict(opts: Option<&PackOptions>) -> Option<&[u8]> {
+    opts.and_then(|o| o.zstd_dict.as_deref())
+        .filter(|_| cfg!(feature = "zstd"))
 }
 
-fn effective_min_gain(opts: Option<&PackOptions>) -> f32 {
+/// Trial-compress `chunk` with each of `codecs`, keeping the smallest output
+/// that meets `min_gain`. `None` means the chunk is best stored as is. With
+/// a `dict`, zstd compresses against it and yields `ZstdDict` chunks.
+fn compress_best(
+    chunk: &[u8],
+    codecs: &[(CodecId, &'static dyn Compressor)],
+    min_gain: f32,
+    dict: Option<&[u8]>,
+) -> Result<Option<(CodecId, Vec<u8>)>> {
+    let mut best: Option<(CodecId, Vec<u8>)> = None;
+    for &(id, compressor) in codecs {
+        if id == CodecId::Store {
+            continue; // the fallback anyway
+        }
+        let (id, out) = match dict {
+            Some(_) if id == CodecId::Zstd => (
+                CodecId::ZstdDict,
+                compress_u8(CodecId::ZstdDict as u8, dict, chunk, 3)?,
+            ),
+            _ => {
+                let mut out = Vec::with_capacity(chunk.len());
+                compressor.compress(&mut &chunk[..], &mut out, 3)?;
+                (id, out)
+            }
+        };
+        if should_compress(chunk.len(), out.len(), min_gain)
+            && best.as_ref().is_none_or(|(_, b)| out.len() < b.len())
+        {
+            best = Some((id, out));
+        }
+    }
+    Ok(best)
+}
+
+pub(crate) fn effective_min_gain(opts: Option<&PackOptions>) -> f32 {
     let val = opts.map(|o| o.min_gain).unwrap_or(0.05);
     if val <= 0.0 { 0.05 } else { val }
 }
-fn should_compress(u: usize, c: usize, min_gain: f32) -> bool {
+fn effective_small_file_threshold(opts: Option<&PackOptions>) -> Result<u64> {
+    let t = opts.map_or(0, |o| o.small_file_threshold);
+    if t > MAX_CHUNK_LIMIT as u64 {
+        return Err(ArxError::Format(format!(
+            "small_file_threshold {t} exceeds the largest chunk ({MAX_CHUNK_LIMIT})"
+        )));
+    }
+    Ok(t)
+}
+fn effective_chunk_params(opts: Option<&PackOptions>) -> Result<ChunkParams> {
+    let Some(o) = opts else {
+        return Ok(ChunkParams::default());
+    };
+    let base = if o.chunk_avg == 0 {
+        ChunkParams::default()
+    } else {
+        ChunkParams::from_avg(o.chunk_avg)
+    };
+    let params = ChunkParams {
+        min: if o.chunk_min == 0 {
+            base.min
+        } else {
+            o.chunk_min
+        },
+        avg: base.avg,
+        max: if o.chunk_max == 0 {
+            base.max
+        } else {
+            o.chunk_max
+        },
+    };
+    params.validate()?;
+    Ok(params)
+}
+pub(crate) fn should_compress(u: usize, c: usize, min_gain: f32) -> bool {
     (u as f64 - c as f64) >= (u as f64 * min_gain as f64)
 }
 
@@ -113,8 +447,16 @@ struct FilePlan {
     path: PathBuf,
     mode: u32,
     mtime: i64,
+    owner: Option<Owner>,
+    attrs: Option<u32>,
     u_size: u64,
     chunks: Vec<NewChunk>,
+    /// Sparse holes `(offset, len)`; `chunks` cover the data between them.
+    holes: Vec<(u64, u64)>,
+    /// blake3 of the whole file; only computed when a listing is requested.
+    file_hash: Option<[u8; 32]>,
+    /// Matched `encrypt_paths`: this file's chunks are sealed individually.
+    sealed: bool,
 }
 struct ChunkPlan {
     src: PathBuf,
@@ -123,95 +465,1096 @@ struct ChunkPlan {
     codec: u8,
 }
 
+impl ChunkPlan {
+    /// Stream this chunk from its source through its codec into `w`: the
+    /// compressed plaintext, with no whole-chunk buffer (zstd+dict aside).
+    fn write_to(&self, zstd_dict: Option<&[u8]>, w: &mut dyn Write) -> Result<()> {
+        let mut src = File::open(&self.src)?;
+        src.seek(SeekFrom::Start(self.off))?;
+        let mut src = src.take(self.len);
+        if self.codec == CodecId::Store as u8 {
+            std::io::copy(&mut src, w)?;
+        } else {
+            compress_to(self.codec, zstd_dict, &mut src, w, 3)?;
+        }
+        Ok(())
+    }
+}
+
+/// Chunk id assignment produced by [`assign_chunk_ids`].
+struct ChunkIds {
+    /// `(file index, chunk index)` of each unique chunk's first occurrence, by id.
+    first: Vec<(usize, usize)>,
+    /// Per file, the id of each of its chunks.
+    refs: Vec<Vec<u64>>,
+}
+
+/// Number unique chunks deterministically.
+///
+/// Invariant: ids are dense and follow the first occurrence of each distinct
+/// hash in (file order, offset within file) order. `file_plans` is already in
+/// sorted path order, so ids depend only on the input tree — never on hash-map
+/// iteration order or on how chunk planning was parallelised.
+///
+/// Sealed and plaintext files never share chunks, so content that appears in
+/// both is stored once in each form.
+fn assign_chunk_ids(file_plans: &[FilePlan], dedup_window: usize) -> ChunkIds {
+    let mut order: Vec<(usize, u64, usize)> = file_plans
+        .iter()
+        .enumerate()
+        .flat_map(|(fi, fp)| {
+            fp.chunks
+                .iter()
+                .enumerate()
+                .map(move |(ci, nc)| (fi, nc.file_off, ci))
+        })
+        .collect();
+    order.sort_unstable_by_key(|&(fi, off, _)| (fi, off));
+
+    let mut by_hash = DedupIndex::new(dedup_window);
+    let mut by_hash_sealed = DedupIndex::new(dedup_window);
+    let mut first = Vec::new();
+    let mut refs: Vec<Vec<u64>> = file_plans
+        .iter()
+        .map(|fp| vec![0; fp.chunks.len()])
+        .collect();
+    for (fi, _, ci) in order {
+        let hash = file_plans[fi].chunks[ci].hash;
+        let by_hash = if file_plans[fi].sealed {
+            &mut by_hash_sealed
+        } else {
+            &mut by_hash
+        };
+        let id = by_hash.get(&hash).unwrap_or_else(|| {
+            first.push((fi, ci));
+            let id = first.len() as u64 - 1;
+            by_hash.insert(hash, id);
+            id
+        });
+        refs[fi][ci] = id;
+    }
+    ChunkIds { first, refs }
+}
+
+/// The archive is written to a temp file beside `out` and renamed into place
+/// only once complete (see [`TempArchive`]), so a failed or interrupted pack
+/// leaves any existing `out` as it was.
 pub fn pack(inputs: &[&Path], out: &Path, opts: Option<&PackOptions>) -> Result<()> {
+    pack_file(PackInputs::Roots(inputs), out, opts)
+}
+
+/// Pack exactly `entries`, each a `(source file, archive path)` pair, instead
+/// of walking directories. Archive paths are used as given once
+/// [`sanitize_entry_path`] accepts them, and files keep the list's order.
+/// Every source must be a regular file; no directory entries are recorded,
+/// and `exclude`, `respect_gitignore` and `follow_symlinks` do not apply.
+/// Written atomically like [`pack`].
+pub fn pack_list(
+    entries: &[(PathBuf, String)],
+    out: &Path,
+    opts: Option<&PackOptions>,
+) -> Result<()> {
+    pack_file(PackInputs::List(entries), out, opts)
+}
+
+fn pack_file(inputs: PackInputs<'_>, out: &Path, opts: Option<&PackOptions>) -> Result<()> {
+    let mut tmp = None;
+    let slot = &mut tmp;
+    let packed = pack_into(inputs, opts, move || {
+        Ok(slot.insert(TempArchive::create(out)?).file())
+    })?;
+    // None after a dry run, which never opens the output
+    let durable = opts.is_some_and(|o| o.durable);
+    tmp.map_or(Ok(()), |t| t.persist(durable))?;
+    packed.persist_index(durable)
+}
+
+/// What [`pack_into`] produced: the plan of a dry run, or the `emit_index`
+/// sidecar of a written archive, left for the caller to persist once the
+/// archive itself is in place.
+enum Packed {
+    Plan(PackPlan),
+    Written(Option<TempArchive>),
+}
+
+impl Packed {
+    fn persist_index(self, durable: bool) -> Result<()> {
+        match self {
+            Packed::Written(Some(index)) => index.persist(durable),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// What [`pack_into`] packs: directory trees walked from their roots, named
+/// relative to them, or an explicit `(source, archive path)` list.
+#[derive(Clone, Copy)]
+enum PackInputs<'a> {
+    Roots(&'a [&'a Path]),
+    List(&'a [(PathBuf, String)]),
+}
+
+/// An archive being written to `<out>.arx.tmp`. [`TempArchive::persist`] syncs
+/// it and renames it onto `out`; dropped before that, the temp file is removed.
+pub(crate) struct TempArchive {
+    file: File,
+    tmp: PathBuf,
+    out: PathBuf,
+    persisted: bool,
+}
+
+impl TempArchive {
+    pub(crate) fn create(out: &Path) -> Result<Self> {
+        Self::create_at(out, out.with_extension("arx.tmp"))
+    }
+
+    /// Like [`Self::create`], but writing to `tmp` instead of `<out>.arx.tmp`.
+    fn create_at(out: &Path, tmp: PathBuf) -> Result<Self> {
+        Ok(Self {
+            file: File::create(&tmp)?,
+            tmp,
+            out: out.to_path_buf(),
+            persisted: false,
+        })
+    }
+
+    pub(crate) fn file(&mut self) -> &mut File {
+        &mut self.file
+    }
+
+    /// A copy of the existing archive `out` at `<out>.arx.tmp`, to be updated
+    /// and then persisted over it.
+    pub(crate) fn copy_of(out: &Path) -> Result<Self> {
+        let mut tmp = Self::create(out)?;
+        std::io::copy(&mut File::open(out)?, &mut tmp.file)?;
+        Ok(tmp)
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.tmp
+    }
+
+    /// Flush the archive to disk and move it to `out`, replacing any file
+    /// there (`fs::rename` replaces existing files on Windows too). `durable`
+    /// also syncs the directory entry; see [`PackOptions::durable`].
+    pub(crate) fn persist(mut self, durable: bool) -> Result<()> {
+        self.file.sync_all()?;
+        fs::rename(&self.tmp, &self.out)?;
+        self.persisted = true;
+        if durable {
+            sync_parent_dir(&self.out)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+fn sync_parent_dir(path: &Path) -> Result<()> {
+    let dir = match path.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p,
+        _ => Path::new("."),
+    };
+    File::open(dir)?.sync_all()?;
+    Ok(())
+}
+
+/// Directories can't be opened for syncing here; rename durability is the
+/// filesystem's.
+#[cfg(not(unix))]
+fn sync_parent_dir(_path: &Path) -> Result<()> {
+    Ok(())
+}
+
+impl Drop for TempArchive {
+    fn drop(&mut self) {
+        if !self.persisted {
+            let _ = fs::remove_file(&self.tmp);
+        }
+    }
+}
+
+/// Walk `inputs` as [`pack`] would, honouring `exclude`, `respect_gitignore`
+/// and `follow_symlinks`, and return every file with its [`chunk_boundaries`]
+/// under the pack's chunk parameters. Nothing is packed.
+///
+/// [`chunk_boundaries`]: crate::chunking::fastcdc::chunk_boundaries
+pub fn pack_boundaries(
+    inputs: &[&Path],
+    opts: Option<&PackOptions>,
+) -> Result<Vec<(PathBuf, Vec<Boundary>)>> {
+    let params = effective_chunk_params(opts)?;
+    let (files, _) = walk_inputs(inputs, opts)?;
+    files
+        .into_iter()
+        .map(|f| {
+            let cuts = chunk_boundaries(&f, params)?;
+            Ok((f, cuts))
+        })
+        .collect()
+}
+
+/// Run [`pack`]'s walk and chunk planning without writing anything, as with
+/// `PackOptions::dry_run`, and report what the archive would hold.
+pub fn plan_pack(inputs: &[&Path], opts: Option<&PackOptions>) -> Result<PackPlan> {
+    let opts = PackOptions {
+        dry_run: true,
+        ..opts.cloned().unwrap_or_default()
+    };
+    match pack_into(
+        PackInputs::Roots(inputs),
+        Some(&opts),
+        || -> Result<std::io::Cursor<Vec<u8>>> { unreachable!("dry run never opens the output") },
+    )? {
+        Packed::Plan(plan) => Ok(plan),
+        Packed::Written(_) => unreachable!("dry run writes nothing"),
+    }
+}
+
+/// Like [`pack`], but writes the archive to `out` (e.g. a `Cursor<Vec<u8>>`)
+/// instead of a file. `emit_listing`, if set, is still written to its path.
+pub fn pack_to_writer<W: Write + Seek>(
+    inputs: &[&Path],
+    out: &mut W,
+    opts: Option<&PackOptions>,
+) -> Result<()> {
+    pack_into(PackInputs::Roots(inputs), opts, || Ok(out))?
+        .persist_index(opts.is_some_and(|o| o.durable))
+}
+
+/// `open_out` runs only once the inputs have been walked and chunked, so a
+/// failed pack leaves no output file behind. A dry run returns the plan instead
+/// and never calls it.
+fn pack_into<W: Write + Seek>(
+    inputs: PackInputs<'_>,
+    opts: Option<&PackOptions>,
+    open_out: impl FnOnce() -> Result<W>,
+) -> Result<Packed> {
     // ── Walk inputs ──────────────────────────────────────────────────────────
-    let mut files: Vec<PathBuf> = Vec::new();
-    let mut dirs: Vec<PathBuf> = Vec::new();
-    for root in inputs {
-        for e in WalkDir::new(root).follow_links(false) {
-            let e = e.map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
-            let p = e.path();
-            if e.file_type().is_dir() {
-                dirs.push(p.to_path_buf());
-            } else if e.file_type().is_file() {
-                files.push(p.to_path_buf());
+    // `names[i]` is the archive path of `files[i]`; a list has no roots, and
+    // its files share no hardlink entries
+    let (roots, files, dirs, links, names) = match inputs {
+        PackInputs::Roots(roots) => {
+            let (files, dirs) = walk_inputs(roots, opts)?;
+            let (files, links) = split_hardlinks(files)?;
+            let names = files
+                .iter()
+                .map(|p| rel_display(p, roots))
+                .collect::<Result<Vec<_>>>()?;
+            (roots, files, dirs, links, names)
+        }
+        PackInputs::List(entries) => {
+            let mut files = Vec::with_capacity(entries.len());
+            let mut names = Vec::with_capacity(entries.len());
+            for (src, name) in entries {
+                if !fs::metadata(src)?.is_file() {
+                    return Err(ArxError::Format(format!(
+                        "{}: not a regular file",
+                        src.display()
+                    )));
+                }
+                let name = sanitize_entry_path(name)?;
+                if name.is_empty() {
+                    return Err(ArxError::Format(format!(
+                        "{}: empty archive path",
+                        src.display()
+                    )));
+                }
+                files.push(src.clone());
+                names.push(name);
             }
+            (&[][..], files, Vec::new(), Vec::new(), names)
         }
-    }
-    dirs.sort();
-    files.sort();
+    };
 
     // ── Plan chunks per file (parallel) ──────────────────────────────────────
     let min_gain = effective_min_gain(opts);
-    let params = ChunkParams::default();
-    let zstd = ZstdCompressor;
+    let params = effective_chunk_params(opts)?;
+    let small_file_threshold = effective_small_file_threshold(opts)?;
+    let codecs = effective_codecs(opts)?;
+    let zstd_dict = effective_zstd_dict(opts);
+    let listing = opts.and_then(|o| o.emit_listing.as_deref());
+    let encrypt_paths = compile_encrypt_paths(opts)?;
+    if encrypt_paths.is_some()
+        && !opts.is_some_and(|o| o.aead_key.is_some() || o.password.is_some())
+    {
+        return Err(ArxError::Format(
+            "encrypt_paths requires a key or password".into(),
+        ));
+    }
+    let public_manifest = encrypt_paths.is_some() && opts.is_some_and(|o| o.public_manifest);
+    if public_manifest && zstd_dict.is_some() {
+        return Err(ArxError::Format(
+            "zstd_dict cannot be combined with public_manifest: the dictionary would sit \
+             in the unsealed manifest"
+                .into(),
+        ));
+    }
+
+    // Key derivation and salt checks fail before any input is read
+    let deterministic = opts.map(|o| o.deterministic).unwrap_or(false);
+    let mut crypto = resolve_crypto(opts, deterministic)?;
+    crypto.per_chunk = encrypt_paths.is_some();
+    crypto.public_regions = public_manifest;
+    let parent_hashes = load_parent_hashes(opts)?;
 
+    let cancel = opts.and_then(|o| o.cancel.as_deref());
     let file_plans: Vec<FilePlan> = files
         .par_iter()
-        .map(|src_path| -> Result<FilePlan> {
+        .zip(&names)
+        .map(|(src_path, name)| -> Result<FilePlan> {
+            check_cancel(cancel)?;
             let meta = fs::metadata(src_path)?;
-            let mut f = File::open(src_path)?;
-            let mut chunker = StreamingChunker::new(params);
+            let f = File::open(src_path)?;
             let mut buf = Vec::<u8>::with_capacity(params.avg);
             let mut chunks = Vec::<NewChunk>::new();
             let mut total_u = 0u64;
-            let mut file_off = 0u64;
+            let mut file_hasher = listing.map(|_| blake3::Hasher::new());
 
-            loop {
-                let n = chunker.next_chunk(&mut f, &mut buf)?;
-                if n == 0 {
-                    break;
+            // Chunk each data run separately so no chunk spans a hole; dense
+            // files are a single run read to EOF. Small files are one chunk.
+            let mut whole = meta.len() < small_file_threshold;
+            let data_runs = if whole {
+                None
+            } else {
+                data_segments(&f, meta.len())
+            };
+            let holes = data_runs
+                .as_deref()
+                .map(|runs| holes_between(runs, meta.len()))
+                .unwrap_or_default();
+            let runs = data_runs.unwrap_or_else(|| vec![(0, u64::MAX)]);
+            let mut pos = 0u64; // logical end of the previous run
+            for (run_off, run_len) in runs {
+                if let Some(h) = file_hasher.as_mut() {
+                    hash_zeros(h, run_off.saturating_sub(pos));
                 }
-                total_u += n as u64;
+                (&f).seek(SeekFrom::Start(run_off))?;
+                let mut r = (&f).take(run_len);
+                let mut chunker = StreamingChunker::new(params);
+                let mut file_off = run_off;
 
-                // Hash (uncompressed)
-                let hash = blake3::hash(&buf[..n]);
+                loop {
+                    let n = if whole {
+                        // Bounded, so a file that grew since `stat` cannot make
+                        // an oversized chunk; anything past it is chunked.
+                        whole = false;
+                        buf.clear();
+                        (&mut r).take(small_file_threshold).read_to_end(&mut buf)?
+                    } else {
+                        chunker.next_chunk(&mut r, &mut buf)?
+                    };
+                    if n == 0 {
+                        break;
+                    }
+                    total_u += n as u64;
+                    if let Some(h) = file_hasher.as_mut() {
+                        h.update(&buf[..n]);
+                    }
 
-                // Trial compress to measure c_size
-                let mut tmp = Vec::with_capacity(n);
-                {
-                    let mut cw = CountingWriter::new(&mut tmp);
-                    let _ = zstd.compress(&mut &buf[..n], &mut cw, 3)?;
-                }
-                let z_csize = tmp.len();
+                    // Hash (uncompressed)
+                    let hash = blake3::hash(&buf[..n]);
 
-                let (codec, c_size) = if should_compress(n, z_csize, min_gain) {
-                    (CodecId::Zstd as u8, z_csize as u64)
-                } else {
-                    (CodecId::Store as u8, n as u64)
-                };
+                    // Trial compress to pick the codec and measure c_size
+                    let (codec, c_size) =
+                        match compress_best(&buf[..n], &codecs, min_gain, zstd_dict)? {
+                            Some((id, comp)) => (id as u8, comp.len() as u64),
+                            None => (CodecId::Store as u8, n as u64),
+                        };
 
-                chunks.push(NewChunk {
-                    hash: *hash.as_bytes(),
-                    u_size: n as u64,
-                    c_size,
-                    codec,
-                    file_off,
-                });
+                    chunks.push(NewChunk {
+                        hash: *hash.as_bytes(),
+                        u_size: n as u64,
+                        c_size,
+                        codec,
+                        file_off,
+                    });
 
-                file_off += n as u64;
+                    file_off += n as u64;
+                }
+                pos = file_off;
             }
+            // Sparse files are as long as the filesystem says, trailing hole included
+            let u_size = if holes.is_empty() {
+                total_u
+            } else {
+                if let Some(h) = file_hasher.as_mut() {
+                    hash_zeros(h, meta.len().saturating_sub(pos));
+                }
+                meta.len()
+            };
 
             Ok(FilePlan {
                 path: src_path.clone(),
                 mode: mode_from(&meta),
                 mtime: mtime_from(&meta),
-                u_size: total_u,
+                owner: owner_from(&meta),
+                attrs: attrs_from(&meta),
+                u_size,
                 chunks,
+                holes,
+                file_hash: file_hasher.map(|h| *h.finalize().as_bytes()),
+                sealed: encrypt_paths
+                    .as_ref()
+                    .is_some_and(|pats| pats.iter().any(|p| p.matches(name))),
             })
         })
         .collect::<Result<Vec<_>>>()?;
 
     // ── Manifest planning ────────────────────────────────────────────────────
-    let deterministic = opts.map(|o| o.deterministic).unwrap_or(false);
-    let created = if deterministic {
-        0
-    } else {
-        OffsetDateTime::now_utc().unix_timestamp()
+
+    let ids = assign_chunk_ids(&file_plans, opts.map(|o| o.dedup_window).unwrap_or(0));
+    let mut chunk_entries: Vec<ChunkEntry> = Vec::with_capacity(ids.first.len());
+    let mut plans: Vec<ChunkPlan> = Vec::with_capacity(ids.first.len()); // first occurrences only
+    for &(fi, ci) in &ids.first {
+        let fp = &file_plans[fi];
+        let nc = &fp.chunks[ci];
+
+        // Chunks the parent holds are referenced, not stored; sealed files
+        // never share chunks with it
+        let in_parent = !fp.sealed && parent_hashes.contains(&nc.hash);
+
+        // ciphertext size includes AEAD tag if enabled
+        let sealed = !in_parent && crypto.enc.is_some() && (!crypto.per_chunk || fp.sealed);
+        let mut csz = if in_parent { 0 } else { nc.c_size };
+        if sealed {
+            csz += TAG_LEN as u64;
+        }
+
+        chunk_entries.push(ChunkEntry {
+            codec: if in_parent {
+                CodecId::Store as u8
+            } else {
+                nc.codec
+            },
+            u_size: nc.u_size,
+            c_size: csz,
+            data_off: 0, // patched after layout
+            // The table is public in per-file mode; don't publish hashes of sealed content
+            blake3: if crypto.per_chunk && sealed {
+                [0u8; 32]
+            } else {
+                nc.hash
+            },
+            encrypted: crypto.per_chunk && sealed,
+            parent: in_parent,
+        });
+        plans.push(ChunkPlan {
+            src: fp.path.clone(),
+            off: nc.file_off,
+            len: nc.u_size,
+            codec: nc.codec,
+        });
+    }
+
+    let mut file_entries: Vec<FileEntry> = Vec::with_capacity(file_plans.len());
+    for ((fp, file_ids), name) in file_plans.iter().zip(&ids.refs).zip(names) {
+        let refs = fp
+            .chunks
+            .iter()
+            .zip(file_ids)
+            .map(|(nc, &id)| ChunkRef {
+                id,
+                u_size: nc.u_size,
+            })
+            .collect();
+
+        file_entries.push(FileEntry {
+            path: name,
+            mode: fp.mode,
+            mtime: if deterministic { 0 } else { fp.mtime },
+            u_size: fp.u_size,
+            chunk_refs: refs,
+            holes: fp.holes.clone(),
+            owner: if deterministic { None } else { fp.owner },
+            attrs: fp.attrs,
+        });
+    }
+
+    let mut dirs_entries: Vec<DirEntry> = dirs
+        .iter()
+        .map(|d| -> Result<DirEntry> {
+            let md = fs::metadata(d).ok();
+            let (m, t, o) = md
+                .map(|md| {
+                    (
+                        mode_from(&md),
+                        if deterministic { 0 } else { mtime_from(&md) },
+                        if deterministic { None } else { owner_from(&md) },
+                    )
+                })
+                .unwrap_or((0o040755, 0, None));
+            Ok(DirEntry {
+                path: rel_display(d, roots)?,
+                mode: m,
+                mtime: t,
+                owner: o,
+            })
+        })
+        .collect::<Result<_>>()?;
+    // Ancestors before descendants by archive path: `dirs` is sorted on the
+    // source paths, which interleave when there are several inputs
+    sort_parents_first(&mut dirs_entries);
+
+    let hardlinks = links
+        .iter()
+        .map(|(link, primary)| {
+            Ok(HardlinkEntry {
+                path: rel_display(link, roots)?,
+                target: rel_display(primary, roots)?,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut meta = new_meta(opts, deterministic);
+    if opts.is_some_and(|o| o.record_origin) && !deterministic {
+        record_origin(&mut meta, roots);
+    }
+    let manifest = Manifest {
+        files: file_entries,
+        dirs: dirs_entries,
+        symlinks: vec![], // symlink walk wired in Phase C
+        hardlinks,
+        meta,
     };
+    // Several inputs can map different entry kinds onto the same relative path
+    manifest.check_path_conflicts()?;
+
+    if opts.is_some_and(|o| o.dry_run) {
+        return plan_summary(&manifest, &chunk_entries, &ids).map(Packed::Plan);
+    }
+
+    // ── Data source: re-read each unique chunk from its first occurrence ─────
+    let out = open_out()?;
+    let (chunk_entries, index) =
+        write_archive(out, opts, &manifest, chunk_entries, &crypto, 0, |i, w| {
+            plans[i].write_to(zstd_dict, w)
+        })?;
+
+    if let Some(listing) = listing {
+        let hashes: Vec<[u8; 32]> = file_plans
+            .iter()
+            .map(|fp| fp.file_hash.unwrap_or_default())
+            .collect();
+        write_listing(
+            listing,
+            &manifest,
+            &chunk_entries,
+            &hashes,
+            crypto.enc.is_some() && !crypto.per_chunk,
+        )?;
+    }
+
+    Ok(Packed::Written(index))
+}
+
+fn plan_summary(
+    manifest: &Manifest,
+    chunk_entries: &[ChunkEntry],
+    ids: &ChunkIds,
+) -> Result<PackPlan> {
+    let mut manifest_cbor = Vec::new();
+    ciborium::ser::into_writer(manifest, &mut manifest_cbor).map_err(std::io::Error::other)?;
+    let c_bytes: u64 = chunk_entries.iter().map(|ce| ce.c_size).sum();
+    Ok(PackPlan {
+        files: manifest.files.len() as u64,
+        dirs: manifest.dirs.len() as u64,
+        chunk_refs: ids.refs.iter().map(|r| r.len() as u64).sum(),
+        unique_chunks: chunk_entries.len() as u64,
+        u_bytes: manifest.total_logical(),
+        c_bytes,
+        est_bytes: HEADER_LEN
+            + 2 * REGION_GUARD_LEN
+            + manifest_cbor.len() as u64
+            + (chunk_entries.len() * ENTRY_SIZE) as u64
+            + c_bytes
+            + TAIL_LEN,
+    })
+}
+
+/// Sort directory entries component-wise, so every directory follows its
+/// ancestors and extraction can create them in manifest order.
+pub(crate) fn sort_parents_first(dirs: &mut [DirEntry]) {
+    dirs.sort_by(|a, b| Path::new(&a.path).cmp(Path::new(&b.path)));
+}
+
+/// Parse `PackOptions::encrypt_paths`; `None` when there are none.
+fn compile_encrypt_paths(opts: Option<&PackOptions>) -> Result<Option<Vec<glob::Pattern>>> {
+    match opts {
+        Some(o) if !o.encrypt_paths.is_empty() => {
+            compile_globs("encrypt_paths", &o.encrypt_paths).map(Some)
+        }
+        _ => Ok(None),
+    }
+}
+
+fn compile_globs(what: &str, pats: &[String]) -> Result<Vec<glob::Pattern>> {
+    pats.iter()
+        .map(|p| {
+            glob::Pattern::new(p)
+                .map_err(|e| ArxError::Format(format!("bad {what} glob {p:?}: {e}")))
+        })
+        .collect()
+}
+
+/// Walk `inputs` into sorted `(files, dirs)`, skipping anything matched by
+/// `PackOptions::exclude`. Excluded directories are pruned without descending.
+/// With `respect_gitignore`, `.gitignore`/`.ignore` files inside each input are
+/// honoured too; with `follow_symlinks`, links are resolved.
+pub(crate) fn walk_inputs(
+    inputs: &[&Path],
+    opts: Option<&PackOptions>,
+) -> Result<(Vec<PathBuf>, Vec<PathBuf>)> {
+    let exclude = compile_globs("exclude", opts.map(|o| &o.exclude[..]).unwrap_or_default())?;
+    let gitignore = opts.is_some_and(|o| o.respect_gitignore);
+    let follow = opts.is_some_and(|o| o.follow_symlinks);
+    let visited = Arc::new(Mutex::new(VisitedDirs::default()));
+    let mut files: Vec<PathBuf> = Vec::new();
+    let mut dirs: Vec<PathBuf> = Vec::new();
+    for root in inputs {
+        if gitignore {
+            walk_gitignore(root, &exclude, follow, &visited, &mut files, &mut dirs)?;
+            continue;
+        }
+        // Name order decides which of two links to one directory is walked
+        let walker = WalkDir::new(root)
+            .follow_links(follow)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_entry(|e| {
+                (e.depth() == 0 || !is_excluded(&exclude, root, e.path()))
+                    && !(follow
+                        && e.file_type().is_dir()
+                        && visited
+                            .lock()
+                            .unwrap()
+                            .seen_via_link(e.path(), e.path_is_symlink()))
+            });
+        for e in walker {
+            let e = match e {
+                // A link to one of its own ancestors, which is being walked already
+                Err(err) if err.loop_ancestor().is_some() => continue,
+                e => e.map_err(std::io::Error::other)?,
+            };
+            let p = e.path();
+            if e.file_type().is_dir() {
+                dirs.push(p.to_path_buf());
+            } else if e.file_type().is_file() {
+                files.push(p.to_path_buf());
+            }
+        }
+    }
+    // Walk order is per directory; sorting the whole list keeps output reproducible
+    dirs.sort();
+    files.sort();
+    Ok((files, dirs))
+}
+
+/// Directories already walked, by `(dev, ino)` of what they resolve to, so
+/// that following symlinks can neither loop nor walk one directory twice.
+#[derive(Default)]
+struct VisitedDirs(HashSet<(u64, u64)>);
+
+impl VisitedDirs {
+    /// Record `dir` and say whether to skip it: only a symlink to a directory
+    /// seen before is skipped, so a real directory is always walked even when
+    /// a link to it sorted first.
+    fn seen_via_link(&mut self, dir: &Path, is_link: bool) -> bool {
+        !self.first_visit(dir) && is_link
+    }
+
+    /// False if `dir` resolves to a directory seen before.
+    #[cfg(unix)]
+    fn first_visit(&mut self, dir: &Path) -> bool {
+        use std::os::unix::fs::MetadataExt;
+        match fs::metadata(dir) {
+            Ok(md) => self.0.insert((md.dev(), md.ino())),
+            Err(_) => true, // the walker reports it
+        }
+    }
+
+    /// No inode numbers here; the walkers' own loop detection has to do.
+    #[cfg(not(unix))]
+    fn first_visit(&mut self, _dir: &Path) -> bool {
+        true
+    }
+}
+
+/// [`walk_inputs`] for one root via `ignore::WalkBuilder`. Only ignore files
+/// inside `root` apply (no parent, global or `.git/info/exclude` rules, and no
+/// `.git` directory required), so the result depends on the tree alone. Hidden
+/// files are still packed.
+fn walk_gitignore(
+    root: &Path,
+    exclude: &[glob::Pattern],
+    follow: bool,
+    visited: &Arc<Mutex<VisitedDirs>>,
+    files: &mut Vec<PathBuf>,
+    dirs: &mut Vec<PathBuf>,
+) -> Result<()> {
+    let (exclude, base, visited) = (exclude.to_vec(), root.to_path_buf(), visited.clone());
+    let walker = ignore::WalkBuilder::new(root)
+        .follow_links(follow)
+        .sort_by_file_name(|a, b| a.cmp(b))
+        .hidden(false)
+        .parents(false)
+        .git_global(false)
+        .git_exclude(false)
+        .require_git(false)
+        .filter_entry(move |e| {
+            (e.depth() == 0 || !is_excluded(&exclude, &base, e.path()))
+                && !(follow
+                    && e.file_type().is_some_and(|t| t.is_dir())
+                    && visited
+                        .lock()
+                        .unwrap()
+                        .seen_via_link(e.path(), e.path_is_symlink()))
+        })
+        .build();
+    for e in walker {
+        let e = match e {
+            Err(err) if is_link_loop(&err) => continue,
+            e => e.map_err(std::io::Error::other)?,
+        };
+        match e.file_type() {
+            Some(t) if t.is_dir() => dirs.push(e.path().to_path_buf()),
+            Some(t) if t.is_file() => files.push(e.path().to_path_buf()),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Whether the gitignore walker stopped at a link to one of its own ancestors.
+fn is_link_loop(err: &ignore::Error) -> bool {
+    match err {
+        ignore::Error::Loop { .. } => true,
+        ignore::Error::WithPath { err, .. } | ignore::Error::WithDepth { err, .. } => {
+            is_link_loop(err)
+        }
+        _ => false,
+    }
+}
+
+fn is_excluded(exclude: &[glob::Pattern], root: &Path, path: &Path) -> bool {
+    if exclude.is_empty() {
+        return false;
+    }
+    let rel = path.strip_prefix(root).unwrap_or(path).to_string_lossy();
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy())
+        .unwrap_or_default();
+    exclude.iter().any(|p| {
+        if p.as_str().contains('/') {
+            p.matches(&rel)
+        } else {
+            p.matches(&name)
+        }
+    })
+}
+
+/// Pack everything read from `reader` into a single-entry archive stored under
+/// `logical_name`. Same FastCDC → codec → AEAD pipeline as [`pack`], but chunked
+/// sequentially, and each unique compressed chunk is spooled to a temp file since
+/// a stream cannot be re-read. Repeated chunks within the stream are deduplicated.
+pub fn pack_stream(
+    reader: impl Read,
+    logical_name: &str,
+    out: &Path,
+    opts: Option<&PackOptions>,
+) -> Result<()> {
+    let mut sp = StreamPacker::new(opts)?;
+    let (chunk_refs, u_size, file_hash) = sp.add(reader)?;
+    let manifest = Manifest {
+        files: vec![FileEntry {
+            path: sanitize_entry_path(logical_name)?,
+            mode: 0o100644,
+            mtime: sp.now(),
+            u_size,
+            chunk_refs,
+            holes: vec![],
+            owner: None,
+            attrs: None,
+        }],
+        dirs: vec![],
+        symlinks: vec![],
+        hardlinks: vec![],
+        meta: new_meta(opts, sp.deterministic),
+    };
+    sp.finish(out, opts, &manifest, &[file_hash])
+}
+
+/// Sequential packer for inputs that can only be read once: chunks each stream,
+/// dedups against every chunk seen so far, and spools unique compressed chunks
+/// to a temp file until the archive layout is known.
+pub(crate) struct StreamPacker {
+    params: ChunkParams,
+    min_gain: f32,
+    codecs: Vec<(CodecId, &'static dyn Compressor)>,
+    zstd_dict: Option<Vec<u8>>,
+    pub(crate) deterministic: bool,
+    crypto: PackCrypto,
+    /// Per-chunk AEAD tag overhead (0 when unencrypted).
+    tag: u64,
+    spool: File,
+    spooled: Vec<(u64, u64)>, // (offset, len) in `spool`, by chunk id
+    spool_off: u64,
+    chunk_entries: Vec<ChunkEntry>,
+    by_hash: DedupIndex,
+    /// Ids below this belong to an existing archive (see [`Self::seed`]).
+    base_id: u64,
+    buf: Vec<u8>,
+}
+
+impl StreamPacker {
+    pub(crate) fn new(opts: Option<&PackOptions>) -> Result<Self> {
+        if opts.is_some_and(|o| !o.encrypt_paths.is_empty()) {
+            return Err(ArxError::Format(
+                "encrypt_paths is only supported when packing a directory tree".into(),
+            ));
+        }
+        if opts.is_some_and(|o| o.dry_run) {
+            return Err(ArxError::Format(
+                "dry_run is only supported when packing a directory tree".into(),
+            ));
+        }
+        if opts.is_some_and(|o| o.parent.is_some()) {
+            return Err(ArxError::Format(
+                "parent is only supported when packing a directory tree".into(),
+            ));
+        }
+        if opts.is_some_and(|o| o.small_file_threshold > 0) {
+            return Err(ArxError::Format(
+                "small_file_threshold is only supported when packing a directory tree".into(),
+            ));
+        }
+        let params = effective_chunk_params(opts)?;
+        let deterministic = opts.map(|o| o.deterministic).unwrap_or(false);
+        let crypto = resolve_crypto(opts, deterministic)?;
+        let tag = if crypto.enc.is_some() {
+            TAG_LEN as u64
+        } else {
+            0
+        };
+        Ok(Self {
+            params,
+            min_gain: effective_min_gain(opts),
+            codecs: effective_codecs(opts)?,
+            zstd_dict: effective_zstd_dict(opts).map(<[u8]>::to_vec),
+            deterministic,
+            crypto,
+            tag,
+            spool: tempfile::tempfile()?,
+            spooled: Vec::new(),
+            spool_off: 0,
+            chunk_entries: Vec::new(),
+            by_hash: DedupIndex::new(opts.map(|o| o.dedup_window).unwrap_or(0)),
+            base_id: 0,
+            buf: Vec::with_capacity(params.avg),
+        })
+    }
+
+    /// Dedup against an existing archive's chunk table: content matching one of
+    /// `existing` reuses its id, and new chunks are numbered after them. Must be
+    /// called before [`Self::add`]. v3 entries (no hash) are never matched.
+    pub(crate) fn seed(&mut self, existing: &[ChunkEntry]) {
+        debug_assert!(self.chunk_entries.is_empty());
+        self.base_id = existing.len() as u64;
+        for (id, ce) in existing.iter().enumerate() {
+            if ce.blake3 != [0u8; 32] {
+                self.by_hash.insert(ce.blake3, id as u64);
+            }
+        }
+    }
+
+    /// Chunks added so far (ids from the seeded count upward), sizes final
+    /// but `data_off` unset.
+    pub(crate) fn new_chunks(&self) -> &[ChunkEntry] {
+        &self.chunk_entries
+    }
+
+    /// Compressed plaintext of the `i`th new chunk, read back from the spool.
+    pub(crate) fn new_chunk_data(&mut self, i: usize) -> Result<Vec<u8>> {
+        read_spooled(&mut self.spool, self.spooled[i])
+    }
+
+    /// mtime for entries without one of their own (0 when deterministic).
+    pub(crate) fn now(&self) -> i64 {
+        if self.deterministic {
+            0
+        } else {
+            OffsetDateTime::now_utc().unix_timestamp()
+        }
+    }
+
+    /// Chunk `reader` to EOF. Returns its chunk refs, length, and content blake3.
+    pub(crate) fn add(&mut self, mut reader: impl Read) -> Result<(Vec<ChunkRef>, u64, [u8; 32])> {
+        let mut chunker = StreamingChunker::new(self.params);
+        let mut file_hasher = blake3::Hasher::new();
+        let mut refs = Vec::new();
+        let mut total_u = 0u64;
+
+        loop {
+            let n = chunker.next_chunk(&mut reader, &mut self.buf)?;
+            if n == 0 {
+                break;
+            }
+            let chunk = &self.buf[..n];
+            total_u += n as u64;
+            file_hasher.update(chunk);
+
+            let hash = *blake3::hash(chunk).as_bytes();
+            let id = match self.by_hash.get(&hash) {
+                Some(id) => id,
+                None => {
+                    let (codec, stored) = match compress_best(
+                        chunk,
+                        &self.codecs,
+                        self.min_gain,
+                        self.zstd_dict.as_deref(),
+                    )? {
+                        Some((id, comp)) => (id as u8, comp),
+                        None => (CodecId::Store as u8, chunk.to_vec()),
+                    };
+                    self.spool.write_all(&stored)?;
+                    self.spooled.push((self.spool_off, stored.len() as u64));
+                    self.spool_off += stored.len() as u64;
+
+                    self.chunk_entries.push(ChunkEntry {
+                        codec,
+                        u_size: n as u64,
+                        c_size: stored.len() as u64 + self.tag,
+                        data_off: 0, // patched after layout
+                        blake3: hash,
+                        encrypted: false,
+                        parent: false,
+                    });
+                    let id = self.base_id + self.chunk_entries.len() as u64 - 1;
+                    self.by_hash.insert(hash, id);
+                    id
+                }
+            };
+            refs.push(ChunkRef {
+                id,
+                u_size: n as u64,
+            });
+        }
+        Ok((refs, total_u, *file_hasher.finalize().as_bytes()))
+    }
+
+    /// Write the archive for `manifest`, whose chunk refs all came from [`Self::add`],
+    /// atomically replacing `out` as [`pack`] does.
+    /// `file_hashes` parallels `manifest.files` and feeds the optional listing.
+    pub(crate) fn finish(
+        self,
+        out: &Path,
+        opts: Option<&PackOptions>,
+        manifest: &Manifest,
+        file_hashes: &[[u8; 32]],
+    ) -> Result<()> {
+        self.finish_with_base(out, opts, manifest, file_hashes, &[], 0, |_| Ok(Vec::new()))
+    }
+
+    /// Like [`Self::finish`], but the chunk table starts with the seeded
+    /// `base` entries, whose compressed plaintext `base_data(i)` supplies.
+    /// Everything is sealed under this packer's (fresh) crypto.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn finish_with_base(
+        mut self,
+        out: &Path,
+        opts: Option<&PackOptions>,
+        manifest: &Manifest,
+        file_hashes: &[[u8; 32]],
+        base: &[ChunkEntry],
+        headroom: u64,
+        mut base_data: impl FnMut(usize) -> Result<Vec<u8>>,
+    ) -> Result<()> {
+        let n = base.len();
+        let mut entries = base.to_vec();
+        entries.append(&mut self.chunk_entries);
+        let spooled = self.spooled;
+        let spool = &mut self.spool;
+        let mut tmp = TempArchive::create(out)?;
+        let (chunk_entries, index) = write_archive(
+            tmp.file(),
+            opts,
+            manifest,
+            entries,
+            &self.crypto,
+            headroom,
+            |i, w| {
+                let data = if i < n {
+                    base_data(i)?
+                } else {
+                    read_spooled(spool, spooled[i - n])?
+                };
+                Ok(w.write_all(&data)?)
+            },
+        )?;
 
-    // Resolve encryption key: raw key > password > none.
-    // Generate a random kdf_salt for every archive (even unencrypted) so it's always present.
+        if let Some(listing) = opts.and_then(|o| o.emit_listing.as_deref()) {
+            write_listing(
+                listing,
+                manifest,
+                &chunk_entries,
+                file_hashes,
+                self.crypto.enc.is_some(),
+            )?;
+        }
+        let durable = opts.is_some_and(|o| o.durable);
+        tmp.persist(durable)?;
+        Packed::Written(index).persist_index(durable)
+    }
+}
+
+fn read_spooled(spool: &mut File, (off, len): (u64, u64)) -> Result<Vec<u8>> {
+    let mut comp = vec![0u8; len as usize];
+    spool.seek(SeekFrom::Start(off))?;
+    spool.read_exact(&mut comp)?;
+    Ok(comp)
+}
+
+/// Salt and AEAD key resolved from `PackOptions`.
+struct PackCrypto {
+    kdf_salt: [u8; 32],
+    enc: Option<(AeadKey, [u8; 32])>,
+    alg: AeadAlg,
+    password_derived: bool,
+    /// Seal only chunks marked `encrypted`.
+    per_chunk: bool,
+    /// Leave the manifest and table plaintext (only with `per_chunk`).
+    public_regions: bool,
+    /// Keyring region wrapping `enc`'s (random) key for `PackOptions::recipients`.
+    keyring: Option<Vec<u8>>,
+}
+
+/// Hashes of the chunks stored in `PackOptions::parent` (empty without one).
+/// The parent's own parent chunks and unhashed (v3 or sealed per-file) chunks
+/// are left out, so every reference resolves in the parent itself.
+fn load_parent_hashes(opts: Option<&PackOptions>) -> Result<HashSet<[u8; 32]>> {
+    let Some((o, path)) = opts.and_then(|o| Some((o, o.parent.as_deref()?))) else {
+        return Ok(HashSet::new());
+    };
+    let parent_opts = ExtractOptions {
+        aead_key: o.aead_key,
+        password: o.password.clone(),
+        ..Default::default()
+    };
+    let parent = open_archive(path, Some(&parent_opts))?;
+    Ok(parent
+        .table
+        .iter()
+        .filter(|ce| !ce.parent && ce.blake3 != [0u8; 32])
+        .map(|ce| ce.blake3)
+        .collect())
+}
+
+/// Resolve encryption key: raw key > password > none.
+/// Generate a random kdf_salt for every archive (even unencrypted) so it's always present.
+/// Refuses to encrypt under an all-zero salt unless `allow_zero_salt` is set:
+/// nonces derive from (salt, region, id), so they would repeat in every other
+/// archive sealed with the same key.
+fn resolve_crypto(opts: Option<&PackOptions>, deterministic: bool) -> Result<PackCrypto> {
     let kdf_salt: [u8; 32] = opts
         .map(|o| {
             if o.key_salt != [0u8; 32] {
@@ -230,7 +1573,20 @@ pub fn pack(inputs: &[&Path], out: &Path, opts: Option<&PackOptions>) -> Result<
             }
         });
 
-    let (enc, password_derived) = if let Some(raw) = opts.and_then(|o| o.aead_key) {
+    let alg = opts.map(|o| o.aead_alg).unwrap_or_default();
+    let recipients = opts.map_or(&[][..], |o| &o.recipients[..]);
+    let mut keyring = None;
+    if !recipients.is_empty() && opts.is_some_and(|o| o.aead_key.is_some() || o.password.is_some())
+    {
+        return Err(ArxError::Format(
+            "recipients replace aead_key and password; set only one of them".into(),
+        ));
+    }
+    let (enc, password_derived) = if !recipients.is_empty() {
+        let data_key = AeadKey(random_salt());
+        keyring = Some(seal_keyring(alg, &kdf_salt, &data_key, recipients, 0)?);
+        (Some((data_key, kdf_salt)), false)
+    } else if let Some(raw) = opts.and_then(|o| o.aead_key) {
         (Some((AeadKey(raw), kdf_salt)), false)
     } else if let Some(pw) = opts.and_then(|o| o.password.as_deref()) {
         let key = kdf::derive_key(pw, &kdf_salt);
@@ -238,94 +1594,98 @@ pub fn pack(inputs: &[&Path], out: &Path, opts: Option<&PackOptions>) -> Result<
     } else {
         (None, false)
     };
-    let _ = password_derived; // used for FLAG_KDF_PASSWORD below
-
-    let mut chunk_map: HashMap<[u8; 32], u64> = HashMap::new(); // hash → id
-    let mut chunk_entries: Vec<ChunkEntry> = Vec::new();
-    let mut plans: Vec<ChunkPlan> = Vec::new(); // first occurrences only
-    let mut file_entries: Vec<FileEntry> = Vec::new();
-
-    for fp in &file_plans {
-        let mut refs = Vec::<ChunkRef>::new();
-        for nc in &fp.chunks {
-            if let Some(&id) = chunk_map.get(&nc.hash) {
-                // duplicate
-                refs.push(ChunkRef {
-                    id,
-                    u_size: nc.u_size,
-                });
-            } else {
-                // first occurrence
-                let id = chunk_entries.len() as u64;
-
-                // ciphertext size includes AEAD tag if enabled
-                let mut csz = nc.c_size;
-                if enc.is_some() {
-                    csz += TAG_LEN as u64;
-                }
-
-                chunk_entries.push(ChunkEntry {
-                    codec: nc.codec,
-                    u_size: nc.u_size,
-                    c_size: csz,
-                    data_off: 0, // patched after layout
-                    blake3: nc.hash,
-                });
-                plans.push(ChunkPlan {
-                    src: fp.path.clone(),
-                    off: nc.file_off,
-                    len: nc.u_size,
-                    codec: nc.codec,
-                });
-                chunk_map.insert(nc.hash, id);
-                refs.push(ChunkRef {
-                    id,
-                    u_size: nc.u_size,
-                });
-            }
-        }
+    if enc.is_some() && kdf_salt == [0u8; 32] && !opts.is_some_and(|o| o.allow_zero_salt) {
+        return Err(ArxError::Format(
+            "refusing to encrypt with an all-zero salt: nonces would repeat across \
+             archives sealed with this key; set key_salt or allow_zero_salt"
+                .into(),
+        ));
+    }
+    Ok(PackCrypto {
+        kdf_salt,
+        enc,
+        alg,
+        password_derived,
+        per_chunk: false,
+        public_regions: false,
+        keyring,
+    })
+}
 
-        file_entries.push(FileEntry {
-            path: rel_display(&fp.path, inputs)?,
-            mode: fp.mode,
-            mtime: if deterministic { 0 } else { fp.mtime },
-            u_size: fp.u_size,
-            chunk_refs: refs,
-        });
+pub(crate) fn new_meta(opts: Option<&PackOptions>, deterministic: bool) -> Meta {
+    Meta {
+        created: if deterministic {
+            0
+        } else {
+            OffsetDateTime::now_utc().unix_timestamp()
+        },
+        tool: format!("arx-core/{}", env!("CARGO_PKG_VERSION")),
+        label: opts.and_then(|o| o.meta_label.clone()),
+        owner: opts.and_then(|o| o.meta_owner.clone()),
+        notes: opts.and_then(|o| o.meta_notes.clone()),
+        manifest_version: MANIFEST_VERSION,
+        source_roots: vec![],
+        hostname: None,
+        zstd_dict: effective_zstd_dict(opts).map(<[u8]>::to_vec),
     }
+}
 
-    let dirs_entries: Vec<DirEntry> = dirs
+/// Record where a filesystem pack came from: the absolute input roots and
+/// the local hostname. Only on request ([`PackOptions::record_origin`]), and
+/// never for deterministic packs, whose output must not depend on the
+/// machine or directory they were built in.
+fn record_origin(meta: &mut Meta, inputs: &[&Path]) {
+    meta.source_roots = inputs
         .iter()
-        .map(|d| {
-            let md = fs::metadata(d).ok();
-            let (m, t) = md
-                .map(|md| {
-                    (
-                        mode_from(&md),
-                        if deterministic { 0 } else { mtime_from(&md) },
-                    )
-                })
-                .unwrap_or((0o040755, 0));
-            DirEntry {
-                path: rel_display(d, inputs).unwrap_or_else(|_| d.display().to_string()),
-                mode: m,
-                mtime: t,
-            }
+        .map(|p| {
+            fs::canonicalize(p)
+                .or_else(|_| std::path::absolute(p))
+                .unwrap_or_else(|_| p.to_path_buf())
+                .display()
+                .to_string()
         })
         .collect();
+    meta.hostname = hostname();
+}
 
-    let manifest = Manifest {
-        files: file_entries,
-        dirs: dirs_entries,
-        symlinks: vec![], // symlink walk wired in Phase C
-        meta: Meta {
-            created,
-            tool: format!("arx-core/{}", env!("CARGO_PKG_VERSION")),
-            label: opts.and_then(|o| o.meta_label.clone()),
-            owner: opts.and_then(|o| o.meta_owner.clone()),
-            notes: opts.and_then(|o| o.meta_notes.clone()),
-        },
-    };
+#[cfg(unix)]
+fn hostname() -> Option<String> {
+    let uname = rustix::system::uname();
+    let name = uname.nodename().to_string_lossy().into_owned();
+    (!name.is_empty()).then_some(name)
+}
+
+#[cfg(not(unix))]
+fn hostname() -> Option<String> {
+    std::env::var("COMPUTERNAME").ok().filter(|s| !s.is_empty())
+}
+
+/// Lay out and write the archive: superblock, manifest, chunk table, data, tail.
+///
+/// `chunk_entries` carry final sizes (AEAD tag included) but unpatched
+/// `data_off`s; `chunk_data(i, w)` must write chunk `i`'s compressed plaintext
+/// to `w`. Plaintext chunks stream straight into the output; sealed ones are
+/// collected once and encrypted in place, the tag going after the ciphertext.
+/// `headroom` zero bytes are left between the manifest and the chunk table so
+/// [`pack_append`] can later grow both without moving data.
+/// Returns the entries with `data_off` patched.
+///
+/// [`pack_append`]: crate::pack::append::pack_append
+fn write_archive(
+    mut out_f: impl Write + Seek,
+    opts: Option<&PackOptions>,
+    manifest: &Manifest,
+    mut chunk_entries: Vec<ChunkEntry>,
+    crypto: &PackCrypto,
+    headroom: u64,
+    mut chunk_data: impl FnMut(usize, &mut dyn Write) -> Result<()>,
+) -> Result<(Vec<ChunkEntry>, Option<TempArchive>)> {
+    let enc = crypto.enc.as_ref();
+    // Manifest and table are sealed whenever anything is, unless asked not to
+    let region_enc = enc.filter(|_| !crypto.public_regions);
+    let cancel = opts.and_then(|o| o.cancel.as_deref());
+    let kdf_salt = crypto.kdf_salt;
+    let alg = crypto.alg;
 
     // ── TailSummary bookkeeping (hashers + totals) ───────────────────────────
     let mut h_manifest = blake3::Hasher::new();
@@ -334,27 +1694,49 @@ pub fn pack(inputs: &[&Path], out: &Path, opts: Option<&PackOptions>) -> Result<
     let mut total_u: u64 = 0;
     let mut total_c: u64 = 0;
 
-    // ── Manifest (plaintext → optional AEAD) ─────────────────────────────────
+    // ── Manifest (plaintext → optional zstd → optional AEAD) ─────────────────
+    // Builds without zstd store the manifest uncompressed rather than failing
+    let compress_manifest =
+        cfg!(feature = "zstd") && opts.map(|o| o.compress_manifest).unwrap_or(false);
     let mut manifest_plain = Vec::new();
-    ciborium::ser::into_writer(&manifest, &mut manifest_plain)
+    ciborium::ser::into_writer(manifest, &mut manifest_plain)
         .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+    #[cfg(feature = "zstd")]
+    if compress_manifest {
+        manifest_plain = zstd::stream::encode_all(manifest_plain.as_slice(), 19)?;
+    }
+    // Like chunk data, the tail hashes the stored (possibly compressed) plaintext.
     h_manifest.update(&manifest_plain);
 
-    let enc_enabled = enc.is_some();
-    let flags = if enc_enabled {
-        FLAG_ENCRYPTED
-            | if password_derived {
-                FLAG_KDF_PASSWORD
-            } else {
-                0
-            }
+    let enc_enabled = region_enc.is_some();
+    let mode = match (crypto.per_chunk, enc_enabled) {
+        (true, true) => FLAG_CHUNK_AEAD | FLAG_ENCRYPTED,
+        (true, false) => FLAG_CHUNK_AEAD,
+        (false, _) => FLAG_ENCRYPTED,
+    };
+    let mut flags = if enc.is_some() {
+        mode | if crypto.password_derived {
+            FLAG_KDF_PASSWORD
+        } else {
+            0
+        } | crypto.alg.flag()
     } else {
         0
     };
+    if compress_manifest {
+        flags |= FLAG_MANIFEST_ZSTD;
+    }
+    if chunk_entries.iter().any(|ce| ce.parent) {
+        flags |= FLAG_PARENT;
+    }
+    let keyring = crypto.keyring.as_deref().filter(|_| enc_enabled);
+    if keyring.is_some() {
+        flags |= FLAG_KEYRING;
+    }
 
-    let (manifest_bytes, manifest_len) = if let Some((ref key, salt)) = enc {
-        let nonce = derive_nonce(&salt, Region::Manifest, 0);
-        let ct = seal_whole(key, &nonce, b"manifest", &manifest_plain);
+    let (manifest_bytes, manifest_len) = if let Some((key, salt)) = region_enc {
+        let nonce = derive_nonce(alg, salt, Region::Manifest, 0);
+        let ct = seal_whole(key, &nonce, b"manifest", &manifest_plain)?;
         (ct.clone(), ct.len() as u64)
     } else {
         (manifest_plain, /*len*/ 0) // set below
@@ -374,7 +1756,12 @@ pub fn pack(inputs: &[&Path], out: &Path, opts: Option<&PackOptions>) -> Result<
         pt_table_len
     };
 
-    let chunk_table_off = HEADER_LEN + manifest_len;
+    // Each region is preceded by its guard (see `Superblock::write_guards`);
+    // a keyring sits between the superblock and the manifest's guard
+    let keyring_len = keyring.map_or(0, |k| k.len() as u64);
+    let keyring_off = HEADER_LEN + keyring.map_or(0, |_| REGION_GUARD_LEN);
+    let manifest_off = keyring_off + keyring_len + REGION_GUARD_LEN;
+    let chunk_table_off = manifest_off + manifest_len + headroom + REGION_GUARD_LEN;
     let data_off = chunk_table_off + table_len;
 
     // Patch data_offs (absolute file offsets into the DATA ciphertext/plaintext region)
@@ -390,9 +1777,9 @@ pub fn pack(inputs: &[&Path], out: &Path, opts: Option<&PackOptions>) -> Result<
     debug_assert_eq!(table_plain.len() as u64, pt_table_len);
     h_chunktab.update(&table_plain);
 
-    let (table_bytes, table_len_check) = if let Some((ref key, salt)) = enc {
-        let nonce = derive_nonce(&salt, Region::ChunkTable, 0);
-        let ct = seal_whole(key, &nonce, b"chunktab", &table_plain);
+    let (table_bytes, table_len_check) = if let Some((key, salt)) = region_enc {
+        let nonce = derive_nonce(alg, salt, Region::ChunkTable, 0);
+        let ct = seal_whole(key, &nonce, b"chunktab", &table_plain)?;
         (ct, pt_table_len + TAG_LEN as u64)
     } else {
         (table_plain, pt_table_len)
@@ -400,7 +1787,6 @@ pub fn pack(inputs: &[&Path], out: &Path, opts: Option<&PackOptions>) -> Result<
     debug_assert_eq!(table_len_check, table_len);
 
     // ── Write superblock stub + regions ──────────────────────────────────────
-    let mut out_f = File::create(out)?;
     Superblock {
         version: VERSION,
         manifest_len: 0,
@@ -409,11 +1795,14 @@ pub fn pack(inputs: &[&Path], out: &Path, opts: Option<&PackOptions>) -> Result<
         data_off: 0,
         flags: 0,
         kdf_salt,
+        file_count: 0,
+        total_logical: 0,
+        keyring_len: 0,
     }
     .write_to(&mut out_f)?;
 
     // Manifest
-    out_f.seek(SeekFrom::Start(HEADER_LEN))?;
+    out_f.seek(SeekFrom::Start(manifest_off))?;
     out_f.write_all(&manifest_bytes)?;
 
     // Chunk table (with correct data_offs)
@@ -421,64 +1810,36 @@ pub fn pack(inputs: &[&Path], out: &Path, opts: Option<&PackOptions>) -> Result<
     out_f.write_all(&table_bytes)?;
 
     // ── Data region ──────────────────────────────────────────────────────────
-    let zstd_w = ZstdCompressor;
-    let mut io_buf = vec![0u8; 1 << 16];
-
     for (i, ce) in chunk_entries.iter().enumerate() {
-        let plan = &plans[i];
-
+        // Parent chunks have no bytes here and are left out of the tail totals
+        if ce.parent {
+            continue;
+        }
+        check_cancel(cancel)?;
         out_f.seek(SeekFrom::Start(ce.data_off))?;
-        let mut src = File::open(&plan.src)?;
-        src.seek(SeekFrom::Start(plan.off))?;
-
-        let mut plain = Vec::with_capacity(plan.len as usize);
-        let mut left = plan.len;
-        while left > 0 {
-            let n = io_buf.len().min(left as usize);
-            let k = src.read(&mut io_buf[..n])?;
-            if k == 0 {
-                break;
-            }
-            plain.extend_from_slice(&io_buf[..k]);
-            left -= k as u64;
-        }
-
-        // Compress/store -> yields COMPRESSED PLAINTEXT bytes
-        let comp = match plan.codec {
-            x if x == CodecId::Store as u8 => plain,
-            x if x == CodecId::Zstd as u8 => {
-                let mut tmp = std::io::Cursor::new(Vec::<u8>::new());
-                let mut cw = CountingWriter::new(&mut tmp);
-                zstd_w.compress(&mut &plain[..], &mut cw, 3)?;
-                tmp.into_inner()
-            }
-            _ => {
-                return Err(
-                    std::io::Error::new(std::io::ErrorKind::Other, "unknown codec id").into(),
-                );
-            }
-        };
+        total_u = total_u.saturating_add(ce.u_size);
 
-        // Tail data hash + totals
-        h_data.update(&comp);
-        total_u = total_u.saturating_add(plan.len);
-        total_c = total_c.saturating_add(comp.len() as u64);
-
-        // AEAD (if enabled) and write
-        if let Some((ref key, salt)) = enc {
-            let nonce = derive_nonce(&salt, Region::ChunkData, i as u64); // id == index
-            let ct = seal_whole(key, &nonce, b"chunk", &comp);
-            debug_assert_eq!(ct.len() as u64, ce.c_size);
-            out_f.write_all(&ct)?;
+        // Tail data hash + totals cover the compressed plaintext
+        if let Some((key, salt)) = enc.filter(|_| !crypto.per_chunk || ce.encrypted) {
+            let mut buf = Vec::with_capacity(ce.c_size as usize);
+            chunk_data(i, &mut HashingForward::new(&mut buf, &mut h_data))?;
+            total_c = total_c.saturating_add(buf.len() as u64);
+            let nonce = derive_nonce(alg, salt, Region::ChunkData, i as u64); // id == index
+            seal_in_place(key, &nonce, b"chunk", &mut buf)?;
+            debug_assert_eq!(buf.len() as u64, ce.c_size);
+            out_f.write_all(&buf)?;
         } else {
-            debug_assert_eq!(comp.len() as u64, ce.c_size);
-            out_f.write_all(&comp)?;
+            let mut w = HashingForward::new(BufWriter::new(&mut out_f), &mut h_data);
+            chunk_data(i, &mut w)?;
+            w.flush()?;
+            debug_assert_eq!(w.counted_c, ce.c_size);
+            total_c = total_c.saturating_add(w.counted_c);
         }
     }
 
     // ── Rewrite real Superblock ──────────────────────────────────────────────
     out_f.seek(SeekFrom::Start(0))?;
-    Superblock {
+    let sb = Superblock {
         version: VERSION,
         manifest_len,
         chunk_table_off,
@@ -486,8 +1847,26 @@ pub fn pack(inputs: &[&Path], out: &Path, opts: Option<&PackOptions>) -> Result<
         data_off,
         flags,
         kdf_salt,
+        file_count: manifest.files.len() as u64,
+        total_logical: manifest.total_logical(),
+        keyring_len,
+    };
+    sb.write_to(&mut out_f)?;
+    if let Some(keyring) = keyring {
+        out_f.write_all(keyring)?;
     }
-    .write_to(&mut out_f)?;
+    sb.write_guards(&mut out_f)?;
+    let index = match opts.and_then(|o| o.emit_index.as_deref()) {
+        Some(index) => {
+            let regions = [
+                (keyring_off, keyring.unwrap_or_default()),
+                (manifest_off, &manifest_bytes[..]),
+                (chunk_table_off, &table_bytes[..]),
+            ];
+            Some(write_index(index, &sb, &regions)?)
+        }
+        None => None,
+    };
 
     // ── Tail Summary at EOF ──────────────────────────────────────────────────
     out_f.seek(SeekFrom::End(0))?;
@@ -501,5 +1880,208 @@ pub fn pack(inputs: &[&Path], out: &Path, opts: Option<&PackOptions>) -> Result<
     tail.write_to(&mut out_f)?;
     out_f.flush()?;
 
+    Ok((chunk_entries, index))
+}
+
+/// Write the `emit_index` sidecar: the archive prefix up to `data_off`, laid out
+/// byte for byte as in the archive (headroom included), from `regions`' bytes.
+/// Like the archive, it goes to a temp file; the caller persists it only after
+/// the archive, so an index never describes an archive that was not written.
+fn write_index(path: &Path, sb: &Superblock, regions: &[(u64, &[u8])]) -> Result<TempArchive> {
+    let mut buf = std::io::Cursor::new(vec![0u8; sb.data_off as usize]);
+    sb.write_to(&mut buf)?;
+    sb.write_guards(&mut buf)?;
+    for &(off, bytes) in regions {
+        buf.get_mut()[off as usize..off as usize + bytes.len()].copy_from_slice(bytes);
+    }
+    let mut tmp = TempArchive::create_at(path, path.with_extension("arxidx.tmp"))?;
+    tmp.file().write_all(buf.get_ref())?;
+    Ok(tmp)
+}
+
+/// Write the `emit_listing` sidecar from data already in memory; the archive is
+/// never reopened. Lines match `list` output plus a trailing `blake3=<hex>`.
+fn write_listing(
+    path: &Path,
+    manifest: &Manifest,
+    chunk_entries: &[ChunkEntry],
+    file_hashes: &[[u8; 32]],
+    encrypted: bool,
+) -> Result<()> {
+    let mut w = std::io::BufWriter::new(File::create(path)?);
+    for (fe, hash) in manifest.files.iter().zip(file_hashes) {
+        let c_size: u64 = fe
+            .chunk_refs
+            .iter()
+            .map(|r| chunk_entries[r.id as usize].c_size)
+            .sum();
+        writeln!(
+            w,
+            "{}  blake3={}",
+            entry_line(
+                &fe.path,
+                encrypted
+                    || fe
+                        .chunk_refs
+                        .iter()
+                        .any(|r| chunk_entries[r.id as usize].encrypted),
+                fe.u_size,
+                c_size,
+                fe.chunk_refs.len(),
+                false
+            ),
+            hex::encode(hash)
+        )?;
+    }
+    w.flush()?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plan(path: &str, hashes: &[u8]) -> FilePlan {
+        let chunks = hashes
+            .iter()
+            .enumerate()
+            .map(|(i, &h)| NewChunk {
+                hash: [h; 32],
+                u_size: 10,
+                c_size: 10,
+                codec: CodecId::Store as u8,
+                file_off: i as u64 * 10,
+            })
+            .collect();
+        FilePlan {
+            path: PathBuf::from(path),
+            mode: 0o644,
+            mtime: 0,
+            u_size: hashes.len() as u64 * 10,
+            chunks,
+            holes: vec![],
+            owner: None,
+            attrs: None,
+            file_hash: None,
+            sealed: false,
+        }
+    }
+
+    #[test]
+    fn test_chunk_ids_follow_file_then_offset_order() {
+        let plans = vec![plan("a", &[7, 3, 7]), plan("b", &[3, 9, 1])];
+        let ids = assign_chunk_ids(&plans, 0);
+        assert_eq!(ids.first, vec![(0, 0), (0, 1), (1, 1), (1, 2)]);
+        assert_eq!(ids.refs, vec![vec![0, 1, 0], vec![1, 2, 3]]);
+    }
+
+    #[test]
+    fn test_chunk_ids_independent_of_map_seed() {
+        // Every HashMap gets a fresh random seed, so repeated runs exercise
+        // different iteration orders of the dedup map.
+        let plans: Vec<FilePlan> = (0..8u8)
+            .map(|i| plan(&format!("f{i}"), &[i, 200 - i, i / 2, 42]))
+            .collect();
+        let want = assign_chunk_ids(&plans, 0);
+        for _ in 0..16 {
+            let got = assign_chunk_ids(&plans, 0);
+            assert_eq!(got.first, want.first);
+            assert_eq!(got.refs, want.refs);
+        }
+    }
+
+    #[test]
+    fn test_streamed_chunks_match_buffered() {
+        use std::io::Cursor;
+
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("src.bin");
+        let data: Vec<u8> = (0..300_000u32)
+            .map(|i| (i % 251) as u8 ^ (i / 4096) as u8)
+            .collect();
+        fs::write(&src, &data).unwrap();
+        let mut codecs = vec![CodecId::Store as u8, CodecId::Deflate as u8];
+        if cfg!(feature = "zstd") {
+            codecs.push(CodecId::Zstd as u8);
+        }
+        let plans: Vec<ChunkPlan> = codecs
+            .iter()
+            .enumerate()
+            .map(|(i, &codec)| ChunkPlan {
+                src: src.clone(),
+                off: i as u64 * 100_000,
+                len: 100_000,
+                codec,
+            })
+            .collect();
+        // The old path: each chunk compressed into its own buffer first
+        let buffered: Vec<Vec<u8>> = plans
+            .iter()
+            .map(|p| {
+                let plain = &data[p.off as usize..][..p.len as usize];
+                compress_u8(p.codec, None, plain, 3).unwrap()
+            })
+            .collect();
+
+        for enc in [None, Some((AeadKey([7; 32]), [9u8; 32]))] {
+            let crypto = PackCrypto {
+                kdf_salt: [9; 32],
+                enc: enc.clone(),
+                alg: AeadAlg::default(),
+                password_derived: false,
+                per_chunk: false,
+                public_regions: false,
+                keyring: None,
+            };
+            let tag = if enc.is_some() { TAG_LEN as u64 } else { 0 };
+            let entries: Vec<ChunkEntry> = plans
+                .iter()
+                .zip(&buffered)
+                .map(|(p, b)| ChunkEntry {
+                    codec: p.codec,
+                    u_size: p.len,
+                    c_size: b.len() as u64 + tag,
+                    data_off: 0,
+                    blake3: [0; 32],
+                    encrypted: false,
+                    parent: false,
+                })
+                .collect();
+            let manifest = Manifest::default();
+
+            let mut streamed = Cursor::new(Vec::new());
+            let (written, _) = write_archive(
+                &mut streamed,
+                None,
+                &manifest,
+                entries.clone(),
+                &crypto,
+                0,
+                |i, w| plans[i].write_to(None, w),
+            )
+            .unwrap();
+            let mut reference = Cursor::new(Vec::new());
+            write_archive(
+                &mut reference,
+                None,
+                &manifest,
+                entries,
+                &crypto,
+                0,
+                |i, w| Ok(w.write_all(&buffered[i])?),
+            )
+            .unwrap();
+            assert_eq!(streamed.get_ref(), reference.get_ref());
+
+            // Sealing in place leaves exactly what seal_whole produced
+            if let Some((key, salt)) = &enc {
+                for (i, ce) in written.iter().enumerate() {
+                    let nonce = derive_nonce(crypto.alg, salt, Region::ChunkData, i as u64);
+                    let want = seal_whole(key, &nonce, b"chunk", &buffered[i]).unwrap();
+                    let off = ce.data_off as usize;
+                    assert_eq!(&streamed.get_ref()[off..off + want.len()], &want[..]);
+                }
+            }
+        }
+    }
+}
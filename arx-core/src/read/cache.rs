@@ -0,0 +1,109 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+/// Decoded chunks by id, least recently used evicted first, holding at most
+/// `budget` bytes of plaintext. With a budget of 0 nothing is kept.
+#[derive(Default)]
+pub(crate) struct ChunkCache {
+    budget: usize,
+    used: usize,
+    tick: u64,
+    entries: HashMap<u64, (Arc<[u8]>, u64)>,
+    /// Last-use tick → chunk id; the first entry is the next to go.
+    order: BTreeMap<u64, u64>,
+}
+
+impl ChunkCache {
+    /// Change the budget, evicting down to it.
+    pub(crate) fn set_budget(&mut self, budget: usize) {
+        self.budget = budget;
+        self.evict_to(budget);
+    }
+
+    pub(crate) fn get(&mut self, id: u64) -> Option<Arc<[u8]>> {
+        let tick = self.next_tick();
+        let (data, last) = self.entries.get_mut(&id)?;
+        self.order.remove(last);
+        *last = tick;
+        self.order.insert(tick, id);
+        Some(data.clone())
+    }
+
+    /// Whether chunk `id` is kept, without counting as a use.
+    pub(crate) fn contains(&self, id: u64) -> bool {
+        self.entries.contains_key(&id)
+    }
+
+    /// Keep `data` as chunk `id`. A chunk larger than the whole budget is not kept.
+    pub(crate) fn insert(&mut self, id: u64, data: Arc<[u8]>) {
+        if data.len() > self.budget {
+            return;
+        }
+        if let Some((old, last)) = self.entries.remove(&id) {
+            self.order.remove(&last);
+            self.used -= old.len();
+        }
+        self.evict_to(self.budget - data.len());
+        let tick = self.next_tick();
+        self.used += data.len();
+        self.order.insert(tick, id);
+        self.entries.insert(id, (data, tick));
+    }
+
+    fn evict_to(&mut self, limit: usize) {
+        while self.used > limit {
+            let Some((_, id)) = self.order.pop_first() else {
+                break;
+            };
+            if let Some((data, _)) = self.entries.remove(&id) {
+                self.used -= data.len();
+            }
+        }
+    }
+
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(len: usize) -> Arc<[u8]> {
+        vec![0u8; len].into()
+    }
+
+    #[test]
+    fn test_evicts_least_recently_used() {
+        let mut c = ChunkCache::default();
+        c.set_budget(300);
+        c.insert(1, chunk(100));
+        c.insert(2, chunk(100));
+        c.insert(3, chunk(100));
+        assert!(c.get(1).is_some()); // 2 is now the oldest
+        c.insert(4, chunk(100));
+        assert!(c.get(2).is_none());
+        assert!(c.get(1).is_some() && c.get(3).is_some() && c.get(4).is_some());
+    }
+
+    #[test]
+    fn test_budget_bounds_contents() {
+        let mut c = ChunkCache::default();
+        c.insert(1, chunk(10));
+        assert!(c.get(1).is_none(), "budget 0 keeps nothing");
+
+        c.set_budget(150);
+        c.insert(1, chunk(200));
+        assert!(c.get(1).is_none(), "larger than the budget");
+        c.insert(1, chunk(100));
+        c.insert(2, chunk(100));
+        assert!(c.get(1).is_none() && c.get(2).is_some());
+        assert_eq!(c.used, 100);
+
+        c.set_budget(0);
+        assert!(c.get(2).is_none());
+        assert_eq!(c.used, 0);
+    }
+}
@@ -0,0 +1,71 @@
+use std::io::Read;
+use std::path::Path;
+
+use crate::error::Result;
+use crate::read::opened::Opened;
+
+/// Digest used by [`file_checksums`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ChecksumAlg {
+    /// Same output as `b3sum`.
+    #[default]
+    Blake3,
+    /// Same output as `sha256sum`; needs the `sha256` feature.
+    Sha256,
+}
+
+/// Content checksum of every file in the archive as `(hex digest, path)`, in
+/// manifest order followed by hardlinks. The manifest stores no whole-file
+/// hashes, so each file is streamed and hashed on the fly.
+pub fn file_checksums(
+    archive: &Path,
+    aead_key: Option<[u8; 32]>,
+    alg: ChecksumAlg,
+) -> Result<Vec<(String, String)>> {
+    let opened = Opened::open(archive, aead_key, [0u8; 32])?;
+    let paths = opened
+        .manifest
+        .files
+        .iter()
+        .map(|f| &f.path)
+        .chain(opened.manifest.hardlinks.iter().map(|h| &h.path));
+
+    let mut out = Vec::new();
+    for path in paths {
+        let r = opened.open_reader(path)?;
+        let hex = match alg {
+            ChecksumAlg::Blake3 => blake3_hex(r)?,
+            ChecksumAlg::Sha256 => sha256_hex(r)?,
+        };
+        out.push((hex, path.clone()));
+    }
+    Ok(out)
+}
+
+fn blake3_hex(mut r: impl Read) -> Result<String> {
+    let mut h = blake3::Hasher::new();
+    std::io::copy(&mut r, &mut h)?;
+    Ok(h.finalize().to_hex().to_string())
+}
+
+#[cfg(feature = "sha256")]
+fn sha256_hex(mut r: impl Read) -> Result<String> {
+    use sha2::{Digest, Sha256};
+    let mut h = Sha256::new();
+    let mut buf = vec![0u8; 1 << 16];
+    loop {
+        let n = r.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        h.update(&buf[..n]);
+    }
+    Ok(hex::encode(h.finalize()))
+}
+
+#[cfg(not(feature = "sha256"))]
+fn sha256_hex(_r: impl Read) -> Result<String> {
+    Err(crate::error::ArxError::Format(
+        "sha256 checksums need arx-core built with the `sha256` feature".into(),
+    ))
+}
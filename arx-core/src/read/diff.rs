@@ -0,0 +1,147 @@
+//! Which regular files differ between two archives, for backup auditing.
+
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::container::manifest::FileEntry;
+use crate::error::Result;
+use crate::read::extract::{ExtractOptions, open_archive};
+use crate::read::opened::Opened;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum ChangeKind {
+    #[serde(rename = "A")]
+    Added,
+    #[serde(rename = "D")]
+    Removed,
+    #[serde(rename = "M")]
+    Modified,
+}
+
+impl ChangeKind {
+    /// `A`, `D` or `M`, as `git status --short` prints them.
+    pub fn letter(self) -> char {
+        match self {
+            ChangeKind::Added => 'A',
+            ChangeKind::Removed => 'D',
+            ChangeKind::Modified => 'M',
+        }
+    }
+}
+
+/// One file that differs. Serialized by `arx diff --json`.
+#[derive(Clone, Debug, Serialize)]
+pub struct FileChange {
+    pub kind: ChangeKind,
+    pub path: String,
+    /// Size in the old archive; absent for added files.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_size: Option<u64>,
+    /// Size in the new archive; absent for removed files.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new_size: Option<u64>,
+    /// Hex blake3 of chunks only the new version has, and of chunks only the
+    /// old one had. Filled for modified files whose chunks all carry hashes.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub chunks_added: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub chunks_removed: Vec<String>,
+}
+
+/// Compare the regular files of archive `old` with those of `new`, both
+/// opened with the key or password in `opts`. Sorted by path.
+pub fn diff(old: &Path, new: &Path, opts: Option<&ExtractOptions>) -> Result<Vec<FileChange>> {
+    diff_opened(&open_archive(old, opts)?, &open_archive(new, opts)?)
+}
+
+/// [`diff`] of two already-opened archives. Chunk ids are local to each
+/// archive, so content is compared by chunk hash; files with unhashed chunks
+/// (v3 archives, sealed chunks of per-file encrypted ones) only by their
+/// chunk sizes.
+pub fn diff_opened(old: &Opened, new: &Opened) -> Result<Vec<FileChange>> {
+    // path → (u_size, chunk signatures)
+    let by_path = |arx: &Opened| -> BTreeMap<String, (u64, Vec<ChunkSig>)> {
+        arx.manifest
+            .files
+            .iter()
+            .map(|fe| (fe.path.clone(), (fe.u_size, chunk_sigs(arx, fe))))
+            .collect()
+    };
+    let (a, b) = (by_path(old), by_path(new));
+
+    let mut changes = Vec::new();
+    for (path, &(old_size, ref old_sigs)) in &a {
+        let Some(&(new_size, ref new_sigs)) = b.get(path) else {
+            changes.push(FileChange {
+                kind: ChangeKind::Removed,
+                path: path.clone(),
+                old_size: Some(old_size),
+                new_size: None,
+                chunks_added: Vec::new(),
+                chunks_removed: Vec::new(),
+            });
+            continue;
+        };
+        if old_size == new_size && old_sigs == new_sigs {
+            continue;
+        }
+        let (mut chunks_added, mut chunks_removed) = (Vec::new(), Vec::new());
+        if old_sigs.iter().chain(new_sigs).all(|s| s.hash.is_some()) {
+            let hashes = |sigs: &[ChunkSig]| -> HashSet<[u8; 32]> {
+                sigs.iter().filter_map(|s| s.hash).collect()
+            };
+            let (ha, hb) = (hashes(old_sigs), hashes(new_sigs));
+            chunks_added = hex_sorted(hb.difference(&ha));
+            chunks_removed = hex_sorted(ha.difference(&hb));
+        }
+        changes.push(FileChange {
+            kind: ChangeKind::Modified,
+            path: path.clone(),
+            old_size: Some(old_size),
+            new_size: Some(new_size),
+            chunks_added,
+            chunks_removed,
+        });
+    }
+    for (path, &(new_size, _)) in b.iter().filter(|(p, _)| !a.contains_key(*p)) {
+        changes.push(FileChange {
+            kind: ChangeKind::Added,
+            path: path.clone(),
+            old_size: None,
+            new_size: Some(new_size),
+            chunks_added: Vec::new(),
+            chunks_removed: Vec::new(),
+        });
+    }
+    changes.sort_by(|x, y| x.path.cmp(&y.path));
+    Ok(changes)
+}
+
+/// What can be compared of a chunk across archives: its size, and its hash
+/// when the table has one.
+#[derive(Debug, PartialEq, Eq)]
+struct ChunkSig {
+    u_size: u64,
+    hash: Option<[u8; 32]>,
+}
+
+fn chunk_sigs(arx: &Opened, fe: &FileEntry) -> Vec<ChunkSig> {
+    fe.chunk_refs
+        .iter()
+        .map(|r| {
+            let hash = arx.table.get(r.id as usize).map(|ce| ce.blake3);
+            ChunkSig {
+                u_size: r.u_size,
+                hash: hash.filter(|h| *h != [0u8; 32]),
+            }
+        })
+        .collect()
+}
+
+fn hex_sorted<'a>(hashes: impl Iterator<Item = &'a [u8; 32]>) -> Vec<String> {
+    let mut out: Vec<String> = hashes.map(hex::encode).collect();
+    out.sort();
+    out
+}
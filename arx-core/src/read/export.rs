@@ -0,0 +1,62 @@
+//! Stream an archive back out as a `.tar`, for tools that don't speak ARX.
+
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+use crate::container::superblock::{FLAG_ENCRYPTED, Superblock};
+use crate::error::Result;
+use crate::read::extract::{ExtractOptions, resolve_enc};
+use crate::read::opened::Opened;
+
+/// Write every entry of `archive` to `writer` as a GNU tar stream: directories
+/// first (parents before children), then files, symlinks and hardlinks, each
+/// with its recorded mode and mtime. File contents are streamed through
+/// [`Opened::open_reader`], so nothing is staged on disk. Long paths get GNU
+/// long-name records. Encrypted archives take their key from `opts`.
+pub fn export_tar(archive: &Path, writer: impl Write, opts: Option<&ExtractOptions>) -> Result<()> {
+    let sb = Superblock::read_from(File::open(archive)?)?;
+    let key = resolve_enc(&sb, opts, (sb.flags & FLAG_ENCRYPTED) != 0)?.map(|(k, _)| k.0);
+    let arx = Opened::open(archive, key, [0u8; 32])?;
+    let m = &arx.manifest;
+    let mut tar = tar::Builder::new(writer);
+
+    // The root itself ("") has no tar entry; "/" would be an absolute path
+    let mut dirs: Vec<_> = m.dirs.iter().filter(|d| !d.path.is_empty()).collect();
+    dirs.sort_by(|a, b| a.path.cmp(&b.path));
+    for d in dirs {
+        let mut h = header(tar::EntryType::Directory, d.mode, d.mtime, 0);
+        tar.append_data(&mut h, format!("{}/", d.path), std::io::empty())?;
+    }
+    for f in &m.files {
+        let mut h = header(tar::EntryType::Regular, f.mode, f.mtime, f.u_size);
+        tar.append_data(&mut h, &f.path, arx.open_reader(&f.path)?)?;
+    }
+    for s in &m.symlinks {
+        let mut h = header(tar::EntryType::Symlink, s.mode, s.mtime, 0);
+        tar.append_link(&mut h, &s.path, &s.target)?;
+    }
+    // Hardlink targets are regular files, which are all written above
+    for l in &m.hardlinks {
+        let target = m
+            .files
+            .iter()
+            .find(|f| f.path == l.target)
+            .map(|f| (f.mode, f.mtime))
+            .unwrap_or((0o100644, 0));
+        let mut h = header(tar::EntryType::Link, target.0, target.1, 0);
+        tar.append_link(&mut h, &l.path, &l.target)?;
+    }
+
+    tar.into_inner()?.flush()?;
+    Ok(())
+}
+
+fn header(kind: tar::EntryType, mode: u32, mtime: i64, size: u64) -> tar::Header {
+    let mut h = tar::Header::new_gnu();
+    h.set_entry_type(kind);
+    h.set_mode(mode & 0o7777);
+    h.set_mtime(mtime.max(0) as u64);
+    h.set_size(size);
+    h
+}
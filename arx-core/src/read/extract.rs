@@ -1,152 +1,148 @@
-use crate::codec::CodecId;
-use crate::container::chunktab::{ChunkEntry, read_table};
-use crate::container::manifest::Manifest;
-use crate::container::superblock::{FLAG_ENCRYPTED, Superblock};
+use crate::container::chunktab::{ChunkEntry, ENTRY_SIZE, ENTRY_SIZE_V3, read_table};
+use crate::container::manifest::{FileEntry as ManifestFile, Manifest, Owner};
+use crate::container::superblock::{FLAG_CHUNK_AEAD, FLAG_ENCRYPTED, Superblock};
 use crate::container::tail::{TAIL_LEN, TailSummary};
-use crate::crypto::aead::{AeadKey, Region, derive_nonce};
-use crate::error::Result;
+use crate::crypto::aead::{AeadAlg, AeadKey, Region, TAG_LEN, derive_nonce};
+use crate::crypto::keyring::region_key;
+use crate::error::{ArxError, Result};
+use crate::read::opened::{Opened, check_chunk_size, decode_chunk, unseal_chunk};
+use crate::read::stream::RangeReader;
+use crate::util::buf::read_exact_at;
+use crate::util::cancel::check_cancel;
 use crate::util::sanitize::safe_join;
 
 use blake3;
-use std::fs::{self, File};
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
 use std::io::{Read, Seek, SeekFrom, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
 
-#[derive(Clone, Default)]
+#[derive(Clone)]
 pub struct ExtractOptions {
     pub aead_key: Option<[u8; 32]>,
     pub key_salt: [u8; 32],
     /// Derive the key from this password via Argon2id (uses the archive's stored kdf_salt).
     pub password: Option<String>,
+    /// Restore recorded numeric owners (Unix only). A uid found as a key is
+    /// replaced by its value; other uids and all gids are kept as stored.
+    /// `None` leaves extracted entries owned by the extracting user; an empty
+    /// map restores owners unchanged (`tar --numeric-owner`).
+    pub owner_map: Option<HashMap<u32, u32>>,
+    /// Parent archive of a differential pack (see `PackOptions::parent`),
+    /// opened with the same key or password. Required for such archives.
+    pub parent: Option<PathBuf>,
+    /// Drop this many leading path segments from every entry before it is
+    /// written (`tar --strip-components`); entries left with no path are skipped.
+    pub strip_components: usize,
+    /// Subdirectory of `dest` to extract into, created as needed.
+    pub prefix: Option<String>,
+    /// Window for copying STORE chunks to their output, which caps the memory
+    /// an extract uses for a chunk of any size; 0 means [`DEFAULT_IO_BUF_SIZE`].
+    pub io_buf_size: usize,
+    /// Refuse to extract more than this many uncompressed bytes in total,
+    /// checked against the chunk table before anything is written. Guards
+    /// against archives built to inflate far beyond their own size.
+    pub max_total_uncompressed: Option<u64>,
+    /// Set each extracted file's and directory's modification time to the one
+    /// recorded in the manifest (default). A recorded mtime of 0, as in
+    /// deterministic archives, is left alone.
+    pub restore_mtime: bool,
+    /// In [`extract_file_to`], decode each chunk in full and check it against
+    /// its stored blake3 before any of it is written, failing with the chunk's
+    /// ordinal within the file. Without it, unsealed STORE chunks stream out in
+    /// windows and a bad one is only caught after its bytes reached the writer.
+    pub verify_chunks: bool,
+    /// Abort with `ArxError::Format("cancelled")` once this is set, checked
+    /// before each file and each chunk (e.g. from a GUI's cancel button). The
+    /// file being written when it trips is removed; earlier ones are kept.
+    pub cancel: Option<Arc<AtomicBool>>,
 }
 
-const MAX_MANIFEST_SIZE: u64 = 256 * 1024 * 1024; // 256 MiB
-const MAX_TABLE_SIZE: u64 = 64 * 1024 * 1024; // 64 MiB (1M chunks × 64B)
-const MAX_CHUNK_SIZE: u64 = 2 * 1024 * 1024; // 2 MiB (FastCDC max 1 MiB + overhead)
-
-pub fn extract(archive: &Path, dest: &Path, opts: Option<&ExtractOptions>) -> Result<()> {
-    let mut f = File::open(archive)?;
-    let sb = Superblock::read_from(&mut f)?;
-    let header_len = sb.header_len();
-    let enc_enabled = (sb.flags & FLAG_ENCRYPTED) != 0;
-
-    let enc = resolve_enc(&sb, opts, enc_enabled)?;
-
-    if sb.manifest_len > MAX_MANIFEST_SIZE {
-        return Err(std::io::Error::new(
-            std::io::ErrorKind::InvalidData,
-            format!(
-                "manifest_len {} exceeds maximum {}",
-                sb.manifest_len, MAX_MANIFEST_SIZE
-            ),
-        )
-        .into());
+impl Default for ExtractOptions {
+    fn default() -> Self {
+        Self {
+            aead_key: None,
+            key_salt: [0u8; 32],
+            password: None,
+            owner_map: None,
+            parent: None,
+            strip_components: 0,
+            prefix: None,
+            io_buf_size: 0,
+            max_total_uncompressed: None,
+            restore_mtime: true,
+            verify_chunks: false,
+            cancel: None,
+        }
     }
+}
 
-    f.seek(SeekFrom::Start(header_len))?;
-    let mut man_bytes = vec![0u8; sb.manifest_len as usize];
-    f.read_exact(&mut man_bytes)?;
-
-    let manifest_bytes = if let Some((ref key, salt)) = enc {
-        let nonce = derive_nonce(&salt, Region::Manifest, 0);
-        crate::crypto::aead::open_whole(key, &nonce, b"manifest", &man_bytes)?
-    } else {
-        man_bytes
-    };
-
-    let manifest: Manifest = ciborium::de::from_reader(&manifest_bytes[..])
-        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+/// Copy window used when [`ExtractOptions::io_buf_size`] is 0.
+pub const DEFAULT_IO_BUF_SIZE: usize = 64 * 1024;
 
-    f.seek(SeekFrom::Start(sb.chunk_table_off))?;
-    let table_len = sb.data_off - sb.chunk_table_off;
-    if table_len > MAX_TABLE_SIZE {
-        return Err(std::io::Error::new(
-            std::io::ErrorKind::InvalidData,
-            format!(
-                "chunk table size {} exceeds maximum {}",
-                table_len, MAX_TABLE_SIZE
-            ),
-        )
-        .into());
+fn io_buf(opts: Option<&ExtractOptions>) -> Vec<u8> {
+    match opts.map_or(0, |o| o.io_buf_size) {
+        0 => vec![0u8; DEFAULT_IO_BUF_SIZE],
+        n => vec![0u8; n],
     }
-    let mut table_bytes = vec![0u8; table_len as usize];
-    f.read_exact(&mut table_bytes)?;
+}
 
-    let raw_table = if let Some((ref key, salt)) = enc {
-        let nonce = derive_nonce(&salt, Region::ChunkTable, 0);
-        crate::crypto::aead::open_whole(key, &nonce, b"chunktab", &table_bytes)?
-    } else {
-        table_bytes
-    };
+const MAX_TABLE_SIZE: u64 = 64 * 1024 * 1024; // 64 MiB (1M chunks × 64B)
 
-    let table = read_table(&mut &raw_table[..], sb.chunk_count)?;
+pub fn extract(archive: &Path, dest: &Path, opts: Option<&ExtractOptions>) -> Result<()> {
+    let arx = open_archive(archive, opts)?;
+    extract_from(&arx, dest, opts)
+}
+
+/// Like [`extract`], for an archive already opened from any source (e.g.
+/// [`Opened::from_reader`]). Keys in `opts` are not used: `arx` holds its own,
+/// and a differential archive needs its parent attached beforehand.
+pub fn extract_from(arx: &Opened, dest: &Path, opts: Option<&ExtractOptions>) -> Result<()> {
+    check_tail_totals(arx)?;
+    check_uncompressed_cap(arx, &arx.manifest.files, opts)?;
+    let manifest = &arx.manifest;
+    manifest.check_path_conflicts()?;
+    let mut buf = io_buf(opts);
 
     for d in &manifest.dirs {
-        let p = safe_join(dest, &d.path)?;
+        let Some(p) = entry_dest(dest, &d.path, opts)? else {
+            continue;
+        };
         fs::create_dir_all(&p)?;
+        restore_owner(&p, d.owner, opts)?;
     }
 
-    let mut buf = vec![0u8; 1 << 16];
-
+    let cancel = opts.and_then(|o| o.cancel.as_deref());
     for fe in &manifest.files {
+        check_cancel(cancel)?;
+        let Some(outp) = entry_dest(dest, &fe.path, opts)? else {
+            continue;
+        };
         eprintln!("extracting {}", fe.path);
-        let outp = safe_join(dest, &fe.path)?;
         if let Some(parent) = outp.parent() {
             fs::create_dir_all(parent)?;
         }
-        let mut out = File::create(&outp)?;
-
-        for cref in &fe.chunk_refs {
-            let id = cref.id as usize;
-            if id >= table.len() {
-                return Err(std::io::Error::new(
-                    std::io::ErrorKind::InvalidData,
-                    format!(
-                        "chunk id {} out of bounds (table has {} entries)",
-                        cref.id,
-                        table.len()
-                    ),
-                )
-                .into());
-            }
-            let ce: &ChunkEntry = &table[id];
-            if ce.c_size > MAX_CHUNK_SIZE {
-                return Err(std::io::Error::new(
-                    std::io::ErrorKind::InvalidData,
-                    format!(
-                        "chunk {} c_size {} exceeds maximum {}",
-                        id, ce.c_size, MAX_CHUNK_SIZE
-                    ),
-                )
-                .into());
-            }
-            f.seek(SeekFrom::Start(ce.data_off))?;
-
-            let mut cbuf = vec![0u8; ce.c_size as usize];
-            f.read_exact(&mut cbuf)?;
+        let mut out = create_output(&outp, fe.attrs)?;
+        // Seeking over holes leaves them unallocated in the output
+        let offsets = fe.chunk_offsets();
+        let sparse = !fe.holes.is_empty();
 
-            let comp = if let Some((ref key, salt)) = enc {
-                let nonce = derive_nonce(&salt, Region::ChunkData, cref.id);
-                crate::crypto::aead::open_whole(key, &nonce, b"chunk", &cbuf)?
-            } else {
-                cbuf
-            };
-
-            // Decompress
-            let decompressed = decompress_chunk(&comp, ce.codec, ce.u_size, &mut buf)?;
-
-            // Per-chunk blake3 integrity check (v4+ archives only — v3 entries have zero hash)
-            if ce.blake3 != [0u8; 32] {
-                let actual = *blake3::hash(&decompressed).as_bytes();
-                if actual != ce.blake3 {
-                    return Err(crate::error::ArxError::Format(format!(
-                        "chunk {} blake3 mismatch: data corrupted",
-                        cref.id
-                    )));
-                }
+        for (i, cref) in fe.chunk_refs.iter().enumerate() {
+            if let Err(e) = check_cancel(cancel) {
+                drop(out);
+                fs::remove_file(&outp)?;
+                return Err(e);
             }
-
-            out.write_all(&decompressed)?;
+            if sparse {
+                out.seek(SeekFrom::Start(offsets[i]))?;
+            }
+            arx.write_chunk_to(cref.id, &mut out, &mut buf)?;
+        }
+        if sparse {
+            out.set_len(fe.u_size)?;
         }
 
         if out.metadata()?.len() != fe.u_size {
@@ -155,18 +151,47 @@ pub fn extract(archive: &Path, dest: &Path, opts: Option<&ExtractOptions>) -> Re
             );
         }
 
+        // Owner before permissions: chown clears setuid/setgid bits
+        restore_owner(&outp, fe.owner, opts)?;
+
         // Restore file permissions
         #[cfg(unix)]
         {
             use std::os::unix::fs::PermissionsExt;
             fs::set_permissions(&outp, fs::Permissions::from_mode(fe.mode))?;
         }
+        restore_mtime(&outp, fe.mtime, opts)?;
+        // Last: a read-only file can't have its times set afterwards
+        restore_attrs(&outp, fe.attrs)?;
+    }
+
+    // Recreate hardlinks against their already-extracted primaries
+    for hl in &manifest.hardlinks {
+        if !manifest.files.iter().any(|fe| fe.path == hl.target) {
+            return Err(ArxError::Format(format!(
+                "hardlink {} targets unknown file {}",
+                hl.path, hl.target
+            )));
+        }
+        let (Some(target), Some(link_path)) = (
+            entry_dest(dest, &hl.target, opts)?,
+            entry_dest(dest, &hl.path, opts)?,
+        ) else {
+            continue;
+        };
+        if let Some(parent) = link_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let _ = fs::remove_file(&link_path);
+        fs::hard_link(&target, &link_path)?;
     }
 
     // Restore symlinks (v4+ archives)
     #[cfg(unix)]
     for sl in &manifest.symlinks {
-        let link_path = safe_join(dest, &sl.path)?;
+        let Some(link_path) = entry_dest(dest, &sl.path, opts)? else {
+            continue;
+        };
         if let Some(parent) = link_path.parent() {
             fs::create_dir_all(parent)?;
         }
@@ -175,102 +200,632 @@ pub fn extract(archive: &Path, dest: &Path, opts: Option<&ExtractOptions>) -> Re
         std::os::unix::fs::symlink(&sl.target, &link_path)?;
     }
 
+    // Last, since creating entries inside a directory bumps its mtime
+    for d in manifest.dirs.iter().rev() {
+        if let Some(p) = entry_dest(dest, &d.path, opts)? {
+            restore_mtime(&p, d.mtime, opts)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Running total of the chunks `files` decode to, failing as soon as it
+/// passes [`ExtractOptions::max_total_uncompressed`]. Each chunk decodes to
+/// exactly its table `u_size` (more is an error), so this bounds the output
+/// before any chunk is read.
+fn check_uncompressed_cap(
+    arx: &Opened,
+    files: &[ManifestFile],
+    opts: Option<&ExtractOptions>,
+) -> Result<()> {
+    let Some(cap) = opts.and_then(|o| o.max_total_uncompressed) else {
+        return Ok(());
+    };
+    let mut total = 0u64;
+    for cref in files.iter().flat_map(|fe| &fe.chunk_refs) {
+        let u = arx.table.get(cref.id as usize).map_or(0, |ce| ce.u_size);
+        total = total.saturating_add(u);
+        if total > cap {
+            return Err(ArxError::Format(format!(
+                "archive decompresses to more than the {cap}-byte limit; refusing to extract"
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Pre-flight for [`extract`], before any file is written: the tail's
+/// `total_u` must match the chunk table, and the table's chunks must fill the
+/// data region exactly. Archives without a tail have nothing to compare and pass.
+fn check_tail_totals(arx: &Opened) -> Result<()> {
+    let Some(tail) = arx.tail() else {
+        return Ok(());
+    };
+    check_table_totals(&tail, &arx.table, arx.sb.data_off, arx.file_end_for_data)
+}
+
+/// The checks behind [`check_tail_totals`], shared with [`verify_quick`]: the
+/// table's `u_size`s must sum to `tail.total_u`, and its chunks, stored from
+/// `data_off`, must end exactly at `data_end`.
+fn check_table_totals(
+    tail: &TailSummary,
+    table: &[ChunkEntry],
+    data_off: u64,
+    data_end: u64,
+) -> Result<()> {
+    // Parent chunks are stored elsewhere and left out of the tail totals
+    let (total_u, total_c) = table
+        .iter()
+        .filter(|ce| !ce.parent)
+        .fold((0u64, 0u64), |(u, c), ce| {
+            (u.saturating_add(ce.u_size), c.saturating_add(ce.c_size))
+        });
+    if tail.total_u != total_u {
+        return Err(ArxError::Format(format!(
+            "tail total_u {} disagrees with the chunk table ({total_u}); archive is corrupt",
+            tail.total_u
+        )));
+    }
+    let table_end = data_off.saturating_add(total_c);
+    if table_end != data_end {
+        return Err(ArxError::Format(format!(
+            "chunk table accounts for data up to offset {table_end}, but the data region \
+             ends at {data_end}; archive is truncated or corrupt"
+        )));
+    }
+    Ok(())
+}
+
+/// Where archive entry `path` is written under `dest`, after
+/// [`ExtractOptions::strip_components`] and [`ExtractOptions::prefix`];
+/// `None` when stripping leaves nothing.
+fn entry_dest(dest: &Path, path: &str, opts: Option<&ExtractOptions>) -> Result<Option<PathBuf>> {
+    let strip = opts.map_or(0, |o| o.strip_components);
+    let prefix = opts
+        .and_then(|o| o.prefix.as_deref())
+        .filter(|p| !p.is_empty());
+    if strip == 0 && prefix.is_none() {
+        return safe_join(dest, path).map(Some);
+    }
+    let rest: Vec<&str> = path
+        .split('/')
+        .filter(|s| !s.is_empty())
+        .skip(strip)
+        .collect();
+    if rest.is_empty() {
+        return Ok(None);
+    }
+    let rel = match prefix {
+        Some(prefix) => format!("{}/{}", prefix.trim_end_matches('/'), rest.join("/")),
+        None => rest.join("/"),
+    };
+    safe_join(dest, &rel).map(Some)
+}
+
+/// Set `path`'s modification time to `mtime` (Unix seconds), unless
+/// [`ExtractOptions::restore_mtime`] is off or nothing was recorded.
+fn restore_mtime(path: &Path, mtime: i64, opts: Option<&ExtractOptions>) -> Result<()> {
+    if mtime == 0 || opts.is_some_and(|o| !o.restore_mtime) {
+        return Ok(());
+    }
+    filetime::set_file_mtime(path, filetime::FileTime::from_unix_time(mtime, 0))?;
+    Ok(())
+}
+
+/// Apply `owner`, remapped through [`ExtractOptions::owner_map`], to `path`.
+/// A no-op when no map is set or the entry has no recorded owner.
+fn restore_owner(path: &Path, owner: Option<Owner>, opts: Option<&ExtractOptions>) -> Result<()> {
+    let (Some(owner), Some(map)) = (owner, opts.and_then(|o| o.owner_map.as_ref())) else {
+        return Ok(());
+    };
+    #[cfg(unix)]
+    {
+        let uid = map.get(&owner.uid).copied().unwrap_or(owner.uid);
+        std::os::unix::fs::chown(path, Some(uid), Some(owner.gid))?;
+    }
+    #[cfg(not(unix))]
+    let _ = (path, owner, map);
     Ok(())
 }
 
+/// Reapply a file's recorded [`ATTR_READONLY`] bit on Windows; a recorded
+/// [`ATTR_HIDDEN`] bit is given when [`create_output`] creates the file. A
+/// no-op elsewhere.
+///
+/// [`ATTR_READONLY`]: crate::container::manifest::ATTR_READONLY
+/// [`ATTR_HIDDEN`]: crate::container::manifest::ATTR_HIDDEN
+fn restore_attrs(path: &Path, attrs: Option<u32>) -> Result<()> {
+    #[cfg(windows)]
+    if let Some(attrs) = attrs {
+        use crate::container::manifest::ATTR_READONLY;
+        let mut perm = fs::metadata(path)?.permissions();
+        let readonly = attrs & ATTR_READONLY != 0;
+        if perm.readonly() != readonly {
+            perm.set_readonly(readonly);
+            fs::set_permissions(path, perm)?;
+        }
+    }
+    #[cfg(not(windows))]
+    let _ = (path, attrs);
+    Ok(())
+}
+
+/// Create (or truncate) an extracted file. On Windows a recorded
+/// [`ATTR_HIDDEN`] bit is passed at creation, since std has no call that
+/// sets it afterwards.
+///
+/// [`ATTR_HIDDEN`]: crate::container::manifest::ATTR_HIDDEN
+fn create_output(path: &Path, attrs: Option<u32>) -> std::io::Result<File> {
+    let mut oo = OpenOptions::new();
+    oo.write(true).create(true).truncate(true);
+    #[cfg(windows)]
+    {
+        use crate::container::manifest::ATTR_HIDDEN;
+        use std::os::windows::fs::OpenOptionsExt;
+        oo.attributes(attrs.unwrap_or(0) & ATTR_HIDDEN);
+    }
+    #[cfg(not(windows))]
+    let _ = attrs;
+    oo.open(path)
+}
+
+/// Stream a single archived file into `w` without extracting the rest of the tree.
+/// Decrypts with the key or password in `opts`. Returns the number of bytes written.
+pub fn extract_file_to<W: Write>(
+    archive: &Path,
+    path: &str,
+    w: &mut W,
+    opts: Option<&ExtractOptions>,
+) -> Result<u64> {
+    let opened = open_archive(archive, opts)?;
+    let path = opened.manifest.resolve_hardlink(path);
+    let fe = opened
+        .manifest
+        .files
+        .iter()
+        .find(|fe| fe.path == path)
+        .ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("no such file: {path}"),
+            )
+        })?;
+    let expected = fe.u_size;
+    check_uncompressed_cap(&opened, std::slice::from_ref(fe), opts)?;
+
+    // Holes are written out as zeros: `w` need not be seekable
+    let verify_chunks = opts.is_some_and(|o| o.verify_chunks);
+    let cancel = opts.and_then(|o| o.cancel.as_deref());
+    let mut buf = io_buf(opts);
+    let mut n = 0u64;
+    for (ordinal, (cref, off)) in fe.chunk_refs.iter().zip(fe.chunk_offsets()).enumerate() {
+        check_cancel(cancel)?;
+        if off > n {
+            n += std::io::copy(&mut std::io::repeat(0).take(off - n), w)?;
+        }
+        if verify_chunks {
+            let plain = opened.read_chunk(cref.id).map_err(|e| {
+                ArxError::Format(format!(
+                    "{path}: chunk {ordinal} of {} (id {}) failed verification: {e}",
+                    fe.chunk_refs.len(),
+                    cref.id
+                ))
+            })?;
+            w.write_all(&plain)?;
+            n += plain.len() as u64;
+        } else {
+            n += opened.write_chunk_to(cref.id, w, &mut buf)?;
+        }
+    }
+    if n < expected {
+        n += std::io::copy(&mut std::io::repeat(0).take(expected - n), w)?;
+    }
+    if n != expected {
+        return Err(std::io::Error::other("extracted size mismatch").into());
+    }
+    Ok(n)
+}
+
+/// Read a single archived file fully into memory, decrypting with the key or
+/// password in `opts`. Fails with `ErrorKind::NotFound` if `path` is not a file
+/// in the archive.
+pub fn read_file_bytes(
+    archive: &Path,
+    path: &str,
+    opts: Option<&ExtractOptions>,
+) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    extract_file_to(archive, path, &mut out, opts)?;
+    Ok(out)
+}
+
+/// Open `len` bytes of a single archived file starting at `start`, for partial
+/// reads (e.g. HTTP range requests). Only the chunks overlapping the range are
+/// decrypted and decompressed. Reading stops early at end of file.
+pub fn read_file_range(
+    archive: &Path,
+    path: &str,
+    start: u64,
+    len: u64,
+    opts: Option<&ExtractOptions>,
+) -> Result<impl Read + Send + use<>> {
+    let opened = open_archive(archive, opts)?;
+    RangeReader::new_shared(Arc::new(opened), path, start, len)
+}
+
+/// Verification depth for [`verify_with`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct VerifyOptions {
+    /// Also decompress every chunk and check its `u_size` and blake3 hash.
+    pub deep: bool,
+    /// Check chunks one at a time instead of in parallel batches.
+    pub sequential: bool,
+}
+
+/// Fast integrity check against the Tail Summary.
+///
+/// The tail's `data_blake3` covers the *compressed* plaintext of each chunk
+/// (after AEAD open, before decompression), so this mode authenticates and
+/// hashes chunks without ever decompressing them.
 pub fn verify(archive: &Path, opts: Option<&ExtractOptions>) -> Result<()> {
+    verify_with(archive, opts, &VerifyOptions::default())
+}
+
+/// Like [`verify`], optionally decompressing every chunk (`deep`) to check
+/// its uncompressed size and content hash as well.
+pub fn verify_with(
+    archive: &Path,
+    opts: Option<&ExtractOptions>,
+    vopts: &VerifyOptions,
+) -> Result<()> {
     let mut f = File::open(archive)?;
     let sb = Superblock::read_from(&mut f)?;
-    let header_len = sb.header_len();
-    let enc_enabled = (sb.flags & FLAG_ENCRYPTED) != 0;
+    // Sealed chunks can't be hashed without the key, even in per-file mode
+    let enc_enabled = (sb.flags & (FLAG_ENCRYPTED | FLAG_CHUNK_AEAD)) != 0;
 
     let tail = read_tail_at_eof(&mut f).map_err(|e| {
         std::io::Error::new(std::io::ErrorKind::Other, format!("tail read failed: {e}"))
     })?;
 
-    let enc = resolve_enc(&sb, opts, enc_enabled)?;
+    let enc = resolve_region_enc(&mut f, &sb, opts, enc_enabled)?;
+    let (got, _) = compute_tail(
+        &mut f,
+        &sb,
+        enc.as_ref(),
+        vopts.deep,
+        !vopts.sequential,
+        None,
+    )?;
 
-    // 1) Manifest hash
-    f.seek(SeekFrom::Start(header_len))?;
-    let mut man_bytes = vec![0u8; sb.manifest_len as usize];
-    f.read_exact(&mut man_bytes)?;
-    let manifest_plain = if let Some((ref key, salt)) = enc {
-        let nonce = derive_nonce(&salt, Region::Manifest, 0);
-        crate::crypto::aead::open_whole(key, &nonce, b"manifest", &man_bytes)?
-    } else {
-        man_bytes
-    };
-    let got_manifest = *blake3::hash(&manifest_plain).as_bytes();
+    let ok = tail.manifest_blake3 == got.manifest_blake3
+        && tail.chunktab_blake3 == got.chunktab_blake3
+        && tail.data_blake3 == got.data_blake3
+        && tail.total_u == got.total_u
+        && tail.total_c == got.total_c;
 
-    // 2) ChunkTable hash
-    f.seek(SeekFrom::Start(sb.chunk_table_off))?;
-    let table_len = sb.data_off - sb.chunk_table_off;
-    let mut table_bytes = vec![0u8; table_len as usize];
-    f.read_exact(&mut table_bytes)?;
-    let chunktab_plain = if let Some((ref key, salt)) = enc {
-        let nonce = derive_nonce(&salt, Region::ChunkTable, 0);
-        crate::crypto::aead::open_whole(key, &nonce, b"chunktab", &table_bytes)?
+    if !ok {
+        return Err(std::io::Error::other("verify mismatch (tail)").into());
+    }
+
+    Ok(())
+}
+
+/// Structural check that hashes nothing: the superblock parses, a tail is
+/// present, the regions lie in order inside the file, the chunk table's size
+/// matches `chunk_count`, and its chunks (with the tail's totals) account for
+/// the data region exactly. Reads only the header, the table and the tail, so
+/// it answers "is this plausibly intact" in well under a second; payload
+/// corruption needs [`verify`]. The table of an encrypted archive still needs
+/// the key to be read.
+pub fn verify_quick(archive: &Path, opts: Option<&ExtractOptions>) -> Result<()> {
+    let mut f = File::open(archive)?;
+    let sb = Superblock::read_from(&mut f)?;
+    let tail = read_tail_at_eof(&mut f)
+        .map_err(|e| std::io::Error::other(format!("tail read failed: {e}")))?;
+    let data_end = f.seek(SeekFrom::End(0))? - TAIL_LEN;
+
+    let manifest_end = sb.header_len().saturating_add(sb.manifest_len);
+    if manifest_end > sb.chunk_table_off
+        || sb.chunk_table_off > sb.data_off
+        || sb.data_off > data_end
+    {
+        return Err(ArxError::Format(format!(
+            "regions out of order: manifest ends at {manifest_end}, chunk table at {}, \
+             data at {}, tail at {data_end}",
+            sb.chunk_table_off, sb.data_off
+        )));
+    }
+
+    let entry_size = if sb.version >= 4 {
+        ENTRY_SIZE
+    } else {
+        ENTRY_SIZE_V3
+    } as u64;
+    let tag = if sb.flags & FLAG_ENCRYPTED != 0 {
+        TAG_LEN as u64
     } else {
-        table_bytes
+        0
     };
-    let got_tab = *blake3::hash(&chunktab_plain).as_bytes();
+    let table_len = sb.data_off - sb.chunk_table_off;
+    if sb
+        .chunk_count
+        .checked_mul(entry_size)
+        .and_then(|n| n.checked_add(tag))
+        != Some(table_len)
+    {
+        return Err(ArxError::Format(format!(
+            "chunk table is {table_len} bytes, not the {} entries of {entry_size} bytes \
+             chunk_count says",
+            sb.chunk_count
+        )));
+    }
 
-    let table = read_table(&mut &chunktab_plain[..], sb.chunk_count)?;
+    let enc = resolve_region_enc(&mut f, &sb, opts, sb.flags & FLAG_ENCRYPTED != 0)?;
+    let table = read_table_plain(&mut f, &sb, enc.as_ref())
+        .ok_or_else(|| ArxError::Format("chunk table unreadable".into()))?;
+    check_table_totals(&tail, &table, sb.data_off, data_end)?;
+    // The tail's total_c counts compressed bytes before sealing
+    let total_c = table
+        .iter()
+        .filter(|ce| !ce.parent)
+        .map(|ce| {
+            let tag = if ce.is_sealed(sb.flags) {
+                TAG_LEN as u64
+            } else {
+                0
+            };
+            ce.c_size.saturating_sub(tag)
+        })
+        .fold(0u64, u64::saturating_add);
+    if tail.total_c != total_c {
+        return Err(ArxError::Format(format!(
+            "tail total_c {} disagrees with the chunk table ({total_c})",
+            tail.total_c
+        )));
+    }
+    Ok(())
+}
 
-    let mut h_data = blake3::Hasher::new();
-    let mut total_u = 0u64;
-    let mut total_c = 0u64;
+/// Per-region outcome of [`verify_report`]; `true` means the region matches the tail.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct VerifyReport {
+    pub manifest_ok: bool,
+    pub chunktab_ok: bool,
+    pub data_ok: bool,
+    pub total_u_ok: bool,
+    pub total_c_ok: bool,
+    /// Chunks that fail to authenticate, decompress, or match their table hash.
+    pub bad_chunks: Vec<u64>,
+    /// Every integrity check this archive supports, and whether it ran.
+    pub coverage: Vec<Check>,
+}
 
-    for (id, ce) in table.iter().enumerate() {
-        f.seek(SeekFrom::Start(ce.data_off))?;
-        let mut cbuf = vec![0u8; ce.c_size as usize];
-        f.read_exact(&mut cbuf)?;
+/// One integrity check in [`VerifyReport::coverage`]. A skipped check is not
+/// a failure: it names corruption that this report could not have caught.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Check {
+    pub name: &'static str,
+    /// `None` if the check ran; otherwise why it was skipped.
+    pub skipped: Option<String>,
+}
 
-        let comp_plain = if let Some((ref key, salt)) = enc {
-            let nonce = derive_nonce(&salt, Region::ChunkData, id as u64);
-            crate::crypto::aead::open_whole(key, &nonce, b"chunk", &cbuf)?
-        } else {
-            cbuf
-        };
+impl Check {
+    fn ran(name: &'static str) -> Self {
+        Self {
+            name,
+            skipped: None,
+        }
+    }
 
-        h_data.update(&comp_plain);
-        total_u = total_u.saturating_add(ce.u_size);
-        total_c = total_c.saturating_add(comp_plain.len() as u64);
+    fn skipped(name: &'static str, why: impl Into<String>) -> Self {
+        Self {
+            name,
+            skipped: Some(why.into()),
+        }
     }
-    let got_data = *h_data.finalize().as_bytes();
+}
 
-    let ok = tail.manifest_blake3 == got_manifest
-        && tail.chunktab_blake3 == got_tab
-        && tail.data_blake3 == got_data
-        && tail.total_u == total_u
-        && tail.total_c == total_c;
+impl VerifyReport {
+    pub fn is_ok(&self) -> bool {
+        self.manifest_ok
+            && self.chunktab_ok
+            && self.data_ok
+            && self.total_u_ok
+            && self.total_c_ok
+            && self.bad_chunks.is_empty()
+    }
 
-    if !ok {
-        return Err(
-            std::io::Error::new(std::io::ErrorKind::Other, "verify mismatch (tail)").into(),
-        );
+    /// Names of the failing components, e.g. `["manifest", "chunk 3"]`.
+    pub fn failures(&self) -> Vec<String> {
+        let mut out: Vec<String> = [
+            (self.manifest_ok, "manifest"),
+            (self.chunktab_ok, "chunk table"),
+            (self.data_ok, "data"),
+            (self.total_u_ok, "total_u"),
+            (self.total_c_ok, "total_c"),
+        ]
+        .into_iter()
+        .filter(|(ok, _)| !ok)
+        .map(|(_, name)| name.to_string())
+        .collect();
+        out.extend(self.bad_chunks.iter().map(|id| format!("chunk {id}")));
+        out
     }
 
-    Ok(())
+    /// Checks that did not run, e.g. `"per-chunk hashes: no per-chunk hashes; …"`.
+    pub fn skipped(&self) -> Vec<String> {
+        self.coverage
+            .iter()
+            .filter_map(|c| Some(format!("{}: {}", c.name, c.skipped.as_deref()?)))
+            .collect()
+    }
+}
+
+/// Like [`verify_with`], but instead of stopping at the first problem, check
+/// every region against the tail and report which ones fail. A region that
+/// fails to authenticate counts as failed rather than aborting. When the data
+/// hash disagrees, chunks are decompressed and checked against their table
+/// hashes to locate the bad ones, even without `deep`; v3 chunks (no stored
+/// hash) can only be flagged when they fail to authenticate or decompress.
+///
+/// [`VerifyReport::coverage`] records which checks actually ran, so a clean
+/// report over an archive without per-chunk hashes, say, is not mistaken for
+/// one that proved every chunk intact.
+pub fn verify_report(
+    archive: &Path,
+    opts: Option<&ExtractOptions>,
+    vopts: &VerifyOptions,
+) -> Result<VerifyReport> {
+    let mut f = File::open(archive)?;
+    let sb = Superblock::read_from(&mut f)?;
+    // Sealed chunks can't be hashed without the key, even in per-file mode
+    let enc_enabled = (sb.flags & (FLAG_ENCRYPTED | FLAG_CHUNK_AEAD)) != 0;
+
+    let tail = read_tail_at_eof(&mut f)
+        .map_err(|e| std::io::Error::other(format!("tail read failed: {e}")))?;
+
+    let enc = resolve_region_enc(&mut f, &sb, opts, enc_enabled)?;
+    let mut bad = Vec::new();
+    let (got, _) = compute_tail(
+        &mut f,
+        &sb,
+        enc.as_ref(),
+        vopts.deep,
+        !vopts.sequential,
+        Some(&mut bad),
+    )?;
+    let mut deep = vopts.deep;
+    if tail.data_blake3 != got.data_blake3 && !deep {
+        // Locate the culprits
+        bad.clear();
+        compute_tail(
+            &mut f,
+            &sb,
+            enc.as_ref(),
+            true,
+            !vopts.sequential,
+            Some(&mut bad),
+        )?;
+        deep = true;
+    }
+
+    let unhashed = read_table_plain(&mut f, &sb, enc.as_ref()).map(|t| {
+        (
+            t.iter().filter(|ce| ce.blake3 == [0u8; 32]).count(),
+            t.len(),
+        )
+    });
+    let mut coverage = vec![Check::ran("region hashes")];
+    coverage.push(if sb.flags & FLAG_CHUNK_AEAD != 0 {
+        Check::skipped(
+            "authentication",
+            "per-file encryption; only sealed chunks are authenticated",
+        )
+    } else if enc.is_some() {
+        Check::ran("authentication")
+    } else {
+        Check::skipped(
+            "authentication",
+            "unencrypted; tampering that also rewrites the tail goes unnoticed",
+        )
+    });
+    coverage.push(match unhashed {
+        None => Check::skipped("per-chunk hashes", "chunk table unreadable"),
+        Some((n, total)) if n == total && total > 0 => Check::skipped(
+            "per-chunk hashes",
+            "no per-chunk hashes; chunk-level corruption undetectable",
+        ),
+        Some(_) if !deep => Check::skipped("per-chunk hashes", "not run; use deep verify"),
+        Some((0, _)) => Check::ran("per-chunk hashes"),
+        Some((n, total)) => Check::skipped(
+            "per-chunk hashes",
+            format!("{n} of {total} chunks have no hash"),
+        ),
+    });
+    coverage.push(if deep {
+        Check::ran("decompressed sizes")
+    } else {
+        Check::skipped("decompressed sizes", "not run; use deep verify")
+    });
+
+    Ok(VerifyReport {
+        manifest_ok: tail.manifest_blake3 == got.manifest_blake3,
+        chunktab_ok: tail.chunktab_blake3 == got.chunktab_blake3,
+        data_ok: tail.data_blake3 == got.data_blake3,
+        total_u_ok: tail.total_u == got.total_u,
+        total_c_ok: tail.total_c == got.total_c,
+        bad_chunks: bad,
+        coverage,
+    })
+}
+
+/// Recompute the Tail Summary from the archive's (decrypted) regions and write
+/// it right after the last chunk, replacing a missing, stale, or corrupt tail.
+/// Anything after the data region is discarded. Returns the tail written.
+pub fn fix_tail(archive: &Path, opts: Option<&ExtractOptions>) -> Result<TailSummary> {
+    let mut f = OpenOptions::new().read(true).write(true).open(archive)?;
+    let sb = Superblock::read_from(&mut f)?;
+    let enc = resolve_region_enc(
+        &mut f,
+        &sb,
+        opts,
+        (sb.flags & (FLAG_ENCRYPTED | FLAG_CHUNK_AEAD)) != 0,
+    )?;
+
+    let (tail, data_end) = compute_tail(&mut f, &sb, enc.as_ref(), false, true, None)?;
+    f.set_len(data_end)?;
+    f.seek(SeekFrom::Start(data_end))?;
+    tail.write_to(&mut f)?;
+    f.sync_all()?;
+    Ok(tail)
 }
 
 // ── Helpers ──────────────────────────────────────────────────────────────────
 
+/// Open `archive` with the key or password in `opts`, attaching
+/// [`ExtractOptions::parent`] if set. A differential archive without its
+/// parent is refused up front rather than at its first parent chunk.
+pub(crate) fn open_archive(archive: &Path, opts: Option<&ExtractOptions>) -> Result<Opened> {
+    let sb = Superblock::read_from(File::open(archive)?)?;
+    let enc = resolve_enc(&sb, opts, (sb.flags & FLAG_ENCRYPTED) != 0)?;
+    let mut opened = Opened::open(archive, enc.map(|(key, _)| key.0), sb.kdf_salt)?;
+    match opts.and_then(|o| o.parent.as_deref()) {
+        Some(parent) => {
+            let parent_opts = ExtractOptions {
+                parent: None,
+                ..opts.cloned().unwrap_or_default()
+            };
+            opened.attach_parent(open_archive(parent, Some(&parent_opts))?);
+        }
+        None if opened.needs_parent() => {
+            return Err(ArxError::Format(format!(
+                "{} is a differential archive; its parent archive is required",
+                archive.display()
+            )));
+        }
+        None => {}
+    }
+    Ok(opened)
+}
+
 /// Resolve encryption context from options + superblock.
-fn resolve_enc(
+///
+/// A key is required when `enc_enabled`. Otherwise a per-file encrypted
+/// (`FLAG_CHUNK_AEAD`) archive still gets the key if one was given, so its
+/// sealed chunks can be opened; without one only its plaintext files can.
+pub(crate) fn resolve_enc(
     sb: &Superblock,
     opts: Option<&ExtractOptions>,
     enc_enabled: bool,
 ) -> Result<Option<(AeadKey, [u8; 32])>> {
     if !enc_enabled {
-        return Ok(None);
+        let has_key = opts.is_some_and(|o| o.aead_key.is_some() || o.password.is_some());
+        if sb.flags & FLAG_CHUNK_AEAD == 0 || !has_key {
+            return Ok(None);
+        }
     }
-    let o = opts.ok_or_else(|| {
-        std::io::Error::new(
-            std::io::ErrorKind::Other,
-            "archive is encrypted; key required",
-        )
-    })?;
+    let o = opts.ok_or_else(|| std::io::Error::other("archive is encrypted; key required"))?;
 
     // Raw key takes precedence over password
     if let Some(raw) = o.aead_key {
@@ -280,30 +835,194 @@ fn resolve_enc(
         let key = crate::crypto::kdf::derive_key(pw, &sb.kdf_salt);
         return Ok(Some((AeadKey(key), sb.kdf_salt)));
     }
-    Err(std::io::Error::new(
-        std::io::ErrorKind::Other,
-        "missing key or password for encrypted archive",
-    )
-    .into())
-}
-
-fn decompress_chunk(comp: &[u8], codec: u8, u_size: u64, buf: &mut Vec<u8>) -> Result<Vec<u8>> {
-    match codec {
-        x if x == CodecId::Store as u8 => Ok(comp.to_vec()),
-        x if x == CodecId::Zstd as u8 => {
-            let mut dec = zstd::stream::read::Decoder::with_buffer(comp)?;
-            let mut out = Vec::with_capacity(u_size as usize);
-            loop {
-                let k = dec.read(buf)?;
-                if k == 0 {
-                    break;
+    Err(std::io::Error::other("missing key or password for encrypted archive").into())
+}
+
+/// [`resolve_enc`] for reading `f`'s regions directly: a keyring archive's
+/// recipient key is swapped for the data key it unwraps.
+fn resolve_region_enc(
+    f: &mut File,
+    sb: &Superblock,
+    opts: Option<&ExtractOptions>,
+    enc_enabled: bool,
+) -> Result<Option<(AeadKey, [u8; 32])>> {
+    match resolve_enc(sb, opts, enc_enabled)? {
+        Some((key, salt)) => Ok(Some((region_key(f, sb, key)?, salt))),
+        None => Ok(None),
+    }
+}
+
+/// The decrypted chunk table, or `None` if it fails to authenticate or parse.
+fn read_table_plain(
+    f: &mut File,
+    sb: &Superblock,
+    enc: Option<&(AeadKey, [u8; 32])>,
+) -> Option<Vec<ChunkEntry>> {
+    let len = sb.data_off.checked_sub(sb.chunk_table_off)?;
+    if len > MAX_TABLE_SIZE {
+        return None;
+    }
+    let mut bytes = vec![0u8; len as usize];
+    f.seek(SeekFrom::Start(sb.chunk_table_off)).ok()?;
+    f.read_exact(&mut bytes).ok()?;
+    if let Some((key, salt)) = enc.filter(|_| sb.flags & FLAG_ENCRYPTED != 0) {
+        let nonce = derive_nonce(AeadAlg::from_flags(sb.flags), salt, Region::ChunkTable, 0);
+        bytes = crate::crypto::aead::open_whole(key, &nonce, b"chunktab", &bytes).ok()?;
+    }
+    read_table(&mut &bytes[..], sb.chunk_count).ok()
+}
+
+/// Maximum number of chunks a parallel verify reads and checks at once before
+/// hashing them in order.
+const VERIFY_BATCH: usize = 64;
+
+/// Hash every region the way `pack` does and return the resulting Tail Summary
+/// together with the end offset of the data region. With `deep`, each chunk is
+/// also decompressed and checked against its table `u_size` and blake3.
+fn compute_tail(
+    f: &mut File,
+    sb: &Superblock,
+    enc: Option<&(AeadKey, [u8; 32])>,
+    deep: bool,
+    parallel: bool,
+    mut bad_chunks: Option<&mut Vec<u64>>,
+) -> Result<(TailSummary, u64)> {
+    // Collecting faults instead of failing: a region that won't authenticate
+    // gets an all-zero hash, which never matches a real tail.
+    let collect = bad_chunks.is_some();
+    let alg = AeadAlg::from_flags(sb.flags);
+    let region_enc = enc.filter(|_| sb.flags & FLAG_ENCRYPTED != 0);
+
+    // 1) Manifest hash
+    f.seek(SeekFrom::Start(sb.header_len()))?;
+    let mut man_bytes = vec![0u8; sb.manifest_len as usize];
+    f.read_exact(&mut man_bytes)
+        .map_err(|e| ArxError::corrupt("manifest", sb.header_len(), e))?;
+    let man_plain = match region_enc {
+        Some((key, salt)) => {
+            let nonce = derive_nonce(alg, salt, Region::Manifest, 0);
+            match crate::crypto::aead::open_whole(key, &nonce, b"manifest", &man_bytes) {
+                Ok(pt) => Some(pt),
+                Err(_) if collect => None,
+                Err(e) => return Err(e),
+            }
+        }
+        None => Some(man_bytes),
+    };
+    let manifest_blake3 = match &man_plain {
+        Some(pt) => *blake3::hash(pt).as_bytes(),
+        None => [0u8; 32],
+    };
+    // Decoding `ZstdDict` chunks needs the dictionary the manifest carries
+    let zstd_dict = man_plain
+        .filter(|_| deep)
+        .and_then(|pt| Manifest::from_region(&pt, sb.flags).ok())
+        .and_then(|m| m.meta.zstd_dict);
+
+    // 2) ChunkTable hash
+    let table_len = sb.data_off.checked_sub(sb.chunk_table_off).ok_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!(
+                "chunk_table_off {} > data_off {}",
+                sb.chunk_table_off, sb.data_off
+            ),
+        )
+    })?;
+    f.seek(SeekFrom::Start(sb.chunk_table_off))?;
+    let mut table_bytes = vec![0u8; table_len as usize];
+    f.read_exact(&mut table_bytes)
+        .map_err(|e| ArxError::corrupt("chunk table", sb.chunk_table_off, e))?;
+    let chunktab_plain = if let Some((key, salt)) = region_enc {
+        let nonce = derive_nonce(alg, salt, Region::ChunkTable, 0);
+        match crate::crypto::aead::open_whole(key, &nonce, b"chunktab", &table_bytes) {
+            Ok(pt) => Some(pt),
+            Err(_) if collect => None,
+            Err(e) => return Err(e),
+        }
+    } else {
+        Some(table_bytes)
+    };
+    let chunktab_blake3 = match &chunktab_plain {
+        Some(pt) => *blake3::hash(pt).as_bytes(),
+        None => [0u8; 32],
+    };
+
+    // Without a readable table no chunk can be located; the data fields then fail too
+    let table = match &chunktab_plain {
+        Some(pt) => read_table(&mut &pt[..], sb.chunk_count)?,
+        None => Vec::new(),
+    };
+
+    // 3) Data region: hash of compressed plaintext, chunk by chunk. Batches of
+    // chunks are read and checked in parallel, then hashed in id order; v3
+    // archives (no per-chunk hashes) keep the one-at-a-time pass.
+    let mut h_data = blake3::Hasher::new();
+    let mut total_u = 0u64;
+    let mut total_c = 0u64;
+    let mut data_end = sb.data_off;
+
+    let file: &File = f;
+    let check_chunk = |&(id, ce): &(usize, &ChunkEntry)| -> Result<Vec<u8>> {
+        check_chunk_size(id as u64, ce)?;
+        let mut cbuf = vec![0u8; ce.c_size as usize];
+        read_exact_at(file, &mut cbuf, ce.data_off)
+            .map_err(|e| ArxError::corrupt("chunk", ce.data_off, e))?;
+
+        let comp_plain = if ce.is_sealed(sb.flags) {
+            unseal_chunk(sb, enc, id as u64, ce, &cbuf)?.into_owned()
+        } else {
+            cbuf
+        };
+        if deep {
+            decode_chunk(id as u64, ce, &comp_plain, zstd_dict.as_deref())?;
+        }
+        Ok(comp_plain)
+    };
+
+    // Stored in the parent archive, as `pack` leaves them out of the tail
+    let live: Vec<(usize, &ChunkEntry)> = table
+        .iter()
+        .enumerate()
+        .filter(|(_, ce)| !ce.parent)
+        .collect();
+    let parallel = parallel && sb.version >= 4;
+    let batch = if parallel { VERIFY_BATCH } else { 1 };
+    for group in live.chunks(batch) {
+        let results: Vec<Result<Vec<u8>>> = if parallel {
+            group.par_iter().map(check_chunk).collect()
+        } else {
+            group.iter().map(check_chunk).collect()
+        };
+        for (&(id, ce), res) in group.iter().zip(results) {
+            match res {
+                Ok(comp_plain) => {
+                    h_data.update(&comp_plain);
                 }
-                out.extend_from_slice(&buf[..k]);
+                Err(e) => match bad_chunks.as_deref_mut() {
+                    Some(bad) => bad.push(id as u64),
+                    None => return Err(e),
+                },
             }
-            Ok(out)
+            data_end = data_end.max(ce.data_off.saturating_add(ce.c_size));
+            total_u = total_u.saturating_add(ce.u_size);
+            let tag = if ce.is_sealed(sb.flags) {
+                TAG_LEN as u64
+            } else {
+                0
+            };
+            total_c = total_c.saturating_add(ce.c_size.saturating_sub(tag));
         }
-        _ => Err(std::io::Error::new(std::io::ErrorKind::Other, "unknown codec").into()),
     }
+
+    let tail = TailSummary {
+        manifest_blake3,
+        chunktab_blake3,
+        data_blake3: *h_data.finalize().as_bytes(),
+        total_u,
+        total_c,
+    };
+    Ok((tail, data_end))
 }
 
 fn read_tail_at_eof(f: &mut File) -> std::io::Result<TailSummary> {
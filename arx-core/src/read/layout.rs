@@ -0,0 +1,118 @@
+//! Byte-level layout of an archive, for tools that fetch chunk bytes directly
+//! (FUSE mounts, HTTP range readers) instead of linking the reader.
+
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::container::superblock::{FLAG_CHUNK_AEAD, FLAG_ENCRYPTED, Superblock};
+use crate::crypto::aead::AeadAlg;
+use crate::error::Result;
+use crate::read::extract::{ExtractOptions, resolve_enc};
+use crate::read::opened::Opened;
+
+/// Bumped whenever a field of [`Layout`] changes meaning or goes away.
+pub const LAYOUT_VERSION: u32 = 2;
+
+/// Where every file's bytes live. Serialized by `arx layout --json`; fields
+/// are only ever added, under the same [`LAYOUT_VERSION`].
+#[derive(Clone, Debug, Serialize)]
+pub struct Layout {
+    pub layout_version: u32,
+    /// When set, some chunks' bytes are AEAD ciphertext (tag included in
+    /// `c_size`); [`ChunkLayout::encrypted`] says which. Version 1 meant all of them.
+    pub encrypted: bool,
+    /// `"xchacha20poly1305"` or `"aes-256-gcm"`; absent when unencrypted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cipher: Option<&'static str>,
+    pub files: Vec<FileLayout>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct FileLayout {
+    pub path: String,
+    pub u_size: u64,
+    /// Sparse holes `(offset, len)` that read as zeros and have no chunks.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub holes: Vec<(u64, u64)>,
+    /// In file order; concatenated (with `holes` zero-filled) they are the file.
+    pub chunks: Vec<ChunkLayout>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ChunkLayout {
+    pub id: u64,
+    /// Logical offset of this chunk within the file.
+    pub file_off: u64,
+    pub u_size: u64,
+    /// Absolute offset of the stored bytes in the archive file.
+    pub data_off: u64,
+    pub c_size: u64,
+    /// On-disk codec byte: 0 store, 1 zstd, 2 zstd against the manifest's
+    /// dictionary, 3 deflate.
+    pub codec: u8,
+    /// Hex blake3 of the uncompressed chunk; empty for v3 archives and for
+    /// sealed chunks of per-file encrypted archives.
+    pub blake3: String,
+    /// These bytes are AEAD ciphertext, sealed under the chunk's id.
+    pub encrypted: bool,
+    /// Stored in the parent archive of a differential archive: `data_off` and
+    /// `c_size` refer to that file, not this one.
+    pub parent: bool,
+}
+
+/// Layout of an already-opened archive.
+pub fn layout(arx: &Opened) -> Result<Layout> {
+    let encrypted = arx.sb.flags & (FLAG_ENCRYPTED | FLAG_CHUNK_AEAD) != 0;
+    let files = arx
+        .manifest
+        .files
+        .iter()
+        .map(|fe| {
+            let chunks = arx
+                .chunk_map_of(fe)?
+                .into_iter()
+                .map(|c| {
+                    let ce = &arx.table[c.id as usize];
+                    ChunkLayout {
+                        id: c.id,
+                        file_off: c.file_off,
+                        u_size: c.u_len,
+                        data_off: c.data_off,
+                        c_size: c.c_len,
+                        codec: c.codec,
+                        blake3: if ce.blake3 == [0u8; 32] {
+                            String::new()
+                        } else {
+                            hex::encode(ce.blake3)
+                        },
+                        encrypted: arx.chunk_sealed(c.id),
+                        parent: ce.parent,
+                    }
+                })
+                .collect();
+            Ok(FileLayout {
+                path: fe.path.clone(),
+                u_size: fe.u_size,
+                holes: fe.holes.clone(),
+                chunks,
+            })
+        })
+        .collect::<Result<_>>()?;
+    Ok(Layout {
+        layout_version: LAYOUT_VERSION,
+        encrypted,
+        cipher: encrypted.then(|| match AeadAlg::from_flags(arx.sb.flags) {
+            AeadAlg::XChaCha20Poly1305 => "xchacha20poly1305",
+            AeadAlg::Aes256Gcm => "aes-256-gcm",
+        }),
+        files,
+    })
+}
+
+/// Open `archive` (keyed from `opts` if encrypted) and return its layout.
+pub fn archive_layout(archive: &Path, opts: Option<&ExtractOptions>) -> Result<Layout> {
+    let sb = Superblock::read_from(std::fs::File::open(archive)?)?;
+    let key = resolve_enc(&sb, opts, (sb.flags & FLAG_ENCRYPTED) != 0)?.map(|(k, _)| k.0);
+    layout(&Opened::open(archive, key, [0u8; 32])?)
+}
@@ -1,15 +1,20 @@
+use crate::codec::CodecId;
 use crate::container::chunktab::{ChunkEntry, read_table_from_slice};
-use crate::container::manifest::Manifest;
-use crate::container::superblock::{FLAG_ENCRYPTED, Superblock};
-use crate::container::tail::{TAIL_LEN, TAIL_MAGIC};
-use crate::crypto::aead::{AeadKey, Region, derive_nonce};
-use crate::error::Result;
+use crate::container::manifest::{FileEntry as ManifestFile, Manifest};
+use crate::container::superblock::{FLAG_CHUNK_AEAD, FLAG_ENCRYPTED, FLAG_PARENT, Superblock};
+use crate::container::tail::{TAIL_LEN, TAIL_MAGIC, TailSummary};
+use crate::crypto::aead::{AeadAlg, AeadKey, Region, derive_nonce};
+use crate::crypto::keyring::region_key;
+use crate::error::{ArxError, Result};
+use crate::read::cache::ChunkCache;
 use crate::util::buf::read_exact_at;
 use std::{
+    borrow::Cow,
+    collections::HashMap,
     fs::File,
-    io::{Read, Seek, SeekFrom},
+    io::{Cursor, Read, Seek, SeekFrom, Write},
     path::Path,
-    sync::Arc,
+    sync::{Arc, Mutex, OnceLock, PoisonError},
 };
 
 #[derive(Clone, Debug)]
@@ -29,32 +34,104 @@ pub struct ChunkView {
     pub c_len: u64,
     pub data_off: u64,
     pub pct_end: f32,
+    /// References to this chunk across every file in the manifest; above 1
+    /// means the chunk is shared (deduplicated).
+    pub refcount: u64,
 }
 
-pub struct Opened {
+/// Anything an archive can be opened from.
+pub trait ReadSeek: Read + Seek {}
+impl<T: Read + Seek> ReadSeek for T {}
+
+/// Where an [`Opened`] archive's bytes are read from.
+pub enum Source {
     /// Thread-safe file handle for lock-free positional reads.
-    pub f: Arc<File>,
+    File(Arc<File>),
+    /// The whole archive, held in memory.
+    Memory(Arc<[u8]>),
+    /// Any other seekable reader; chunk reads serialize on the lock.
+    Reader(Mutex<Box<dyn ReadSeek + Send>>),
+}
+
+pub struct Opened {
+    pub source: Source,
     pub sb: Superblock,
     pub manifest: Manifest,
     pub table: Vec<ChunkEntry>,
     pub aead: Option<(AeadKey, [u8; 32])>,
     pub file_end_for_data: u64,
+    /// Per-chunk reference counts, built by [`Opened::chunk_refcounts`] on first use.
+    refcounts: OnceLock<Vec<u64>>,
+    /// Decoded chunks for the readers; off until [`Opened::set_chunk_cache`].
+    cache: Mutex<ChunkCache>,
+    /// Parent archive set by [`Opened::attach_parent`], with its chunk ids by blake3.
+    #[allow(clippy::type_complexity)]
+    parent: Option<(Box<Opened>, HashMap<[u8; 32], u64>)>,
 }
 
 impl Opened {
     /// `key_salt` is accepted for backward API compatibility but the archive's own
     /// stored `kdf_salt` (from the superblock) is used for nonce derivation.
     pub fn open(path: &Path, aead_key: Option<[u8; 32]>, _key_salt: [u8; 32]) -> Result<Self> {
-        let mut f = File::open(path)?;
-        let file_len = f.metadata()?.len();
+        let f = File::open(path)?;
+        Self::load(f, aead_key, false, |f| Source::File(Arc::new(f)))
+    }
+
+    /// Open an index sidecar written by `PackOptions::emit_index`: the
+    /// archive's bytes up to `data_off`, with no data region. The manifest,
+    /// chunk table, listing and chunk maps are all there; reading chunk data
+    /// fails.
+    pub fn open_index(path: &Path, aead_key: Option<[u8; 32]>) -> Result<Self> {
+        let f = File::open(path)?;
+        Self::load(f, aead_key, true, |f| Source::File(Arc::new(f)))
+    }
+
+    /// Open an archive held entirely in memory, e.g. one written by
+    /// [`pack_to_writer`](crate::pack::writer::pack_to_writer) into a `Cursor<Vec<u8>>`.
+    pub fn from_bytes(bytes: impl Into<Arc<[u8]>>, aead_key: Option<[u8; 32]>) -> Result<Self> {
+        Self::load(Cursor::new(bytes.into()), aead_key, false, |c| {
+            Source::Memory(c.into_inner())
+        })
+    }
+
+    /// Open an archive from any seekable reader. Prefer [`Opened::open`] or
+    /// [`Opened::from_bytes`] when they apply: concurrent chunk reads here
+    /// take turns on one seek position.
+    pub fn from_reader<R: Read + Seek + Send + 'static>(
+        r: R,
+        aead_key: Option<[u8; 32]>,
+    ) -> Result<Self> {
+        Self::load(r, aead_key, false, |r| {
+            Source::Reader(Mutex::new(Box::new(r)))
+        })
+    }
+
+    /// Read and check the front regions from `f`, then keep `f` (as
+    /// `into_source` wraps it) for chunk reads. With `index_only`, `f` must
+    /// end at `data_off` and chunks are not checked against its end.
+    fn load<R: Read + Seek>(
+        mut f: R,
+        aead_key: Option<[u8; 32]>,
+        index_only: bool,
+        into_source: impl FnOnce(R) -> Source,
+    ) -> Result<Self> {
+        let file_len = f.seek(SeekFrom::End(0))?;
+        f.seek(SeekFrom::Start(0))?;
 
         let sb = Superblock::read_from(&mut f)?;
         let header_len = sb.header_len();
         let enc_enabled = (sb.flags & FLAG_ENCRYPTED) != 0;
+        let alg = AeadAlg::from_flags(sb.flags);
+        if index_only && file_len != sb.data_off {
+            return Err(ArxError::Format(format!(
+                "not an archive index: {file_len} bytes, expected data_off {}",
+                sb.data_off
+            )));
+        }
 
         // Detect optional tail
         let mut file_end_for_data = file_len;
-        if file_len >= TAIL_LEN {
+        if !index_only && file_len >= TAIL_LEN {
             f.seek(SeekFrom::End(-(TAIL_LEN as i64)))?;
             let mut magic = [0u8; 8];
             if f.read_exact(&mut magic).is_ok() && magic == TAIL_MAGIC {
@@ -62,10 +139,10 @@ impl Opened {
             }
         }
 
-        // Resolve key
+        // Resolve key; a keyring archive's regions take the data key it unwraps
         let resolved_key: Option<AeadKey> = if enc_enabled {
             if let Some(raw) = aead_key {
-                Some(AeadKey(raw))
+                Some(region_key(&mut f, &sb, AeadKey(raw))?)
             } else {
                 return Err(std::io::Error::new(
                     std::io::ErrorKind::Other,
@@ -73,6 +150,9 @@ impl Opened {
                 )
                 .into());
             }
+        } else if sb.flags & FLAG_CHUNK_AEAD != 0 {
+            // Per-file encryption: the key is only needed for sealed chunks
+            aead_key.map(AeadKey)
         } else {
             None
         };
@@ -93,17 +173,19 @@ impl Opened {
             )
             .into());
         }
+        sb.check_guards(&mut f)?;
         f.seek(SeekFrom::Start(header_len))?;
         let mut mbytes = vec![0u8; sb.manifest_len as usize];
-        f.read_exact(&mut mbytes)?;
-        let manifest_bytes = if let Some(ref key) = resolved_key {
-            let nonce = derive_nonce(&salt, Region::Manifest, 0);
+        f.read_exact(&mut mbytes)
+            .map_err(|e| ArxError::corrupt("manifest", header_len, e))?;
+        let region_key = resolved_key.as_ref().filter(|_| enc_enabled);
+        let manifest_bytes = if let Some(key) = region_key {
+            let nonce = derive_nonce(alg, &salt, Region::Manifest, 0);
             crate::crypto::aead::open_whole(key, &nonce, b"manifest", &mbytes)?
         } else {
             mbytes
         };
-        let manifest: Manifest = ciborium::de::from_reader(&manifest_bytes[..])
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+        let manifest = Manifest::from_region(&manifest_bytes, sb.flags)?;
 
         // Chunk table
         if sb.chunk_table_off > sb.data_off {
@@ -129,19 +211,21 @@ impl Opened {
         }
         f.seek(SeekFrom::Start(sb.chunk_table_off))?;
         let mut tbytes = vec![0u8; table_ct_len as usize];
-        f.read_exact(&mut tbytes)?;
-        let raw_table = if let Some(ref key) = resolved_key {
-            let nonce = derive_nonce(&salt, Region::ChunkTable, 0);
+        f.read_exact(&mut tbytes)
+            .map_err(|e| ArxError::corrupt("chunk table", sb.chunk_table_off, e))?;
+        let raw_table = if let Some(key) = region_key {
+            let nonce = derive_nonce(alg, &salt, Region::ChunkTable, 0);
             crate::crypto::aead::open_whole(key, &nonce, b"chunktab", &tbytes)?
         } else {
             tbytes
         };
         let table = read_table_from_slice(&raw_table, sb.chunk_count)?;
+        manifest.validate_chunk_refs(table.len())?;
 
         // Bounds check
         for (i, ce) in table.iter().enumerate() {
             if ce.data_off < sb.data_off
-                || ce.data_off.saturating_add(ce.c_size) > file_end_for_data
+                || (!index_only && ce.data_off.saturating_add(ce.c_size) > file_end_for_data)
             {
                 return Err(std::io::Error::new(
                     std::io::ErrorKind::InvalidData,
@@ -154,15 +238,43 @@ impl Opened {
         let aead = resolved_key.map(|k| (k, salt));
 
         Ok(Self {
-            f: Arc::new(f),
+            source: into_source(f),
             sb,
             manifest,
             table,
             aead,
             file_end_for_data,
+            refcounts: OnceLock::new(),
+            cache: Mutex::default(),
+            parent: None,
         })
     }
 
+    /// Resolve this differential archive's `parent` chunks against `parent`,
+    /// matching them by blake3. Entries without a hash in `parent` can't be matched.
+    pub fn attach_parent(&mut self, parent: Opened) {
+        let mut ids = HashMap::with_capacity(parent.table.len());
+        for (id, ce) in parent.table.iter().enumerate() {
+            if ce.blake3 != [0u8; 32] {
+                ids.entry(ce.blake3).or_insert(id as u64);
+            }
+        }
+        self.parent = Some((Box::new(parent), ids));
+    }
+
+    /// Whether some chunks live in a parent archive, so reading them needs
+    /// [`Opened::attach_parent`].
+    pub fn needs_parent(&self) -> bool {
+        self.sb.flags & FLAG_PARENT != 0
+    }
+
+    /// Whether chunk `id` is AEAD-sealed, i.e. reading it needs the key.
+    pub fn chunk_sealed(&self, id: u64) -> bool {
+        self.table
+            .get(id as usize)
+            .is_some_and(|ce| ce.is_sealed(self.sb.flags))
+    }
+
     pub fn list_entries(&self) -> impl Iterator<Item = FileEntry> + '_ {
         self.manifest.files.iter().map(|fe| FileEntry {
             path: fe.path.clone(),
@@ -172,6 +284,18 @@ impl Opened {
     }
 
     pub fn chunk_map_for(&self, path: &str) -> Result<Vec<ChunkView>> {
+        self.chunk_map_of(self.file_entry(path)?)
+    }
+
+    /// Chunk map for the `i`th manifest file (the order `list_entries` yields),
+    /// skipping the path lookup.
+    pub fn chunk_map_by_index(&self, i: usize) -> Result<Vec<ChunkView>> {
+        self.chunk_map_of(self.file_entry_by_index(i)?)
+    }
+
+    /// Manifest entry for `path`; hardlinks resolve to their primary file.
+    pub(crate) fn file_entry(&self, path: &str) -> Result<&ManifestFile> {
+        let path = self.manifest.resolve_hardlink(path);
         let fe = self
             .manifest
             .files
@@ -183,51 +307,256 @@ impl Opened {
                     format!("no such file: {path}"),
                 )
             })?;
-        let mut acc = 0u64;
-        let mut out = Vec::with_capacity(fe.chunk_refs.len());
-        for (ord, cref) in fe.chunk_refs.iter().enumerate() {
-            let id = cref.id as usize;
-            if id >= self.table.len() {
-                return Err(std::io::Error::new(
-                    std::io::ErrorKind::InvalidData,
-                    format!(
-                        "manifest chunk id {} out of bounds (table has {} entries)",
-                        cref.id,
-                        self.table.len()
-                    ),
-                )
-                .into());
+        Ok(fe)
+    }
+
+    pub(crate) fn file_entry_by_index(&self, i: usize) -> Result<&ManifestFile> {
+        let fe = self.manifest.files.get(i).ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!(
+                    "no file at index {i} (manifest has {})",
+                    self.manifest.files.len()
+                ),
+            )
+        })?;
+        Ok(fe)
+    }
+
+    /// How many manifest chunk references point at each chunk id, indexed by
+    /// id. Scans every file once and caches the result.
+    pub fn chunk_refcounts(&self) -> &[u64] {
+        self.refcounts.get_or_init(|| {
+            let mut counts = vec![0u64; self.table.len()];
+            for cref in self.manifest.files.iter().flat_map(|fe| &fe.chunk_refs) {
+                if let Some(n) = counts.get_mut(cref.id as usize) {
+                    *n += 1;
+                }
             }
-            let ce = &self.table[id];
-            let end = acc + ce.u_size;
+            counts
+        })
+    }
+
+    /// Table entry of chunk `id`. Manifests are checked against the table on
+    /// open, so an id out of range here means the manifest or table was
+    /// changed since; it is an `ArxError::Format`, never a panic.
+    pub fn chunk_entry(&self, id: u64) -> Result<&ChunkEntry> {
+        self.table.get(id as usize).ok_or_else(|| {
+            ArxError::Format(format!(
+                "chunk id {id} out of range (table has {} entries)",
+                self.table.len()
+            ))
+        })
+    }
+
+    /// `file_off` is the chunk's logical offset, which skips over sparse holes.
+    pub(crate) fn chunk_map_of(&self, fe: &ManifestFile) -> Result<Vec<ChunkView>> {
+        let offsets = fe.chunk_offsets();
+        let mut out = Vec::with_capacity(fe.chunk_refs.len());
+        for (ord, (cref, &file_off)) in fe.chunk_refs.iter().zip(&offsets).enumerate() {
+            let ce = self.chunk_entry(cref.id)?;
+            let end = file_off + ce.u_size;
             let pct_end = (end as f64 / fe.u_size.max(1) as f64) as f32;
             out.push(ChunkView {
                 ordinal: ord as u64,
                 id: cref.id,
                 codec: ce.codec,
-                file_off: acc,
+                file_off,
                 u_len: ce.u_size,
                 c_len: ce.c_size,
                 data_off: ce.data_off,
                 pct_end,
+                refcount: self.chunk_refcounts()[cref.id as usize],
             });
-            acc = end;
         }
         Ok(out)
     }
 
-    /// Read raw (possibly encrypted) chunk bytes at the given offset using a
-    /// lock-free positional read — safe for concurrent callers on the same file.
+    /// Read raw (possibly encrypted) chunk bytes at the given offset. File
+    /// sources use a lock-free positional read — safe for concurrent callers
+    /// on the same file.
     pub fn read_chunk_bytes(&self, data_off: u64, c_size: u64) -> std::io::Result<Vec<u8>> {
         let mut buf = vec![0u8; c_size as usize];
-        read_exact_at(&self.f, &mut buf, data_off)?;
+        match &self.source {
+            Source::File(f) => read_exact_at(f, &mut buf, data_off)?,
+            Source::Memory(bytes) => buf.copy_from_slice(slice_at(bytes, data_off, c_size)?),
+            Source::Reader(r) => {
+                let mut r = r.lock().unwrap_or_else(PoisonError::into_inner);
+                r.seek(SeekFrom::Start(data_off))?;
+                r.read_exact(&mut buf)?;
+            }
+        }
         Ok(buf)
     }
 
+    /// Raw chunk bytes: borrowed from the buffer of an in-memory archive,
+    /// otherwise read like [`Opened::read_chunk_bytes`].
+    pub fn chunk_bytes(&self, data_off: u64, c_size: u64) -> std::io::Result<Cow<'_, [u8]>> {
+        match &self.source {
+            Source::Memory(bytes) => slice_at(bytes, data_off, c_size).map(Cow::Borrowed),
+            _ => self.read_chunk_bytes(data_off, c_size).map(Cow::Owned),
+        }
+    }
+
+    /// Uncompressed bytes of chunk `id`: read from the archive, AEAD-opened
+    /// if sealed, decompressed per its codec, and checked against the table's
+    /// `u_size` and blake3. The one place chunks are decoded for reading.
+    pub fn read_chunk(&self, id: u64) -> Result<Vec<u8>> {
+        let ce = self.chunk_entry(id)?;
+        if ce.parent {
+            return self.read_parent_chunk(id, ce);
+        }
+        check_chunk_size(id, ce)?;
+        let stored = self
+            .chunk_bytes(ce.data_off, ce.c_size)
+            .map_err(|e| ArxError::corrupt("chunk", ce.data_off, e))?;
+        let comp = unseal_chunk(&self.sb, self.aead.as_ref(), id, ce, &stored)?;
+        decode_chunk(id, ce, &comp, self.manifest.meta.zstd_dict.as_deref())
+    }
+
+    /// Write the uncompressed bytes of chunk `id` to `out`; returns the count.
+    /// An unsealed STORE chunk of this archive is copied through `buf` one
+    /// window at a time, so memory stays at `buf.len()` however large the
+    /// chunk; other chunks go through [`Opened::read_chunk`]. The copied
+    /// chunk's hash is checked after its last window, so on a mismatch `out`
+    /// already holds the bad bytes.
+    pub fn write_chunk_to<W: Write + ?Sized>(
+        &self,
+        id: u64,
+        out: &mut W,
+        buf: &mut [u8],
+    ) -> Result<u64> {
+        let ce = match self.direct_store(id) {
+            Some(ce) if !buf.is_empty() => ce,
+            _ => {
+                let plain = self.read_chunk(id)?;
+                out.write_all(&plain)?;
+                return Ok(plain.len() as u64);
+            }
+        };
+        let mut hasher = blake3::Hasher::new();
+        let mut pos = 0u64;
+        while pos < ce.c_size {
+            let n = (ce.c_size - pos).min(buf.len() as u64) as usize;
+            let window = &mut buf[..n];
+            self.read_window(ce.data_off + pos, window)
+                .map_err(|e| ArxError::corrupt("chunk", ce.data_off, e))?;
+            hasher.update(window);
+            out.write_all(window)?;
+            pos += n as u64;
+        }
+        if ce.blake3 != [0u8; 32] && *hasher.finalize().as_bytes() != ce.blake3 {
+            return Err(ArxError::Format(format!(
+                "chunk {id} blake3 mismatch: data corrupted"
+            )));
+        }
+        Ok(pos)
+    }
+
+    /// Chunk `id`'s entry if its stored bytes are its plaintext: an unsealed
+    /// STORE chunk of this archive whose `c_size` matches its `u_size`. Any
+    /// part of such a chunk can be read in place with [`Opened::read_window`];
+    /// the rest (and a mismatched entry, so it reports) go through
+    /// [`Opened::read_chunk`].
+    pub(crate) fn direct_store(&self, id: u64) -> Option<&ChunkEntry> {
+        self.table.get(id as usize).filter(|ce| {
+            !ce.parent
+                && !ce.is_sealed(self.sb.flags)
+                && ce.codec == CodecId::Store as u8
+                && ce.c_size == ce.u_size
+        })
+    }
+
+    /// The tail summary, if the archive has one.
+    pub fn tail(&self) -> Option<TailSummary> {
+        let mut buf = [0u8; TAIL_LEN as usize];
+        self.read_window(self.file_end_for_data, &mut buf).ok()?;
+        TailSummary::read_from(&buf[..]).ok()
+    }
+
+    /// Fill `buf` from archive offset `off`, as [`Opened::read_chunk_bytes`]
+    /// reads, without allocating.
+    pub(crate) fn read_window(&self, off: u64, buf: &mut [u8]) -> std::io::Result<()> {
+        match &self.source {
+            Source::File(f) => read_exact_at(f, buf, off),
+            Source::Memory(bytes) => {
+                buf.copy_from_slice(slice_at(bytes, off, buf.len() as u64)?);
+                Ok(())
+            }
+            Source::Reader(r) => {
+                let mut r = r.lock().unwrap_or_else(PoisonError::into_inner);
+                r.seek(SeekFrom::Start(off))?;
+                r.read_exact(buf)
+            }
+        }
+    }
+
+    /// Keep up to `budget` bytes of decoded chunks for the file and range
+    /// readers, evicting the least recently used, so overlapping reads decode
+    /// each chunk once. 0, the default, turns the cache off.
+    pub fn set_chunk_cache(&self, budget: usize) {
+        self.cache
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .set_budget(budget);
+    }
+
+    /// Whether the chunk cache holds chunk `id`.
+    pub(crate) fn chunk_cached(&self, id: u64) -> bool {
+        self.cache
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .contains(id)
+    }
+
+    /// [`Opened::read_chunk`], served from the chunk cache when it holds `id`.
+    pub fn read_chunk_shared(&self, id: u64) -> Result<Arc<[u8]>> {
+        let cache = || self.cache.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(hit) = cache().get(id) {
+            return Ok(hit);
+        }
+        // Decoded without holding the lock; racing readers may both decode
+        let plain: Arc<[u8]> = self.read_chunk(id)?.into();
+        cache().insert(id, plain.clone());
+        Ok(plain)
+    }
+
+    /// Chunk `id` of a differential archive, read from the attached parent.
+    fn read_parent_chunk(&self, id: u64, ce: &ChunkEntry) -> Result<Vec<u8>> {
+        let (parent, ids) = self.parent.as_ref().ok_or_else(|| {
+            ArxError::Format(format!(
+                "chunk {id} is stored in the parent archive; open it with a parent"
+            ))
+        })?;
+        let pid = ids.get(&ce.blake3).ok_or_else(|| {
+            ArxError::Format(format!("chunk {id} not found in the parent archive"))
+        })?;
+        let plain = parent.read_chunk(*pid)?;
+        if plain.len() as u64 != ce.u_size {
+            return Err(ArxError::Format(format!(
+                "chunk {} u_size mismatch: table says {}, parent has {}",
+                id,
+                ce.u_size,
+                plain.len()
+            )));
+        }
+        Ok(plain)
+    }
+
     pub fn open_reader(&self, path: &str) -> Result<crate::read::stream::FileReader<'_>> {
         crate::read::stream::FileReader::new(self, path)
     }
 
+    pub fn open_reader_by_index(&self, i: usize) -> Result<crate::read::stream::FileReader<'_>> {
+        crate::read::stream::FileReader::new_by_index(self, i)
+    }
+
+    /// Read `len` bytes of `path` from `start`, with `len` clamped to the end
+    /// of the file; [`RangeReader::remaining`] reports how many that leaves.
+    /// A `start` past the file's `u_size` is an `InvalidInput` error, while
+    /// `start == u_size` gives an empty range.
+    ///
+    /// [`RangeReader::remaining`]: crate::read::stream::RangeReader::remaining
     pub fn open_range(
         &self,
         path: &str,
@@ -237,3 +566,128 @@ impl Opened {
         crate::read::stream::RangeReader::new(self, path, start, len)
     }
 }
+
+/// Upper bound on a stored chunk (FastCDC max 2 MiB - 4 KiB, plus codec and AEAD overhead).
+pub(crate) const MAX_CHUNK_SIZE: u64 = 2 * 1024 * 1024;
+
+/// Refuse to allocate for a chunk whose table entry claims an absurd size.
+pub(crate) fn check_chunk_size(id: u64, ce: &ChunkEntry) -> std::io::Result<()> {
+    for (field, size) in [("c_size", ce.c_size), ("u_size", ce.u_size)] {
+        if size > MAX_CHUNK_SIZE {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("chunk {id} {field} {size} exceeds maximum {MAX_CHUNK_SIZE}"),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Decompression sink that refuses to grow past `limit` bytes, so a chunk
+/// that inflates beyond its `u_size` fails before it can exhaust memory.
+struct Bounded {
+    buf: Vec<u8>,
+    limit: usize,
+}
+
+impl Write for Bounded {
+    fn write(&mut self, b: &[u8]) -> std::io::Result<usize> {
+        if self.buf.len() + b.len() > self.limit {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("chunk inflates past its u_size {}", self.limit),
+            ));
+        }
+        self.buf.extend_from_slice(b);
+        Ok(b.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The key for sealed chunk `id`, or an error naming the chunk when there is none.
+pub(crate) fn chunk_key(
+    enc: Option<&(AeadKey, [u8; 32])>,
+    id: u64,
+) -> std::io::Result<&(AeadKey, [u8; 32])> {
+    enc.ok_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::PermissionDenied,
+            format!("chunk {id} is encrypted; key or password required"),
+        )
+    })
+}
+
+/// Compressed plaintext of chunk `id` from its `stored` bytes: opened with
+/// `enc` when the chunk is sealed, borrowed as-is otherwise.
+pub(crate) fn unseal_chunk<'a>(
+    sb: &Superblock,
+    enc: Option<&(AeadKey, [u8; 32])>,
+    id: u64,
+    ce: &ChunkEntry,
+    stored: &'a [u8],
+) -> Result<Cow<'a, [u8]>> {
+    if !ce.is_sealed(sb.flags) {
+        return Ok(Cow::Borrowed(stored));
+    }
+    let (key, salt) = chunk_key(enc, id)?;
+    let nonce = derive_nonce(AeadAlg::from_flags(sb.flags), salt, Region::ChunkData, id);
+    Ok(Cow::Owned(crate::crypto::aead::open_whole(
+        key, &nonce, b"chunk", stored,
+    )?))
+}
+
+/// Decompress chunk `id` and check it against its table entry. v3 entries
+/// (zero hash) only get the size check. `dict` is the archive's zstd
+/// dictionary, for `ZstdDict` chunks.
+pub(crate) fn decode_chunk(
+    id: u64,
+    ce: &ChunkEntry,
+    comp: &[u8],
+    dict: Option<&[u8]>,
+) -> Result<Vec<u8>> {
+    // Frames that say they hold more than the table allows are refused up
+    // front; the bounded sink catches those that don't say
+    let declared = crate::codec::declared_size(ce.codec, comp);
+    if declared > ce.u_size {
+        return Err(crate::error::ArxError::Format(format!(
+            "chunk {id} zstd frame declares {declared} bytes, more than its u_size {}",
+            ce.u_size
+        )));
+    }
+    let mut out = Bounded {
+        buf: Vec::with_capacity(ce.u_size.min(MAX_CHUNK_SIZE) as usize),
+        limit: ce.u_size as usize,
+    };
+    crate::codec::decompress_u8(ce.codec, dict, &mut &comp[..], &mut out)?;
+    let plain = out.buf;
+    if plain.len() as u64 != ce.u_size {
+        return Err(crate::error::ArxError::Format(format!(
+            "chunk {} u_size mismatch: table says {}, decompressed {}",
+            id,
+            ce.u_size,
+            plain.len()
+        )));
+    }
+    if ce.blake3 != [0u8; 32] && *blake3::hash(&plain).as_bytes() != ce.blake3 {
+        return Err(crate::error::ArxError::Format(format!(
+            "chunk {} blake3 mismatch: data corrupted",
+            id
+        )));
+    }
+    Ok(plain)
+}
+
+fn slice_at(bytes: &[u8], data_off: u64, c_size: u64) -> std::io::Result<&[u8]> {
+    let range = usize::try_from(data_off)
+        .ok()
+        .and_then(|start| Some(start..start.checked_add(usize::try_from(c_size).ok()?)?));
+    range.and_then(|r| bytes.get(r)).ok_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::UnexpectedEof,
+            format!("chunk at {data_off}+{c_size} lies past the end of the archive"),
+        )
+    })
+}
@@ -1,3 +1,4 @@
+use std::collections::HashSet;
 use std::path::Path;
 
 use crate::container::superblock::{FLAG_ENCRYPTED, Superblock};
@@ -43,6 +44,61 @@ pub fn compute_stats(archive_path: &Path, aead_key: Option<[u8; 32]>) -> Result<
     })
 }
 
+/// Dedup/compression report for a packed archive (base only, no crud overlay).
+#[derive(Clone, Debug, Default)]
+pub struct ArchiveStats {
+    pub files: u64,
+    /// Chunk references across all files (duplicates counted each time).
+    pub chunk_refs: u64,
+    /// Distinct chunks referenced by the manifest.
+    pub unique_chunks: u64,
+    /// Sum of `FileEntry::u_size`.
+    pub logical_bytes: u64,
+    /// Uncompressed size of the distinct chunks.
+    pub unique_logical_bytes: u64,
+    /// Sum of the distinct chunks' stored `c_size` (includes AEAD tags).
+    pub physical_bytes: u64,
+    /// logical / unique-logical; 1.0 when nothing is shared.
+    pub dedup_ratio: f64,
+    /// unique-logical / physical; above 1.0 when codecs saved space.
+    pub compression_ratio: f64,
+}
+
+/// Compute dedup and compression ratios from the manifest and chunk table.
+pub fn archive_stats(archive_path: &Path, aead_key: Option<[u8; 32]>) -> Result<ArchiveStats> {
+    let opened = Opened::open(archive_path, aead_key, [0u8; 32])?;
+
+    let mut st = ArchiveStats {
+        files: opened.manifest.files.len() as u64,
+        ..Default::default()
+    };
+    let mut seen: HashSet<u64> = HashSet::new();
+    for f in &opened.manifest.files {
+        st.logical_bytes = st.logical_bytes.saturating_add(f.u_size);
+        for r in &f.chunk_refs {
+            st.chunk_refs += 1;
+            if !seen.insert(r.id) {
+                continue;
+            }
+            let ce = opened.chunk_entry(r.id)?;
+            st.unique_chunks += 1;
+            st.unique_logical_bytes = st.unique_logical_bytes.saturating_add(ce.u_size);
+            st.physical_bytes = st.physical_bytes.saturating_add(ce.c_size);
+        }
+    }
+    st.dedup_ratio = ratio(st.logical_bytes, st.unique_logical_bytes);
+    st.compression_ratio = ratio(st.unique_logical_bytes, st.physical_bytes);
+    Ok(st)
+}
+
+fn ratio(num: u64, den: u64) -> f64 {
+    if den == 0 {
+        1.0
+    } else {
+        num as f64 / den as f64
+    }
+}
+
 /// Read only the `encrypted` flag from the archive superblock — cheaper than
 /// opening the full manifest.
 pub fn read_encrypted_flag(archive_path: &Path) -> Result<bool> {
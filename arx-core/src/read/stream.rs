@@ -1,73 +1,125 @@
 use super::opened::Opened;
-use crate::crypto::aead::{Region, derive_nonce};
-use crate::error::Result;
-use blake3;
-use std::io::{Cursor, Read};
+use crate::error::{ArxError, Result};
+use std::io::{Cursor, Read, Seek, SeekFrom};
+use std::ops::Deref;
+use std::sync::Arc;
+
+/// The archive a reader pulls chunks from: borrowed, or shared so the reader
+/// can outlive the caller's `Opened` (e.g. when returned from a function).
+enum ArxRef<'a> {
+    Borrowed(&'a Opened),
+    Shared(Arc<Opened>),
+}
+
+impl Deref for ArxRef<'_> {
+    type Target = Opened;
+
+    fn deref(&self) -> &Opened {
+        match self {
+            ArxRef::Borrowed(o) => o,
+            ArxRef::Shared(o) => o,
+        }
+    }
+}
+
+/// Holes are served as zero-filled segments of at most this many bytes.
+const ZERO_SEGMENT: u64 = 1 << 20;
 
 pub struct FileReader<'a> {
-    arx: &'a Opened,
-    chunk_ids: Vec<u32>,
+    arx: ArxRef<'a>,
+    /// Chunk id per segment; `None` marks a run of zeros (a sparse hole).
+    chunk_ids: Vec<Option<u32>>,
+    /// Logical file offset where each segment starts (same order as `chunk_ids`).
+    chunk_starts: Vec<u64>,
+    size: u64,
     cur: usize,
-    cur_buf: Option<Cursor<Vec<u8>>>,
+    cur_buf: Option<Cursor<Arc<[u8]>>>,
 }
 
 impl<'a> FileReader<'a> {
     pub fn new(arx: &'a Opened, path: &str) -> Result<Self> {
-        let map = arx.chunk_map_for(path)?;
-        Ok(Self {
+        let fe = arx.file_entry(path)?;
+        Ok(Self::from_map(
+            ArxRef::Borrowed(arx),
+            arx.chunk_map_of(fe)?,
+            fe.u_size,
+        ))
+    }
+
+    /// Reader for the `i`th manifest file, without a path lookup.
+    pub fn new_by_index(arx: &'a Opened, i: usize) -> Result<Self> {
+        let fe = arx.file_entry_by_index(i)?;
+        Ok(Self::from_map(
+            ArxRef::Borrowed(arx),
+            arx.chunk_map_of(fe)?,
+            fe.u_size,
+        ))
+    }
+
+    fn from_map(arx: ArxRef<'a>, map: Vec<super::opened::ChunkView>, size: u64) -> Self {
+        let mut chunk_ids = Vec::with_capacity(map.len());
+        let mut chunk_starts = Vec::with_capacity(map.len());
+        let mut pos = 0u64;
+        for v in &map {
+            push_zeros(&mut chunk_ids, &mut chunk_starts, pos, v.file_off);
+            chunk_ids.push(Some(v.id as u32));
+            chunk_starts.push(v.file_off);
+            pos = v.file_off + v.u_len;
+        }
+        push_zeros(&mut chunk_ids, &mut chunk_starts, pos, size);
+        Self {
             arx,
-            chunk_ids: map.into_iter().map(|v| v.id as u32).collect(),
+            chunk_ids,
+            chunk_starts,
+            size,
             cur: 0,
             cur_buf: None,
-        })
+        }
+    }
+
+    /// Total uncompressed length of the file.
+    pub fn len(&self) -> u64 {
+        self.size
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    /// Current logical position within the file.
+    fn position(&self) -> u64 {
+        match self.cur_buf {
+            Some(ref c) => self.chunk_starts[self.cur - 1] + c.position(),
+            None => self
+                .chunk_starts
+                .get(self.cur)
+                .copied()
+                .unwrap_or(self.size),
+        }
     }
 
     fn load_next(&mut self) -> std::io::Result<bool> {
         if self.cur >= self.chunk_ids.len() {
             return Ok(false);
         }
-        let idx = self.chunk_ids[self.cur] as usize;
-        if idx >= self.arx.table.len() {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::InvalidData,
-                format!(
-                    "chunk id {} out of bounds (table has {} entries)",
-                    idx,
-                    self.arx.table.len()
-                ),
-            ));
-        }
-        let ce = &self.arx.table[idx];
-
-        // Lock-free positional read (no Mutex needed)
-        let ct = self.arx.read_chunk_bytes(ce.data_off, ce.c_size)?;
-
-        // AEAD decrypt if enabled
-        let pt = if let Some((ref key, salt)) = self.arx.aead {
-            let nonce = derive_nonce(&salt, Region::ChunkData, idx as u64);
-            crate::crypto::aead::open_whole(key, &nonce, b"chunk", &ct)
-                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string()))?
-        } else {
-            ct
+        let Some(idx) = self.chunk_ids[self.cur] else {
+            let end = self
+                .chunk_starts
+                .get(self.cur + 1)
+                .copied()
+                .unwrap_or(self.size);
+            let len = end - self.chunk_starts[self.cur];
+            self.cur += 1;
+            self.cur_buf = Some(Cursor::new(vec![0u8; len as usize].into()));
+            return Ok(true);
         };
-
-        // Decompress
-        let mut plain = Vec::with_capacity(ce.u_size as usize);
-        crate::codec::get_decoder_u8(ce.codec)
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?
-            .decompress(&mut pt.as_slice(), &mut plain)
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
-
-        // Per-chunk blake3 integrity check (v4+ archives only — v3 entries have zero hash)
-        if ce.blake3 != [0u8; 32] {
-            let actual = *blake3::hash(&plain).as_bytes();
-            if actual != ce.blake3 {
-                return Err(std::io::Error::new(
-                    std::io::ErrorKind::InvalidData,
-                    format!("chunk {} blake3 mismatch: data corrupted", idx),
-                ));
-            }
-        }
+        let plain = self
+            .arx
+            .read_chunk_shared(idx.into())
+            .map_err(|e| match e {
+                ArxError::Io(e) => e,
+                e => std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string()),
+            })?;
 
         self.cur += 1;
         self.cur_buf = Some(Cursor::new(plain));
@@ -75,6 +127,15 @@ impl<'a> FileReader<'a> {
     }
 }
 
+/// Append zero segments covering `from..to` (nothing when `from >= to`).
+fn push_zeros(ids: &mut Vec<Option<u32>>, starts: &mut Vec<u64>, mut from: u64, to: u64) {
+    while from < to {
+        ids.push(None);
+        starts.push(from);
+        from = (from + ZERO_SEGMENT).min(to);
+    }
+}
+
 impl<'a> Read for FileReader<'a> {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
         loop {
@@ -92,56 +153,132 @@ impl<'a> Read for FileReader<'a> {
     }
 }
 
+impl<'a> Seek for FileReader<'a> {
+    /// Jump straight to the chunk containing the target byte; only that chunk
+    /// is loaded. Seeking past EOF is clamped to EOF.
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(n) => Some(n),
+            SeekFrom::End(d) => self.size.checked_add_signed(d),
+            SeekFrom::Current(d) => self.position().checked_add_signed(d),
+        }
+        .ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })?;
+
+        if target >= self.size {
+            self.cur = self.chunk_ids.len();
+            self.cur_buf = None;
+            return Ok(self.size);
+        }
+
+        // Index of the last chunk starting at or before `target`
+        let idx = self.chunk_starts.partition_point(|&s| s <= target) - 1;
+        let in_chunk = target - self.chunk_starts[idx];
+        let already_loaded = self.cur_buf.is_some() && self.cur == idx + 1;
+        if !already_loaded {
+            self.cur = idx;
+            self.cur_buf = None;
+            self.load_next()?;
+        }
+        if let Some(ref mut c) = self.cur_buf {
+            c.set_position(in_chunk);
+        }
+        Ok(target)
+    }
+}
+
+/// A byte range of one file. Where the range covers only part of an unsealed
+/// STORE chunk, those bytes are read from the archive in place, so a few
+/// bytes of a large chunk cost a few bytes of I/O; such partial reads skip
+/// the chunk's blake3 check, which needs the whole chunk. Chunks the range
+/// covers whole, chunks the cache holds and all other chunks are decoded
+/// through the inner [`FileReader`], which checks them.
 pub struct RangeReader<'a> {
     inner: FileReader<'a>,
+    /// Logical file offset of the next byte to return.
+    pos: u64,
     remain: u64,
 }
 
+impl RangeReader<'static> {
+    /// Like [`RangeReader::new`], but keeps the archive alive through `arx`.
+    pub fn new_shared(arx: Arc<Opened>, path: &str, start: u64, len: u64) -> Result<Self> {
+        let fe = arx.file_entry(path)?;
+        let (map, size) = (arx.chunk_map_of(fe)?, fe.u_size);
+        Self::from_reader(
+            FileReader::from_map(ArxRef::Shared(arx), map, size),
+            start,
+            len,
+        )
+    }
+}
+
 impl<'a> RangeReader<'a> {
     pub fn new(arx: &'a Opened, path: &str, start: u64, len: u64) -> Result<Self> {
-        let map = arx.chunk_map_for(path)?;
-
-        // Find which chunk index `start` falls in and the byte offset within that chunk
-        let mut chunk_start_idx = 0usize;
-        let mut offset_in_chunk = start;
-        let chunk_ids: Vec<u32> = map.iter().map(|v| v.id as u32).collect();
-        let mut found_chunk = false;
-
-        for (i, cv) in map.iter().enumerate() {
-            if offset_in_chunk < cv.u_len {
-                chunk_start_idx = i;
-                found_chunk = true;
-                break;
-            }
-            offset_in_chunk -= cv.u_len;
-        }
+        Self::from_reader(FileReader::new(arx, path)?, start, len)
+    }
 
-        // If start > 0 and no chunk contained it, start is past EOF
-        if !found_chunk && start > 0 {
+    /// `start` may be the file size (an empty range) but not beyond it;
+    /// `len` is clamped to the bytes left after `start`.
+    fn from_reader(fr: FileReader<'a>, start: u64, len: u64) -> Result<Self> {
+        let size = fr.len();
+        if start > size {
             return Err(std::io::Error::new(
                 std::io::ErrorKind::InvalidInput,
-                format!("start offset {start} exceeds file size"),
+                format!("start offset {start} is past the end of the file ({size} bytes)"),
             )
             .into());
         }
+        // No seek yet: the first read decides whether `start`'s chunk is
+        // decoded at all
+        Ok(Self {
+            inner: fr,
+            pos: start,
+            remain: len.min(size - start),
+        })
+    }
 
-        // Build a FileReader starting at the right chunk
-        let mut fr = FileReader {
-            arx,
-            chunk_ids,
-            cur: chunk_start_idx,
-            cur_buf: None,
+    /// Fill `buf` straight from the archive when `pos` lies in a direct STORE
+    /// chunk that the inner reader has not already loaded, stopping at the
+    /// chunk's end. `None` when the inner reader should serve `pos` instead,
+    /// which includes a range that reads the chunk from its start to its end
+    /// (so it is verified) and a chunk already in the cache.
+    fn read_direct(&self, buf: &mut [u8]) -> std::io::Result<Option<usize>> {
+        let fr = &self.inner;
+        let idx = fr.chunk_starts.partition_point(|&s| s <= self.pos) - 1;
+        if fr.cur_buf.is_some() && fr.cur == idx + 1 {
+            return Ok(None);
+        }
+        let Some(id) = fr.chunk_ids[idx].map(u64::from) else {
+            return Ok(None);
         };
-
-        // Consume intra-chunk offset bytes
-        if offset_in_chunk > 0 {
-            std::io::copy(&mut (&mut fr).take(offset_in_chunk), &mut std::io::sink())?;
+        let Some(ce) = fr.arx.direct_store(id) else {
+            return Ok(None);
+        };
+        let start = fr.chunk_starts[idx];
+        let end = fr.chunk_starts.get(idx + 1).copied().unwrap_or(fr.size);
+        let in_chunk = self.pos - start;
+        if (in_chunk == 0 && self.remain >= ce.u_size) || fr.arx.chunk_cached(id) {
+            return Ok(None);
         }
+        let n = (end - self.pos)
+            .min(ce.u_size.saturating_sub(in_chunk))
+            .min(buf.len() as u64) as usize;
+        if n == 0 {
+            return Ok(None);
+        }
+        fr.arx.read_window(ce.data_off + in_chunk, &mut buf[..n])?;
+        Ok(Some(n))
+    }
 
-        Ok(Self {
-            inner: fr,
-            remain: len,
-        })
+    /// Bytes left to read; before the first read, the effective length of
+    /// the range.
+    pub fn remaining(&self) -> u64 {
+        self.remain
     }
 }
 
@@ -151,7 +288,16 @@ impl<'a> Read for RangeReader<'a> {
             return Ok(0);
         }
         let cap = std::cmp::min(self.remain, buf.len() as u64) as usize;
-        let n = self.inner.read(&mut buf[..cap])?;
+        let n = match self.read_direct(&mut buf[..cap])? {
+            Some(n) => n,
+            None => {
+                if self.inner.position() != self.pos {
+                    self.inner.seek(SeekFrom::Start(self.pos))?;
+                }
+                self.inner.read(&mut buf[..cap])?
+            }
+        };
+        self.pos += n as u64;
         self.remain -= n as u64;
         Ok(n)
     }
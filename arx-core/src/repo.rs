@@ -14,7 +14,15 @@ pub trait ArchiveRepo: Send + Sync {
 
     fn chunk_map(&self, path: &str) -> Result<Vec<ChunkRow>>;
 
+    /// Chunk map for the `i`th entry of `list_files` (no path lookup).
+    fn chunk_map_by_index(&self, i: usize) -> Result<Vec<ChunkRow>>;
+
     fn open_reader(&self, path: &str) -> Result<Box<dyn Read + Send + '_>>;
 
+    /// Reader for the `i`th entry of `list_files` (no path lookup).
+    fn open_reader_by_index(&self, i: usize) -> Result<Box<dyn Read + Send + '_>>;
+
+    /// Reader over `len` bytes from `start`; `len = u64::MAX` reads to EOF.
+    /// Seeks straight to the chunk holding `start` rather than reading up to it.
     fn open_range(&self, path: &str, start: u64, len: u64) -> Result<Box<dyn Read + Send + '_>>;
 }
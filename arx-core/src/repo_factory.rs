@@ -1,13 +1,27 @@
 use crate::error::Result;
 use crate::repo::{ArchiveRepo, OpenParams};
 use crate::repo_fs::FsArchiveRepo;
+#[cfg(feature = "http")]
+use crate::repo_http::HttpArchiveRepo;
+use crate::repo_mem::MemArchiveRepo;
 
 pub enum Backend {
     Fs,
+    /// Archive bytes already in memory; `OpenParams::archive_path` is ignored.
+    Memory(Vec<u8>),
+    /// Archive served at `url`, read by HTTP range requests;
+    /// `OpenParams::archive_path` is ignored.
+    #[cfg(feature = "http")]
+    Http {
+        url: String,
+    },
 }
 
 pub fn open_repo(backend: Backend, p: OpenParams) -> Result<Box<dyn ArchiveRepo>> {
     match backend {
         Backend::Fs => Ok(Box::new(FsArchiveRepo::new(p)?)),
+        Backend::Memory(bytes) => Ok(Box::new(MemArchiveRepo::new(bytes, p.aead_key)?)),
+        #[cfg(feature = "http")]
+        Backend::Http { url } => Ok(Box::new(HttpArchiveRepo::new(&url, p.aead_key)?)),
     }
 }
@@ -1,9 +1,10 @@
 use std::io::Read;
 use std::sync::Arc;
 
+use crate::container::superblock::{FLAG_CHUNK_AEAD, FLAG_ENCRYPTED};
 use crate::domain::{ChunkRow, FileRow};
 use crate::error::Result;
-use crate::read::opened::Opened;
+use crate::read::opened::{ChunkView, Opened};
 use crate::repo::{ArchiveRepo, OpenParams};
 
 pub struct FsArchiveRepo {
@@ -21,34 +22,15 @@ impl FsArchiveRepo {
 
 impl ArchiveRepo for FsArchiveRepo {
     fn list_files(&self) -> Result<Vec<FileRow>> {
-        let enc = self.opened.aead.is_some();
-        let rows = self
-            .opened
-            .list_entries()
-            .map(|e| FileRow {
-                path: e.path,
-                u_size: e.u_size,
-                chunks: e.chunks.len(),
-                encrypted: enc,
-            })
-            .collect();
-        Ok(rows)
+        Ok(file_rows(&self.opened))
     }
 
     fn chunk_map(&self, path: &str) -> Result<Vec<ChunkRow>> {
-        let v = self.opened.chunk_map_for(path)?;
-        Ok(v.into_iter()
-            .map(|r| ChunkRow {
-                ordinal: r.ordinal,
-                id: r.id,
-                codec: r.codec,
-                file_off: r.file_off,
-                u_len: r.u_len,
-                c_len: r.c_len,
-                data_off: r.data_off,
-                pct_end: r.pct_end,
-            })
-            .collect())
+        Ok(to_rows(self.opened.chunk_map_for(path)?))
+    }
+
+    fn chunk_map_by_index(&self, i: usize) -> Result<Vec<ChunkRow>> {
+        Ok(to_rows(self.opened.chunk_map_by_index(i)?))
     }
 
     fn open_reader(&self, path: &str) -> Result<Box<dyn Read + Send + '_>> {
@@ -57,8 +39,44 @@ impl ArchiveRepo for FsArchiveRepo {
         Ok(Box::new(r))
     }
 
+    fn open_reader_by_index(&self, i: usize) -> Result<Box<dyn Read + Send + '_>> {
+        let r = self.opened.open_reader_by_index(i)?;
+        Ok(Box::new(r))
+    }
+
     fn open_range(&self, path: &str, start: u64, len: u64) -> Result<Box<dyn Read + Send + '_>> {
         let r = self.opened.open_range(path, start, len)?;
         Ok(Box::new(r))
     }
 }
+
+/// One row per file; a file is encrypted when the whole archive is, or when
+/// any of its chunks is sealed on its own.
+pub(crate) fn file_rows(opened: &Opened) -> Vec<FileRow> {
+    let enc = opened.sb.flags & (FLAG_ENCRYPTED | FLAG_CHUNK_AEAD) == FLAG_ENCRYPTED;
+    opened
+        .list_entries()
+        .map(|e| FileRow {
+            path: e.path,
+            u_size: e.u_size,
+            chunks: e.chunks.len(),
+            encrypted: enc || e.chunks.iter().any(|&id| opened.chunk_sealed(id.into())),
+        })
+        .collect()
+}
+
+pub(crate) fn to_rows(v: Vec<ChunkView>) -> Vec<ChunkRow> {
+    v.into_iter()
+        .map(|r| ChunkRow {
+            ordinal: r.ordinal,
+            id: r.id,
+            codec: r.codec,
+            file_off: r.file_off,
+            u_len: r.u_len,
+            c_len: r.c_len,
+            data_off: r.data_off,
+            pct_end: r.pct_end,
+            refcount: r.refcount,
+        })
+        .collect()
+}
@@ -0,0 +1,177 @@
+use std::io::{self, Read, Seek, SeekFrom};
+use std::sync::Arc;
+
+use crate::domain::{ChunkRow, FileRow};
+use crate::error::Result;
+use crate::read::opened::Opened;
+use crate::repo::ArchiveRepo;
+use crate::repo_fs::{file_rows, to_rows};
+
+/// Smallest range fetched per request. Opening an archive reads the
+/// superblock field by field; this keeps that to one round trip.
+const READ_AHEAD: u64 = 256 * 1024;
+
+/// A remote archive read through HTTP `Range` requests. Keeps the last
+/// fetched range, so small sequential reads don't each cost a request.
+pub struct HttpSource {
+    agent: ureq::Agent,
+    url: String,
+    len: u64,
+    pos: u64,
+    block: Vec<u8>,
+    block_off: u64,
+}
+
+impl HttpSource {
+    /// Probe `url` with a first range request, which also yields the archive
+    /// length. Fails if the server answers without honouring the range.
+    pub fn open(url: &str) -> io::Result<Self> {
+        let agent = ureq::Agent::new();
+        let (block, len) = fetch(&agent, url, 0, READ_AHEAD - 1)?;
+        Ok(Self {
+            agent,
+            url: url.to_string(),
+            len,
+            pos: 0,
+            block,
+            block_off: 0,
+        })
+    }
+
+    /// Archive length, from the server's `Content-Range`.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+/// GET bytes `start..=end` of `url`; returns them with the total length.
+fn fetch(agent: &ureq::Agent, url: &str, start: u64, end: u64) -> io::Result<(Vec<u8>, u64)> {
+    let resp = match agent
+        .get(url)
+        .set("Range", &format!("bytes={start}-{end}"))
+        .call()
+    {
+        Ok(resp) => resp,
+        Err(ureq::Error::Status(416, _)) => {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("{url}: range starting at {start} lies past the end"),
+            ));
+        }
+        Err(e) => return Err(io::Error::other(format!("GET {url}: {e}"))),
+    };
+    if resp.status() != 206 {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!(
+                "{url}: server answered a range request with status {} instead of 206; \
+                 HTTP archives need range support",
+                resp.status()
+            ),
+        ));
+    }
+    // "bytes 0-99/12345"
+    let total = resp
+        .header("Content-Range")
+        .and_then(|v| v.rsplit_once('/'))
+        .and_then(|(_, total)| total.trim().parse::<u64>().ok())
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{url}: 206 response without a usable Content-Range"),
+            )
+        })?;
+    let want = end.min(total.saturating_sub(1)).saturating_sub(start) + 1;
+    let mut body = Vec::with_capacity(want as usize);
+    resp.into_reader().take(want).read_to_end(&mut body)?;
+    if body.len() as u64 != want {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            format!(
+                "{url}: short range response at {start}: {} of {want} bytes",
+                body.len()
+            ),
+        ));
+    }
+    Ok((body, total))
+}
+
+impl Read for HttpSource {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos >= self.len || buf.is_empty() {
+            return Ok(0);
+        }
+        let cached = self.block_off..self.block_off + self.block.len() as u64;
+        if !cached.contains(&self.pos) {
+            let end = (self.pos + (buf.len() as u64).max(READ_AHEAD)).min(self.len) - 1;
+            let (block, _) = fetch(&self.agent, &self.url, self.pos, end)?;
+            self.block = block;
+            self.block_off = self.pos;
+        }
+        let from = (self.pos - self.block_off) as usize;
+        let n = buf.len().min(self.block.len() - from);
+        buf[..n].copy_from_slice(&self.block[from..from + n]);
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl Seek for HttpSource {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(n) => Some(n),
+            SeekFrom::End(d) => self.len.checked_add_signed(d),
+            SeekFrom::Current(d) => self.pos.checked_add_signed(d),
+        };
+        self.pos = target.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "seek before start of archive")
+        })?;
+        Ok(self.pos)
+    }
+}
+
+/// An archive served over HTTP. The front regions are read once on open;
+/// chunks are fetched by range as files are read, so streaming one file out
+/// of a large remote archive downloads little more than that file.
+pub struct HttpArchiveRepo {
+    opened: Arc<Opened>,
+}
+
+impl HttpArchiveRepo {
+    pub fn new(url: &str, aead_key: Option<[u8; 32]>) -> Result<Self> {
+        let opened = Opened::from_reader(HttpSource::open(url)?, aead_key)?;
+        Ok(Self {
+            opened: Arc::new(opened),
+        })
+    }
+}
+
+impl ArchiveRepo for HttpArchiveRepo {
+    fn list_files(&self) -> Result<Vec<FileRow>> {
+        Ok(file_rows(&self.opened))
+    }
+
+    fn chunk_map(&self, path: &str) -> Result<Vec<ChunkRow>> {
+        Ok(to_rows(self.opened.chunk_map_for(path)?))
+    }
+
+    fn chunk_map_by_index(&self, i: usize) -> Result<Vec<ChunkRow>> {
+        Ok(to_rows(self.opened.chunk_map_by_index(i)?))
+    }
+
+    fn open_reader(&self, path: &str) -> Result<Box<dyn Read + Send + '_>> {
+        Ok(Box::new(self.opened.open_reader(path)?))
+    }
+
+    fn open_reader_by_index(&self, i: usize) -> Result<Box<dyn Read + Send + '_>> {
+        Ok(Box::new(self.opened.open_reader_by_index(i)?))
+    }
+
+    fn open_range(&self, path: &str, start: u64, len: u64) -> Result<Box<dyn Read + Send + '_>> {
+        Ok(Box::new(self.opened.open_range(path, start, len)?))
+    }
+}
@@ -0,0 +1,74 @@
+use std::io::{Cursor, Read};
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::container::superblock::Superblock;
+use crate::crypto::kdf;
+use crate::domain::{ChunkRow, FileRow};
+use crate::error::Result;
+use crate::pack::writer::{PackOptions, pack_to_writer};
+use crate::read::opened::Opened;
+use crate::repo::ArchiveRepo;
+use crate::repo_fs::{file_rows, to_rows};
+
+/// An archive served entirely from RAM, for tests and for embedding ARX
+/// without touching the filesystem.
+pub struct MemArchiveRepo {
+    opened: Arc<Opened>,
+}
+
+impl MemArchiveRepo {
+    /// Serve the archive whose bytes are `bytes`.
+    pub fn new(bytes: Vec<u8>, aead_key: Option<[u8; 32]>) -> Result<Self> {
+        let opened = Opened::from_bytes(bytes, aead_key)?;
+        Ok(Self {
+            opened: Arc::new(opened),
+        })
+    }
+
+    /// Pack `inputs` straight into memory and serve the result.
+    pub fn pack(inputs: &[&Path], opts: Option<&PackOptions>) -> Result<Self> {
+        let mut buf = Cursor::new(Vec::new());
+        pack_to_writer(inputs, &mut buf, opts)?;
+        let bytes = buf.into_inner();
+        let key = match opts {
+            Some(PackOptions {
+                aead_key: Some(k), ..
+            }) => Some(*k),
+            Some(PackOptions {
+                password: Some(pw), ..
+            }) => {
+                let sb = Superblock::read_from(bytes.as_slice())?;
+                Some(kdf::derive_key(pw, &sb.kdf_salt))
+            }
+            _ => None,
+        };
+        Self::new(bytes, key)
+    }
+}
+
+impl ArchiveRepo for MemArchiveRepo {
+    fn list_files(&self) -> Result<Vec<FileRow>> {
+        Ok(file_rows(&self.opened))
+    }
+
+    fn chunk_map(&self, path: &str) -> Result<Vec<ChunkRow>> {
+        Ok(to_rows(self.opened.chunk_map_for(path)?))
+    }
+
+    fn chunk_map_by_index(&self, i: usize) -> Result<Vec<ChunkRow>> {
+        Ok(to_rows(self.opened.chunk_map_by_index(i)?))
+    }
+
+    fn open_reader(&self, path: &str) -> Result<Box<dyn Read + Send + '_>> {
+        Ok(Box::new(self.opened.open_reader(path)?))
+    }
+
+    fn open_reader_by_index(&self, i: usize) -> Result<Box<dyn Read + Send + '_>> {
+        Ok(Box::new(self.opened.open_reader_by_index(i)?))
+    }
+
+    fn open_range(&self, path: &str, start: u64, len: u64) -> Result<Box<dyn Read + Send + '_>> {
+        Ok(Box::new(self.opened.open_range(path, start, len)?))
+    }
+}
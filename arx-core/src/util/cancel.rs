@@ -0,0 +1,12 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::error::{ArxError, Result};
+
+/// The `"cancelled"` error once `cancel` (`ExtractOptions::cancel` or
+/// `PackOptions::cancel`) is set.
+pub(crate) fn check_cancel(cancel: Option<&AtomicBool>) -> Result<()> {
+    if cancel.is_some_and(|c| c.load(Ordering::Relaxed)) {
+        return Err(ArxError::Format("cancelled".into()));
+    }
+    Ok(())
+}
@@ -22,9 +22,11 @@ impl<'a, W: Write> HashingForward<'a, W> {
 
 impl<'a, W: Write> Write for HashingForward<'a, W> {
     fn write(&mut self, buf: &[u8]) -> Result<usize> {
-        self.hasher.update(buf);
-        self.counted_c += buf.len() as u64;
-        self.inner.write(buf)
+        // Only what the inner writer took; the caller retries the rest
+        let k = self.inner.write(buf)?;
+        self.hasher.update(&buf[..k]);
+        self.counted_c += k as u64;
+        Ok(k)
     }
     fn flush(&mut self) -> Result<()> {
         self.inner.flush()
@@ -11,6 +11,25 @@ pub fn safe_join(root: &Path, rel: &str) -> Result<PathBuf> {
     Ok(root.join(p))
 }
 
+/// Normalize an archive path before it is written to the manifest: `.` and
+/// empty segments are dropped, while absolute paths and `..` components are
+/// rejected. The empty path (a single-file input) is kept as is. CRUD paths
+/// and tar members come through here too, after dropping a leading `/`.
+pub fn sanitize_entry_path(rel: &str) -> Result<String> {
+    if Path::new(rel).is_absolute() || rel.starts_with('/') {
+        return Err(ArxError::Format(format!("unsafe path: {rel}")));
+    }
+    let mut parts = Vec::new();
+    for seg in rel.split('/') {
+        match seg {
+            "" | "." => {}
+            ".." => return Err(ArxError::Format(format!("unsafe path: {rel}"))),
+            s => parts.push(s),
+        }
+    }
+    Ok(parts.join("/"))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -36,4 +55,18 @@ mod tests {
         assert!(safe_join(root, "foo/../../../etc/passwd").is_err());
         assert!(safe_join(root, "..").is_err());
     }
+
+    #[test]
+    fn test_sanitize_entry_path_normalizes() {
+        assert_eq!(sanitize_entry_path("a/./b//c").unwrap(), "a/b/c");
+        assert_eq!(sanitize_entry_path("./a/").unwrap(), "a");
+        assert_eq!(sanitize_entry_path("").unwrap(), "");
+    }
+
+    #[test]
+    fn test_sanitize_entry_path_rejects_escapes() {
+        assert!(sanitize_entry_path("/etc/passwd").is_err());
+        assert!(sanitize_entry_path("..").is_err());
+        assert!(sanitize_entry_path("a/../../b").is_err());
+    }
 }
@@ -91,6 +91,6 @@ mod tests {
     #[test]
     fn test_clean_eof_returns_none() {
         let buf: &[u8] = &[];
-        assert!(read_uvarint(&mut buf.as_ref()).unwrap().is_none());
+        assert!(read_uvarint(&mut &buf[..]).unwrap().is_none());
     }
 }
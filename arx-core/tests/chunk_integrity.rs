@@ -1,6 +1,9 @@
 /// Chunk integrity tests: verify that per-chunk blake3 mismatch and AEAD
 /// tag failures are detected during extraction and verification.
-use arx_core::read::extract::{ExtractOptions, extract, verify};
+use arx_core::read::extract::{
+    ExtractOptions, VerifyOptions, VerifyReport, extract, fix_tail, verify, verify_quick,
+    verify_report, verify_with,
+};
 use arx_core::{PackOptions, pack};
 use std::fs::{self, OpenOptions};
 use std::io::{Seek, SeekFrom, Write};
@@ -102,3 +105,708 @@ fn test_verify_detects_corruption() {
     let result = verify(&archive, None);
     assert!(result.is_err(), "verify should fail on corrupted archive");
 }
+
+#[test]
+fn test_verify_modes_on_clean_encrypted_archive() {
+    let src = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+
+    let big: Vec<u8> = (0u32..300_000).flat_map(|i| i.to_le_bytes()).collect();
+    fs::write(src.path().join("big.bin"), &big).unwrap();
+    let archive = tmp.path().join("enc_verify.arx");
+    let key = [0x3Cu8; 32];
+    pack(
+        &[src.path()],
+        &archive,
+        Some(&PackOptions {
+            aead_key: Some(key),
+            ..Default::default()
+        }),
+    )
+    .unwrap();
+
+    let opts = ExtractOptions {
+        aead_key: Some(key),
+        ..Default::default()
+    };
+    verify_with(
+        &archive,
+        Some(&opts),
+        &VerifyOptions {
+            deep: false,
+            ..Default::default()
+        },
+    )
+    .expect("quick verify should pass");
+    verify_with(
+        &archive,
+        Some(&opts),
+        &VerifyOptions {
+            deep: true,
+            ..Default::default()
+        },
+    )
+    .expect("deep verify should pass");
+}
+
+#[test]
+fn test_deep_verify_catches_bad_u_size() {
+    use arx_core::container::superblock::Superblock;
+    use arx_core::container::tail::TAIL_LEN;
+    use std::io::Read;
+
+    let src = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+    fs::write(src.path().join("data.bin"), vec![0x5Au8; 8192]).unwrap();
+    let archive = tmp.path().join("u_size.arx");
+    pack(&[src.path()], &archive, None).unwrap();
+
+    let sb = {
+        let mut f = fs::File::open(&archive).unwrap();
+        Superblock::read_from(&mut f).unwrap()
+    };
+    let mut f = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(&archive)
+        .unwrap();
+
+    // Bump chunk 0's u_size in the (plaintext) table...
+    let mut table = vec![0u8; (sb.data_off - sb.chunk_table_off) as usize];
+    f.seek(SeekFrom::Start(sb.chunk_table_off)).unwrap();
+    f.read_exact(&mut table).unwrap();
+    let u = u64::from_le_bytes(table[8..16].try_into().unwrap());
+    table[8..16].copy_from_slice(&(u + 1).to_le_bytes());
+    f.seek(SeekFrom::Start(sb.chunk_table_off)).unwrap();
+    f.write_all(&table).unwrap();
+
+    // ...and make the tail agree, so only decompression can notice.
+    let tail_off = f.seek(SeekFrom::End(0)).unwrap() - TAIL_LEN;
+    f.seek(SeekFrom::Start(tail_off + 8 + 32)).unwrap();
+    f.write_all(blake3::hash(&table).as_bytes()).unwrap();
+    let total_u_off = tail_off + 8 + 96;
+    let mut buf8 = [0u8; 8];
+    f.seek(SeekFrom::Start(total_u_off)).unwrap();
+    f.read_exact(&mut buf8).unwrap();
+    let total_u = u64::from_le_bytes(buf8) + 1;
+    f.seek(SeekFrom::Start(total_u_off)).unwrap();
+    f.write_all(&total_u.to_le_bytes()).unwrap();
+    drop(f);
+
+    verify(&archive, None).expect("quick verify only checks the tail");
+    let deep = verify_with(
+        &archive,
+        None,
+        &VerifyOptions {
+            deep: true,
+            ..Default::default()
+        },
+    );
+    assert!(
+        deep.is_err(),
+        "deep verify should catch the u_size mismatch"
+    );
+}
+
+#[test]
+fn test_fix_tail_repairs_corrupt_and_missing_tail() {
+    use arx_core::container::tail::TAIL_LEN;
+
+    let src = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+    fs::write(src.path().join("data.bin"), vec![0x7Eu8; 4096]).unwrap();
+    fs::write(src.path().join("other.txt"), b"other").unwrap();
+    let archive = tmp.path().join("tail.arx");
+    let key = [0x99u8; 32];
+    pack(
+        &[src.path()],
+        &archive,
+        Some(&PackOptions {
+            aead_key: Some(key),
+            ..Default::default()
+        }),
+    )
+    .unwrap();
+    let opts = ExtractOptions {
+        aead_key: Some(key),
+        ..Default::default()
+    };
+    let good_len = fs::metadata(&archive).unwrap().len();
+
+    // Corrupt the data hash inside the tail
+    let mut f = OpenOptions::new().write(true).open(&archive).unwrap();
+    f.seek(SeekFrom::Start(good_len - TAIL_LEN + 8 + 64))
+        .unwrap();
+    f.write_all(&[0u8; 32]).unwrap();
+    drop(f);
+    assert!(verify(&archive, Some(&opts)).is_err());
+
+    fix_tail(&archive, Some(&opts)).expect("fix_tail failed");
+    verify(&archive, Some(&opts)).expect("verify should pass after fix_tail");
+    assert_eq!(fs::metadata(&archive).unwrap().len(), good_len);
+
+    // Drop the tail entirely
+    let f = OpenOptions::new().write(true).open(&archive).unwrap();
+    f.set_len(good_len - TAIL_LEN).unwrap();
+    drop(f);
+    assert!(verify(&archive, Some(&opts)).is_err());
+
+    fix_tail(&archive, Some(&opts)).expect("fix_tail failed");
+    verify(&archive, Some(&opts)).expect("verify should pass after re-adding tail");
+}
+
+/// A zstd chunk swapped for a frame that declares far more than its `u_size`
+/// (padded to the same `c_size` with a skippable frame) is refused before it
+/// is decoded.
+#[cfg(feature = "zstd")]
+#[test]
+fn test_zstd_chunk_declaring_more_than_u_size_is_refused() {
+    use arx_core::codec::CodecId;
+    use arx_core::error::ArxError;
+    use arx_core::read::opened::Opened;
+
+    let src = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+    let dst = TempDir::new().unwrap();
+    // Four bits per byte: zstd wins, but the frame stays a few KiB
+    let mut x = 1u64;
+    let data: Vec<u8> = (0..8192)
+        .map(|_| {
+            x = x
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (x >> 60) as u8
+        })
+        .collect();
+    fs::write(src.path().join("victim.bin"), &data).unwrap();
+    let archive = tmp.path().join("bomb.arx");
+    pack(&[src.path()], &archive, None).unwrap();
+
+    let ce = {
+        let arx = Opened::open(&archive, None, [0u8; 32]).unwrap();
+        assert_eq!(arx.table.len(), 1);
+        arx.table[0]
+    };
+    assert_eq!(ce.codec, CodecId::Zstd as u8);
+
+    // 16 MiB of zeros in a frame that records its content size
+    let bomb = zstd::bulk::compress(&vec![0u8; 16 << 20], 3).unwrap();
+    let pad = ce.c_size as usize - bomb.len();
+    assert!(pad >= 8, "bomb frame {} vs chunk {}", bomb.len(), ce.c_size);
+    let mut stored = bomb;
+    stored.extend_from_slice(&0x184D_2A50u32.to_le_bytes());
+    stored.extend_from_slice(&(pad as u32 - 8).to_le_bytes());
+    stored.resize(ce.c_size as usize, 0);
+
+    let mut f = OpenOptions::new().write(true).open(&archive).unwrap();
+    f.seek(SeekFrom::Start(ce.data_off)).unwrap();
+    f.write_all(&stored).unwrap();
+    drop(f);
+
+    let arx = Opened::open(&archive, None, [0u8; 32]).unwrap();
+    match arx.read_chunk(0) {
+        Err(ArxError::Format(msg)) => {
+            assert!(msg.contains("declares 16777216 bytes"), "{msg}")
+        }
+        other => panic!("expected Format error, got {:?}", other.map(|v| v.len())),
+    }
+    assert!(extract(&archive, dst.path(), None).is_err());
+}
+
+#[test]
+fn test_out_of_range_chunk_ref_is_format_error() {
+    use arx_core::container::manifest::Manifest;
+    use arx_core::container::superblock::Superblock;
+    use arx_core::error::ArxError;
+    use arx_core::read::opened::Opened;
+    use std::io::Read;
+
+    let src = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+    let dst = TempDir::new().unwrap();
+    fs::write(src.path().join("victim.txt"), b"only one chunk").unwrap();
+    let archive = tmp.path().join("badref.arx");
+    let opts = PackOptions {
+        deterministic: true,
+        ..Default::default()
+    };
+    pack(&[src.path()], &archive, Some(&opts)).unwrap();
+
+    // Rewrite the (plaintext) manifest so the sole chunk ref points past the table
+    let mut f = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(&archive)
+        .unwrap();
+    let sb = Superblock::read_from(&mut f).unwrap();
+    let mut mbytes = vec![0u8; sb.manifest_len as usize];
+    f.seek(SeekFrom::Start(sb.header_len())).unwrap();
+    f.read_exact(&mut mbytes).unwrap();
+    let mut manifest = Manifest::from_cbor(&mbytes).unwrap();
+    assert_eq!(manifest.files[0].chunk_refs[0].id, 0);
+    manifest.files[0].chunk_refs[0].id = 5; // still a one-byte CBOR uint
+    let mut patched = Vec::new();
+    ciborium::ser::into_writer(&manifest, &mut patched).unwrap();
+    assert_eq!(patched.len(), mbytes.len());
+    f.seek(SeekFrom::Start(sb.header_len())).unwrap();
+    f.write_all(&patched).unwrap();
+    drop(f);
+
+    let expect_format = |r: arx_core::Result<()>| match r {
+        Err(ArxError::Format(msg)) => {
+            assert!(msg.contains("victim.txt"), "{msg}");
+            assert!(msg.contains("chunk id 5"), "{msg}");
+        }
+        other => panic!("expected Format error, got {other:?}"),
+    };
+    expect_format(extract(&archive, dst.path(), None));
+    expect_format(arx_core::list(&archive, None));
+    expect_format(Opened::open(&archive, None, [0u8; 32]).map(|_| ()));
+}
+
+/// A manifest changed after open (its fields are public) must not make the
+/// readers index past the chunk table.
+#[test]
+fn test_out_of_range_chunk_ref_after_open_is_format_error() {
+    use arx_core::error::ArxError;
+    use arx_core::index::inmem::InMemIndex;
+    use arx_core::read::layout::layout;
+    use arx_core::read::opened::Opened;
+
+    let src = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+    fs::write(src.path().join("victim.txt"), b"only one chunk").unwrap();
+    let archive = tmp.path().join("ref.arx");
+    pack(&[src.path()], &archive, None).unwrap();
+    let mut arx = Opened::open(&archive, None, [0u8; 32]).unwrap();
+    arx.manifest.files[0].chunk_refs[0].id = 99;
+
+    let expect_format = |r: arx_core::Result<()>| match r {
+        Err(ArxError::Format(msg)) => assert!(msg.contains("chunk id 99 out of range"), "{msg}"),
+        other => panic!("expected Format error, got {other:?}"),
+    };
+    expect_format(arx.read_chunk(99).map(|_| ()));
+    expect_format(arx.chunk_map_for("victim.txt").map(|_| ()));
+    expect_format(arx.open_reader("victim.txt").map(|_| ()));
+    expect_format(arx.open_range("victim.txt", 0, 1).map(|_| ()));
+    expect_format(layout(&arx).map(|_| ()));
+    expect_format(InMemIndex::from_base(&arx).map(|_| ()));
+}
+
+#[test]
+fn test_file_dir_path_conflict_is_format_error() {
+    use arx_core::container::manifest::Manifest;
+    use arx_core::container::superblock::Superblock;
+    use arx_core::error::ArxError;
+    use std::io::Read;
+
+    let src = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+    let dst = TempDir::new().unwrap();
+    fs::write(src.path().join("foo"), b"a file named foo").unwrap();
+    fs::create_dir(src.path().join("fop")).unwrap();
+    let archive = tmp.path().join("conflict.arx");
+    let opts = PackOptions {
+        deterministic: true,
+        ..Default::default()
+    };
+    pack(&[src.path()], &archive, Some(&opts)).unwrap();
+
+    // Rename directory `fop` to `foo` in the manifest (same encoded length)
+    let mut f = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(&archive)
+        .unwrap();
+    let sb = Superblock::read_from(&mut f).unwrap();
+    let mut mbytes = vec![0u8; sb.manifest_len as usize];
+    f.seek(SeekFrom::Start(sb.header_len())).unwrap();
+    f.read_exact(&mut mbytes).unwrap();
+    let mut manifest = Manifest::from_cbor(&mbytes).unwrap();
+    let d = manifest.dirs.iter_mut().find(|d| d.path == "fop").unwrap();
+    d.path = "foo".into();
+    let mut patched = Vec::new();
+    ciborium::ser::into_writer(&manifest, &mut patched).unwrap();
+    assert_eq!(patched.len(), mbytes.len());
+    f.seek(SeekFrom::Start(sb.header_len())).unwrap();
+    f.write_all(&patched).unwrap();
+    drop(f);
+
+    match extract(&archive, dst.path(), None) {
+        Err(ArxError::Format(msg)) => {
+            assert!(msg.contains("foo is both a directory and a file"), "{msg}")
+        }
+        other => panic!("expected Format error, got {other:?}"),
+    }
+    // Rejected before anything was written
+    assert!(!dst.path().join("foo").exists());
+}
+
+fn flip_byte_at(archive_path: &std::path::Path, off: u64) {
+    use std::io::Read;
+    let mut f = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(archive_path)
+        .unwrap();
+    f.seek(SeekFrom::Start(off)).unwrap();
+    let mut buf = [0u8; 1];
+    f.read_exact(&mut buf).unwrap();
+    buf[0] ^= 0xFF;
+    f.seek(SeekFrom::Start(off)).unwrap();
+    f.write_all(&buf).unwrap();
+}
+
+#[test]
+fn test_verify_report_names_the_corrupt_region() {
+    use arx_core::container::superblock::Superblock;
+
+    let src = TempDir::new().unwrap();
+    fs::write(src.path().join("a.txt"), b"alpha alpha alpha\n").unwrap();
+    fs::write(src.path().join("b.bin"), vec![0x5Au8; 8192]).unwrap();
+
+    let all_ok = VerifyReport {
+        manifest_ok: true,
+        chunktab_ok: true,
+        data_ok: true,
+        total_u_ok: true,
+        total_c_ok: true,
+        bad_chunks: vec![],
+        coverage: vec![],
+    };
+    // (what to flip, expected report)
+    let cases: [(&str, VerifyReport); 5] = [
+        (
+            "manifest",
+            VerifyReport {
+                manifest_ok: false,
+                ..all_ok.clone()
+            },
+        ),
+        (
+            "chunk table",
+            VerifyReport {
+                chunktab_ok: false,
+                ..all_ok.clone()
+            },
+        ),
+        (
+            "data",
+            VerifyReport {
+                data_ok: false,
+                bad_chunks: vec![0],
+                ..all_ok.clone()
+            },
+        ),
+        (
+            "total_u",
+            VerifyReport {
+                total_u_ok: false,
+                ..all_ok.clone()
+            },
+        ),
+        (
+            "total_c",
+            VerifyReport {
+                total_c_ok: false,
+                ..all_ok.clone()
+            },
+        ),
+    ];
+
+    for (region, want) in cases {
+        let tmp = TempDir::new().unwrap();
+        let archive = tmp.path().join("report.arx");
+        pack(&[src.path()], &archive, None).unwrap();
+        let report = verify_report(&archive, None, &VerifyOptions::default()).unwrap();
+        assert!(report.is_ok(), "clean archive: {report:?}");
+
+        let sb = Superblock::read_from(fs::File::open(&archive).unwrap()).unwrap();
+        let len = fs::metadata(&archive).unwrap().len();
+        let off = match region {
+            "manifest" => sb.header_len() + sb.manifest_len / 2,
+            // Inside entry 0's blake3, which the fast pass never compares
+            "chunk table" => sb.chunk_table_off + 40,
+            "data" => sb.data_off + 4,
+            "total_u" => len - 16,
+            _ => len - 8,
+        };
+        flip_byte_at(&archive, off);
+
+        let report = verify_report(&archive, None, &VerifyOptions::default()).unwrap();
+        let outcome = VerifyReport {
+            coverage: vec![],
+            ..report.clone()
+        };
+        assert_eq!(outcome, want, "flipped {region}");
+        assert!(verify(&archive, None).is_err(), "flipped {region}");
+        assert!(
+            report.failures().iter().any(|f| f == region),
+            "flipped {region}: {:?}",
+            report.failures()
+        );
+    }
+}
+
+#[test]
+fn test_verify_report_flags_missing_chunk_hashes() {
+    use arx_core::container::superblock::Superblock;
+    use std::io::Read;
+
+    let src = TempDir::new().unwrap();
+    fs::write(src.path().join("a.txt"), b"alpha alpha alpha\n").unwrap();
+    fs::write(src.path().join("b.bin"), vec![0x5Au8; 8192]).unwrap();
+    let tmp = TempDir::new().unwrap();
+    let archive = tmp.path().join("nohash.arx");
+    pack(&[src.path()], &archive, None).unwrap();
+
+    let deep = VerifyOptions {
+        deep: true,
+        ..Default::default()
+    };
+    let report = verify_report(&archive, None, &deep).unwrap();
+    assert!(report.is_ok());
+    let check = |r: &VerifyReport, name: &str| {
+        r.coverage
+            .iter()
+            .find(|c| c.name == name)
+            .unwrap_or_else(|| panic!("no {name} check"))
+            .skipped
+            .clone()
+    };
+    assert_eq!(check(&report, "per-chunk hashes"), None);
+    assert!(check(&report, "authentication").is_some(), "unencrypted");
+
+    // Zero every entry's blake3, as in a v3-era table, and re-seal the tail
+    let sb = Superblock::read_from(fs::File::open(&archive).unwrap()).unwrap();
+    let mut f = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(&archive)
+        .unwrap();
+    let mut table = vec![0u8; (sb.data_off - sb.chunk_table_off) as usize];
+    f.seek(SeekFrom::Start(sb.chunk_table_off)).unwrap();
+    f.read_exact(&mut table).unwrap();
+    for entry in table.chunks_mut(64) {
+        entry[32..64].fill(0);
+    }
+    f.seek(SeekFrom::Start(sb.chunk_table_off)).unwrap();
+    f.write_all(&table).unwrap();
+    drop(f);
+    fix_tail(&archive, None).unwrap();
+
+    let report = verify_report(&archive, None, &deep).unwrap();
+    assert!(report.is_ok(), "nothing is corrupt: {report:?}");
+    let why = check(&report, "per-chunk hashes").expect("check should be skipped");
+    assert!(why.contains("chunk-level corruption undetectable"), "{why}");
+    assert!(
+        report
+            .skipped()
+            .iter()
+            .any(|s| s.starts_with("per-chunk hashes:")),
+        "{:?}",
+        report.skipped()
+    );
+}
+
+#[test]
+fn test_parallel_verify_matches_sequential() {
+    let src = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+
+    // Well past one batch of chunks, with content that won't dedupe
+    let mut state = 0x9E37_79B9u32;
+    let big: Vec<u8> = (0..24 * 1024 * 1024)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        })
+        .collect();
+    fs::write(src.path().join("big.bin"), &big).unwrap();
+    let archive = tmp.path().join("parallel.arx");
+    pack(&[src.path()], &archive, None).unwrap();
+
+    let report = |sequential: bool| {
+        let vopts = VerifyOptions {
+            deep: true,
+            sequential,
+        };
+        verify_report(&archive, None, &vopts).unwrap()
+    };
+    let parallel = report(false);
+    assert!(parallel.is_ok(), "{parallel:?}");
+    assert_eq!(parallel, report(true));
+
+    corrupt_byte_in_data_region(&archive);
+    let parallel = report(false);
+    assert_eq!(parallel.bad_chunks, vec![0]);
+    assert!(!parallel.data_ok);
+    assert_eq!(parallel, report(true));
+}
+
+#[test]
+fn test_extract_preflight_rejects_tail_mismatch_before_writing() {
+    use arx_core::container::tail::TAIL_LEN;
+
+    let src = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+    // Incompressible, so the data region is far longer than the cut below
+    let mut x = 5u64;
+    let noise: Vec<u8> = (0..100_000)
+        .map(|_| {
+            x = x
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (x >> 56) as u8
+        })
+        .collect();
+    fs::write(src.path().join("a.bin"), &noise[..50_000]).unwrap();
+    fs::write(src.path().join("b.bin"), &noise[50_000..]).unwrap();
+    let archive = tmp.path().join("good.arx");
+    pack(&[src.path()], &archive, None).unwrap();
+    let bytes = fs::read(&archive).unwrap();
+    let (body, tail) = bytes.split_at(bytes.len() - TAIL_LEN as usize);
+
+    let expect_refused = |name: &str, content: Vec<u8>, needle: &str| {
+        let path = tmp.path().join(name);
+        fs::write(&path, content).unwrap();
+        let dst = TempDir::new().unwrap();
+        let err = extract(&path, dst.path(), None).expect_err(name);
+        assert!(err.to_string().contains(needle), "{name}: {err}");
+        assert_eq!(
+            fs::read_dir(dst.path()).unwrap().count(),
+            0,
+            "{name}: nothing should be written"
+        );
+    };
+
+    // Data region cut short, tail kept
+    expect_refused(
+        "cut.arx",
+        [&body[..body.len() - 100], tail].concat(),
+        "out of bounds",
+    );
+
+    // Stray bytes between the last chunk and the tail
+    expect_refused(
+        "padded.arx",
+        [body, &[0u8; 100][..], tail].concat(),
+        "truncated or corrupt",
+    );
+
+    // Tail total_u no longer matches the table
+    let mut bad_total = bytes.clone();
+    let total_u_at = bad_total.len() - 16;
+    bad_total[total_u_at] ^= 0x01;
+    expect_refused("total_u.arx", bad_total, "total_u");
+}
+
+#[test]
+fn test_verify_chunks_names_corrupt_chunk_before_writing_it() {
+    use arx_core::codec::CodecId;
+    use arx_core::error::ArxError;
+    use arx_core::read::extract::extract_file_to;
+    use arx_core::read::opened::Opened;
+
+    let src = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+    // Incompressible-looking bytes, stored as-is so the default path streams them
+    let data: Vec<u8> = (0u32..200_000)
+        .flat_map(|i| i.wrapping_mul(2_654_435_761).to_le_bytes())
+        .collect();
+    fs::write(src.path().join("big.bin"), &data).unwrap();
+    let archive = tmp.path().join("store.arx");
+    let opts = PackOptions {
+        codec: Some(CodecId::Store),
+        chunk_avg: 64 * 1024,
+        ..Default::default()
+    };
+    pack(&[src.path()], &archive, Some(&opts)).unwrap();
+
+    let map = Opened::open(&archive, None, [0u8; 32])
+        .unwrap()
+        .chunk_map_for("big.bin")
+        .unwrap();
+    assert!(map.len() > 3, "want several chunks, got {}", map.len());
+    let bad = &map[2];
+    flip_byte_at(&archive, bad.data_off + 10);
+
+    let xopts = ExtractOptions {
+        verify_chunks: true,
+        ..Default::default()
+    };
+    let mut out = Vec::new();
+    match extract_file_to(&archive, "big.bin", &mut out, Some(&xopts)) {
+        Err(ArxError::Format(msg)) => {
+            let want = format!("big.bin: chunk 2 of {} (id {})", map.len(), bad.id);
+            assert!(msg.contains(&want), "{msg}");
+            assert!(msg.contains("blake3 mismatch"), "{msg}");
+        }
+        other => panic!("expected Format error, got {other:?}"),
+    }
+    // Only the chunks before the bad one reached the writer
+    assert_eq!(out.len() as u64, bad.file_off);
+    assert_eq!(out, data[..bad.file_off as usize]);
+
+    // The default streaming path also fails, but after writing the bad chunk
+    let mut out = Vec::new();
+    assert!(extract_file_to(&archive, "big.bin", &mut out, None).is_err());
+    assert_eq!(out.len() as u64, bad.file_off + bad.u_len);
+}
+
+/// Quick verify catches layout damage but, hashing nothing, passes a flipped
+/// payload byte that full verify rejects.
+#[test]
+fn test_verify_quick_checks_geometry_not_payloads() {
+    use arx_core::container::superblock::Superblock;
+
+    let src = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+    let data: Vec<u8> = (0u32..100_000).flat_map(|i| i.to_le_bytes()).collect();
+    fs::write(src.path().join("data.bin"), &data).unwrap();
+    let archive = tmp.path().join("a.arx");
+    let opts = PackOptions {
+        chunk_avg: 64 * 1024,
+        ..Default::default()
+    };
+    pack(&[src.path()], &archive, Some(&opts)).unwrap();
+    verify_quick(&archive, None).unwrap();
+    let sb = Superblock::read_from(&mut fs::File::open(&archive).unwrap()).unwrap();
+
+    // Data-corrupt: same layout, wrong bytes
+    let data_corrupt = tmp.path().join("data.arx");
+    fs::copy(&archive, &data_corrupt).unwrap();
+    flip_byte_at(&data_corrupt, sb.data_off + 10);
+    verify_quick(&data_corrupt, None).unwrap();
+    assert!(verify(&data_corrupt, None).is_err());
+
+    // Geometry-corrupt: one stray byte grows the data region
+    let grown = tmp.path().join("grown.arx");
+    let mut bytes = fs::read(&archive).unwrap();
+    bytes.insert(sb.data_off as usize + 10, 0);
+    fs::write(&grown, &bytes).unwrap();
+    let err = verify_quick(&grown, None).unwrap_err();
+    assert!(err.to_string().contains("data region"), "{err}");
+    assert!(verify(&grown, None).is_err());
+
+    // Geometry-corrupt: chunk_count no longer matches the table
+    let miscounted = tmp.path().join("count.arx");
+    fs::copy(&archive, &miscounted).unwrap();
+    let mut bad = sb;
+    bad.chunk_count += 1;
+    bad.write_to(OpenOptions::new().write(true).open(&miscounted).unwrap())
+        .unwrap();
+    let err = verify_quick(&miscounted, None).unwrap_err();
+    assert!(err.to_string().contains("chunk_count"), "{err}");
+
+    // No tail at all
+    let tailless = tmp.path().join("tailless.arx");
+    fs::write(
+        &tailless,
+        &fs::read(&archive).unwrap()[..sb.data_off as usize + 8],
+    )
+    .unwrap();
+    assert!(verify_quick(&tailless, None).is_err());
+}
@@ -1,5 +1,6 @@
 /// CRUD overlay integration tests.
 use arx_core::crud::CrudArchive;
+use arx_core::policy::Policy;
 use arx_core::read::extract::extract;
 use std::fs;
 use std::io::Read;
@@ -18,6 +19,29 @@ fn issue(out: &std::path::Path) {
     .expect("issue_archive failed");
 }
 
+#[test]
+fn test_issue_records_metadata_in_manifest() {
+    use arx_core::read::opened::Opened;
+
+    let tmp = TempDir::new().unwrap();
+    let archive = tmp.path().join("base.arx");
+    issue(&archive);
+
+    let opened = Opened::open(&archive, None, [0u8; 32]).unwrap();
+    let meta = &opened.manifest.meta;
+    assert_eq!(meta.label.as_deref(), Some("test"));
+    assert_eq!(meta.owner.as_deref(), Some("tester"));
+    assert_eq!(meta.notes.as_deref(), Some("integration test"));
+
+    // Metadata lives in `Meta` only, not in a file beside the entries
+    assert!(
+        opened.manifest.files.is_empty(),
+        "{:?}",
+        opened.manifest.files
+    );
+    assert!(arx_core::list_entries(&archive, None).unwrap().is_empty());
+}
+
 #[test]
 fn test_put_and_ls() {
     let tmp = TempDir::new().unwrap();
@@ -178,3 +202,602 @@ fn test_diff_shows_changes() {
     assert_eq!(added.len(), 1, "should have one added file");
     assert_eq!(added[0].path, "new_file.txt");
 }
+
+#[test]
+fn test_leading_slash_paths_are_the_same_entry() {
+    let tmp = TempDir::new().unwrap();
+    let archive = tmp.path().join("base.arx");
+    issue(&archive);
+
+    let first = tmp.path().join("first.txt");
+    let second = tmp.path().join("second.txt");
+    fs::write(&first, b"first").unwrap();
+    fs::write(&second, b"second").unwrap();
+
+    let mut arc = CrudArchive::open(&archive).unwrap();
+    arc.put_file(&first, "/a/b", 0o644, 1000).unwrap();
+    arc.put_file(&second, "a/b", 0o644, 1001).unwrap();
+
+    // One entry, stored without the slash, holding the later content
+    let keys: Vec<&String> = arc.index.by_path.keys().collect();
+    assert_eq!(keys, ["a/b"]);
+    for path in ["a/b", "/a/b", "./a//b"] {
+        let mut buf = Vec::new();
+        arc.open_reader(path)
+            .unwrap()
+            .read_to_end(&mut buf)
+            .unwrap();
+        assert_eq!(buf, b"second", "reading {path}");
+    }
+
+    arc.rename("/a/b", "c").unwrap();
+    assert!(arc.index.by_path.contains_key("c"));
+    assert!(arc.put_file(&first, "../x", 0o644, 0).is_err());
+    drop(arc);
+
+    // Sync agrees with the overlay
+    CrudArchive::sync_to_base(&archive, None, true, 0.05, None, [0u8; 32], false).unwrap();
+    let dst = TempDir::new().unwrap();
+    extract(&archive, dst.path(), None).unwrap();
+    assert_eq!(fs::read(dst.path().join("c")).unwrap(), b"second");
+    assert!(!dst.path().join("a/b").exists());
+
+    let mut arc = CrudArchive::open(&archive).unwrap();
+    arc.delete_path("/c").unwrap();
+    assert!(arc.index.by_path.is_empty());
+}
+
+#[test]
+fn test_replay_normalizes_paths_from_old_journals() {
+    use arx_core::container::journal::LogRecord;
+
+    let tmp = TempDir::new().unwrap();
+    let archive = tmp.path().join("base.arx");
+    issue(&archive);
+    let src = tmp.path().join("a.txt");
+    fs::write(&src, b"old journal").unwrap();
+
+    // Journals from before paths were normalized kept them as given
+    let mut arc = CrudArchive::open(&archive).unwrap();
+    arc.put_file(&src, "a.txt", 0o644, 1000).unwrap();
+    let Some(LogRecord::Put { chunks, .. }) = arc.journal_records().unwrap().pop() else {
+        panic!("expected a put record");
+    };
+    for path in ["/old/a.txt", "/old//b.txt"] {
+        arc.journal
+            .append(&LogRecord::Put {
+                path: path.into(),
+                mode: 0o644,
+                mtime: 1000,
+                size: 11,
+                chunks: chunks.clone(),
+            })
+            .unwrap();
+    }
+    arc.journal
+        .append(&LogRecord::Delete {
+            path: "/a.txt".into(),
+        })
+        .unwrap();
+    drop(arc);
+
+    let mut arc = CrudArchive::open(&archive).unwrap();
+    let keys: Vec<&String> = arc.index.by_path.keys().collect();
+    assert_eq!(keys, ["old/a.txt", "old/b.txt"]);
+    let mut buf = Vec::new();
+    arc.open_reader("/old/a.txt")
+        .unwrap()
+        .read_to_end(&mut buf)
+        .unwrap();
+    assert_eq!(buf, b"old journal");
+    arc.delete_path("/old/b.txt").unwrap();
+    assert!(!arc.index.by_path.contains_key("old/b.txt"));
+}
+
+#[test]
+fn test_put_dedups_unchanged_chunks() {
+    let tmp = TempDir::new().unwrap();
+    let archive = tmp.path().join("base.arx");
+    issue(&archive);
+
+    // 20 MB of incompressible data so every new chunk costs its full size
+    let mut state = 0x9E37_79B9_7F4A_7C15u64;
+    let mut content: Vec<u8> = (0..20 * 1024 * 1024)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .collect();
+    let src = tmp.path().join("big.bin");
+    fs::write(&src, &content).unwrap();
+
+    let mut arc = CrudArchive::open(&archive).unwrap();
+    arc.put_file(&src, "big.bin", 0o644, 1).unwrap();
+    let after_first = fs::metadata(&arc.delta_path).unwrap().len();
+    assert!(after_first >= content.len() as u64);
+
+    // Overwrite one 4 KiB block in the middle
+    content[10 * 1024 * 1024..10 * 1024 * 1024 + 4096].fill(0xAB);
+    fs::write(&src, &content).unwrap();
+    arc.put_file(&src, "big.bin", 0o644, 2).unwrap();
+    let grown = fs::metadata(&arc.delta_path).unwrap().len() - after_first;
+    assert!(
+        grown < content.len() as u64 / 10,
+        "second put added {grown} bytes for a 4 KiB edit"
+    );
+
+    let mut buf = Vec::new();
+    arc.open_reader("big.bin")
+        .unwrap()
+        .read_to_end(&mut buf)
+        .unwrap();
+    assert!(buf == content, "modified file must read back intact");
+}
+
+#[test]
+fn test_put_reuses_base_chunks() {
+    let tmp = TempDir::new().unwrap();
+    let archive = tmp.path().join("base.arx");
+    issue(&archive);
+
+    let src = tmp.path().join("a.txt");
+    fs::write(&src, b"shared content between base and overlay").unwrap();
+    {
+        let mut arc = CrudArchive::open(&archive).unwrap();
+        arc.put_file(&src, "a.txt", 0o644, 1).unwrap();
+    }
+    CrudArchive::sync_to_base(&archive, None, true, 0.05, None, [0u8; 32], false).unwrap();
+
+    // Same bytes under a new name: served from the base chunk, nothing appended
+    let mut arc = CrudArchive::open(&archive).unwrap();
+    let before = fs::metadata(&arc.delta_path).map(|m| m.len()).unwrap_or(0);
+    arc.put_file(&src, "b.txt", 0o644, 2).unwrap();
+    let after = fs::metadata(&arc.delta_path).map(|m| m.len()).unwrap_or(0);
+    assert_eq!(before, after);
+
+    let mut buf = Vec::new();
+    arc.open_reader("b.txt")
+        .unwrap()
+        .read_to_end(&mut buf)
+        .unwrap();
+    assert_eq!(buf, b"shared content between base and overlay");
+    assert!(
+        arc.diff()
+            .iter()
+            .any(|d| d.kind == "A" && d.path == "b.txt")
+    );
+}
+
+#[test]
+fn test_durable_journal_put_survives_reopen() {
+    let tmp = TempDir::new().unwrap();
+    let archive = tmp.path().join("base.arx");
+    issue(&archive);
+    let src = tmp.path().join("durable.txt");
+    fs::write(&src, b"synced to disk").unwrap();
+
+    let mut arc = CrudArchive::open(&archive).unwrap();
+    arc.journal.set_durable(true);
+    arc.put_file(&src, "durable.txt", 0o644, 1000).unwrap();
+    arc.delete_path("durable.txt").unwrap();
+    arc.put_file(&src, "again.txt", 0o644, 1000).unwrap();
+    drop(arc);
+
+    let arc = CrudArchive::open(&archive).unwrap();
+    assert!(!arc.index.by_path.contains_key("durable.txt"));
+    let mut buf = Vec::new();
+    arc.open_reader("again.txt")
+        .unwrap()
+        .read_to_end(&mut buf)
+        .unwrap();
+    assert_eq!(buf, b"synced to disk");
+}
+
+fn noise(len: usize) -> Vec<u8> {
+    let mut state = 0x2545_F491u32;
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        })
+        .collect()
+}
+
+fn expect_policy_error(r: arx_core::Result<()>, limit: &str) {
+    match r {
+        Err(arx_core::error::ArxError::Format(msg)) => assert!(msg.contains(limit), "{msg}"),
+        other => panic!("expected a {limit} error, got {other:?}"),
+    }
+}
+
+#[test]
+fn test_policy_max_entries_rejects_new_paths() {
+    let tmp = TempDir::new().unwrap();
+    let archive = tmp.path().join("base.arx");
+    issue(&archive);
+    let src = tmp.path().join("f.txt");
+    fs::write(&src, b"one entry").unwrap();
+
+    let mut arc = CrudArchive::open(&archive).unwrap();
+    arc.set_policy(Policy {
+        max_entries: Some(1),
+        ..Default::default()
+    })
+    .unwrap();
+    arc.put_file(&src, "a.txt", 0o644, 1000).unwrap();
+    // Replacing an existing path doesn't add an entry
+    arc.put_file(&src, "a.txt", 0o644, 2000).unwrap();
+    expect_policy_error(arc.put_file(&src, "b.txt", 0o644, 1000), "max_entries");
+    drop(arc);
+
+    // The policy is journaled, so it still applies after reopening
+    let mut arc = CrudArchive::open(&archive).unwrap();
+    expect_policy_error(arc.put_file(&src, "b.txt", 0o644, 1000), "max_entries");
+    assert!(!arc.index.by_path.contains_key("b.txt"));
+}
+
+#[test]
+fn test_policy_max_delta_bytes_leaves_rejected_frames_to_compaction() {
+    let tmp = TempDir::new().unwrap();
+    let archive = tmp.path().join("base.arx");
+    issue(&archive);
+    let small = tmp.path().join("small.bin");
+    let other = tmp.path().join("other.bin");
+    let big = tmp.path().join("big.bin");
+    fs::write(&small, noise(100)).unwrap();
+    fs::write(&other, noise(200)).unwrap();
+    fs::write(&big, noise(64 * 1024)).unwrap();
+
+    let mut arc = CrudArchive::open(&archive).unwrap();
+    arc.set_policy(Policy {
+        max_delta_bytes: Some(4096),
+        ..Default::default()
+    })
+    .unwrap();
+    arc.put_file(&small, "small.bin", 0o644, 1000).unwrap();
+    let delta_len = fs::metadata(&arc.delta_path).unwrap().len();
+
+    expect_policy_error(
+        arc.put_file(&big, "big.bin", 0o644, 1000),
+        "max_delta_bytes",
+    );
+    assert!(!arc.index.by_path.contains_key("big.bin"));
+    // The frames it wrote stay behind, unreferenced
+    let orphaned = fs::metadata(&arc.delta_path).unwrap().len();
+    assert!(orphaned > delta_len);
+
+    // Compaction drops them; later puts and earlier files still read back
+    arc.set_policy(Policy::default()).unwrap();
+    arc.put_file(&other, "other.bin", 0o644, 1000).unwrap();
+    let (_, after) = arc.compact_delta().unwrap();
+    assert!(after < orphaned, "{after}");
+    for (path, want) in [("small.bin", noise(100)), ("other.bin", noise(200))] {
+        let mut buf = Vec::new();
+        arc.open_reader(path)
+            .unwrap()
+            .read_to_end(&mut buf)
+            .unwrap();
+        assert_eq!(buf, want, "{path}");
+    }
+}
+
+#[test]
+fn test_policy_min_compression_ratio_rejects_incompressible_puts() {
+    let tmp = TempDir::new().unwrap();
+    let archive = tmp.path().join("base.arx");
+    issue(&archive);
+    let src = tmp.path().join("random.bin");
+    fs::write(&src, noise(32 * 1024)).unwrap();
+
+    let mut arc = CrudArchive::open(&archive).unwrap();
+    arc.set_policy(Policy {
+        min_compression_ratio: Some(1.05),
+        ..Default::default()
+    })
+    .unwrap();
+    expect_policy_error(
+        arc.put_file(&src, "random.bin", 0o644, 1000),
+        "min_compression_ratio",
+    );
+    assert!(arc.index.by_path.is_empty());
+    // Its frames are unreferenced, so compaction drops them all
+    arc.compact_delta().unwrap();
+    assert_eq!(fs::metadata(&arc.delta_path).unwrap().len(), 0);
+}
+
+#[test]
+fn test_policy_max_uncompressed_counts_replaced_files_once() {
+    let tmp = TempDir::new().unwrap();
+    let archive = tmp.path().join("base.arx");
+    issue(&archive);
+    let (a, b) = (tmp.path().join("a"), tmp.path().join("b"));
+    fs::write(&a, vec![b'a'; 80]).unwrap();
+    fs::write(&b, vec![b'b'; 30]).unwrap();
+
+    let mut arc = CrudArchive::open(&archive).unwrap();
+    arc.set_policy(Policy {
+        max_uncompressed: Some(100),
+        ..Default::default()
+    })
+    .unwrap();
+    arc.put_file(&a, "a", 0o644, 1000).unwrap();
+    expect_policy_error(arc.put_file(&b, "b", 0o644, 1000), "max_uncompressed");
+    // 80 bytes replaced by 30 leaves room
+    arc.put_file(&b, "a", 0o644, 2000).unwrap();
+    arc.put_file(&b, "b", 0o644, 2000).unwrap();
+    // and replacing it back counts only the new 80, over the 30 it replaces
+    expect_policy_error(arc.put_file(&a, "a", 0o644, 3000), "max_uncompressed");
+}
+
+fn read_all(arc: &CrudArchive, path: &str) -> Vec<u8> {
+    let mut buf = Vec::new();
+    arc.open_reader(path)
+        .unwrap()
+        .read_to_end(&mut buf)
+        .unwrap();
+    buf
+}
+
+#[test]
+fn test_compact_delta_drops_overwritten_frames() {
+    let tmp = TempDir::new().unwrap();
+    let archive = tmp.path().join("base.arx");
+    issue(&archive);
+    let src = tmp.path().join("v.bin");
+    let keep = tmp.path().join("keep.txt");
+    fs::write(&keep, b"untouched by the overwrites").unwrap();
+
+    let mut arc = CrudArchive::open(&archive).unwrap();
+    arc.put_file(&keep, "keep.txt", 0o644, 1000).unwrap();
+    for i in 0..10u32 {
+        let mut data = noise(16 * 1024);
+        data.extend_from_slice(&i.to_le_bytes());
+        fs::write(&src, &data).unwrap();
+        arc.put_file(&src, "v.bin", 0o644, 1000 + i as u64).unwrap();
+    }
+    arc.put_file(&keep, "copy.txt", 0o644, 1000).unwrap();
+    arc.delete_path("keep.txt").unwrap();
+    let latest = fs::read(&src).unwrap();
+
+    let before = fs::metadata(&arc.delta_path).unwrap().len();
+    let (reported_before, after) = arc.compact_delta().unwrap();
+    assert_eq!(reported_before, before);
+    assert_eq!(fs::metadata(&arc.delta_path).unwrap().len(), after);
+    assert!(after * 5 < before, "delta {before} -> {after}");
+    assert_eq!(read_all(&arc, "v.bin"), latest);
+    assert_eq!(read_all(&arc, "copy.txt"), b"untouched by the overwrites");
+
+    // Puts after the compaction append to the new delta, and a reopen
+    // replays the relocation
+    arc.put_file(&keep, "keep.txt", 0o644, 2000).unwrap();
+    drop(arc);
+    let arc = CrudArchive::open(&archive).unwrap();
+    assert_eq!(read_all(&arc, "v.bin"), latest);
+    assert_eq!(read_all(&arc, "keep.txt"), b"untouched by the overwrites");
+    assert!(!archive.with_extension("arx.delta.gc").exists());
+}
+
+#[test]
+fn test_compact_delta_reseals_encrypted_frames() {
+    let tmp = TempDir::new().unwrap();
+    let archive = tmp.path().join("base.arx");
+    issue(&archive);
+    let src = tmp.path().join("secret.bin");
+    let (key, salt) = ([0x42u8; 32], [0x17u8; 32]);
+
+    let mut arc = CrudArchive::open_with_crypto(&archive, Some(key), salt).unwrap();
+    for i in 0..4u8 {
+        fs::write(&src, vec![i; 8192]).unwrap();
+        arc.put_file(&src, "secret.bin", 0o600, 1000).unwrap();
+    }
+    let (before, after) = arc.compact_delta().unwrap();
+    assert!(after < before, "{before} -> {after}");
+    assert_eq!(read_all(&arc, "secret.bin"), vec![3u8; 8192]);
+    drop(arc);
+
+    let arc = CrudArchive::open_with_crypto(&archive, Some(key), salt).unwrap();
+    assert_eq!(read_all(&arc, "secret.bin"), vec![3u8; 8192]);
+}
+
+#[test]
+fn test_uncommitted_compaction_is_discarded_on_open() {
+    let tmp = TempDir::new().unwrap();
+    let archive = tmp.path().join("base.arx");
+    issue(&archive);
+    let src = tmp.path().join("a.txt");
+    fs::write(&src, b"still here").unwrap();
+    let mut arc = CrudArchive::open(&archive).unwrap();
+    arc.put_file(&src, "a.txt", 0o644, 1000).unwrap();
+    drop(arc);
+
+    // A compaction that died before its journal record
+    let gc = archive.with_extension("arx.delta.gc");
+    fs::write(&gc, b"partial").unwrap();
+    let arc = CrudArchive::open(&archive).unwrap();
+    assert!(!gc.exists());
+    assert_eq!(read_all(&arc, "a.txt"), b"still here");
+}
+
+#[test]
+fn test_open_reader_streams_interleaved_base_and_delta_chunks() {
+    use arx_core::container::journal::Loc;
+
+    let tmp = TempDir::new().unwrap();
+    let archive = tmp.path().join("base.arx");
+    issue(&archive);
+    let src = tmp.path().join("big.bin");
+    let original = noise(3 * 1024 * 1024);
+    fs::write(&src, &original).unwrap();
+    {
+        let mut arc = CrudArchive::open(&archive).unwrap();
+        arc.put_file(&src, "big.bin", 0o644, 1).unwrap();
+    }
+    CrudArchive::sync_to_base(&archive, None, true, 0.05, None, [0u8; 32], false).unwrap();
+
+    // Edit one region in the middle: chunking resyncs around it, so the
+    // overlay file mixes base chunks with new delta chunks
+    let mut edited = original.clone();
+    edited[1_500_000..1_500_100].fill(0xEE);
+    fs::write(&src, &edited).unwrap();
+    let mut arc = CrudArchive::open(&archive).unwrap();
+    arc.put_file(&src, "big.bin", 0o644, 2).unwrap();
+    let locs: Vec<Loc> = arc.index.by_path["big.bin"]
+        .chunks
+        .iter()
+        .map(|c| c.loc)
+        .collect();
+    assert!(locs.contains(&Loc::Delta), "{locs:?}");
+    assert_eq!(locs.first(), Some(&Loc::Base));
+    assert_eq!(locs.last(), Some(&Loc::Base));
+
+    // Odd-sized reads cross every chunk boundary
+    let mut r = arc.open_reader("big.bin").unwrap();
+    let mut out = Vec::new();
+    let mut buf = [0u8; 4093];
+    loop {
+        let n = r.read(&mut buf).unwrap();
+        if n == 0 {
+            break;
+        }
+        out.extend_from_slice(&buf[..n]);
+    }
+    assert_eq!(out, edited);
+}
+
+#[test]
+fn test_journal_records_in_order() {
+    use arx_core::container::journal::LogRecord;
+
+    let tmp = TempDir::new().unwrap();
+    let archive = tmp.path().join("base.arx");
+    issue(&archive);
+    let src = tmp.path().join("src.txt");
+    fs::write(&src, b"journal history").unwrap();
+
+    let mut arc = CrudArchive::open(&archive).unwrap();
+    assert!(arc.journal_records().unwrap().is_empty());
+    arc.put_file(&src, "a.txt", 0o644, 1000).unwrap();
+    arc.put_file(&src, "b.txt", 0o600, 2000).unwrap();
+    arc.delete_path("a.txt").unwrap();
+    arc.rename("b.txt", "c.txt").unwrap();
+
+    let check = |records: Vec<LogRecord>| {
+        assert_eq!(records.len(), 4, "{records:?}");
+        match &records[0] {
+            LogRecord::Put {
+                path, mtime, size, ..
+            } => assert_eq!((path.as_str(), *mtime, *size), ("a.txt", 1000, 15)),
+            r => panic!("expected put, got {r:?}"),
+        }
+        match &records[1] {
+            LogRecord::Put {
+                path, mode, mtime, ..
+            } => {
+                assert_eq!((path.as_str(), *mode, *mtime), ("b.txt", 0o600, 2000))
+            }
+            r => panic!("expected put, got {r:?}"),
+        }
+        assert!(
+            matches!(&records[2], LogRecord::Delete { path } if path == "a.txt"),
+            "{records:?}"
+        );
+        assert!(
+            matches!(&records[3], LogRecord::Rename { from, to } if from == "b.txt" && to == "c.txt"),
+            "{records:?}"
+        );
+    };
+    check(arc.journal_records().unwrap());
+
+    // Listing the history leaves the journal appendable
+    arc.delete_path("c.txt").unwrap();
+    assert_eq!(arc.journal_records().unwrap().len(), 5);
+    drop(arc);
+
+    let mut reopened = CrudArchive::open(&archive).unwrap();
+    let mut records = reopened.journal_records().unwrap();
+    assert!(matches!(records.pop(), Some(LogRecord::Delete { path }) if path == "c.txt"));
+    check(records);
+}
+
+#[test]
+fn test_open_reader_at_reads_earlier_version() {
+    let tmp = TempDir::new().unwrap();
+    let archive = tmp.path().join("base.arx");
+    issue(&archive);
+    let v1 = tmp.path().join("v1.txt");
+    let v2 = tmp.path().join("v2.txt");
+    fs::write(&v1, b"first version of the note").unwrap();
+    fs::write(&v2, b"second, rewritten version").unwrap();
+
+    let mut arc = CrudArchive::open(&archive).unwrap();
+    arc.put_file(&v1, "note.txt", 0o644, 1000).unwrap();
+    arc.put_file(&v2, "note.txt", 0o644, 2000).unwrap();
+
+    let read = |mut r: Box<dyn std::io::Read + Send + '_>| {
+        let mut out = Vec::new();
+        r.read_to_end(&mut out).unwrap();
+        out
+    };
+    assert_eq!(
+        read(arc.open_reader_at("note.txt", 1).unwrap()),
+        b"first version of the note"
+    );
+    assert_eq!(
+        read(arc.open_reader("note.txt").unwrap()),
+        b"second, rewritten version"
+    );
+    assert_eq!(arc.index_at(1).unwrap().by_path["note.txt"].mtime, 1000);
+    assert!(!arc.index_at(0).unwrap().by_path.contains_key("note.txt"));
+    assert!(arc.open_reader_at("note.txt", 0).is_err());
+}
+
+#[test]
+fn test_delta_codec_compresses_frames() {
+    use arx_core::codec::CodecId;
+
+    let tmp = TempDir::new().unwrap();
+    let src = tmp.path().join("log.txt");
+    // Compressible but without repeated chunks, so dedup doesn't shrink it
+    let text: Vec<u8> = (0..40_000)
+        .flat_map(|i| format!("GET /page/{i} 200\n").into_bytes())
+        .collect();
+    fs::write(&src, &text).unwrap();
+
+    let put_with = |codec: CodecId| {
+        let dir = tmp.path().join(format!("{codec:?}"));
+        fs::create_dir_all(&dir).unwrap();
+        let archive = dir.join("base.arx");
+        issue(&archive);
+        let mut arc = CrudArchive::open(&archive).unwrap();
+        arc.set_codec(codec).unwrap();
+        arc.put_file(&src, "log.txt", 0o644, 0).unwrap();
+        let mut back = Vec::new();
+        arc.open_reader("log.txt")
+            .unwrap()
+            .read_to_end(&mut back)
+            .unwrap();
+        assert_eq!(back, text);
+        drop(arc);
+        // Reopened, the journal's codec still decodes the frames
+        let arc = CrudArchive::open(&archive).unwrap();
+        let mut back = Vec::new();
+        arc.open_reader("log.txt")
+            .unwrap()
+            .read_to_end(&mut back)
+            .unwrap();
+        assert_eq!(back, text);
+        fs::metadata(&arc.delta_path).unwrap().len()
+    };
+
+    assert!(put_with(CodecId::Store) >= text.len() as u64);
+    let deflated = put_with(CodecId::Deflate);
+    assert!(deflated < text.len() as u64 / 2, "{deflated}");
+
+    let archive = tmp.path().join("Store/base.arx");
+    let mut arc = CrudArchive::open(&archive).unwrap();
+    assert!(arc.set_codec(CodecId::ZstdDict).is_err());
+}
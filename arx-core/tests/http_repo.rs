@@ -0,0 +1,175 @@
+//! HTTP range backend: `cargo test -p arx-core --features http --test http_repo`
+#![cfg(feature = "http")]
+
+use arx_core::repo::OpenParams;
+use arx_core::repo_factory::{Backend, open_repo};
+use arx_core::{PackOptions, pack};
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use tempfile::TempDir;
+
+/// Serve `bytes` on a local port until the test exits. With `ranges` off the
+/// server ignores `Range` and always sends the whole body with a 200.
+/// Returns the URL and a counter of body bytes sent.
+fn serve(bytes: Vec<u8>, ranges: bool) -> (String, Arc<AtomicU64>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/archive.arx", listener.local_addr().unwrap());
+    let bytes = Arc::new(bytes);
+    let sent = Arc::new(AtomicU64::new(0));
+    let counter = sent.clone();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(stream) = stream else { break };
+            let (bytes, sent) = (bytes.clone(), counter.clone());
+            thread::spawn(move || respond(stream, &bytes, ranges, &sent));
+        }
+    });
+    (url, sent)
+}
+
+/// Answer requests on one (keep-alive) connection until the client closes it.
+fn respond(stream: TcpStream, bytes: &[u8], ranges: bool, sent: &AtomicU64) {
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut out = stream;
+    let len = bytes.len() as u64;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).unwrap_or(0) == 0 {
+            return;
+        }
+        let mut range = None;
+        loop {
+            let mut h = String::new();
+            if reader.read_line(&mut h).unwrap_or(0) == 0 {
+                return;
+            }
+            let h = h.trim_end().to_ascii_lowercase();
+            if h.is_empty() {
+                break;
+            }
+            if let Some((a, b)) = h
+                .strip_prefix("range: bytes=")
+                .and_then(|r| r.split_once('-'))
+            {
+                range = Some((a.parse::<u64>().unwrap(), b.parse::<u64>().unwrap()));
+            }
+        }
+        let (head, body) = match range.filter(|_| ranges) {
+            Some((a, _)) if a >= len => (
+                format!(
+                    "HTTP/1.1 416 Range Not Satisfiable\r\nContent-Range: bytes */{len}\r\nContent-Length: 0\r\n\r\n"
+                ),
+                &[][..],
+            ),
+            Some((a, b)) => {
+                let b = b.min(len - 1);
+                let body = &bytes[a as usize..=b as usize];
+                (
+                    format!(
+                        "HTTP/1.1 206 Partial Content\r\nContent-Range: bytes {a}-{b}/{len}\r\nContent-Length: {}\r\n\r\n",
+                        body.len()
+                    ),
+                    body,
+                )
+            }
+            None => (
+                format!("HTTP/1.1 200 OK\r\nContent-Length: {len}\r\n\r\n"),
+                bytes,
+            ),
+        };
+        sent.fetch_add(body.len() as u64, Ordering::Relaxed);
+        if out.write_all(head.as_bytes()).is_err() || out.write_all(body).is_err() {
+            return;
+        }
+    }
+}
+
+fn noise(len: usize, seed: u64) -> Vec<u8> {
+    let mut x = seed;
+    (0..len)
+        .map(|_| {
+            x = x
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (x >> 56) as u8
+        })
+        .collect()
+}
+
+fn params() -> OpenParams {
+    OpenParams {
+        archive_path: Default::default(),
+        aead_key: None,
+        key_salt: [0u8; 32],
+    }
+}
+
+#[test]
+fn test_http_repo_streams_one_file_by_range() {
+    let src = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+    let big = noise(4 * 1024 * 1024, 1);
+    fs::write(src.path().join("big.bin"), &big).unwrap();
+    fs::write(src.path().join("small.txt"), b"just this one\n").unwrap();
+    let archive = tmp.path().join("remote.arx");
+    pack(
+        &[src.path()],
+        &archive,
+        Some(&PackOptions {
+            deterministic: true,
+            ..Default::default()
+        }),
+    )
+    .unwrap();
+    let bytes = fs::read(&archive).unwrap();
+    let archive_len = bytes.len() as u64;
+    let (url, sent) = serve(bytes, true);
+
+    let repo = open_repo(Backend::Http { url }, params()).unwrap();
+    let mut paths: Vec<String> = repo
+        .list_files()
+        .unwrap()
+        .into_iter()
+        .map(|r| r.path)
+        .collect();
+    paths.sort();
+    assert_eq!(paths, ["big.bin", "small.txt"]);
+
+    let mut small = String::new();
+    repo.open_reader("small.txt")
+        .unwrap()
+        .read_to_string(&mut small)
+        .unwrap();
+    assert_eq!(small, "just this one\n");
+    assert!(
+        sent.load(Ordering::Relaxed) < archive_len / 4,
+        "read {} of {archive_len} bytes for one small file",
+        sent.load(Ordering::Relaxed)
+    );
+
+    let mut part = Vec::new();
+    repo.open_range("big.bin", 3_000_000, 5000)
+        .unwrap()
+        .read_to_end(&mut part)
+        .unwrap();
+    assert_eq!(part, &big[3_000_000..3_005_000]);
+}
+
+#[test]
+fn test_http_repo_requires_range_support() {
+    let src = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+    fs::write(src.path().join("a.txt"), b"aaa").unwrap();
+    let archive = tmp.path().join("plain.arx");
+    pack(&[src.path()], &archive, None).unwrap();
+    let (url, _) = serve(fs::read(&archive).unwrap(), false);
+
+    let err = open_repo(Backend::Http { url }, params())
+        .err()
+        .expect("a server without range support should be refused");
+    assert!(err.to_string().contains("range support"), "{err}");
+}
@@ -0,0 +1,72 @@
+//! Store-only builds: `cargo test -p arx-core --no-default-features --test no_zstd`
+#![cfg(not(feature = "zstd"))]
+
+use arx_core::codec::CodecId;
+use arx_core::container::superblock::{FLAG_MANIFEST_ZSTD, Superblock};
+use arx_core::read::extract::{extract, verify};
+use arx_core::read::opened::Opened;
+use arx_core::{PackOptions, pack};
+use std::fs::{self, OpenOptions};
+use std::io::{Seek, SeekFrom, Write};
+use tempfile::TempDir;
+
+fn pack_compressible(tmp: &TempDir) -> std::path::PathBuf {
+    let src = tmp.path().join("src");
+    fs::create_dir_all(src.join("sub")).unwrap();
+    fs::write(src.join("hello.txt"), b"hello world\n").unwrap();
+    // Highly compressible, so a zstd build would never store it raw
+    fs::write(src.join("sub/zeros.bin"), vec![0u8; 300_000]).unwrap();
+    let archive = tmp.path().join("store.arx");
+    let opts = PackOptions {
+        compress_manifest: true,
+        ..Default::default()
+    };
+    pack(&[src.as_path()], &archive, Some(&opts)).unwrap();
+    archive
+}
+
+#[test]
+fn test_pack_falls_back_to_store() {
+    let tmp = TempDir::new().unwrap();
+    let dst = TempDir::new().unwrap();
+    let archive = pack_compressible(&tmp);
+
+    let opened = Opened::open(&archive, None, [0u8; 32]).unwrap();
+    assert_eq!(opened.sb.flags & FLAG_MANIFEST_ZSTD, 0);
+    assert!(!opened.table.is_empty());
+    for ce in &opened.table {
+        assert_eq!(ce.codec, CodecId::Store as u8);
+        assert_eq!(ce.c_size, ce.u_size);
+    }
+
+    verify(&archive, None).unwrap();
+    extract(&archive, dst.path(), None).unwrap();
+    assert_eq!(
+        fs::read(dst.path().join("hello.txt")).unwrap(),
+        b"hello world\n"
+    );
+    assert_eq!(
+        fs::read(dst.path().join("sub/zeros.bin")).unwrap(),
+        vec![0u8; 300_000]
+    );
+}
+
+#[test]
+fn test_zstd_chunk_is_a_clear_error() {
+    let tmp = TempDir::new().unwrap();
+    let dst = TempDir::new().unwrap();
+    let archive = pack_compressible(&tmp);
+
+    // Relabel the first chunk as zstd (entry byte 0 is the codec)
+    let sb = Superblock::read_from(fs::File::open(&archive).unwrap()).unwrap();
+    let mut f = OpenOptions::new().write(true).open(&archive).unwrap();
+    f.seek(SeekFrom::Start(sb.chunk_table_off)).unwrap();
+    f.write_all(&[CodecId::Zstd as u8]).unwrap();
+    drop(f);
+
+    let err = extract(&archive, dst.path(), None).unwrap_err();
+    assert!(
+        err.to_string().contains("without the `zstd` feature"),
+        "{err}"
+    );
+}
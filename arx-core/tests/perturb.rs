@@ -9,6 +9,7 @@
 /// Run: `cargo test -p arx-core perturb`
 use arx_core::container::superblock::Superblock;
 use arx_core::crud::CrudArchive;
+use arx_core::error::ArxError;
 use arx_core::read::extract::extract;
 use arx_core::read::opened::Opened;
 use arx_core::{PackOptions, pack};
@@ -53,6 +54,15 @@ fn read_superblock(archive: &Path) -> Superblock {
     Superblock::read_from(&mut f).unwrap()
 }
 
+/// Change superblock fields and write it back with a valid checksum, as a
+/// writer bug (rather than bit rot) would.
+fn rewrite_superblock(archive: &Path, change: impl FnOnce(&mut Superblock)) {
+    let mut sb = read_superblock(archive);
+    change(&mut sb);
+    let mut f = OpenOptions::new().write(true).open(archive).unwrap();
+    sb.write_to(&mut f).unwrap();
+}
+
 fn patch_bytes(path: &Path, offset: u64, bytes: &[u8]) {
     let mut f = OpenOptions::new().write(true).open(path).unwrap();
     f.seek(SeekFrom::Start(offset)).unwrap();
@@ -113,9 +123,8 @@ fn perturb_unknown_version() {
 fn perturb_huge_manifest_len() {
     let tmp = TempDir::new().unwrap();
     let archive = make_archive(tmp.path(), b"oom test");
-    // manifest_len at offset 8, u64 little-endian
     let huge: u64 = 512 * 1024 * 1024; // 512 MiB
-    patch_bytes(&archive, 8, &huge.to_le_bytes());
+    rewrite_superblock(&archive, |sb| sb.manifest_len = huge);
     let result = Opened::open(&archive, None, [0u8; 32]);
     assert!(
         result.is_err(),
@@ -125,12 +134,29 @@ fn perturb_huge_manifest_len() {
     assert!(msg.contains("manifest") || msg.contains("maximum"), "{msg}");
 }
 
+/// Flip one bit of data_off — the superblock checksum must catch it before
+/// any region is read from the bogus offset.
+#[test]
+fn perturb_flipped_data_off_fails_superblock_checksum() {
+    let tmp = TempDir::new().unwrap();
+    let archive = make_archive(tmp.path(), b"checksum test");
+    // data_off at offset 32, u64 little-endian
+    flip_byte(&archive, 33);
+    let msg = Opened::open(&archive, None, [0u8; 32])
+        .err()
+        .expect("corrupt superblock should return Err")
+        .to_string();
+    assert!(msg.contains("superblock checksum mismatch"), "{msg}");
+    let msg = arx_core::list(&archive, None).unwrap_err().to_string();
+    assert!(msg.contains("superblock checksum mismatch"), "{msg}");
+}
+
 /// Set manifest_len to u64::MAX — must return Err, not allocate.
 #[test]
 fn perturb_manifest_len_max() {
     let tmp = TempDir::new().unwrap();
     let archive = make_archive(tmp.path(), b"max manifest");
-    patch_bytes(&archive, 8, &u64::MAX.to_le_bytes());
+    rewrite_superblock(&archive, |sb| sb.manifest_len = u64::MAX);
     let result = Opened::open(&archive, None, [0u8; 32]);
     assert!(result.is_err(), "manifest_len=MAX should return Err");
 }
@@ -153,7 +179,7 @@ fn perturb_invalid_chunk_id_in_manifest() {
     // then read — this makes valid chunk_ids exceed the (now smaller) table.
     // Set chunk_count to 0 but keep data: any chunk reference in manifest
     // would be >= table.len() == 0.
-    patch_bytes(&archive, 24, &0u64.to_le_bytes()); // chunk_count = 0
+    rewrite_superblock(&archive, |sb| sb.chunk_count = 0);
     // Now from_base() will try to index table[id] where table is empty → bounds error
     let result = Opened::open(&archive, None, [0u8; 32]);
     // Opened may succeed (0 entries in table is valid for empty archive),
@@ -195,14 +221,63 @@ fn perturb_truncate_data_region() {
     assert!(result.is_err(), "truncated data region should fail extract");
 }
 
+/// Cut a tailless archive mid-data — `list` must report truncation, not a bad offset.
+#[test]
+fn perturb_truncated_tailless_archive_list() {
+    use arx_core::list::list_to;
+
+    let tmp = TempDir::new().unwrap();
+    // Incompressible, so the chunk data is large enough to cut into
+    let mut x: u32 = 0x1234_5678;
+    let content: Vec<u8> = (0..65536)
+        .map(|_| {
+            x ^= x << 13;
+            x ^= x >> 17;
+            x ^= x << 5;
+            x as u8
+        })
+        .collect();
+    let archive = make_archive(tmp.path(), &content);
+    let sb = read_superblock(&archive);
+    let cut = sb.data_off + 1000;
+    truncate_file(&archive, cut);
+
+    let err = list_to(&archive, None, &mut Vec::new()).unwrap_err();
+    let msg = err.to_string();
+    assert!(
+        msg.contains(&format!("archive truncated (EOF at {cut}, expected >= ")),
+        "{msg}"
+    );
+}
+
+/// A chunk pointing past EOF in an archive that still has its tail is a bad offset.
+#[test]
+fn perturb_chunk_offset_past_eof_with_tail_list() {
+    use arx_core::list::list_to;
+
+    let tmp = TempDir::new().unwrap();
+    let archive = make_archive(tmp.path(), &vec![0xABu8; 4096]);
+    let sb = read_superblock(&archive);
+    // Entry 0 data_off lives at bytes 24..32 of the first table entry
+    patch_bytes(
+        &archive,
+        sb.chunk_table_off + 24,
+        &(sb.data_off + 1_000_000).to_le_bytes(),
+    );
+
+    let err = list_to(&archive, None, &mut Vec::new()).unwrap_err();
+    let msg = err.to_string();
+    assert!(msg.contains("corrupt offset"), "{msg}");
+    assert!(!msg.contains("truncated"), "{msg}");
+}
+
 /// Set chunk_table_off past end-of-file — Opened::open must return Err.
 #[test]
 fn perturb_chunk_table_off_beyond_eof() {
     let tmp = TempDir::new().unwrap();
     let archive = make_archive(tmp.path(), b"table off test");
     let file_len = fs::metadata(&archive).unwrap().len();
-    // chunk_table_off is at offset 16
-    patch_bytes(&archive, 16, &(file_len + 1_000_000).to_le_bytes());
+    rewrite_superblock(&archive, |sb| sb.chunk_table_off = file_len + 1_000_000);
     let result = Opened::open(&archive, None, [0u8; 32]);
     assert!(
         result.is_err(),
@@ -210,6 +285,36 @@ fn perturb_chunk_table_off_beyond_eof() {
     );
 }
 
+/// Grow manifest_len by one byte — the manifest guard must report the length
+/// mismatch instead of letting the decoder choke on a trailing byte.
+#[test]
+fn perturb_manifest_len_off_by_one() {
+    let tmp = TempDir::new().unwrap();
+    let archive = make_archive(tmp.path(), b"guard test");
+    rewrite_superblock(&archive, |sb| sb.manifest_len += 1);
+    let msg = Opened::open(&archive, None, [0u8; 32])
+        .err()
+        .unwrap()
+        .to_string();
+    assert!(msg.contains("manifest region length mismatch"), "{msg}");
+    let msg = arx_core::list(&archive, None).unwrap_err().to_string();
+    assert!(msg.contains("manifest region length mismatch"), "{msg}");
+}
+
+/// Shift chunk_table_off forward by one entry — the table guard is no longer
+/// where the superblock says, so open fails naming the chunk table region.
+#[test]
+fn perturb_chunk_table_off_shifted() {
+    let tmp = TempDir::new().unwrap();
+    let archive = make_archive(tmp.path(), b"guard test");
+    rewrite_superblock(&archive, |sb| sb.chunk_table_off += 64);
+    let msg = Opened::open(&archive, None, [0u8; 32])
+        .err()
+        .unwrap()
+        .to_string();
+    assert!(msg.contains("chunk table region"), "{msg}");
+}
+
 // ── CRUD JOURNAL PERTURBATIONS ────────────────────────────────────────────────
 
 /// Corrupt the journal magic — on next open, the journal should be backed up
@@ -387,6 +492,70 @@ fn perturb_sync_cleans_sidecars() {
     );
 }
 
+/// A file cut off inside the chunk table reports the region and its offset.
+#[test]
+fn perturb_truncated_chunk_table_reports_offset() {
+    let tmp = TempDir::new().unwrap();
+    let archive = make_archive(tmp.path(), b"truncated table test");
+    let sb = read_superblock(&archive);
+
+    let f = OpenOptions::new().write(true).open(&archive).unwrap();
+    f.set_len(sb.chunk_table_off + 10).unwrap();
+    drop(f);
+
+    let err = Opened::open(&archive, None, [0u8; 32])
+        .err()
+        .expect("truncated chunk table should return Err");
+    assert!(
+        matches!(err, ArxError::Corrupt { region: "chunk table", offset, .. } if offset == sb.chunk_table_off),
+        "unexpected error: {err:?}"
+    );
+    let msg = err.to_string();
+    assert!(
+        msg.contains(&format!("offset {}", sb.chunk_table_off)),
+        "message should name the offset: {msg}"
+    );
+}
+
+/// A chunk whose codec byte names no known codec fails with `UnsupportedCodec`.
+#[test]
+fn perturb_unknown_codec_id() {
+    let tmp = TempDir::new().unwrap();
+    let archive = make_archive(tmp.path(), b"unknown codec test");
+    let sb = read_superblock(&archive);
+
+    // Byte 0 of the first table entry is its codec id
+    patch_bytes(&archive, sb.chunk_table_off, &[0xEE]);
+
+    let opened = Opened::open(&archive, None, [0u8; 32]).unwrap();
+    let err = opened.read_chunk(0).unwrap_err();
+    assert!(
+        matches!(err, ArxError::UnsupportedCodec(0xEE)),
+        "unexpected error: {err:?}"
+    );
+    assert!(err.to_string().contains("unsupported codec id 238"));
+}
+
+/// A table entry claiming an absurd `u_size` is refused before anything is
+/// allocated for it.
+#[test]
+fn perturb_absurd_u_size() {
+    let tmp = TempDir::new().unwrap();
+    let archive = make_archive(tmp.path(), b"absurd u_size test");
+    let sb = read_superblock(&archive);
+
+    // Bytes 8..16 of the first table entry hold its u_size
+    patch_bytes(
+        &archive,
+        sb.chunk_table_off + 8,
+        &(1u64 << 40).to_le_bytes(),
+    );
+
+    let opened = Opened::open(&archive, None, [0u8; 32]).unwrap();
+    let err = opened.read_chunk(0).unwrap_err();
+    assert!(err.to_string().contains("u_size"), "{err}");
+}
+
 // ── SUMMARY ──────────────────────────────────────────────────────────────────
 // Run all: cargo test -p arx-core perturb
 // Run one: cargo test -p arx-core perturb::perturb_bad_magic
@@ -110,6 +110,493 @@ fn test_encrypted_roundtrip() {
     compare_trees(src.path(), dst.path());
 }
 
+#[test]
+fn test_wrong_key_is_an_error_on_every_read_path() {
+    use arx_core::error::ArxError;
+    use arx_core::read::extract::verify;
+    use arx_core::read::opened::Opened;
+    use arx_core::{ListOptions, list::list_to};
+
+    let src = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+    write_tree(src.path());
+    let archive = tmp.path().join("enc.arx");
+    let pack_opts = PackOptions {
+        aead_key: Some([0x11u8; 32]),
+        ..Default::default()
+    };
+    pack(&[src.path()], &archive, Some(&pack_opts)).unwrap();
+    let wrong = [0x22u8; 32];
+
+    let err = Opened::open(&archive, Some(wrong), [0u8; 32])
+        .err()
+        .unwrap();
+    assert!(matches!(err, ArxError::AeadError), "{err}");
+
+    let lopts = ListOptions {
+        aead_key: Some(wrong),
+        ..Default::default()
+    };
+    let err = list_to(&archive, Some(&lopts), &mut Vec::new()).unwrap_err();
+    assert!(matches!(err, ArxError::AeadError), "{err}");
+
+    let xopts = ExtractOptions {
+        aead_key: Some(wrong),
+        ..Default::default()
+    };
+    let err = verify(&archive, Some(&xopts)).unwrap_err();
+    assert!(matches!(err, ArxError::AeadError), "{err}");
+}
+
+#[test]
+fn test_encrypted_round_trip_both_ciphers() {
+    use arx_core::crypto::aead::AeadAlg;
+    use arx_core::read::extract::verify;
+    use arx_core::{ListOptions, Superblock, list::list_to};
+
+    let src = TempDir::new().unwrap();
+    write_tree(src.path());
+    let key = [0x42u8; 32];
+
+    for alg in [AeadAlg::XChaCha20Poly1305, AeadAlg::Aes256Gcm] {
+        let tmp = TempDir::new().unwrap();
+        let dst = TempDir::new().unwrap();
+        let archive = tmp.path().join("enc.arx");
+        let pack_opts = PackOptions {
+            aead_key: Some(key),
+            aead_alg: alg,
+            ..Default::default()
+        };
+        pack(&[src.path()], &archive, Some(&pack_opts)).unwrap();
+
+        let sb = Superblock::read_from(&mut fs::File::open(&archive).unwrap()).unwrap();
+        assert_eq!(AeadAlg::from_flags(sb.flags), alg);
+
+        let mut listing = Vec::new();
+        let lopts = ListOptions {
+            aead_key: Some(key),
+            ..Default::default()
+        };
+        list_to(&archive, Some(&lopts), &mut listing).unwrap();
+        assert!(String::from_utf8(listing).unwrap().contains("big.bin [E]"));
+
+        let ext_opts = ExtractOptions {
+            aead_key: Some(key),
+            ..Default::default()
+        };
+        verify(&archive, Some(&ext_opts)).unwrap();
+        extract(&archive, dst.path(), Some(&ext_opts)).unwrap();
+        compare_trees(src.path(), dst.path());
+    }
+}
+
+#[test]
+fn test_exclude_prunes_matching_paths() {
+    use arx_core::read::opened::Opened;
+
+    let src = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+    write_tree(src.path());
+    fs::create_dir_all(src.path().join(".git/objects")).unwrap();
+    fs::write(src.path().join(".git/objects/ab"), b"blob").unwrap();
+    fs::create_dir_all(src.path().join("sub/node_modules/pkg")).unwrap();
+    fs::write(src.path().join("sub/node_modules/pkg/index.js"), b"x").unwrap();
+    fs::write(src.path().join("scratch.tmp"), b"tmp").unwrap();
+    let archive = tmp.path().join("excl.arx");
+    let opts = PackOptions {
+        exclude: vec![".git".into(), "sub/node_modules".into(), "*.tmp".into()],
+        ..Default::default()
+    };
+    pack(&[src.path()], &archive, Some(&opts)).unwrap();
+
+    let opened = Opened::open(&archive, None, [0u8; 32]).unwrap();
+    let paths: Vec<&str> = opened
+        .manifest
+        .files
+        .iter()
+        .map(|f| f.path.as_str())
+        .chain(opened.manifest.dirs.iter().map(|d| d.path.as_str()))
+        .collect();
+    for skipped in [".git", "node_modules", ".tmp"] {
+        assert!(
+            !paths.iter().any(|p| p.contains(skipped)),
+            "{skipped} leaked into {paths:?}"
+        );
+    }
+    assert_eq!(opened.manifest.files.len(), 4, "{paths:?}");
+
+    let bad = PackOptions {
+        exclude: vec!["[".into()],
+        ..Default::default()
+    };
+    assert!(pack(&[src.path()], &tmp.path().join("bad.arx"), Some(&bad)).is_err());
+}
+
+#[test]
+fn test_respect_gitignore_skips_ignored_paths() {
+    use arx_core::read::opened::Opened;
+
+    let src = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+    write_tree(src.path());
+    fs::write(src.path().join(".gitignore"), "target/\n").unwrap();
+    fs::create_dir_all(src.path().join("target/debug")).unwrap();
+    fs::write(src.path().join("target/debug/app"), b"binary").unwrap();
+    // Nested rules apply below their own directory only
+    fs::write(src.path().join("sub/.ignore"), "*.bin\n").unwrap();
+
+    let archive = tmp.path().join("gi.arx");
+    let opts = PackOptions {
+        respect_gitignore: true,
+        ..Default::default()
+    };
+    pack(&[src.path()], &archive, Some(&opts)).unwrap();
+
+    let opened = Opened::open(&archive, None, [0u8; 32]).unwrap();
+    let mut paths: Vec<&str> = opened
+        .manifest
+        .files
+        .iter()
+        .map(|f| f.path.as_str())
+        .collect();
+    paths.sort();
+    assert_eq!(
+        paths,
+        [
+            ".gitignore",
+            "big.bin",
+            "hello.txt",
+            "sub/.ignore",
+            "sub/empty.txt"
+        ]
+    );
+    assert!(
+        !opened
+            .manifest
+            .dirs
+            .iter()
+            .any(|d| d.path.starts_with("target"))
+    );
+
+    // Off by default: everything is packed
+    let plain = tmp.path().join("plain.arx");
+    pack(&[src.path()], &plain, None).unwrap();
+    let opened = Opened::open(&plain, None, [0u8; 32]).unwrap();
+    assert!(
+        opened
+            .manifest
+            .files
+            .iter()
+            .any(|f| f.path == "target/debug/app")
+    );
+}
+
+#[test]
+fn test_plan_pack_matches_real_pack() {
+    use arx_core::{Superblock, plan_pack};
+
+    let src = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+    write_tree(src.path());
+    // A duplicate so some chunks dedup
+    fs::copy(src.path().join("big.bin"), src.path().join("big2.bin")).unwrap();
+    let archive = tmp.path().join("plan.arx");
+    let opts = PackOptions {
+        dry_run: true,
+        ..Default::default()
+    };
+
+    // A dry run through `pack` writes nothing
+    pack(&[src.path()], &archive, Some(&opts)).unwrap();
+    assert!(!archive.exists());
+
+    let plan = plan_pack(&[src.path()], None).unwrap();
+    assert_eq!(plan, plan_pack(&[src.path()], Some(&opts)).unwrap());
+    assert_eq!(plan.files, 5);
+    assert!(plan.chunk_refs > plan.unique_chunks);
+
+    pack(&[src.path()], &archive, None).unwrap();
+    let sb = Superblock::read_from(&mut fs::File::open(&archive).unwrap()).unwrap();
+    assert_eq!(plan.unique_chunks, sb.chunk_count);
+    assert!(plan.c_bytes < fs::metadata(&archive).unwrap().len());
+}
+
+#[test]
+fn test_read_file_bytes_returns_file_contents() {
+    use arx_core::error::ArxError;
+    use arx_core::read::extract::read_file_bytes;
+
+    let src = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+    write_tree(src.path());
+    let archive = tmp.path().join("mem.arx");
+    let key = [0x42u8; 32];
+    let opts = PackOptions {
+        aead_key: Some(key),
+        ..Default::default()
+    };
+    pack(&[src.path()], &archive, Some(&opts)).unwrap();
+
+    let ext_opts = ExtractOptions {
+        aead_key: Some(key),
+        ..Default::default()
+    };
+    let got = read_file_bytes(&archive, "big.bin", Some(&ext_opts)).unwrap();
+    assert_eq!(got, fs::read(src.path().join("big.bin")).unwrap());
+    assert!(
+        read_file_bytes(&archive, "sub/empty.txt", Some(&ext_opts))
+            .unwrap()
+            .is_empty()
+    );
+
+    let err = read_file_bytes(&archive, "missing.txt", Some(&ext_opts)).unwrap_err();
+    assert!(
+        matches!(err, ArxError::Io(ref e) if e.kind() == std::io::ErrorKind::NotFound),
+        "{err:?}"
+    );
+}
+
+#[test]
+fn test_empty_nested_dirs_survive_round_trip() {
+    use arx_core::read::opened::Opened;
+
+    let src = TempDir::new().unwrap();
+    let other = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+    let dst = TempDir::new().unwrap();
+    write_tree(src.path());
+    fs::create_dir_all(src.path().join("deep/er/empty")).unwrap();
+    fs::create_dir_all(src.path().join("deep-sibling")).unwrap();
+    fs::write(src.path().join("deep-sibling/f.txt"), b"f").unwrap();
+    fs::create_dir_all(other.path().join("extra/empty")).unwrap();
+    let archive = tmp.path().join("dirs.arx");
+    pack(&[src.path(), other.path()], &archive, None).unwrap();
+
+    let opened = Opened::open(&archive, None, [0u8; 32]).unwrap();
+    let dirs: Vec<&str> = opened
+        .manifest
+        .dirs
+        .iter()
+        .map(|d| d.path.as_str())
+        .collect();
+    for (i, d) in dirs.iter().enumerate() {
+        let mut parent = Path::new(d).parent();
+        while let Some(p) = parent.filter(|p| !p.as_os_str().is_empty()) {
+            let at = dirs.iter().position(|x| Path::new(x) == p);
+            assert!(
+                at.is_some_and(|at| at < i),
+                "{d} before its parent: {dirs:?}"
+            );
+            parent = p.parent();
+        }
+    }
+
+    extract(&archive, dst.path(), None).unwrap();
+    assert!(dst.path().join("deep/er/empty").is_dir());
+    assert!(dst.path().join("extra/empty").is_dir());
+    assert_eq!(
+        fs::read(dst.path().join("deep-sibling/f.txt")).unwrap(),
+        b"f"
+    );
+}
+
+#[test]
+fn test_chunk_map_reports_shared_chunk_refcounts() {
+    use arx_core::read::opened::Opened;
+
+    let src = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+    write_tree(src.path());
+    fs::copy(src.path().join("hello.txt"), src.path().join("hello2.txt")).unwrap();
+    let archive = tmp.path().join("refs.arx");
+    pack(&[src.path()], &archive, None).unwrap();
+
+    let opened = Opened::open(&archive, None, [0u8; 32]).unwrap();
+    let shared = opened.chunk_map_for("hello2.txt").unwrap();
+    assert_eq!(shared.len(), 1);
+    assert!(shared[0].refcount >= 2, "{shared:?}");
+    assert_eq!(
+        opened.chunk_map_for("hello.txt").unwrap()[0].id,
+        shared[0].id
+    );
+    assert_eq!(opened.chunk_map_for("sub/data.bin").unwrap()[0].refcount, 1);
+
+    let total: u64 = opened.chunk_refcounts().iter().sum();
+    let refs: usize = opened
+        .manifest
+        .files
+        .iter()
+        .map(|f| f.chunk_refs.len())
+        .sum();
+    assert_eq!(total, refs as u64);
+}
+
+#[test]
+fn test_superblock_records_file_count_and_size() {
+    use arx_core::Superblock;
+    use arx_core::container::superblock::VERSION;
+    use arx_core::pack_append;
+
+    let src = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+    write_tree(src.path());
+    let archive = tmp.path().join("counts.arx");
+    pack(&[src.path()], &archive, None).unwrap();
+
+    let read_sb = || Superblock::read_from(fs::File::open(&archive).unwrap()).unwrap();
+    let sb = read_sb();
+    assert_eq!(sb.version, VERSION);
+    assert_eq!(sb.file_count, 4);
+    let big = fs::metadata(src.path().join("big.bin")).unwrap().len();
+    assert_eq!(sb.total_logical, 12 + 4096 + big);
+
+    // Appending keeps them current
+    let more = TempDir::new().unwrap();
+    fs::write(more.path().join("extra.txt"), b"12345").unwrap();
+    pack_append(&archive, &[more.path()], None).unwrap();
+    let sb = read_sb();
+    assert_eq!(sb.file_count, 5);
+    assert_eq!(sb.total_logical, 12 + 4096 + big + 5);
+}
+
+#[test]
+fn test_encrypt_paths_seals_manifest_by_default() {
+    use arx_core::container::superblock::{FLAG_CHUNK_AEAD, FLAG_ENCRYPTED};
+    use arx_core::read::extract::{extract_file_to, verify};
+    use arx_core::{Superblock, list::list_to};
+
+    let src = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+    let dst = TempDir::new().unwrap();
+    write_tree(src.path());
+    let archive = tmp.path().join("mixed.arx");
+    let key = [0x42u8; 32];
+    let opts = PackOptions {
+        aead_key: Some(key),
+        encrypt_paths: vec!["sub/*".into()],
+        ..Default::default()
+    };
+    pack(&[src.path()], &archive, Some(&opts)).unwrap();
+
+    let sb = Superblock::read_from(&mut fs::File::open(&archive).unwrap()).unwrap();
+    assert_ne!(sb.flags & FLAG_CHUNK_AEAD, 0);
+    assert_ne!(sb.flags & FLAG_ENCRYPTED, 0);
+
+    // Without the key not even the file list or an unmatched file reads
+    assert!(list_to(&archive, None, &mut Vec::new()).is_err());
+    assert!(extract_file_to(&archive, "hello.txt", &mut Vec::new(), None).is_err());
+
+    let ext_opts = ExtractOptions {
+        aead_key: Some(key),
+        ..Default::default()
+    };
+    verify(&archive, Some(&ext_opts)).unwrap();
+    extract(&archive, dst.path(), Some(&ext_opts)).unwrap();
+    compare_trees(src.path(), dst.path());
+}
+
+#[test]
+fn test_encrypt_paths_public_manifest_seals_only_matching_files() {
+    use arx_core::container::superblock::{FLAG_CHUNK_AEAD, FLAG_ENCRYPTED};
+    use arx_core::read::extract::{extract_file_to, verify};
+    use arx_core::{ListOptions, Superblock, list::list_to};
+
+    let src = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+    let dst = TempDir::new().unwrap();
+    write_tree(src.path());
+    // Same bytes as a sealed file: must not be served from the sealed copy
+    fs::write(src.path().join("public.bin"), [0xFFu8; 4096]).unwrap();
+    let archive = tmp.path().join("mixed.arx");
+    let key = [0x42u8; 32];
+    let opts = PackOptions {
+        aead_key: Some(key),
+        encrypt_paths: vec!["sub/*".into()],
+        public_manifest: true,
+        ..Default::default()
+    };
+    pack(&[src.path()], &archive, Some(&opts)).unwrap();
+
+    let sb = Superblock::read_from(&mut fs::File::open(&archive).unwrap()).unwrap();
+    assert_ne!(sb.flags & FLAG_CHUNK_AEAD, 0);
+    assert_eq!(sb.flags & FLAG_ENCRYPTED, 0);
+
+    // The file list and unmatched files need no key
+    let mut listing = Vec::new();
+    list_to(&archive, None, &mut listing).unwrap();
+    let listing = String::from_utf8(listing).unwrap();
+    assert!(listing.contains("sub/data.bin [E]"), "{listing}");
+    assert!(listing.contains("hello.txt  u="), "{listing}");
+
+    for (path, want) in [
+        ("hello.txt", fs::read(src.path().join("hello.txt")).unwrap()),
+        ("public.bin", vec![0xFFu8; 4096]),
+    ] {
+        let mut out = Vec::new();
+        extract_file_to(&archive, path, &mut out, None).unwrap();
+        assert_eq!(out, want, "{path}");
+    }
+    assert!(extract_file_to(&archive, "sub/data.bin", &mut Vec::new(), None).is_err());
+
+    // With the key, everything comes back
+    let ext_opts = ExtractOptions {
+        aead_key: Some(key),
+        ..Default::default()
+    };
+    verify(&archive, Some(&ext_opts)).unwrap();
+    extract(&archive, dst.path(), Some(&ext_opts)).unwrap();
+    compare_trees(src.path(), dst.path());
+
+    let lopts = ListOptions {
+        aead_key: Some(key),
+        ..Default::default()
+    };
+    assert!(list_to(&archive, Some(&lopts), &mut Vec::new()).is_ok());
+}
+
+#[test]
+fn test_encrypting_with_zero_salt_is_refused() {
+    use arx_core::Superblock;
+    use arx_core::error::ArxError;
+
+    let src = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+    write_tree(src.path());
+    let archive = tmp.path().join("zero.arx");
+    let mut opts = PackOptions {
+        deterministic: true,
+        aead_key: Some([0x42u8; 32]),
+        ..Default::default()
+    };
+    let err = pack(&[src.path()], &archive, Some(&opts)).unwrap_err();
+    assert!(
+        matches!(err, ArxError::Format(ref m) if m.contains("zero salt")),
+        "{err}"
+    );
+    assert!(!archive.exists(), "refused pack left an output file");
+
+    // An explicit salt keeps the build reproducible without the zero salt
+    opts.key_salt = [0x5Au8; 32];
+    pack(&[src.path()], &archive, Some(&opts)).unwrap();
+
+    // Non-deterministic packs draw a fresh salt per archive
+    let salts: Vec<[u8; 32]> = (0..2)
+        .map(|i| {
+            let out = tmp.path().join(format!("rand{i}.arx"));
+            let opts = PackOptions {
+                aead_key: Some([0x42u8; 32]),
+                ..Default::default()
+            };
+            pack(&[src.path()], &out, Some(&opts)).unwrap();
+            Superblock::read_from(fs::File::open(&out).unwrap())
+                .unwrap()
+                .kdf_salt
+        })
+        .collect();
+    assert_ne!(salts[0], [0u8; 32]);
+    assert_ne!(salts[0], salts[1]);
+}
+
 #[test]
 fn test_encrypted_wrong_key_fails() {
     let src = TempDir::new().unwrap();
@@ -164,3 +651,2796 @@ fn test_password_roundtrip() {
     let content = fs::read(dst.path().join("file.txt")).unwrap();
     assert_eq!(content, b"password protected");
 }
+
+#[test]
+fn test_chunk_avg_changes_chunk_count() {
+    use arx_core::read::opened::Opened;
+
+    let src = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+
+    // Incompressible, non-repeating data so every chunk is unique
+    let mut x = 0x9E37_79B9_7F4A_7C15u64;
+    let data: Vec<u8> = (0..4 * 1024 * 1024)
+        .map(|_| {
+            x ^= x << 13;
+            x ^= x >> 7;
+            x ^= x << 17;
+            x as u8
+        })
+        .collect();
+    fs::write(src.path().join("noise.bin"), &data).unwrap();
+
+    let count_chunks = |avg: usize, name: &str| -> usize {
+        let archive = tmp.path().join(name);
+        let opts = PackOptions {
+            deterministic: true,
+            chunk_avg: avg,
+            ..Default::default()
+        };
+        pack(&[src.path()], &archive, Some(&opts)).expect("pack failed");
+        Opened::open(&archive, None, [0u8; 32]).unwrap().table.len()
+    };
+
+    let small = count_chunks(64 * 1024, "small.arx");
+    let large = count_chunks(512 * 1024, "large.arx");
+    assert!(
+        small > large,
+        "smaller avg should yield more chunks ({small} vs {large})"
+    );
+}
+
+#[test]
+fn test_invalid_chunk_params_rejected() {
+    let src = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+    fs::write(src.path().join("a.txt"), b"a").unwrap();
+
+    let opts = PackOptions {
+        chunk_min: 512 * 1024,
+        chunk_avg: 128 * 1024,
+        ..Default::default()
+    };
+    let result = pack(&[src.path()], &tmp.path().join("bad.arx"), Some(&opts));
+    assert!(result.is_err(), "min > avg should be rejected");
+}
+
+#[test]
+fn test_extract_single_file_to_writer() {
+    use arx_core::read::extract::extract_file_to;
+
+    let src = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+
+    write_tree(src.path());
+    let archive = tmp.path().join("enc.arx");
+    let key = [0x5Au8; 32];
+    let pack_opts = PackOptions {
+        aead_key: Some(key),
+        ..Default::default()
+    };
+    pack(&[src.path()], &archive, Some(&pack_opts)).unwrap();
+
+    let ext_opts = ExtractOptions {
+        aead_key: Some(key),
+        ..Default::default()
+    };
+    let mut out = Vec::new();
+    let n = extract_file_to(&archive, "big.bin", &mut out, Some(&ext_opts))
+        .expect("single-file extract failed");
+    assert_eq!(n as usize, out.len());
+    assert_eq!(out, fs::read(src.path().join("big.bin")).unwrap());
+
+    let mut sink = Vec::new();
+    let missing = extract_file_to(&archive, "nope.txt", &mut sink, Some(&ext_opts));
+    assert!(missing.is_err(), "missing path should error");
+    assert!(sink.is_empty());
+}
+
+#[test]
+fn test_file_reader_seek_random_offsets() {
+    use arx_core::read::opened::Opened;
+    use std::io::{Read, Seek, SeekFrom};
+
+    let src = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+    write_tree(src.path());
+    let archive = tmp.path().join("seek.arx");
+    let opts = PackOptions {
+        chunk_avg: 64 * 1024,
+        ..Default::default()
+    };
+    pack(&[src.path()], &archive, Some(&opts)).unwrap();
+
+    let expected = fs::read(src.path().join("big.bin")).unwrap();
+    let opened = Opened::open(&archive, None, [0u8; 32]).unwrap();
+    assert!(
+        opened.chunk_map_for("big.bin").unwrap().len() > 1,
+        "fixture should span multiple chunks"
+    );
+    let mut r = opened.open_reader("big.bin").unwrap();
+
+    let mut x = 12345u64;
+    for _ in 0..32 {
+        x = x
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        let off = (x >> 33) % expected.len() as u64;
+        assert_eq!(r.seek(SeekFrom::Start(off)).unwrap(), off);
+        let mut buf = vec![0u8; 4096];
+        let want = &expected[off as usize..(off as usize + 4096).min(expected.len())];
+        let n = r.read(&mut buf).unwrap();
+        assert!(n > 0);
+        assert_eq!(&buf[..n], &want[..n], "mismatch at offset {off}");
+    }
+
+    // Relative seeks and EOF
+    r.seek(SeekFrom::Start(10)).unwrap();
+    assert_eq!(r.seek(SeekFrom::Current(5)).unwrap(), 15);
+    assert_eq!(
+        r.seek(SeekFrom::End(-4)).unwrap(),
+        expected.len() as u64 - 4
+    );
+    let mut rest = Vec::new();
+    r.read_to_end(&mut rest).unwrap();
+    assert_eq!(rest, &expected[expected.len() - 4..]);
+    assert!(
+        r.seek(SeekFrom::Current(-(expected.len() as i64) - 1))
+            .is_err()
+    );
+}
+
+#[test]
+fn test_deflate_codec_roundtrip_and_external_inflate() {
+    use arx_core::codec::CodecId;
+    use arx_core::read::opened::Opened;
+    use std::io::Read;
+
+    let src = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+    let dst = TempDir::new().unwrap();
+    write_tree(src.path());
+    let archive = tmp.path().join("deflate.arx");
+
+    let opts = PackOptions {
+        codec: Some(CodecId::Deflate),
+        ..Default::default()
+    };
+    pack(&[src.path()], &archive, Some(&opts)).expect("deflate pack failed");
+    extract(&archive, dst.path(), None).expect("deflate extract failed");
+    compare_trees(src.path(), dst.path());
+
+    // A stock raw-DEFLATE decoder can inflate a chunk given only offset/length
+    let opened = Opened::open(&archive, None, [0u8; 32]).unwrap();
+    let chunks = opened.chunk_map_for("big.bin").unwrap();
+    let first = &chunks[0];
+    assert_eq!(first.codec, CodecId::Deflate as u8);
+    let raw = opened
+        .read_chunk_bytes(first.data_off, first.c_len)
+        .unwrap();
+    let mut inflated = Vec::new();
+    flate2::read::DeflateDecoder::new(raw.as_slice())
+        .read_to_end(&mut inflated)
+        .unwrap();
+    let original = fs::read(src.path().join("big.bin")).unwrap();
+    assert_eq!(inflated, &original[..first.u_len as usize]);
+}
+
+#[cfg(feature = "zstd")]
+#[test]
+fn test_best_of_codec_strategy_picks_per_chunk() {
+    use arx_core::codec::{CodecId, CodecStrategy};
+    use arx_core::read::opened::Opened;
+
+    let src = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+    let dst = TempDir::new().unwrap();
+    write_tree(src.path());
+    let mut x = 0x2545_F491_4F6C_DD1Du64;
+    let noise: Vec<u8> = (0..300_000)
+        .map(|_| {
+            x ^= x << 13;
+            x ^= x >> 7;
+            x ^= x << 17;
+            x as u8
+        })
+        .collect();
+    fs::write(src.path().join("noise.bin"), &noise).unwrap();
+
+    let pack_with = |name: &str, strategy: CodecStrategy| {
+        let archive = tmp.path().join(name);
+        let opts = PackOptions {
+            codec_strategy: strategy,
+            ..Default::default()
+        };
+        pack(&[src.path()], &archive, Some(&opts)).unwrap();
+        archive
+    };
+
+    let archive = pack_with(
+        "best.arx",
+        CodecStrategy::BestOf(vec![CodecId::Zstd, CodecId::Store]),
+    );
+    let opened = Opened::open(&archive, None, [0u8; 32]).unwrap();
+    let codecs: Vec<u8> = opened.table.iter().map(|ce| ce.codec).collect();
+    assert!(codecs.contains(&(CodecId::Zstd as u8)), "{codecs:?}");
+    assert!(codecs.contains(&(CodecId::Store as u8)), "{codecs:?}");
+    extract(&archive, dst.path(), None).unwrap();
+    compare_trees(src.path(), dst.path());
+
+    // Per chunk, the best of two never loses to either alone
+    let data_size = |archive: &Path| -> u64 {
+        let opened = Opened::open(archive, None, [0u8; 32]).unwrap();
+        opened.table.iter().map(|ce| ce.c_size).sum()
+    };
+    let zstd = pack_with("zstd.arx", CodecStrategy::Fixed(CodecId::Zstd));
+    let both = pack_with(
+        "both.arx",
+        CodecStrategy::BestOf(vec![CodecId::Deflate, CodecId::Zstd]),
+    );
+    assert!(data_size(&both) <= data_size(&zstd));
+
+    let empty = PackOptions {
+        codec_strategy: CodecStrategy::BestOf(vec![]),
+        ..Default::default()
+    };
+    assert!(pack(&[src.path()], &tmp.path().join("none.arx"), Some(&empty)).is_err());
+}
+
+#[cfg(feature = "zstd")]
+#[test]
+fn test_compressed_manifest_roundtrip() {
+    use arx_core::container::superblock::{FLAG_MANIFEST_ZSTD, Superblock};
+    use arx_core::read::extract::{VerifyOptions, verify_with};
+
+    let src = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+    let dst = TempDir::new().unwrap();
+    // Many tiny files: the manifest dominates the archive
+    for d in 0..10 {
+        let dir = src.path().join(format!("pkg_{d:02}"));
+        fs::create_dir_all(&dir).unwrap();
+        for i in 0..200 {
+            fs::write(
+                dir.join(format!("module_{i:04}.rs")),
+                format!("// {d}/{i}\n"),
+            )
+            .unwrap();
+        }
+    }
+
+    let plain = tmp.path().join("plain.arx");
+    let packed = tmp.path().join("packed.arx");
+    let key = [7u8; 32];
+    pack(
+        &[src.path()],
+        &plain,
+        Some(&PackOptions {
+            deterministic: true,
+            aead_key: Some(key),
+            allow_zero_salt: true,
+            ..Default::default()
+        }),
+    )
+    .unwrap();
+    pack(
+        &[src.path()],
+        &packed,
+        Some(&PackOptions {
+            deterministic: true,
+            aead_key: Some(key),
+            compress_manifest: true,
+            allow_zero_salt: true,
+            ..Default::default()
+        }),
+    )
+    .unwrap();
+
+    let sb_plain = Superblock::read_from(fs::File::open(&plain).unwrap()).unwrap();
+    let sb_packed = Superblock::read_from(fs::File::open(&packed).unwrap()).unwrap();
+    assert_eq!(sb_plain.flags & FLAG_MANIFEST_ZSTD, 0);
+    assert_ne!(sb_packed.flags & FLAG_MANIFEST_ZSTD, 0);
+    assert!(
+        sb_packed.manifest_len * 2 < sb_plain.manifest_len,
+        "compressed manifest {} vs plain {}",
+        sb_packed.manifest_len,
+        sb_plain.manifest_len
+    );
+
+    let xopts = ExtractOptions {
+        aead_key: Some(key),
+        ..Default::default()
+    };
+    extract(&packed, dst.path(), Some(&xopts)).expect("extract failed");
+    compare_trees(src.path(), dst.path());
+    verify_with(
+        &packed,
+        Some(&xopts),
+        &VerifyOptions {
+            deep: true,
+            ..Default::default()
+        },
+    )
+    .expect("verify failed");
+}
+
+#[test]
+fn test_concurrent_open_range_reads_match_source() {
+    use arx_core::read::opened::Opened;
+    use std::io::Read;
+
+    let src = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+    // 8 MiB of varied bytes so the file spans many chunks
+    let big: Vec<u8> = (0u32..2_000_000)
+        .flat_map(|i| i.wrapping_mul(2_654_435_761).to_le_bytes())
+        .collect();
+    fs::write(src.path().join("big.bin"), &big).unwrap();
+    let archive = tmp.path().join("big.arx");
+    let opts = PackOptions {
+        aead_key: Some([0x42u8; 32]),
+        chunk_avg: 64 * 1024,
+        ..Default::default()
+    };
+    pack(&[src.path()], &archive, Some(&opts)).unwrap();
+
+    // Chunk reads are positional, so the threads share one Opened without a lock
+    let arx = Opened::open(&archive, Some([0x42u8; 32]), [0u8; 32]).unwrap();
+    let arx = &arx;
+    let ranges: Vec<(u64, u64)> = (0..64u64)
+        .map(|i| ((i * 131_071) % big.len() as u64, 200_000))
+        .collect();
+    let got: Vec<Vec<u8>> = std::thread::scope(|s| {
+        let handles: Vec<_> = ranges
+            .iter()
+            .map(|&(start, len)| {
+                s.spawn(move || {
+                    let mut out = Vec::new();
+                    arx.open_range("big.bin", start, len)
+                        .unwrap()
+                        .read_to_end(&mut out)
+                        .unwrap();
+                    out
+                })
+            })
+            .collect();
+        handles.into_iter().map(|h| h.join().unwrap()).collect()
+    });
+
+    for (&(start, len), got) in ranges.iter().zip(&got) {
+        let end = (start + len).min(big.len() as u64);
+        assert_eq!(got.as_slice(), &big[start as usize..end as usize]);
+    }
+}
+
+#[test]
+fn test_checksums_match_source_files() {
+    use arx_core::read::checksums::{ChecksumAlg, file_checksums};
+
+    let src = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+    write_tree(src.path());
+    let archive = tmp.path().join("sums.arx");
+    pack(&[src.path()], &archive, None).unwrap();
+
+    let sums = file_checksums(&archive, None, ChecksumAlg::Blake3).unwrap();
+    assert_eq!(sums.len(), 4);
+    for (hex, path) in &sums {
+        let expected = blake3::hash(&fs::read(src.path().join(path)).unwrap());
+        assert_eq!(hex, &expected.to_hex().to_string(), "{path}");
+    }
+
+    #[cfg(feature = "sha256")]
+    {
+        let sums = file_checksums(&archive, None, ChecksumAlg::Sha256).unwrap();
+        let (hex, _) = sums.iter().find(|(_, p)| p == "hello.txt").unwrap();
+        // sha256sum of "hello world\n"
+        assert_eq!(
+            hex,
+            "a948904f2f0f479b8f8197694b30184b0d2ed1c1cd2a1ec0fb85d299a192a447"
+        );
+    }
+}
+
+#[test]
+fn test_stats_reports_dedup_of_triplicated_file() {
+    use arx_core::read::stats::archive_stats;
+
+    let src = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+    // Incompressible payload so only dedup (not the codec) shrinks it
+    let mut x: u64 = 0x9E37_79B9_7F4A_7C15;
+    let payload: Vec<u8> = (0..1_000_000)
+        .map(|_| {
+            x ^= x << 13;
+            x ^= x >> 7;
+            x ^= x << 17;
+            x as u8
+        })
+        .collect();
+    for name in ["a.bin", "b.bin", "c.bin"] {
+        fs::write(src.path().join(name), &payload).unwrap();
+    }
+    let archive = tmp.path().join("dup.arx");
+    pack(&[src.path()], &archive, None).unwrap();
+
+    let st = archive_stats(&archive, None).unwrap();
+    assert_eq!(st.files, 3);
+    assert_eq!(st.logical_bytes, 3 * payload.len() as u64);
+    assert_eq!(st.unique_logical_bytes, payload.len() as u64);
+    assert_eq!(st.chunk_refs, 3 * st.unique_chunks);
+    assert!(
+        (st.dedup_ratio - 3.0).abs() < 0.01,
+        "dedup {}",
+        st.dedup_ratio
+    );
+    assert!(st.compression_ratio > 0.9 && st.compression_ratio <= 1.01);
+}
+
+#[test]
+fn test_read_by_index_matches_path_reads() {
+    use arx_core::repo::{ArchiveRepo, OpenParams};
+    use arx_core::repo_fs::FsArchiveRepo;
+    use std::io::Read;
+
+    let src = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+    write_tree(src.path());
+    let archive = tmp.path().join("idx.arx");
+    pack(&[src.path()], &archive, None).unwrap();
+
+    let repo = FsArchiveRepo::new(OpenParams {
+        archive_path: archive.clone(),
+        aead_key: None,
+        key_salt: [0u8; 32],
+    })
+    .unwrap();
+    let files = repo.list_files().unwrap();
+    assert!(!files.is_empty());
+    for (i, row) in files.iter().enumerate() {
+        let mut by_path = Vec::new();
+        repo.open_reader(&row.path)
+            .unwrap()
+            .read_to_end(&mut by_path)
+            .unwrap();
+        let mut by_index = Vec::new();
+        repo.open_reader_by_index(i)
+            .unwrap()
+            .read_to_end(&mut by_index)
+            .unwrap();
+        assert_eq!(by_index, by_path, "content mismatch for {}", row.path);
+        assert_eq!(by_index.len() as u64, row.u_size);
+
+        let map_path = repo.chunk_map(&row.path).unwrap();
+        let map_index = repo.chunk_map_by_index(i).unwrap();
+        let ids = |m: &[arx_core::domain::ChunkRow]| m.iter().map(|c| c.id).collect::<Vec<_>>();
+        assert_eq!(ids(&map_index), ids(&map_path));
+    }
+    assert!(repo.open_reader_by_index(files.len()).is_err());
+    assert!(repo.chunk_map_by_index(files.len()).is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_hardlinks_preserved() {
+    use arx_core::read::opened::Opened;
+    use std::os::unix::fs::MetadataExt;
+
+    let src = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+    let dst = TempDir::new().unwrap();
+    fs::create_dir_all(src.path().join("sub")).unwrap();
+    fs::write(src.path().join("a.txt"), b"shared inode\n").unwrap();
+    fs::hard_link(src.path().join("a.txt"), src.path().join("sub/b.txt")).unwrap();
+    fs::write(src.path().join("c.txt"), b"standalone\n").unwrap();
+    let archive = tmp.path().join("links.arx");
+    pack(&[src.path()], &archive, None).unwrap();
+
+    let opened = Opened::open(&archive, None, [0u8; 32]).unwrap();
+    assert_eq!(opened.manifest.files.len(), 2);
+    assert_eq!(opened.manifest.hardlinks.len(), 1);
+    assert_eq!(opened.manifest.hardlinks[0].path, "sub/b.txt");
+    assert_eq!(opened.manifest.hardlinks[0].target, "a.txt");
+
+    extract(&archive, dst.path(), None).unwrap();
+    compare_trees(src.path(), dst.path());
+    let a = fs::metadata(dst.path().join("a.txt")).unwrap();
+    let b = fs::metadata(dst.path().join("sub/b.txt")).unwrap();
+    assert_eq!(a.ino(), b.ino());
+    assert_eq!(a.nlink(), 2);
+    let c = fs::metadata(dst.path().join("c.txt")).unwrap();
+    assert_ne!(a.ino(), c.ino());
+}
+
+#[test]
+fn test_listing_sidecar_matches_list() {
+    use arx_core::list::list_to;
+
+    let src = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+    write_tree(src.path());
+    let archive = tmp.path().join("listed.arx");
+    let listing = tmp.path().join("listed.txt");
+    let opts = PackOptions {
+        aead_key: Some([3u8; 32]),
+        emit_listing: Some(listing.clone()),
+        ..Default::default()
+    };
+    pack(&[src.path()], &archive, Some(&opts)).unwrap();
+
+    let sidecar = fs::read_to_string(&listing).unwrap();
+    let mut listed = Vec::new();
+    let lopts = arx_core::ListOptions {
+        aead_key: Some([3u8; 32]),
+        key_salt: [0u8; 32],
+        ..Default::default()
+    };
+    list_to(&archive, Some(&lopts), &mut listed).unwrap();
+    let listed = String::from_utf8(listed).unwrap();
+
+    assert_eq!(sidecar.lines().count(), listed.lines().count());
+    for (side, line) in sidecar.lines().zip(listed.lines()) {
+        let (prefix, hash) = side.rsplit_once("  blake3=").unwrap();
+        assert_eq!(prefix, line);
+        let path = line.split(" [E]").next().unwrap();
+        let expected = blake3::hash(&fs::read(src.path().join(path)).unwrap());
+        assert_eq!(hash, expected.to_hex().as_str());
+    }
+}
+
+/// A contiguous member is a regular file, not something to skip.
+#[test]
+fn test_pack_from_tar_keeps_contiguous_files() {
+    use arx_core::pack_from_tar;
+
+    let mut b = tar::Builder::new(Vec::new());
+    let mut h = tar::Header::new_gnu();
+    h.set_entry_type(tar::EntryType::Continuous);
+    h.set_mode(0o644);
+    h.set_size(11);
+    b.append_data(&mut h, "contig.bin", &b"contiguous\n"[..])
+        .unwrap();
+    let tar_bytes = b.into_inner().unwrap();
+
+    let tmp = TempDir::new().unwrap();
+    let dst = TempDir::new().unwrap();
+    let archive = tmp.path().join("contig.arx");
+    pack_from_tar(tar_bytes.as_slice(), &archive, None).unwrap();
+    extract(&archive, dst.path(), None).unwrap();
+    assert_eq!(
+        fs::read(dst.path().join("contig.bin")).unwrap(),
+        b"contiguous\n"
+    );
+}
+
+/// A GNU sparse member comes back with its holes as zeros.
+#[test]
+fn test_pack_from_tar_expands_gnu_sparse_files() {
+    use arx_core::pack_from_tar;
+
+    // 4 KiB of data at offset 8 KiB in a 16 KiB file, holes on both sides
+    let data = vec![0xABu8; 4096];
+    let mut h = tar::Header::new_gnu();
+    h.set_entry_type(tar::EntryType::GNUSparse);
+    h.set_mode(0o644);
+    h.set_size(data.len() as u64);
+    let gnu = h.as_gnu_mut().unwrap();
+    gnu.set_real_size(16 * 1024);
+    gnu.sparse[0].set_offset(8 * 1024);
+    gnu.sparse[0].set_length(data.len() as u64);
+    // GNU tar closes the map with an empty block at the real size
+    gnu.sparse[1].set_offset(16 * 1024);
+    gnu.sparse[1].set_length(0);
+    let mut b = tar::Builder::new(Vec::new());
+    b.append_data(&mut h, "sparse.bin", data.as_slice())
+        .unwrap();
+    let tar_bytes = b.into_inner().unwrap();
+
+    let tmp = TempDir::new().unwrap();
+    let dst = TempDir::new().unwrap();
+    let archive = tmp.path().join("sparse.arx");
+    pack_from_tar(tar_bytes.as_slice(), &archive, None).unwrap();
+    extract(&archive, dst.path(), None).unwrap();
+    let mut want = vec![0u8; 16 * 1024];
+    want[8 * 1024..12 * 1024].copy_from_slice(&data);
+    assert_eq!(fs::read(dst.path().join("sparse.bin")).unwrap(), want);
+}
+
+#[test]
+fn test_pack_from_tar_round_trip() {
+    use arx_core::pack_from_tar;
+    use arx_core::read::opened::Opened;
+
+    let long_dir = "deep/".repeat(30); // > 100 bytes: needs a GNU long-name record
+    let pax_path = format!("pax/{}", "p".repeat(150));
+    let big: Vec<u8> = (0u32..100_000).flat_map(|i| i.to_le_bytes()).collect();
+
+    let mut b = tar::Builder::new(Vec::new());
+    let mut dir = tar::Header::new_gnu();
+    dir.set_entry_type(tar::EntryType::Directory);
+    dir.set_mode(0o755);
+    dir.set_mtime(1_700_000_000);
+    dir.set_size(0);
+    b.append_data(&mut dir, "proj/", std::io::empty()).unwrap();
+    let file = |b: &mut tar::Builder<Vec<u8>>, path: &str, data: &[u8]| {
+        let mut h = tar::Header::new_gnu();
+        h.set_mode(0o640);
+        h.set_mtime(1_700_000_000);
+        h.set_size(data.len() as u64);
+        b.append_data(&mut h, path, data).unwrap();
+    };
+    file(&mut b, "proj/hello.txt", b"hello tar\n");
+    file(&mut b, "proj/big.bin", &big);
+    file(&mut b, &format!("{long_dir}leaf.txt"), b"long name\n");
+    b.append_pax_extensions([("path", pax_path.as_bytes())])
+        .unwrap();
+    file(&mut b, "short-placeholder", b"pax named\n");
+    let mut link = tar::Header::new_gnu();
+    link.set_entry_type(tar::EntryType::Symlink);
+    link.set_mode(0o777);
+    link.set_size(0);
+    b.append_link(&mut link, "proj/link", "hello.txt").unwrap();
+    let mut hard = tar::Header::new_gnu();
+    hard.set_entry_type(tar::EntryType::Link);
+    hard.set_mode(0o640);
+    hard.set_size(0);
+    b.append_link(&mut hard, "proj/hard.txt", "proj/hello.txt")
+        .unwrap();
+    let tar_bytes = b.into_inner().unwrap();
+
+    let tmp = TempDir::new().unwrap();
+    let dst = TempDir::new().unwrap();
+    let archive = tmp.path().join("from_tar.arx");
+    pack_from_tar(tar_bytes.as_slice(), &archive, None).unwrap();
+
+    let opened = Opened::open(&archive, None, [0u8; 32]).unwrap();
+    let fe = opened
+        .manifest
+        .files
+        .iter()
+        .find(|f| f.path == "proj/hello.txt")
+        .unwrap();
+    assert_eq!(fe.mode & 0o7777, 0o640);
+    assert_eq!(fe.mtime, 1_700_000_000);
+    assert_eq!(opened.manifest.dirs[0].path, "proj");
+    assert_eq!(opened.manifest.hardlinks[0].target, "proj/hello.txt");
+
+    extract(&archive, dst.path(), None).unwrap();
+    let d = dst.path();
+    assert_eq!(fs::read(d.join("proj/hello.txt")).unwrap(), b"hello tar\n");
+    assert_eq!(fs::read(d.join("proj/big.bin")).unwrap(), big);
+    assert_eq!(
+        fs::read(d.join(format!("{long_dir}leaf.txt"))).unwrap(),
+        b"long name\n"
+    );
+    assert_eq!(fs::read(d.join(&pax_path)).unwrap(), b"pax named\n");
+    assert_eq!(fs::read(d.join("proj/hard.txt")).unwrap(), b"hello tar\n");
+    #[cfg(unix)]
+    assert_eq!(
+        fs::read_link(d.join("proj/link")).unwrap(),
+        Path::new("hello.txt")
+    );
+}
+
+#[test]
+fn test_pack_stream_matches_file_pack() {
+    use arx_core::pack_stream;
+    use arx_core::read::extract::extract_file_to;
+    use arx_core::read::opened::Opened;
+
+    let src = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+    // A pseudo-random block repeated three times: dedups within the stream
+    let mut x: u64 = 0x2545_F491_4F6C_DD1D;
+    let block: Vec<u8> = (0..700_000)
+        .map(|_| {
+            x ^= x << 13;
+            x ^= x >> 7;
+            x ^= x << 17;
+            (x % 16) as u8
+        })
+        .collect();
+    let data = block.repeat(3);
+    fs::write(src.path().join("dump.bin"), &data).unwrap();
+
+    let key = [9u8; 32];
+    let opts = PackOptions {
+        deterministic: true,
+        aead_key: Some(key),
+        chunk_avg: 16 * 1024,
+        allow_zero_salt: true,
+        ..Default::default()
+    };
+    let from_file = tmp.path().join("file.arx");
+    let from_stream = tmp.path().join("stream.arx");
+    pack(&[src.path()], &from_file, Some(&opts)).unwrap();
+    pack_stream(data.as_slice(), "dump.bin", &from_stream, Some(&opts)).unwrap();
+
+    let a = Opened::open(&from_file, Some(key), [0u8; 32]).unwrap();
+    let b = Opened::open(&from_stream, Some(key), [0u8; 32]).unwrap();
+    assert_eq!(b.manifest.files.len(), 1);
+    let (fa, fb) = (&a.manifest.files[0], &b.manifest.files[0]);
+    assert_eq!(fb.path, "dump.bin");
+    assert_eq!(fa.u_size, fb.u_size);
+    let ids = |f: &arx_core::FileEntry| f.chunk_refs.iter().map(|r| r.id).collect::<Vec<_>>();
+    assert_eq!(ids(fa), ids(fb));
+    assert!(
+        b.table.len() < fb.chunk_refs.len(),
+        "no dedup within stream"
+    );
+    assert_eq!(a.table.len(), b.table.len());
+    for (ca, cb) in a.table.iter().zip(&b.table) {
+        assert_eq!(
+            (ca.codec, ca.u_size, ca.c_size),
+            (cb.codec, cb.u_size, cb.c_size)
+        );
+        assert_eq!(ca.blake3, cb.blake3);
+    }
+
+    let xopts = ExtractOptions {
+        aead_key: Some(key),
+        ..Default::default()
+    };
+    let mut out = Vec::new();
+    extract_file_to(&from_stream, "dump.bin", &mut out, Some(&xopts)).unwrap();
+    assert_eq!(out, data);
+}
+
+#[test]
+fn test_read_file_range_mid_file() {
+    use arx_core::read::extract::read_file_range;
+    use std::io::Read;
+
+    let src = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+    write_tree(src.path());
+    let archive = tmp.path().join("range.arx");
+    let opts = PackOptions {
+        password: Some("range-pw".into()),
+        chunk_avg: 64 * 1024,
+        ..Default::default()
+    };
+    pack(&[src.path()], &archive, Some(&opts)).unwrap();
+
+    let original = fs::read(src.path().join("big.bin")).unwrap();
+    let xopts = ExtractOptions {
+        password: Some("range-pw".into()),
+        ..Default::default()
+    };
+    let (start, len) = (300_001u64, 250_000u64);
+    let mut got = Vec::new();
+    read_file_range(&archive, "big.bin", start, len, Some(&xopts))
+        .unwrap()
+        .read_to_end(&mut got)
+        .unwrap();
+    assert_eq!(got, &original[start as usize..(start + len) as usize]);
+
+    // A range running past EOF is truncated at EOF
+    let tail_start = original.len() as u64 - 10;
+    let mut tail = Vec::new();
+    read_file_range(&archive, "big.bin", tail_start, 100, Some(&xopts))
+        .unwrap()
+        .read_to_end(&mut tail)
+        .unwrap();
+    assert_eq!(tail, &original[tail_start as usize..]);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_sparse_file_holes_round_trip() {
+    use arx_core::read::opened::Opened;
+    use std::io::{Read, Seek, SeekFrom, Write};
+    use std::os::unix::fs::MetadataExt;
+
+    let src = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+    let dst = TempDir::new().unwrap();
+    let path = src.path().join("disk.img");
+    let mut f = fs::File::create(&path).unwrap();
+    f.write_all(b"header").unwrap();
+    f.seek(SeekFrom::Start(64 << 20)).unwrap();
+    f.write_all(b"middle").unwrap();
+    f.set_len(128 << 20).unwrap();
+    drop(f);
+    let sparse_src = fs::metadata(&path).unwrap().blocks() * 512 < 1 << 20;
+
+    let archive = tmp.path().join("sparse.arx");
+    pack(&[src.path()], &archive, None).unwrap();
+    let opened = Opened::open(&archive, None, [0u8; 32]).unwrap();
+    let fe = &opened.manifest.files[0];
+    assert_eq!(fe.u_size, 128 << 20);
+    if sparse_src {
+        // Holes are recorded, not chunked
+        assert!(!fe.holes.is_empty());
+        let data: u64 = fe
+            .chunk_refs
+            .iter()
+            .map(|r| opened.table[r.id as usize].u_size)
+            .sum();
+        assert!(data < 1 << 20, "chunked {data} bytes");
+    }
+
+    let mut via_reader = Vec::new();
+    opened
+        .open_reader("disk.img")
+        .unwrap()
+        .read_to_end(&mut via_reader)
+        .unwrap();
+    assert_eq!(via_reader.len(), 128 << 20);
+    assert_eq!(&via_reader[..6], b"header");
+    assert_eq!(&via_reader[64 << 20..(64 << 20) + 6], b"middle");
+
+    extract(&archive, dst.path(), None).unwrap();
+    let out = fs::read(dst.path().join("disk.img")).unwrap();
+    assert_eq!(out, via_reader);
+    assert_eq!(out, fs::read(&path).unwrap());
+}
+
+#[test]
+fn test_pack_rejects_file_dir_conflict_across_inputs() {
+    let a = TempDir::new().unwrap();
+    let b = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+    fs::write(a.path().join("foo"), b"file").unwrap();
+    fs::create_dir(b.path().join("foo")).unwrap();
+    fs::write(b.path().join("foo/inner.txt"), b"nested").unwrap();
+
+    let err = pack(&[a.path(), b.path()], &tmp.path().join("x.arx"), None).unwrap_err();
+    assert!(err.to_string().contains("path conflict: foo"), "{err}");
+}
+
+#[test]
+fn test_append_records_three_batches() {
+    use arx_core::read::extract::verify;
+    use arx_core::read::opened::Opened;
+    use arx_core::{LogOptions, append_records, create_log};
+    use std::io::Read;
+
+    let tmp = TempDir::new().unwrap();
+    let archive = tmp.path().join("events.arx");
+    create_log(&archive, "events.log", &LogOptions::default(), None).unwrap();
+
+    let batches: [Vec<u8>; 3] = [
+        b"first record\n".repeat(10),
+        (0..300_000u32).map(|i| (i % 251) as u8).collect(),
+        b"third record\n".repeat(1000),
+    ];
+    let mut expected = Vec::new();
+    let mut first_chunks = Vec::new();
+    for (i, batch) in batches.iter().enumerate() {
+        append_records(&archive, batch).unwrap();
+        expected.extend_from_slice(batch);
+        verify(&archive, None).unwrap();
+
+        let opened = Opened::open(&archive, None, [0u8; 32]).unwrap();
+        let used: Vec<_> = opened
+            .table
+            .iter()
+            .filter(|e| e.u_size > 0)
+            .copied()
+            .collect();
+        if i == 0 {
+            first_chunks = used;
+        } else {
+            // Earlier chunks are neither moved nor rewritten
+            assert_eq!(&used[..first_chunks.len()], &first_chunks[..]);
+        }
+
+        let mut got = Vec::new();
+        opened
+            .open_reader("events.log")
+            .unwrap()
+            .read_to_end(&mut got)
+            .unwrap();
+        assert_eq!(got, expected);
+        assert_eq!(opened.manifest.files[0].u_size, expected.len() as u64);
+    }
+
+    // Running out of slots rebuilds the archive with more room
+    let small = tmp.path().join("small.arx");
+    let log = LogOptions {
+        slots: 1,
+        manifest_reserve: 64,
+    };
+    create_log(&small, "log", &log, None).unwrap();
+    for batch in &batches {
+        append_records(&small, batch).unwrap();
+    }
+    verify(&small, None).unwrap();
+    let opened = Opened::open(&small, None, [0u8; 32]).unwrap();
+    let mut got = Vec::new();
+    opened
+        .open_reader("log")
+        .unwrap()
+        .read_to_end(&mut got)
+        .unwrap();
+    assert_eq!(got, expected);
+}
+
+#[test]
+fn test_source_roots_recorded_only_on_request() {
+    use arx_core::read::opened::Opened;
+
+    let src = TempDir::new().unwrap();
+    let out = TempDir::new().unwrap();
+    write_tree(src.path());
+    let root = fs::canonicalize(src.path()).unwrap();
+    let meta_of = |name: &str, opts: &PackOptions| {
+        let archive = out.path().join(name);
+        pack(&[src.path()], &archive, Some(opts)).unwrap();
+        Opened::open(&archive, None, [0u8; 32])
+            .unwrap()
+            .manifest
+            .meta
+    };
+
+    // By default nothing about the build machine goes into the archive
+    let meta = meta_of("default.arx", &PackOptions::default());
+    assert!(meta.source_roots.is_empty());
+    assert!(meta.hostname.is_none());
+
+    let meta = meta_of(
+        "origin.arx",
+        &PackOptions {
+            record_origin: true,
+            ..Default::default()
+        },
+    );
+    assert_eq!(meta.source_roots, [root.display().to_string()]);
+    assert!(Path::new(&meta.source_roots[0]).is_absolute());
+    if cfg!(unix) {
+        assert!(meta.hostname.is_some_and(|h| !h.is_empty()));
+    }
+
+    let meta = meta_of(
+        "det.arx",
+        &PackOptions {
+            record_origin: true,
+            deterministic: true,
+            ..Default::default()
+        },
+    );
+    assert!(meta.source_roots.is_empty());
+    assert!(meta.hostname.is_none());
+}
+
+#[test]
+fn test_pack_append_adds_files_and_keeps_offsets() {
+    use arx_core::pack_append;
+    use arx_core::read::extract::verify;
+    use arx_core::read::opened::Opened;
+
+    let tmp = TempDir::new().unwrap();
+    let old = tmp.path().join("old");
+    fs::create_dir_all(&old).unwrap();
+    fs::write(old.join("a.txt"), b"original file\n".repeat(100)).unwrap();
+    let archive = tmp.path().join("backup.arx");
+    pack(&[old.as_path()], &archive, None).unwrap();
+
+    // First append: a new file, plus a copy of existing content that dedups
+    let new = tmp.path().join("new");
+    fs::create_dir_all(new.join("sub")).unwrap();
+    fs::write(new.join("sub/b.bin"), vec![7u8; 200_000]).unwrap();
+    fs::write(new.join("a_copy.txt"), b"original file\n".repeat(100)).unwrap();
+    pack_append(&archive, &[new.as_path()], None).unwrap();
+    verify(&archive, None).unwrap();
+
+    let opened = Opened::open(&archive, None, [0u8; 32]).unwrap();
+    let before = opened.table.clone();
+    let first_chunk = |path: &str| {
+        let f = opened
+            .manifest
+            .files
+            .iter()
+            .find(|f| f.path == path)
+            .unwrap();
+        f.chunk_refs[0].id
+    };
+    assert_eq!(
+        first_chunk("a_copy.txt"),
+        first_chunk("a.txt"),
+        "duplicate content should reuse the existing chunk"
+    );
+    drop(opened);
+
+    // Second append fits in the headroom: existing chunks must not move
+    let more = tmp.path().join("more");
+    fs::create_dir_all(&more).unwrap();
+    fs::write(more.join("c.txt"), b"third batch").unwrap();
+    pack_append(&archive, &[more.as_path()], None).unwrap();
+    verify(&archive, None).unwrap();
+    let opened = Opened::open(&archive, None, [0u8; 32]).unwrap();
+    assert_eq!(&opened.table[..before.len()], &before[..]);
+    drop(opened);
+
+    let dst = TempDir::new().unwrap();
+    extract(&archive, dst.path(), None).unwrap();
+    assert_eq!(
+        fs::read(dst.path().join("a.txt")).unwrap(),
+        b"original file\n".repeat(100)
+    );
+    assert_eq!(
+        fs::read(dst.path().join("sub/b.bin")).unwrap(),
+        vec![7u8; 200_000]
+    );
+    assert_eq!(
+        fs::read(dst.path().join("a_copy.txt")).unwrap(),
+        b"original file\n".repeat(100)
+    );
+    assert_eq!(fs::read(dst.path().join("c.txt")).unwrap(), b"third batch");
+}
+
+#[test]
+fn test_pack_append_encrypted_reseals() {
+    use arx_core::pack_append;
+
+    let tmp = TempDir::new().unwrap();
+    let old = tmp.path().join("old");
+    let new = tmp.path().join("new");
+    fs::create_dir_all(&old).unwrap();
+    fs::create_dir_all(&new).unwrap();
+    fs::write(old.join("a.txt"), b"secret a").unwrap();
+    fs::write(new.join("b.txt"), b"secret b").unwrap();
+    let archive = tmp.path().join("enc.arx");
+    let opts = PackOptions {
+        password: Some("hunter2".into()),
+        ..Default::default()
+    };
+    pack(&[old.as_path()], &archive, Some(&opts)).unwrap();
+
+    assert!(pack_append(&archive, &[new.as_path()], None).is_err());
+    pack_append(&archive, &[new.as_path()], Some(&opts)).unwrap();
+
+    let dst = TempDir::new().unwrap();
+    let xopts = ExtractOptions {
+        password: Some("hunter2".into()),
+        ..Default::default()
+    };
+    extract(&archive, dst.path(), Some(&xopts)).unwrap();
+    assert_eq!(fs::read(dst.path().join("a.txt")).unwrap(), b"secret a");
+    assert_eq!(fs::read(dst.path().join("b.txt")).unwrap(), b"secret b");
+}
+
+#[test]
+fn test_small_dedup_window_still_dedups_nearby_duplicates() {
+    use arx_core::read::extract::verify;
+    use arx_core::read::opened::Opened;
+
+    // Single-chunk files, packed in path order: a b c d e f
+    let src = TempDir::new().unwrap();
+    for (name, body) in [
+        ("a", "xxxx"),
+        ("b", "xxxx"),
+        ("c", "yyyy"),
+        ("d", "zzzz"),
+        ("e", "wwww"),
+        ("f", "xxxx"),
+    ] {
+        fs::write(src.path().join(name), body.repeat(50)).unwrap();
+    }
+    let tmp = TempDir::new().unwrap();
+
+    let chunks = |archive: &Path| {
+        let opened = Opened::open(archive, None, [0u8; 32]).unwrap();
+        let id = |p: &str| {
+            let f = opened.manifest.files.iter().find(|f| f.path == p).unwrap();
+            f.chunk_refs[0].id
+        };
+        (opened.table.len(), id("a"), id("b"), id("f"))
+    };
+
+    let unbounded = tmp.path().join("all.arx");
+    pack(&[src.path()], &unbounded, None).unwrap();
+    let (n, a, b, f) = chunks(&unbounded);
+    assert_eq!(n, 4);
+    assert!(a == b && b == f);
+
+    // With room for two hashes, "x" has aged out by the time f is packed
+    let windowed = tmp.path().join("win.arx");
+    let opts = PackOptions {
+        dedup_window: 2,
+        ..Default::default()
+    };
+    pack(&[src.path()], &windowed, Some(&opts)).unwrap();
+    verify(&windowed, None).unwrap();
+    let (n, a, b, f) = chunks(&windowed);
+    assert_eq!(n, 5);
+    assert_eq!(a, b, "adjacent duplicate should still dedup");
+    assert_ne!(a, f);
+
+    let dst = TempDir::new().unwrap();
+    extract(&windowed, dst.path(), None).unwrap();
+    compare_trees(src.path(), dst.path());
+}
+
+#[test]
+fn test_memory_repo_serves_packed_tree() {
+    use arx_core::pack_to_writer;
+    use arx_core::repo::{ArchiveRepo, OpenParams};
+    use arx_core::repo_factory::{Backend, open_repo};
+    use arx_core::repo_mem::MemArchiveRepo;
+    use std::io::{Cursor, Read};
+
+    let src = TempDir::new().unwrap();
+    write_tree(src.path());
+    let repo = MemArchiveRepo::pack(&[src.path()], None).unwrap();
+
+    let files = repo.list_files().unwrap();
+    assert!(!files.is_empty());
+    for (i, row) in files.iter().enumerate() {
+        let want = fs::read(src.path().join(&row.path)).unwrap();
+        let mut got = Vec::new();
+        repo.open_reader(&row.path)
+            .unwrap()
+            .read_to_end(&mut got)
+            .unwrap();
+        assert_eq!(got, want, "content mismatch for {}", row.path);
+        let mut by_index = Vec::new();
+        repo.open_reader_by_index(i)
+            .unwrap()
+            .read_to_end(&mut by_index)
+            .unwrap();
+        assert_eq!(by_index, want);
+
+        let map = repo.chunk_map(&row.path).unwrap();
+        assert_eq!(map.len(), row.chunks);
+        assert_eq!(map.iter().map(|c| c.u_len).sum::<u64>(), row.u_size);
+        let ids = |m: &[arx_core::domain::ChunkRow]| m.iter().map(|c| c.id).collect::<Vec<_>>();
+        assert_eq!(ids(&repo.chunk_map_by_index(i).unwrap()), ids(&map));
+
+        if want.len() > 2 {
+            let mut mid = Vec::new();
+            repo.open_range(&row.path, 1, want.len() as u64 - 2)
+                .unwrap()
+                .read_to_end(&mut mid)
+                .unwrap();
+            assert_eq!(mid, want[1..want.len() - 1]);
+        }
+    }
+    assert!(repo.open_reader("no/such/file").is_err());
+
+    // Same bytes through the factory, encrypted
+    let key = [9u8; 32];
+    let opts = PackOptions {
+        aead_key: Some(key),
+        ..Default::default()
+    };
+    let mut buf = Cursor::new(Vec::new());
+    pack_to_writer(&[src.path()], &mut buf, Some(&opts)).unwrap();
+    let params = OpenParams {
+        archive_path: "unused".into(),
+        aead_key: Some(key),
+        key_salt: [0u8; 32],
+    };
+    let repo = open_repo(Backend::Memory(buf.into_inner()), params).unwrap();
+    let rows = repo.list_files().unwrap();
+    assert_eq!(rows.len(), files.len());
+    assert!(rows.iter().all(|r| r.encrypted));
+    let mut got = Vec::new();
+    repo.open_reader(&rows[0].path)
+        .unwrap()
+        .read_to_end(&mut got)
+        .unwrap();
+    assert_eq!(got, fs::read(src.path().join(&rows[0].path)).unwrap());
+}
+
+#[test]
+fn test_read_chunk_returns_uncompressed_bytes() {
+    use arx_core::read::opened::Opened;
+
+    let src = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+    // Compressible, so (with zstd) chunk 0 has to be decompressed as well as opened
+    let text = b"the quick brown fox jumps over the lazy dog\n".repeat(100);
+    fs::write(src.path().join("fox.txt"), &text).unwrap();
+    let archive = tmp.path().join("c.arx");
+    let key = [0x42u8; 32];
+    let opts = PackOptions {
+        aead_key: Some(key),
+        ..Default::default()
+    };
+    pack(&[src.path()], &archive, Some(&opts)).unwrap();
+
+    let arx = Opened::open(&archive, Some(key), [0u8; 32]).unwrap();
+    assert_eq!(arx.table.len(), 1);
+    assert_eq!(arx.read_chunk(0).unwrap(), text);
+    assert!(arx.read_chunk(1).is_err());
+}
+
+#[test]
+fn test_opened_from_reader_matches_file_open() {
+    use arx_core::read::opened::Opened;
+    use std::io::{Cursor, Read};
+
+    let src = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+    write_tree(src.path());
+    let archive = tmp.path().join("r.arx");
+    pack(&[src.path()], &archive, None).unwrap();
+
+    let from_file = Opened::open(&archive, None, [0u8; 32]).unwrap();
+    let from_reader = Opened::from_reader(Cursor::new(fs::read(&archive).unwrap()), None).unwrap();
+    assert_eq!(from_reader.table, from_file.table);
+    for f in &from_file.manifest.files {
+        let (mut a, mut b) = (Vec::new(), Vec::new());
+        from_file
+            .open_reader(&f.path)
+            .unwrap()
+            .read_to_end(&mut a)
+            .unwrap();
+        from_reader
+            .open_reader(&f.path)
+            .unwrap()
+            .read_to_end(&mut b)
+            .unwrap();
+        assert_eq!(a, b);
+    }
+}
+
+#[test]
+fn test_list_and_extract_from_cursor() {
+    use arx_core::extract_from;
+    use arx_core::read::opened::Opened;
+    use std::io::Cursor;
+
+    let src = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+    let dst = TempDir::new().unwrap();
+    write_tree(src.path());
+    let archive = tmp.path().join("c.arx");
+    pack(&[src.path()], &archive, None).unwrap();
+
+    let arx = Opened::from_reader(Cursor::new(fs::read(&archive).unwrap()), None).unwrap();
+    assert!(arx.tail().is_some());
+    let mut paths: Vec<String> = arx.list_entries().map(|e| e.path).collect();
+    paths.sort();
+    let from_file = Opened::open(&archive, None, [0u8; 32]).unwrap();
+    let mut want: Vec<String> = from_file.list_entries().map(|e| e.path).collect();
+    want.sort();
+    assert_eq!(paths, want);
+
+    extract_from(&arx, dst.path(), None).unwrap();
+    for p in &paths {
+        assert_eq!(
+            fs::read(dst.path().join(p)).unwrap(),
+            fs::read(src.path().join(p)).unwrap(),
+            "{p}"
+        );
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn test_extract_owner_map_remaps_uid() {
+    use std::collections::HashMap;
+    use std::os::unix::fs::MetadataExt;
+
+    let src = TempDir::new().unwrap();
+    fs::create_dir(src.path().join("d")).unwrap();
+    fs::write(src.path().join("d/f.txt"), b"owned").unwrap();
+    let tmp = TempDir::new().unwrap();
+    let archive = tmp.path().join("own.arx");
+    pack(&[src.path()], &archive, None).unwrap();
+    let me = fs::metadata(src.path().join("d/f.txt")).unwrap();
+
+    // An empty map restores the recorded owner, which anyone may chown to
+    let dst = TempDir::new().unwrap();
+    let opts = ExtractOptions {
+        owner_map: Some(HashMap::new()),
+        ..Default::default()
+    };
+    extract(&archive, dst.path(), Some(&opts)).unwrap();
+    let md = fs::metadata(dst.path().join("d/f.txt")).unwrap();
+    assert_eq!((md.uid(), md.gid()), (me.uid(), me.gid()));
+
+    if me.uid() != 0 {
+        eprintln!("skipping uid remap check: needs root to chown to another uid");
+        return;
+    }
+    let dst = TempDir::new().unwrap();
+    let opts = ExtractOptions {
+        owner_map: Some(HashMap::from([(0, 4242)])),
+        ..Default::default()
+    };
+    extract(&archive, dst.path(), Some(&opts)).unwrap();
+    for p in ["d", "d/f.txt"] {
+        let md = fs::metadata(dst.path().join(p)).unwrap();
+        assert_eq!(md.uid(), 4242, "{p}");
+        assert_eq!(md.gid(), me.gid(), "{p}");
+    }
+}
+
+#[test]
+fn test_rekey_then_extract_with_only_new_key() {
+    use arx_core::crypto::rekey::rekey;
+    use arx_core::read::extract::verify;
+
+    let src = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+    write_tree(src.path());
+    let (old_key, new_key) = ([0x11u8; 32], [0x22u8; 32]);
+    let archive = tmp.path().join("old.arx");
+    let opts = PackOptions {
+        aead_key: Some(old_key),
+        ..Default::default()
+    };
+    pack(&[src.path()], &archive, Some(&opts)).unwrap();
+
+    let keyed = |k: [u8; 32]| ExtractOptions {
+        aead_key: Some(k),
+        ..Default::default()
+    };
+    // A wrong "old" key is rejected before anything is written
+    let rekeyed = tmp.path().join("new.arx");
+    assert!(rekey(&archive, new_key, new_key, [7u8; 32], &rekeyed).is_err());
+    assert!(!rekeyed.exists());
+
+    rekey(&archive, old_key, new_key, [7u8; 32], &rekeyed).unwrap();
+    assert_eq!(
+        fs::metadata(&rekeyed).unwrap().len(),
+        fs::metadata(&archive).unwrap().len()
+    );
+    assert!(verify(&rekeyed, Some(&keyed(old_key))).is_err());
+    verify(&rekeyed, Some(&keyed(new_key))).unwrap();
+
+    let dst = TempDir::new().unwrap();
+    extract(&rekeyed, dst.path(), Some(&keyed(new_key))).unwrap();
+    compare_trees(src.path(), dst.path());
+}
+
+#[test]
+fn test_each_recipient_opens_the_archive() {
+    use arx_core::crypto::rekey::{rekey, rekey_recipients};
+    use arx_core::read::extract::verify;
+
+    let src = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+    write_tree(src.path());
+    let (alice, bob, eve) = ([0xA1u8; 32], [0xB0u8; 32], [0xEEu8; 32]);
+    let archive = tmp.path().join("shared.arx");
+    let opts = PackOptions {
+        recipients: vec![alice, bob],
+        ..Default::default()
+    };
+    pack(&[src.path()], &archive, Some(&opts)).unwrap();
+
+    let keyed = |k: [u8; 32]| ExtractOptions {
+        aead_key: Some(k),
+        ..Default::default()
+    };
+    for key in [alice, bob] {
+        verify(&archive, Some(&keyed(key))).unwrap();
+        let dst = TempDir::new().unwrap();
+        extract(&archive, dst.path(), Some(&keyed(key))).unwrap();
+        compare_trees(src.path(), dst.path());
+    }
+    assert!(verify(&archive, Some(&keyed(eve))).is_err());
+
+    // Recipients replace a single key; plain rekey does not drop the keyring
+    let both = PackOptions {
+        aead_key: Some(eve),
+        ..opts.clone()
+    };
+    assert!(pack(&[src.path()], &tmp.path().join("x.arx"), Some(&both)).is_err());
+    assert!(rekey(&archive, alice, eve, [7u8; 32], &tmp.path().join("y.arx")).is_err());
+
+    // Hand the archive from alice to bob and eve
+    let moved = tmp.path().join("moved.arx");
+    assert!(rekey_recipients(&archive, eve, &[bob], &moved).is_err());
+    rekey_recipients(&archive, alice, &[bob, eve], &moved).unwrap();
+    assert!(verify(&moved, Some(&keyed(alice))).is_err());
+    for key in [bob, eve] {
+        let dst = TempDir::new().unwrap();
+        extract(&moved, dst.path(), Some(&keyed(key))).unwrap();
+        compare_trees(src.path(), dst.path());
+    }
+}
+
+#[test]
+fn test_pack_list_uses_given_archive_paths() {
+    use arx_core::error::ArxError;
+    use arx_core::pack_list;
+    use arx_core::read::opened::Opened;
+    use std::path::PathBuf;
+
+    let src = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+    write_tree(src.path());
+    let entries: Vec<(PathBuf, String)> = vec![
+        (src.path().join("sub/data.bin"), "bin/payload.bin".into()),
+        (src.path().join("hello.txt"), "./docs//README".into()),
+        (src.path().join("big.bin"), "big.bin".into()),
+    ];
+    let archive = tmp.path().join("listed.arx");
+    pack_list(&entries, &archive, None).unwrap();
+
+    let opened = Opened::open(&archive, None, [0u8; 32]).unwrap();
+    let paths: Vec<_> = opened.manifest.files.iter().map(|f| &f.path[..]).collect();
+    assert_eq!(paths, ["bin/payload.bin", "docs/README", "big.bin"]);
+    assert!(opened.manifest.dirs.is_empty());
+
+    let dst = TempDir::new().unwrap();
+    extract(&archive, dst.path(), None).unwrap();
+    for (src_path, name) in [
+        ("sub/data.bin", "bin/payload.bin"),
+        ("hello.txt", "docs/README"),
+        ("big.bin", "big.bin"),
+    ] {
+        assert!(
+            fs::read(dst.path().join(name)).unwrap()
+                == fs::read(src.path().join(src_path)).unwrap(),
+            "{name}"
+        );
+    }
+
+    // Names are sanitized, must be unique, and sources must be files
+    let bad = tmp.path().join("bad.arx");
+    for entries in [
+        vec![(src.path().join("hello.txt"), "../escape".to_string())],
+        vec![
+            (src.path().join("hello.txt"), "same".to_string()),
+            (src.path().join("big.bin"), "same".to_string()),
+        ],
+        vec![(src.path().join("sub"), "sub".to_string())],
+    ] {
+        let err = pack_list(&entries, &bad, None).unwrap_err();
+        assert!(matches!(err, ArxError::Format(_)), "{err}");
+        assert!(!bad.exists());
+    }
+}
+
+#[test]
+fn test_pack_rejects_escaping_entry_paths() {
+    use arx_core::error::ArxError;
+    use arx_core::pack_stream;
+    use arx_core::read::opened::Opened;
+
+    let tmp = TempDir::new().unwrap();
+    let archive = tmp.path().join("evil.arx");
+    for name in ["../evil", "a/../../evil", "/etc/passwd"] {
+        let err = pack_stream(&b"payload"[..], name, &archive, None).unwrap_err();
+        assert!(matches!(err, ArxError::Format(_)), "{name}: {err}");
+    }
+
+    let ok = tmp.path().join("ok.arx");
+    pack_stream(&b"payload"[..], "./dir//file.bin", &ok, None).unwrap();
+    let opened = Opened::open(&ok, None, [0u8; 32]).unwrap();
+    assert_eq!(opened.manifest.files[0].path, "dir/file.bin");
+}
+
+#[test]
+fn test_differential_pack_stores_only_new_chunks() {
+    use arx_core::error::ArxError;
+    use arx_core::read::extract::verify;
+    use arx_core::read::layout::layout;
+    use arx_core::read::opened::Opened;
+
+    let src = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+    write_tree(src.path());
+    // Incompressible, so the full archive isn't small to begin with
+    let mut x: u64 = 0x9E37_79B9_7F4A_7C15;
+    let noise: Vec<u8> = (0..2_000_000)
+        .map(|_| {
+            x ^= x << 13;
+            x ^= x >> 7;
+            x ^= x << 17;
+            x as u8
+        })
+        .collect();
+    fs::write(src.path().join("noise.bin"), &noise).unwrap();
+    let full = tmp.path().join("full.arx");
+    pack(&[src.path()], &full, None).unwrap();
+
+    // A day later: one file edited, one added
+    fs::write(src.path().join("hello.txt"), b"hello again\n").unwrap();
+    fs::write(src.path().join("sub/new.txt"), b"new file\n").unwrap();
+    let diff = tmp.path().join("diff.arx");
+    let opts = PackOptions {
+        parent: Some(full.clone()),
+        ..Default::default()
+    };
+    pack(&[src.path()], &diff, Some(&opts)).unwrap();
+
+    let (full_len, diff_len) = (
+        fs::metadata(&full).unwrap().len(),
+        fs::metadata(&diff).unwrap().len(),
+    );
+    assert!(
+        diff_len * 10 < full_len,
+        "diff {diff_len} vs full {full_len}"
+    );
+    verify(&diff, None).unwrap();
+
+    // The layout says which chunks' offsets point into the parent
+    let arx = Opened::open(&diff, None, [0u8; 32]).unwrap();
+    let files = layout(&arx).unwrap().files;
+    let chunks = |path: &str| {
+        files
+            .iter()
+            .find(|f| f.path == path)
+            .unwrap()
+            .chunks
+            .clone()
+    };
+    assert!(chunks("noise.bin").iter().all(|c| c.parent));
+    assert!(chunks("sub/new.txt").iter().all(|c| !c.parent));
+    drop(arx);
+
+    let err = extract(&diff, tmp.path().join("orphan").as_path(), None).unwrap_err();
+    assert!(matches!(err, ArxError::Format(_)), "{err}");
+
+    let dst = TempDir::new().unwrap();
+    let xopts = ExtractOptions {
+        parent: Some(full),
+        ..Default::default()
+    };
+    extract(&diff, dst.path(), Some(&xopts)).unwrap();
+    compare_trees(src.path(), dst.path());
+}
+
+#[test]
+fn test_list_tree_groups_files_under_their_directories() {
+    use arx_core::ListOptions;
+    use arx_core::list::list_to;
+
+    let src = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+    write_tree(src.path());
+    fs::create_dir_all(src.path().join("sub/deeper")).unwrap();
+    fs::write(src.path().join("sub/deeper/leaf.txt"), b"leaf").unwrap();
+    let archive = tmp.path().join("tree.arx");
+    pack(&[src.path()], &archive, None).unwrap();
+
+    let opts = ListOptions {
+        tree: true,
+        human: true,
+        ..Default::default()
+    };
+    let mut out = Vec::new();
+    list_to(&archive, Some(&opts), &mut out).unwrap();
+    let text = String::from_utf8(out).unwrap();
+    let lines: Vec<&str> = text.lines().collect();
+    let at = |prefix: &str| {
+        lines
+            .iter()
+            .position(|l| l.starts_with(prefix))
+            .unwrap_or_else(|| panic!("no line starting {prefix:?} in\n{text}"))
+    };
+
+    // big.bin is 800_000 bytes
+    assert!(lines[at("big.bin")].contains("u=781."), "{text}");
+    let sub = at("sub/  ");
+    // 4096 + 0 + 4 bytes of files below sub/
+    assert!(lines[sub].contains("u=4.0KiB"), "{text}");
+    let deeper = at("  deeper/  ");
+    let leaf = at("    leaf.txt");
+    let data = at("  data.bin");
+    let empty = at("  empty.txt");
+    assert!(
+        sub < data && data < deeper && deeper < leaf && leaf < empty,
+        "{text}"
+    );
+    assert!(at("hello.txt") < sub);
+}
+
+#[test]
+fn test_list_entries_returns_a_row_per_file() {
+    use arx_core::list_entries;
+    use arx_core::read::opened::Opened;
+
+    let src = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+    write_tree(src.path());
+    let archive = tmp.path().join("rows.arx");
+    pack(&[src.path()], &archive, None).unwrap();
+
+    let rows = list_entries(&archive, None).unwrap();
+    let opened = Opened::open(&archive, None, [0u8; 32]).unwrap();
+    assert_eq!(rows.len(), opened.manifest.files.len());
+    let big = rows.iter().find(|r| r.path == "big.bin").unwrap();
+    assert_eq!(big.u_size, 800_000);
+    assert!(big.chunks > 0 && big.c_size > 0 && !big.encrypted);
+}
+
+#[test]
+fn test_list_sort_by_size_descending() {
+    use arx_core::list::list_to;
+    use arx_core::{ListOptions, SortKey};
+
+    let src = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+    for (name, len) in [
+        ("a.txt", 300),
+        ("b.txt", 5000),
+        ("c.txt", 40),
+        ("d.txt", 5000),
+    ] {
+        fs::write(src.path().join(name), vec![b'x'; len]).unwrap();
+    }
+    let archive = tmp.path().join("sorted.arx");
+    pack(&[src.path()], &archive, None).unwrap();
+
+    let paths = |sort, reverse| {
+        let opts = ListOptions {
+            sort,
+            reverse,
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        list_to(&archive, Some(&opts), &mut out).unwrap();
+        String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|l| l.split_whitespace().next().unwrap().to_string())
+            .collect::<Vec<_>>()
+    };
+    // Equal sizes keep manifest order
+    assert_eq!(
+        paths(Some(SortKey::USize), true),
+        ["b.txt", "d.txt", "a.txt", "c.txt"]
+    );
+    assert_eq!(
+        paths(Some(SortKey::USize), false),
+        ["c.txt", "a.txt", "b.txt", "d.txt"]
+    );
+    assert_eq!(
+        paths(Some(SortKey::Name), true),
+        ["d.txt", "c.txt", "b.txt", "a.txt"]
+    );
+    assert_eq!(paths(None, true), ["a.txt", "b.txt", "c.txt", "d.txt"]);
+}
+
+#[test]
+fn test_failed_pack_leaves_existing_output_untouched() {
+    let src = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+    write_tree(src.path());
+    let archive = tmp.path().join("out.arx");
+    fs::write(&archive, b"previous archive").unwrap();
+
+    // The listing is written after the archive body but before the rename,
+    // so an unwritable listing path fails the pack mid-way
+    let opts = PackOptions {
+        emit_listing: Some(tmp.path().join("missing/dir/listing.txt")),
+        ..Default::default()
+    };
+    assert!(pack(&[src.path()], &archive, Some(&opts)).is_err());
+    assert_eq!(fs::read(&archive).unwrap(), b"previous archive");
+    assert!(!archive.with_extension("arx.tmp").exists());
+
+    pack(&[src.path()], &archive, None).unwrap();
+    let dst = TempDir::new().unwrap();
+    extract(&archive, dst.path(), None).unwrap();
+    compare_trees(src.path(), dst.path());
+}
+
+#[test]
+fn test_durable_pack_round_trips() {
+    let src = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+    let dst = TempDir::new().unwrap();
+    write_tree(src.path());
+    let archive = tmp.path().join("durable.arx");
+    let opts = PackOptions {
+        durable: true,
+        ..Default::default()
+    };
+    pack(&[src.path()], &archive, Some(&opts)).unwrap();
+    extract(&archive, dst.path(), None).unwrap();
+    compare_trees(src.path(), dst.path());
+}
+
+#[test]
+fn test_extract_strip_components_drops_leading_dir() {
+    let src = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+    let dst = TempDir::new().unwrap();
+    fs::create_dir_all(src.path().join("project/src")).unwrap();
+    fs::write(src.path().join("project/README"), b"readme").unwrap();
+    fs::write(src.path().join("project/src/main.rs"), b"fn main() {}").unwrap();
+    fs::write(src.path().join("top.txt"), b"left at the root").unwrap();
+    let archive = tmp.path().join("strip.arx");
+    pack(&[src.path()], &archive, None).unwrap();
+
+    let opts = ExtractOptions {
+        strip_components: 1,
+        ..Default::default()
+    };
+    extract(&archive, dst.path(), Some(&opts)).unwrap();
+    assert_eq!(fs::read(dst.path().join("README")).unwrap(), b"readme");
+    assert_eq!(
+        fs::read(dst.path().join("src/main.rs")).unwrap(),
+        b"fn main() {}"
+    );
+    // Nothing is left of a top-level file's path once it is stripped
+    assert!(!dst.path().join("top.txt").exists());
+    assert!(!dst.path().join("project").exists());
+}
+
+#[test]
+fn test_extract_prefix_writes_under_subdir() {
+    let src = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+    let dst = TempDir::new().unwrap();
+    write_tree(src.path());
+    let archive = tmp.path().join("prefix.arx");
+    pack(&[src.path()], &archive, None).unwrap();
+
+    let opts = ExtractOptions {
+        prefix: Some("restored/v1".into()),
+        ..Default::default()
+    };
+    extract(&archive, dst.path(), Some(&opts)).unwrap();
+    compare_trees(src.path(), &dst.path().join("restored/v1"));
+
+    let escaping = ExtractOptions {
+        prefix: Some("../outside".into()),
+        ..Default::default()
+    };
+    assert!(extract(&archive, dst.path(), Some(&escaping)).is_err());
+}
+
+#[cfg(feature = "zstd")]
+#[test]
+fn test_verbose_list_reports_codec_histogram() {
+    use arx_core::codec::CodecId;
+    use arx_core::{ListOptions, chunk_stats, list::list_to};
+
+    let src = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+    // Text compresses under zstd; LCG noise doesn't, so it falls back to Store
+    fs::write(
+        src.path().join("text.txt"),
+        "compressible line\n".repeat(20_000),
+    )
+    .unwrap();
+    let mut x = 7u64;
+    let noise: Vec<u8> = (0..300_000)
+        .map(|_| {
+            x = x
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (x >> 56) as u8
+        })
+        .collect();
+    fs::write(src.path().join("noise.bin"), &noise).unwrap();
+    let archive = tmp.path().join("mixed.arx");
+    pack(&[src.path()], &archive, None).unwrap();
+
+    let (files, total) = chunk_stats(&archive, None).unwrap();
+    assert!(total.count(CodecId::Zstd) > 0, "{total:?}");
+    assert!(total.count(CodecId::Store) > 0, "{total:?}");
+    assert_eq!(
+        files.iter().map(|s| s.chunks).sum::<u64>(),
+        total.chunks,
+        "no chunk is shared here"
+    );
+    assert!(total.min_size <= total.avg_size() && total.avg_size() <= total.max_size);
+
+    let opts = ListOptions {
+        verbose: true,
+        ..Default::default()
+    };
+    let mut out = Vec::new();
+    list_to(&archive, Some(&opts), &mut out).unwrap();
+    let text = String::from_utf8(out).unwrap();
+    let totals = text.lines().last().unwrap();
+    assert!(totals.starts_with("total: "), "{text}");
+    assert!(
+        totals.contains("store=") && totals.contains("zstd="),
+        "{text}"
+    );
+    let noise_at = text
+        .lines()
+        .position(|l| l.starts_with("noise.bin"))
+        .unwrap();
+    let noise_stats = text.lines().nth(noise_at + 1).unwrap();
+    assert!(noise_stats.contains("store="), "{text}");
+    assert!(noise_stats.contains("size min="), "{text}");
+}
+
+#[test]
+fn test_extract_streams_store_chunks_in_bounded_windows() {
+    use arx_core::codec::CodecId;
+    use arx_core::read::extract::extract_file_to;
+    use arx_core::read::opened::Opened;
+
+    /// Records the largest single write it sees.
+    struct Recorder {
+        data: Vec<u8>,
+        max_write: usize,
+    }
+    impl std::io::Write for Recorder {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.max_write = self.max_write.max(buf.len());
+            self.data.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let src = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+    let dst = TempDir::new().unwrap();
+    let mut x = 99u64;
+    let data: Vec<u8> = (0..1_500_000)
+        .map(|_| {
+            x = x
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (x >> 56) as u8
+        })
+        .collect();
+    fs::write(src.path().join("big.bin"), &data).unwrap();
+    let archive = tmp.path().join("store.arx");
+    let popts = PackOptions {
+        codec: Some(CodecId::Store),
+        ..Default::default()
+    };
+    pack(&[src.path()], &archive, Some(&popts)).unwrap();
+
+    const WINDOW: usize = 4096;
+    let opened = Opened::open(&archive, None, [0u8; 32]).unwrap();
+    let map = opened.chunk_map_for("big.bin").unwrap();
+    assert!(map.iter().all(|c| c.codec == CodecId::Store as u8));
+    assert!(
+        map.iter().any(|c| c.u_len > WINDOW as u64),
+        "fixture needs a chunk larger than the window"
+    );
+
+    let xopts = ExtractOptions {
+        io_buf_size: WINDOW,
+        ..Default::default()
+    };
+    let mut rec = Recorder {
+        data: Vec::new(),
+        max_write: 0,
+    };
+    extract_file_to(&archive, "big.bin", &mut rec, Some(&xopts)).unwrap();
+    assert!(rec.data == data, "streamed bytes differ");
+    assert!(
+        rec.max_write <= WINDOW,
+        "wrote {} bytes at once",
+        rec.max_write
+    );
+
+    extract(&archive, dst.path(), Some(&xopts)).unwrap();
+    assert!(fs::read(dst.path().join("big.bin")).unwrap() == data);
+}
+
+#[test]
+fn test_range_in_store_chunk_reads_only_the_range() {
+    use arx_core::codec::CodecId;
+    use arx_core::read::opened::Opened;
+    use std::io::{Cursor, Read, Seek, SeekFrom};
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    /// Counts the bytes read through it.
+    struct Counting {
+        inner: Cursor<Vec<u8>>,
+        read: Arc<AtomicU64>,
+    }
+    impl Read for Counting {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = self.inner.read(buf)?;
+            self.read.fetch_add(n as u64, Ordering::Relaxed);
+            Ok(n)
+        }
+    }
+    impl Seek for Counting {
+        fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+            self.inner.seek(pos)
+        }
+    }
+
+    let src = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+    let mut x = 5u64;
+    let data: Vec<u8> = (0..1_500_000)
+        .map(|_| {
+            x = x
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (x >> 56) as u8
+        })
+        .collect();
+    fs::write(src.path().join("big.bin"), &data).unwrap();
+    let archive = tmp.path().join("store.arx");
+    let popts = PackOptions {
+        codec: Some(CodecId::Store),
+        ..Default::default()
+    };
+    pack(&[src.path()], &archive, Some(&popts)).unwrap();
+
+    let read = Arc::new(AtomicU64::new(0));
+    let arx = Opened::from_reader(
+        Counting {
+            inner: Cursor::new(fs::read(&archive).unwrap()),
+            read: read.clone(),
+        },
+        None,
+    )
+    .unwrap();
+    let map = arx.chunk_map_for("big.bin").unwrap();
+    assert!(map.len() > 1, "fixture should span multiple chunks");
+    let range = |start: u64, len: u64| {
+        let before = read.load(Ordering::Relaxed);
+        let mut out = Vec::new();
+        arx.open_range("big.bin", start, len)
+            .unwrap()
+            .read_to_end(&mut out)
+            .unwrap();
+        assert!(out == data[start as usize..(start + len) as usize]);
+        read.load(Ordering::Relaxed) - before
+    };
+
+    // Ten bytes from the middle of the largest chunk
+    let big = map.iter().max_by_key(|c| c.u_len).unwrap();
+    assert!(big.u_len > 64 * 1024, "{}", big.u_len);
+    assert_eq!(range(big.file_off + big.u_len / 2, 10), 10);
+
+    // Across a chunk boundary: still just the range
+    assert_eq!(range(map[1].file_off - 5, 10), 10);
+    assert_eq!(range(0, data.len() as u64), data.len() as u64);
+}
+
+#[test]
+fn test_extract_refuses_archives_over_the_uncompressed_cap() {
+    let src = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+    let dst = TempDir::new().unwrap();
+    // Compresses to almost nothing, like a bomb in miniature
+    fs::write(src.path().join("a.bin"), vec![0u8; 300_000]).unwrap();
+    fs::write(src.path().join("b.bin"), vec![1u8; 300_000]).unwrap();
+    let archive = tmp.path().join("bomb.arx");
+    pack(&[src.path()], &archive, None).unwrap();
+
+    let capped = |cap| ExtractOptions {
+        max_total_uncompressed: Some(cap),
+        ..Default::default()
+    };
+    let err = extract(&archive, dst.path(), Some(&capped(400_000))).unwrap_err();
+    assert!(err.to_string().contains("400000-byte limit"), "{err}");
+    assert_eq!(fs::read_dir(dst.path()).unwrap().count(), 0);
+
+    let err = arx_core::read::extract::extract_file_to(
+        &archive,
+        "a.bin",
+        &mut Vec::new(),
+        Some(&capped(1000)),
+    )
+    .unwrap_err();
+    assert!(err.to_string().contains("limit"), "{err}");
+
+    extract(&archive, dst.path(), Some(&capped(600_000))).unwrap();
+    assert_eq!(fs::read(dst.path().join("b.bin")).unwrap().len(), 300_000);
+}
+
+#[test]
+fn test_extract_restores_mtimes() {
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    let src = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+    let dst = TempDir::new().unwrap();
+    fs::create_dir(src.path().join("sub")).unwrap();
+    fs::write(src.path().join("sub/old.txt"), b"from 2020").unwrap();
+    let file_t = UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+    let dir_t = UNIX_EPOCH + Duration::from_secs(1_500_000_000);
+    fs::File::options()
+        .write(true)
+        .open(src.path().join("sub/old.txt"))
+        .unwrap()
+        .set_modified(file_t)
+        .unwrap();
+    fs::File::open(src.path().join("sub"))
+        .unwrap()
+        .set_modified(dir_t)
+        .unwrap();
+    let archive = tmp.path().join("mtime.arx");
+    pack(&[src.path()], &archive, None).unwrap();
+
+    let close = |p: &Path, want: SystemTime| {
+        let got = fs::metadata(p).unwrap().modified().unwrap();
+        let diff = got.duration_since(want).unwrap_or_else(|e| e.duration());
+        assert!(
+            diff <= Duration::from_secs(1),
+            "{}: {got:?} vs {want:?}",
+            p.display()
+        );
+    };
+    extract(&archive, dst.path(), None).unwrap();
+    close(&dst.path().join("sub/old.txt"), file_t);
+    close(&dst.path().join("sub"), dir_t);
+
+    let touched = TempDir::new().unwrap();
+    let opts = ExtractOptions {
+        restore_mtime: false,
+        ..Default::default()
+    };
+    extract(&archive, touched.path(), Some(&opts)).unwrap();
+    let got = fs::metadata(touched.path().join("sub/old.txt"))
+        .unwrap()
+        .modified()
+        .unwrap();
+    assert!(got > file_t + Duration::from_secs(86_400 * 365));
+}
+
+#[test]
+fn test_chunk_cache_serves_overlapping_ranges_once() {
+    use arx_core::read::opened::Opened;
+    use std::io::{Cursor, Read, Seek, SeekFrom};
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    /// Counts the bytes read through it.
+    struct Counting {
+        inner: Cursor<Vec<u8>>,
+        read: Arc<AtomicU64>,
+    }
+    impl Read for Counting {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = self.inner.read(buf)?;
+            self.read.fetch_add(n as u64, Ordering::Relaxed);
+            Ok(n)
+        }
+    }
+    impl Seek for Counting {
+        fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+            self.inner.seek(pos)
+        }
+    }
+
+    let src = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+    write_tree(src.path());
+    let archive = tmp.path().join("cache.arx");
+    pack(&[src.path()], &archive, None).unwrap();
+    let expected = fs::read(src.path().join("big.bin")).unwrap();
+
+    let read = Arc::new(AtomicU64::new(0));
+    let arx = Opened::from_reader(
+        Counting {
+            inner: Cursor::new(fs::read(&archive).unwrap()),
+            read: read.clone(),
+        },
+        None,
+    )
+    .unwrap();
+    let map = arx.chunk_map_for("big.bin").unwrap();
+    assert!(map.len() > 1, "fixture should span multiple chunks");
+    let stored: u64 = map.iter().map(|c| c.c_len).sum();
+    arx.set_chunk_cache(16 << 20);
+
+    let range = |start: u64, len: u64| {
+        let mut out = Vec::new();
+        arx.open_range("big.bin", start, len)
+            .unwrap()
+            .read_to_end(&mut out)
+            .unwrap();
+        assert_eq!(out, &expected[start as usize..(start + len) as usize]);
+    };
+    let before = read.load(Ordering::Relaxed);
+    range(0, expected.len() as u64);
+    assert_eq!(read.load(Ordering::Relaxed) - before, stored);
+    range(100_000, 500_000);
+    range(1, 10);
+    assert_eq!(
+        read.load(Ordering::Relaxed) - before,
+        stored,
+        "overlapping ranges should come from the cache"
+    );
+
+    arx.set_chunk_cache(0);
+    let cached = read.load(Ordering::Relaxed);
+    range(100_000, 500_000);
+    assert!(
+        read.load(Ordering::Relaxed) > cached,
+        "with the cache off the range is read again"
+    );
+}
+
+#[test]
+fn test_diff_reports_changed_files_and_chunks() {
+    use arx_core::read::diff::{ChangeKind, diff};
+
+    let src = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+    write_tree(src.path());
+    let old = tmp.path().join("old.arx");
+    pack(&[src.path()], &old, None).unwrap();
+
+    // Edit a few bytes in the middle of big.bin only
+    let mut big = fs::read(src.path().join("big.bin")).unwrap();
+    big[400_000..400_008].copy_from_slice(b"CHANGED!");
+    fs::write(src.path().join("big.bin"), &big).unwrap();
+    let new = tmp.path().join("new.arx");
+    pack(&[src.path()], &new, None).unwrap();
+
+    assert!(diff(&old, &old, None).unwrap().is_empty());
+
+    let changes = diff(&old, &new, None).unwrap();
+    assert_eq!(changes.len(), 1, "{changes:?}");
+    let c = &changes[0];
+    assert_eq!((c.kind, c.path.as_str()), (ChangeKind::Modified, "big.bin"));
+    assert_eq!((c.old_size, c.new_size), (Some(800_000), Some(800_000)));
+    assert!(!c.chunks_added.is_empty() && !c.chunks_removed.is_empty());
+    let total = arx_core::read::opened::Opened::open(&new, None, [0u8; 32])
+        .unwrap()
+        .chunk_map_for("big.bin")
+        .unwrap()
+        .len();
+    assert!(
+        c.chunks_added.len() < total,
+        "unchanged chunks are not reported"
+    );
+
+    // Added and removed files
+    fs::remove_file(src.path().join("hello.txt")).unwrap();
+    fs::write(src.path().join("new.txt"), b"brand new").unwrap();
+    let newer = tmp.path().join("newer.arx");
+    pack(&[src.path()], &newer, None).unwrap();
+    let kinds: Vec<(ChangeKind, String)> = diff(&new, &newer, None)
+        .unwrap()
+        .into_iter()
+        .map(|c| (c.kind, c.path))
+        .collect();
+    assert_eq!(
+        kinds,
+        [
+            (ChangeKind::Removed, "hello.txt".to_string()),
+            (ChangeKind::Added, "new.txt".to_string()),
+        ]
+    );
+}
+
+#[cfg(feature = "zstd")]
+#[test]
+fn test_zstd_dict_shrinks_similar_small_files() {
+    use arx_core::codec::CodecId;
+    use arx_core::codec::zstdc::train_dict;
+    use arx_core::read::extract::{VerifyOptions, verify_with};
+    use arx_core::read::opened::Opened;
+
+    let src = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+    let dst = TempDir::new().unwrap();
+    // API-response-like JSON: same keys and shape, different values
+    let docs: Vec<Vec<u8>> = (0..400u32)
+        .map(|i| {
+            format!(
+                "{{\"id\":{i},\"user\":{{\"name\":\"user-{}\",\"email\":\"user{}@example.com\",\
+                 \"active\":{}}},\"status\":\"{}\",\"tags\":[\"alpha\",\"beta\",\"t{}\"],\
+                 \"created_at\":\"2024-03-{:02}T12:{:02}:00Z\",\"score\":{}.{}}}\n",
+                i * 7919 % 1000,
+                i * 31 % 977,
+                i % 2 == 0,
+                ["ok", "pending", "failed"][i as usize % 3],
+                i % 13,
+                i % 28 + 1,
+                i % 60,
+                i * 13 % 100,
+                i % 10
+            )
+            .into_bytes()
+        })
+        .collect();
+    for (i, doc) in docs.iter().enumerate() {
+        fs::write(src.path().join(format!("resp-{i:04}.json")), doc).unwrap();
+    }
+    let dict = train_dict(&docs, 4096).unwrap();
+
+    let plain = tmp.path().join("plain.arx");
+    let with_dict = tmp.path().join("dict.arx");
+    let base = PackOptions {
+        deterministic: true,
+        ..Default::default()
+    };
+    pack(&[src.path()], &plain, Some(&base)).unwrap();
+    pack(
+        &[src.path()],
+        &with_dict,
+        Some(&PackOptions {
+            zstd_dict: Some(dict.clone()),
+            ..base
+        }),
+    )
+    .unwrap();
+
+    let (plain_len, dict_len) = (
+        fs::metadata(&plain).unwrap().len(),
+        fs::metadata(&with_dict).unwrap().len(),
+    );
+    assert!(
+        dict_len < plain_len,
+        "with dictionary {dict_len} bytes, without {plain_len}"
+    );
+    let arx = Opened::open(&with_dict, None, [0u8; 32]).unwrap();
+    assert_eq!(arx.manifest.meta.zstd_dict.as_deref(), Some(&dict[..]));
+    assert!(
+        arx.table
+            .iter()
+            .any(|ce| ce.codec == CodecId::ZstdDict as u8)
+    );
+
+    verify_with(
+        &with_dict,
+        None,
+        &VerifyOptions {
+            deep: true,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    extract(&with_dict, dst.path(), None).unwrap();
+    compare_trees(src.path(), dst.path());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_follow_symlinks_packs_link_targets() {
+    use arx_core::read::opened::Opened;
+    use std::os::unix::fs::symlink;
+
+    let outside = TempDir::new().unwrap();
+    let src = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+    let dst = TempDir::new().unwrap();
+    let real = outside.path().join("real");
+    fs::create_dir_all(real.join("nested")).unwrap();
+    fs::write(real.join("data.txt"), b"linked content\n").unwrap();
+    fs::write(real.join("nested/deep.txt"), b"deeper\n").unwrap();
+    fs::write(src.path().join("own.txt"), b"own\n").unwrap();
+    symlink(real.join("data.txt"), src.path().join("link.txt")).unwrap();
+    symlink(&real, src.path().join("again")).unwrap();
+    symlink(&real, src.path().join("dir_link")).unwrap(); // same directory twice
+    symlink(src.path(), src.path().join("loop")).unwrap(); // back to the root
+
+    let paths = |archive: &Path| -> Vec<String> {
+        let opened = Opened::open(archive, None, [0u8; 32]).unwrap();
+        let mut p: Vec<String> = opened
+            .manifest
+            .files
+            .iter()
+            .map(|f| f.path.clone())
+            .collect();
+        p.sort();
+        p
+    };
+
+    let plain = tmp.path().join("plain.arx");
+    pack(&[src.path()], &plain, None).unwrap();
+    assert_eq!(paths(&plain), ["own.txt"]);
+
+    let followed = tmp.path().join("followed.arx");
+    let opts = PackOptions {
+        follow_symlinks: true,
+        ..Default::default()
+    };
+    pack(&[src.path()], &followed, Some(&opts)).unwrap();
+    assert_eq!(
+        paths(&followed),
+        [
+            "again/data.txt",
+            "again/nested/deep.txt",
+            "link.txt",
+            "own.txt"
+        ]
+    );
+    extract(&followed, dst.path(), None).unwrap();
+    assert_eq!(
+        fs::read(dst.path().join("link.txt")).unwrap(),
+        b"linked content\n"
+    );
+    assert!(
+        fs::symlink_metadata(dst.path().join("link.txt"))
+            .unwrap()
+            .is_file()
+    );
+    assert_eq!(
+        fs::read(dst.path().join("again/nested/deep.txt")).unwrap(),
+        b"deeper\n"
+    );
+
+    // The gitignore walker follows the same way
+    let gi = tmp.path().join("gi.arx");
+    let opts = PackOptions {
+        respect_gitignore: true,
+        ..opts
+    };
+    pack(&[src.path()], &gi, Some(&opts)).unwrap();
+    assert_eq!(paths(&gi), paths(&followed));
+}
+
+/// A link that sorts before its own target must not hide the real directory.
+#[cfg(unix)]
+#[test]
+fn test_follow_symlinks_keeps_real_dir_behind_earlier_link() {
+    use arx_core::read::opened::Opened;
+    use std::os::unix::fs::symlink;
+
+    let src = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+    fs::create_dir(src.path().join("z_real")).unwrap();
+    fs::write(src.path().join("z_real/f.txt"), b"real\n").unwrap();
+    symlink(src.path().join("z_real"), src.path().join("a_link")).unwrap();
+
+    for respect_gitignore in [false, true] {
+        let out = tmp.path().join(format!("gi{respect_gitignore}.arx"));
+        let opts = PackOptions {
+            follow_symlinks: true,
+            respect_gitignore,
+            ..Default::default()
+        };
+        pack(&[src.path()], &out, Some(&opts)).unwrap();
+        let opened = Opened::open(&out, None, [0u8; 32]).unwrap();
+        let mut paths: Vec<&str> = opened
+            .manifest
+            .files
+            .iter()
+            .map(|f| f.path.as_str())
+            .collect();
+        paths.sort();
+        assert_eq!(paths, ["a_link/f.txt", "z_real/f.txt"]);
+    }
+}
+
+/// A pack whose archive never lands leaves no index sidecar behind.
+#[test]
+fn test_index_sidecar_not_left_when_archive_fails() {
+    let src = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+    write_tree(src.path());
+    // Renaming the finished archive onto a non-empty directory fails
+    let out = tmp.path().join("busy.arx");
+    fs::create_dir(&out).unwrap();
+    fs::write(out.join("occupant"), b"x").unwrap();
+    let index = tmp.path().join("busy.arxidx");
+    let opts = PackOptions {
+        emit_index: Some(index.clone()),
+        ..Default::default()
+    };
+    assert!(pack(&[src.path()], &out, Some(&opts)).is_err());
+    assert!(!index.exists());
+    assert!(!index.with_extension("arxidx.tmp").exists());
+}
+
+/// The `emit_index` sidecar is the archive's prefix up to the data region, and
+/// lists and maps chunks exactly as the archive does.
+#[test]
+fn test_index_sidecar_lists_like_archive() {
+    use arx_core::list::list_to;
+    use arx_core::read::opened::Opened;
+    use arx_core::{ListOptions, Superblock};
+    use std::io::Read;
+
+    let src = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+    write_tree(src.path());
+    let archive = tmp.path().join("idx.arx");
+    let index = tmp.path().join("idx.arxidx");
+    let opts = PackOptions {
+        emit_index: Some(index.clone()),
+        ..Default::default()
+    };
+    pack(&[src.path()], &archive, Some(&opts)).unwrap();
+
+    let full = fs::read(&archive).unwrap();
+    let idx = fs::read(&index).unwrap();
+    let sb = Superblock::read_from(&mut &full[..]).unwrap();
+    assert_eq!(idx.len() as u64, sb.data_off);
+    assert_eq!(&idx[..], &full[..idx.len()]);
+    // Written through a temp file that is renamed into place
+    assert!(!tmp.path().join("idx.arxidx.tmp").exists());
+
+    let listing = |path: &Path, index: bool, verbose: bool| {
+        let lopts = ListOptions {
+            index,
+            verbose,
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        list_to(path, Some(&lopts), &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    };
+    assert_eq!(
+        listing(&index, true, false),
+        listing(&archive, false, false)
+    );
+    assert_eq!(listing(&index, true, true), listing(&archive, false, true));
+
+    // Without `index` the sidecar is rejected like any archive cut short
+    let lopts = ListOptions::default();
+    assert!(list_to(&index, Some(&lopts), &mut Vec::new()).is_err());
+    assert!(Opened::open(&index, None, [0u8; 32]).is_err());
+
+    let from_idx = Opened::open_index(&index, None).unwrap();
+    let from_arx = Opened::open(&archive, None, [0u8; 32]).unwrap();
+    let map = |o: &Opened| {
+        o.chunk_map_for("big.bin")
+            .unwrap()
+            .iter()
+            .map(|c| (c.id, c.codec, c.file_off, c.u_len, c.c_len, c.data_off))
+            .collect::<Vec<_>>()
+    };
+    assert!(map(&from_arx).len() > 1);
+    assert_eq!(map(&from_idx), map(&from_arx));
+
+    // The data itself is not there
+    let mut buf = Vec::new();
+    let read = from_idx
+        .open_reader("hello.txt")
+        .and_then(|mut r| Ok(r.read_to_end(&mut buf)?));
+    assert!(read.is_err());
+
+    // and a whole archive is not an index
+    let err = Opened::open_index(&archive, None).err().unwrap();
+    assert!(err.to_string().contains("not an archive index"), "{err}");
+}
+
+/// `chunk_boundaries` cuts where `pack` does: increasing ends that sum to the
+/// file size, with the hashes the chunk table records.
+#[test]
+fn test_chunk_boundaries_match_packed_chunks() {
+    use arx_core::chunking::fastcdc::{ChunkParams, chunk_boundaries};
+    use arx_core::read::opened::Opened;
+
+    let src = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+    write_tree(src.path());
+    let big = src.path().join("big.bin");
+    let params = ChunkParams::from_avg(16 * 1024);
+    let cuts = chunk_boundaries(&big, params).unwrap();
+
+    let size = fs::metadata(&big).unwrap().len();
+    assert!(cuts.len() > 1);
+    assert!(cuts.windows(2).all(|w| w[0].end < w[1].end));
+    let lens: Vec<u64> = cuts
+        .iter()
+        .scan(0, |start, b| Some(b.end - std::mem::replace(start, b.end)))
+        .collect();
+    assert_eq!(lens.iter().sum::<u64>(), size);
+    assert!(lens.iter().all(|&n| n <= params.max as u64));
+
+    let archive = tmp.path().join("cuts.arx");
+    let opts = PackOptions {
+        chunk_avg: 16 * 1024,
+        ..Default::default()
+    };
+    pack(&[src.path()], &archive, Some(&opts)).unwrap();
+    let opened = Opened::open(&archive, None, [0u8; 32]).unwrap();
+    let packed: Vec<(u64, [u8; 32])> = opened
+        .chunk_map_for("big.bin")
+        .unwrap()
+        .iter()
+        .map(|c| (c.file_off + c.u_len, opened.table[c.id as usize].blake3))
+        .collect();
+    let found: Vec<(u64, [u8; 32])> = cuts.iter().map(|b| (b.end, b.blake3)).collect();
+    assert_eq!(found, packed);
+
+    assert!(chunk_boundaries(&big, ChunkParams::from_avg(32)).is_err());
+}
+
+/// `pack_boundaries` walks like `pack`: excluded files are left out and each
+/// remaining file is cut with the pack's chunk parameters.
+#[test]
+fn test_pack_boundaries_follow_pack_walk() {
+    use arx_core::chunking::fastcdc::{ChunkParams, chunk_boundaries};
+    use arx_core::pack_boundaries;
+
+    let src = TempDir::new().unwrap();
+    write_tree(src.path());
+    fs::write(src.path().join("skip.tmp"), b"scratch").unwrap();
+    let opts = PackOptions {
+        chunk_avg: 16 * 1024,
+        exclude: vec!["*.tmp".into()],
+        ..Default::default()
+    };
+    let files = pack_boundaries(&[src.path()], Some(&opts)).unwrap();
+
+    assert!(!files.iter().any(|(p, _)| p.ends_with("skip.tmp")));
+    let (_, cuts) = files.iter().find(|(p, _)| p.ends_with("big.bin")).unwrap();
+    let big = src.path().join("big.bin");
+    let want = chunk_boundaries(&big, ChunkParams::from_avg(16 * 1024)).unwrap();
+    assert_eq!(cuts.len(), want.len());
+    assert!(
+        cuts.iter()
+            .zip(&want)
+            .all(|(a, b)| (a.end, a.blake3) == (b.end, b.blake3))
+    );
+}
+
+/// Files under `small_file_threshold` become one chunk each, however the
+/// chunker would have cut them.
+#[test]
+fn test_small_file_threshold_stores_files_whole() {
+    use arx_core::Superblock;
+
+    let src = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+    let many = src.path().join("many");
+    fs::create_dir_all(&many).unwrap();
+    for i in 0..10_000u32 {
+        let body = format!("{i:>8} {}\n", "x".repeat(90));
+        assert_eq!(body.len(), 100);
+        fs::write(many.join(format!("{i:05}.txt")), body).unwrap();
+    }
+    // Several chunks' worth at a 16 KiB average, but under the threshold
+    let mid: Vec<u8> = (0u32..50_000).flat_map(|i| i.to_le_bytes()).collect();
+    fs::write(src.path().join("mid.bin"), &mid).unwrap();
+
+    let chunk_count = |archive: &Path| {
+        let sb = Superblock::read_from(&mut fs::File::open(archive).unwrap()).unwrap();
+        sb.chunk_count
+    };
+    let base = PackOptions {
+        chunk_avg: 16 * 1024,
+        ..Default::default()
+    };
+    let chunked = tmp.path().join("chunked.arx");
+    pack(&[src.path()], &chunked, Some(&base)).unwrap();
+    let whole = tmp.path().join("whole.arx");
+    let opts = PackOptions {
+        small_file_threshold: 256 * 1024,
+        ..base.clone()
+    };
+    pack(&[src.path()], &whole, Some(&opts)).unwrap();
+
+    assert!(chunk_count(&chunked) > 10_001, "{}", chunk_count(&chunked));
+    assert_eq!(chunk_count(&whole), 10_001);
+
+    let dst = TempDir::new().unwrap();
+    extract(&whole, dst.path(), None).unwrap();
+    compare_trees(src.path(), dst.path());
+
+    let too_big = PackOptions {
+        small_file_threshold: 64 << 20,
+        ..Default::default()
+    };
+    let err = pack(&[src.path()], &tmp.path().join("x.arx"), Some(&too_big)).unwrap_err();
+    assert!(err.to_string().contains("small_file_threshold"), "{err}");
+}
+
+/// Encrypted deterministic packs with a fixed salt are byte-identical,
+/// whatever the input location, mtimes or rayon thread count.
+#[test]
+fn test_deterministic_encrypted_packs_are_identical() {
+    let a = TempDir::new().unwrap();
+    let b = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+    for root in [a.path(), b.path()] {
+        write_tree(root);
+        // Duplicate content across files exercises chunk dedup ids
+        fs::write(root.join("sub/copy.bin"), [0xFFu8; 4096]).unwrap();
+        fs::write(
+            root.join("zzz.bin"),
+            fs::read(root.join("big.bin")).unwrap(),
+        )
+        .unwrap();
+    }
+    let opts = PackOptions {
+        deterministic: true,
+        aead_key: Some([0x42; 32]),
+        key_salt: [7u8; 32],
+        chunk_avg: 16 * 1024,
+        compress_manifest: true,
+        ..Default::default()
+    };
+    let first = tmp.path().join("a.arx");
+    pack(&[a.path()], &first, Some(&opts)).unwrap();
+    let second = tmp.path().join("b.arx");
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(1)
+        .build()
+        .unwrap()
+        .install(|| pack(&[b.path()], &second, Some(&opts)))
+        .unwrap();
+    assert_eq!(fs::read(&first).unwrap(), fs::read(&second).unwrap());
+
+    let xopts = ExtractOptions {
+        aead_key: opts.aead_key,
+        ..Default::default()
+    };
+    let dst = TempDir::new().unwrap();
+    extract(&first, dst.path(), Some(&xopts)).unwrap();
+    compare_trees(a.path(), dst.path());
+}
+
+/// `open_range` clamps `len` to the file, accepts `start == u_size` as an
+/// empty range, and rejects a `start` beyond it.
+#[test]
+fn test_open_range_bounds() {
+    use arx_core::read::opened::Opened;
+    use std::io::Read;
+
+    let src = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+    write_tree(src.path());
+    let archive = tmp.path().join("ranges.arx");
+    pack(&[src.path()], &archive, None).unwrap();
+    let arx = Opened::open(&archive, None, [0u8; 32]).unwrap();
+    let read = |path: &str, start: u64, len: u64| {
+        let mut r = arx.open_range(path, start, len).unwrap();
+        let want = r.remaining();
+        let mut out = Vec::new();
+        r.read_to_end(&mut out).unwrap();
+        assert_eq!(out.len() as u64, want);
+        out
+    };
+
+    // start == len == 0, on an empty file and on a non-empty one
+    assert!(read("sub/empty.txt", 0, 0).is_empty());
+    assert!(read("sub/empty.txt", 0, 100).is_empty());
+    assert!(read("hello.txt", 0, 0).is_empty());
+
+    // start == u_size: an empty range, not an error
+    let size = b"hello world\n".len() as u64;
+    assert!(read("hello.txt", size, 10).is_empty());
+
+    // len is clamped to what is left
+    assert_eq!(read("hello.txt", 6, u64::MAX), b"world\n");
+    assert_eq!(read("hello.txt", 0, 5), b"hello");
+
+    // start > u_size
+    let err = arx.open_range("hello.txt", size + 1, 1).err().unwrap();
+    assert!(
+        err.to_string()
+            .contains("past the end of the file (12 bytes)"),
+        "{err}"
+    );
+    assert!(arx.open_range("sub/empty.txt", 1, 0).is_err());
+}
+
+/// The read-only attribute of a file packed on Windows is recorded and set
+/// again on extract.
+#[cfg(windows)]
+#[test]
+fn test_windows_readonly_attribute_round_trips() {
+    use arx_core::container::manifest::ATTR_READONLY;
+    use arx_core::read::opened::Opened;
+
+    let set_readonly = |p: &Path, ro: bool| {
+        let mut perm = fs::metadata(p).unwrap().permissions();
+        perm.set_readonly(ro);
+        fs::set_permissions(p, perm).unwrap();
+    };
+    let src = TempDir::new().unwrap();
+    let tmp = TempDir::new().unwrap();
+    let dst = TempDir::new().unwrap();
+    fs::write(src.path().join("locked.txt"), b"do not edit\n").unwrap();
+    fs::write(src.path().join("open.txt"), b"edit away\n").unwrap();
+    set_readonly(&src.path().join("locked.txt"), true);
+
+    let archive = tmp.path().join("attrs.arx");
+    pack(&[src.path()], &archive, None).unwrap();
+    let arx = Opened::open(&archive, None, [0u8; 32]).unwrap();
+    let attrs = |path: &str| {
+        let fe = arx.manifest.files.iter().find(|f| f.path == path);
+        fe.unwrap().attrs
+    };
+    assert_eq!(attrs("locked.txt"), Some(ATTR_READONLY));
+    assert_eq!(attrs("open.txt"), None);
+
+    extract(&archive, dst.path(), None).unwrap();
+    let ro = |p: &Path| fs::metadata(p).unwrap().permissions().readonly();
+    assert!(ro(&dst.path().join("locked.txt")));
+    assert!(!ro(&dst.path().join("open.txt")));
+    assert_eq!(
+        fs::read(dst.path().join("locked.txt")).unwrap(),
+        b"do not edit\n"
+    );
+
+    // Read-only files would keep the temp dirs from being removed
+    set_readonly(&src.path().join("locked.txt"), false);
+    set_readonly(&dst.path().join("locked.txt"), false);
+}
+
+/// Setting `cancel` mid-run stops pack and extract at the next chunk.
+#[test]
+fn test_cancel_stops_after_first_chunk() {
+    use arx_core::container::superblock::Superblock;
+    use arx_core::pack_to_writer;
+    use arx_core::read::extract::extract_file_to;
+    use std::io::{Cursor, Seek, SeekFrom, Write};
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    /// Trips `cancel` on its first write at or past `trip_at`, and records
+    /// every seek landing there: one per chunk written.
+    struct TripWriter {
+        inner: Cursor<Vec<u8>>,
+        trip_at: u64,
+        cancel: Arc<AtomicBool>,
+        chunk_seeks: usize,
+    }
+    impl Write for TripWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            if self.inner.position() >= self.trip_at {
+                self.cancel.store(true, Ordering::Relaxed);
+            }
+            self.inner.write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+    impl Seek for TripWriter {
+        fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+            let at = self.inner.seek(pos)?;
+            if at >= self.trip_at && !self.cancel.load(Ordering::Relaxed) {
+                self.chunk_seeks += 1;
+            }
+            Ok(at)
+        }
+    }
+
+    let tmp = TempDir::new().unwrap();
+    let src = tmp.path().join("src");
+    fs::create_dir_all(&src).unwrap();
+    // Incompressible, so every chunk is stored as is
+    let data: Vec<u8> = (0u64..200_000)
+        .map(|i| (i.wrapping_mul(0x9E37_79B9_7F4A_7C15) >> 56) as u8)
+        .collect();
+    fs::write(src.join("data.bin"), &data).unwrap();
+    let opts = PackOptions {
+        deterministic: true,
+        chunk_avg: 16 * 1024,
+        ..Default::default()
+    };
+    let archive = tmp.path().join("a.arx");
+    pack(&[src.as_path()], &archive, Some(&opts)).unwrap();
+    let sb = Superblock::read_from(&mut fs::File::open(&archive).unwrap()).unwrap();
+    assert!(sb.chunk_count > 3, "{}", sb.chunk_count);
+
+    // Pack: the flag trips while the first chunk is written
+    let cancel = Arc::new(AtomicBool::new(false));
+    let mut w = TripWriter {
+        inner: Cursor::new(Vec::new()),
+        trip_at: sb.data_off,
+        cancel: cancel.clone(),
+        chunk_seeks: 0,
+    };
+    let cancelling = PackOptions {
+        cancel: Some(cancel.clone()),
+        ..opts.clone()
+    };
+    let err = pack_to_writer(&[src.as_path()], &mut w, Some(&cancelling)).unwrap_err();
+    assert_eq!(err.to_string(), "Format error: cancelled");
+    assert_eq!(w.chunk_seeks, 1);
+
+    // Already cancelled: pack leaves no archive, not even its temp file
+    let out = tmp.path().join("never.arx");
+    pack(&[src.as_path()], &out, Some(&cancelling)).unwrap_err();
+    assert!(!out.exists());
+    assert!(!out.with_extension("arx.tmp").exists());
+    let dst = TempDir::new().unwrap();
+    let eopts = ExtractOptions {
+        cancel: Some(cancel.clone()),
+        ..Default::default()
+    };
+    let err = extract(&archive, dst.path(), Some(&eopts)).unwrap_err();
+    assert_eq!(err.to_string(), "Format error: cancelled");
+    assert!(!dst.path().join("data.bin").exists());
+
+    // Extract: the flag trips on the first bytes out; only that chunk is written
+    cancel.store(false, Ordering::Relaxed);
+    struct TripOnWrite(Vec<u8>, Arc<AtomicBool>);
+    impl Write for TripOnWrite {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.1.store(true, Ordering::Relaxed);
+            self.0.write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+    let mut w = TripOnWrite(Vec::new(), cancel.clone());
+    let err = extract_file_to(&archive, "data.bin", &mut w, Some(&eopts)).unwrap_err();
+    assert_eq!(err.to_string(), "Format error: cancelled");
+    assert!(!w.0.is_empty() && w.0.len() < data.len(), "{}", w.0.len());
+    assert_eq!(w.0[..], data[..w.0.len()]);
+}
@@ -47,6 +47,8 @@ fn arx_err(e: ArxError) -> Status {
             Status::internal("storage error")
         }
         ArxError::Format(msg) => Status::invalid_argument(msg),
+        e @ ArxError::Corrupt { .. } => Status::data_loss(e.to_string()),
+        e @ ArxError::UnsupportedCodec(_) => Status::unimplemented(e.to_string()),
     }
 }
 
@@ -64,6 +66,8 @@ fn arx_err_ctx(e: ArxError, tenant_id: &str, archive_id: &str) -> Status {
             Status::internal("storage error")
         }
         ArxError::Format(msg) => Status::invalid_argument(msg),
+        e @ ArxError::Corrupt { .. } => Status::data_loss(e.to_string()),
+        e @ ArxError::UnsupportedCodec(_) => Status::unimplemented(e.to_string()),
     }
 }
 
@@ -245,6 +249,7 @@ impl ArxService for ArxServiceImpl {
         let opts = aead_key.map(|k| ListOptions {
             aead_key: Some(k),
             key_salt: [0u8; 32],
+            ..Default::default()
         });
 
         // Use the repo to get entries
@@ -1,16 +1,32 @@
+use std::collections::HashMap;
 use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
 use std::time::{SystemTime, UNIX_EPOCH};
 
-use arx_core::container::superblock::Superblock;
-use arx_core::crud::CrudArchive;
+use arx_core::container::superblock::{FLAG_CHUNK_AEAD, FLAG_ENCRYPTED, Superblock};
+use arx_core::crud::{CrudArchive, normalize_path};
+use arx_core::crypto::aead::AeadAlg;
 use arx_core::crypto::hex::parse_hex_array;
 use arx_core::crypto::kdf::derive_key;
+use arx_core::crypto::nonce::random_salt;
+use arx_core::crypto::rekey::{rekey, rekey_password};
 use arx_core::error::Result;
-use arx_core::read::extract::verify;
+use arx_core::policy::Policy;
+use arx_core::read::checksums::{ChecksumAlg, file_checksums};
+use arx_core::read::export::export_tar;
+use arx_core::read::extract::{
+    VerifyOptions, extract_file_to, fix_tail, verify_quick, verify_report,
+};
+use arx_core::read::opened::Opened;
+use arx_core::read::stats::archive_stats;
 use arx_core::repo::{ArchiveRepo, OpenParams};
 use arx_core::repo_factory::{Backend, open_repo};
-use arx_core::{ExtractOptions, ListOptions, PackOptions, extract, list, pack};
+use arx_core::{
+    ExtractOptions, ListOptions, PackOptions, SortKey, extract, list, pack, pack_boundaries,
+    pack_from_tar, pack_list, pack_stream, plan_pack,
+};
+
+use super::shell::run_shell;
 
 #[cfg(unix)]
 use std::os::unix::fs::PermissionsExt;
@@ -90,6 +106,7 @@ fn infer_mtime(src: &Path, override_mtime: Option<u64>) -> u64 {
 
 // ── Command handlers ──────────────────────────────────────────────────────────
 
+#[allow(clippy::too_many_arguments)]
 pub fn handle_pack(
     out: PathBuf,
     inputs: Vec<PathBuf>,
@@ -97,40 +114,319 @@ pub fn handle_pack(
     min_gain: f32,
     encrypt_raw_hex: Option<String>,
     password: Option<String>,
+    recipients: Vec<String>,
+    aes_gcm: bool,
+    chunk_avg: usize,
+    listing: Option<PathBuf>,
+    index: bool,
+    record_origin: bool,
+    dedup_window: usize,
+    encrypt_paths: Vec<String>,
+    public_manifest: bool,
+    exclude: Vec<String>,
+    gitignore: bool,
+    dereference: bool,
+    dry_run: bool,
+    emit_boundaries: Option<PathBuf>,
+    parent: Option<PathBuf>,
+    stdin_name: Option<String>,
+    tar: Option<PathBuf>,
+    files_from: Option<PathBuf>,
+    zstd_dict: Option<PathBuf>,
 ) -> Result<()> {
     let refs: Vec<_> = inputs.iter().map(|p| p.as_path()).collect();
     let aead_key = encrypt_raw_hex
         .map(|hex| parse_hex_array::<32>(&hex))
         .transpose()?;
-    eprintln!("packing {} input(s) → {}", inputs.len(), out.display());
+    let recipients = recipients
+        .iter()
+        .map(|hex| parse_hex_array::<32>(hex))
+        .collect::<Result<Vec<_>>>()?;
+    let zstd_dict = zstd_dict.map(std::fs::read).transpose()?;
     let opts = PackOptions {
         deterministic,
         min_gain,
         aead_key,
         password,
+        recipients,
+        chunk_avg,
+        emit_listing: listing,
+        emit_index: index.then(|| out.with_extension("arxidx")),
+        record_origin,
+        dedup_window,
+        encrypt_paths,
+        public_manifest,
+        exclude,
+        respect_gitignore: gitignore,
+        follow_symlinks: dereference,
+        parent,
+        zstd_dict,
+        aead_alg: if aes_gcm {
+            AeadAlg::Aes256Gcm
+        } else {
+            AeadAlg::XChaCha20Poly1305
+        },
         ..Default::default()
     };
-    pack(&refs, &out, Some(&opts))?;
+    if let Some(dest) = emit_boundaries {
+        return write_boundaries(&dest, &refs, &opts);
+    }
+    if dry_run {
+        let plan = plan_pack(&refs, Some(&opts))?;
+        let dedup = if plan.chunk_refs == 0 {
+            0.0
+        } else {
+            100.0 * (1.0 - plan.unique_chunks as f64 / plan.chunk_refs as f64)
+        };
+        println!("files:             {}", plan.files);
+        println!("dirs:              {}", plan.dirs);
+        println!("chunk refs:        {}", plan.chunk_refs);
+        println!("unique chunks:     {}", plan.unique_chunks);
+        println!("dedup rate:        {dedup:.1}%");
+        println!("logical bytes:     {}", plan.u_bytes);
+        println!("data bytes:        {}", plan.c_bytes);
+        println!("est. output bytes: {}", plan.est_bytes);
+        return Ok(());
+    }
+    if let Some(list) = files_from {
+        let entries = read_file_list(&list)?;
+        eprintln!(
+            "packing {} listed file(s) → {}",
+            entries.len(),
+            out.display()
+        );
+        pack_list(&entries, &out, Some(&opts))?;
+    } else if let Some(tar) = tar {
+        eprintln!("importing {} → {}", tar.display(), out.display());
+        let f = std::io::BufReader::new(std::fs::File::open(&tar)?);
+        pack_from_tar(f, &out, Some(&opts))?;
+    } else if let Some(name) = stdin_name {
+        eprintln!("packing stdin as {name} → {}", out.display());
+        pack_stream(std::io::stdin().lock(), &name, &out, Some(&opts))?;
+    } else {
+        eprintln!("packing {} input(s) → {}", inputs.len(), out.display());
+        pack(&refs, &out, Some(&opts))?;
+    }
     eprintln!("pack: done → {}", out.display());
     Ok(())
 }
 
+/// `pack --files-from` entries: NUL-separated if `list` contains a NUL,
+/// otherwise one per line; each is `SOURCE` or `SOURCE<TAB>ARCHIVE_PATH`.
+/// Empty entries are skipped.
+fn read_file_list(list: &Path) -> Result<Vec<(PathBuf, String)>> {
+    let bytes = std::fs::read(list)?;
+    let text = String::from_utf8(bytes).map_err(|_| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("{}: file list is not UTF-8", list.display()),
+        )
+    })?;
+    let sep = if text.contains('\0') { '\0' } else { '\n' };
+    let entries = text
+        .split(sep)
+        .map(|e| e.strip_suffix('\r').unwrap_or(e))
+        .filter(|e| !e.is_empty())
+        .map(|e| match e.split_once('\t') {
+            Some((src, name)) => (PathBuf::from(src), name.to_string()),
+            None => (PathBuf::from(e), e.to_string()),
+        })
+        .collect();
+    Ok(entries)
+}
+
+/// One `path<TAB>end<TAB>blake3` line per chunk of every file `pack` would
+/// take from `inputs` under `opts`, cut with the chunk sizes it would use.
+fn write_boundaries(dest: &Path, inputs: &[&Path], opts: &PackOptions) -> Result<()> {
+    let mut w = std::io::BufWriter::new(std::fs::File::create(dest)?);
+    let files = pack_boundaries(inputs, Some(opts))?;
+    let mut chunks = 0usize;
+    for (path, cuts) in &files {
+        for b in cuts {
+            writeln!(
+                w,
+                "{}\t{}\t{}",
+                path.display(),
+                b.end,
+                hex::encode(b.blake3)
+            )?;
+        }
+        chunks += cuts.len();
+    }
+    w.flush()?;
+    eprintln!(
+        "{chunks} chunk(s) in {} file(s) → {}",
+        files.len(),
+        dest.display()
+    );
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn handle_list(
     archive: PathBuf,
+    human: bool,
+    tree: bool,
+    verbose: bool,
+    index: bool,
+    sort: Option<&str>,
+    reverse: bool,
     key_hex: Option<String>,
     password: Option<String>,
 ) -> Result<()> {
     let aead_key = resolve_key(&archive, key_hex.as_deref(), password.as_deref())?;
-    let opts = aead_key.map(|k| ListOptions {
+    let sort = sort.map(|key| match key {
+        "size" => SortKey::USize,
+        "csize" => SortKey::CSize,
+        "chunks" => SortKey::Chunks,
+        _ => SortKey::Name,
+    });
+    let opts = ListOptions {
+        aead_key,
+        key_salt: [0u8; 32],
+        human,
+        tree,
+        verbose,
+        index,
+        sort,
+        reverse,
+    };
+    list(&archive, Some(&opts))
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn handle_extract(
+    archive: PathBuf,
+    dest: Option<PathBuf>,
+    file: Option<String>,
+    numeric_owner: bool,
+    owner_map: Vec<String>,
+    parent: Option<PathBuf>,
+    strip_components: usize,
+    prefix: Option<String>,
+    max_total_uncompressed: Option<u64>,
+    touch: bool,
+    verify_chunks: bool,
+    key_hex: Option<String>,
+    password: Option<String>,
+) -> Result<()> {
+    let aead_key = key_hex.map(|hex| parse_hex_array::<32>(&hex)).transpose()?;
+    let owner_map = if numeric_owner || !owner_map.is_empty() {
+        Some(parse_owner_map(&owner_map)?)
+    } else {
+        None
+    };
+    // A password is passed through rather than resolved here: a parent
+    // archive derives its key from its own salt
+    let opts = ExtractOptions {
+        aead_key,
+        key_salt: [0u8; 32],
+        password,
+        owner_map,
+        parent,
+        strip_components,
+        prefix,
+        io_buf_size: 0,
+        max_total_uncompressed,
+        restore_mtime: !touch,
+        verify_chunks,
+        cancel: None,
+    };
+    let opts = (opts.aead_key.is_some()
+        || opts.password.is_some()
+        || opts.owner_map.is_some()
+        || opts.parent.is_some()
+        || opts.strip_components > 0
+        || opts.prefix.is_some()
+        || opts.max_total_uncompressed.is_some()
+        || !opts.restore_mtime
+        || opts.verify_chunks)
+        .then_some(opts);
+    if let Some(path) = file {
+        let mut out = std::io::stdout().lock();
+        extract_file_to(&archive, &path, &mut out, opts.as_ref())?;
+        out.flush()?;
+        return Ok(());
+    }
+    let dest = dest.ok_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "extract: DEST is required unless --file is given",
+        )
+    })?;
+    extract(&archive, &dest, opts.as_ref())
+}
+
+/// Parse `--owner-map` values of the form `SRC:DST` (numeric uids).
+fn parse_owner_map(specs: &[String]) -> Result<HashMap<u32, u32>> {
+    specs
+        .iter()
+        .map(|s| -> Result<(u32, u32)> {
+            let parsed = s
+                .split_once(':')
+                .and_then(|(a, b)| Some((a.trim().parse().ok()?, b.trim().parse().ok()?)));
+            Ok(parsed.ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!("--owner-map: expected SRC:DST uids, got {s:?}"),
+                )
+            })?)
+        })
+        .collect()
+}
+
+pub fn handle_verify(
+    archive: PathBuf,
+    deep: bool,
+    quick: bool,
+    key_hex: Option<String>,
+    password: Option<String>,
+) -> Result<()> {
+    let aead_key = resolve_key(&archive, key_hex.as_deref(), password.as_deref())?;
+    let opts = aead_key.map(|k| ExtractOptions {
         aead_key: Some(k),
         key_salt: [0u8; 32],
+        password: None,
+        owner_map: None,
+        parent: None,
+        strip_components: 0,
+        prefix: None,
+        io_buf_size: 0,
+        max_total_uncompressed: None,
+        restore_mtime: true,
+        verify_chunks: false,
+        cancel: None,
     });
-    list(&archive, opts.as_ref())
+    if quick {
+        verify_quick(&archive, opts.as_ref())?;
+        eprintln!("verify: OK (quick; payloads not hashed)");
+        return Ok(());
+    }
+    let report = verify_report(
+        &archive,
+        opts.as_ref(),
+        &VerifyOptions {
+            deep,
+            ..Default::default()
+        },
+    )?;
+    if !report.is_ok() {
+        for failure in report.failures() {
+            eprintln!("verify: FAILED {failure}");
+        }
+        return Err(
+            std::io::Error::new(std::io::ErrorKind::InvalidData, "verify mismatch (tail)").into(),
+        );
+    }
+    eprintln!("verify: OK{}", if deep { " (deep)" } else { "" });
+    for skipped in report.skipped() {
+        eprintln!("verify: skipped {skipped}");
+    }
+    Ok(())
 }
 
-pub fn handle_extract(
+pub fn handle_fix_tail(
     archive: PathBuf,
-    dest: PathBuf,
     key_hex: Option<String>,
     password: Option<String>,
 ) -> Result<()> {
@@ -139,12 +435,216 @@ pub fn handle_extract(
         aead_key: Some(k),
         key_salt: [0u8; 32],
         password: None,
+        owner_map: None,
+        parent: None,
+        strip_components: 0,
+        prefix: None,
+        io_buf_size: 0,
+        max_total_uncompressed: None,
+        restore_mtime: true,
+        verify_chunks: false,
+        cancel: None,
     });
-    extract(&archive, &dest, opts.as_ref())
+    let tail = fix_tail(&archive, opts.as_ref())?;
+    eprintln!(
+        "fix-tail: rewrote tail for {} (u={} c={})",
+        archive.display(),
+        tail.total_u,
+        tail.total_c
+    );
+    Ok(())
 }
 
-pub fn handle_verify(
+pub fn handle_info(
+    archive: PathBuf,
+    quick: bool,
+    key_hex: Option<String>,
+    password: Option<String>,
+) -> Result<()> {
+    if quick {
+        let sb = Superblock::read_from(std::fs::File::open(&archive)?)?;
+        println!("version:          {}", sb.version);
+        if sb.version >= 5 {
+            println!("files:            {}", sb.file_count);
+            println!("logical bytes:    {}", sb.total_logical);
+        } else {
+            println!("files:            unknown (v{} header)", sb.version);
+        }
+        println!("chunks:           {}", sb.chunk_count);
+        return Ok(());
+    }
+    let aead_key = resolve_key(&archive, key_hex.as_deref(), password.as_deref())?;
+    let opened = Opened::open(&archive, aead_key, [0u8; 32])?;
+    let m = &opened.manifest;
+    let meta = &m.meta;
+    println!("created:          {}", meta.created);
+    println!("tool:             {}", meta.tool);
+    println!("manifest version: {}", meta.manifest_version);
+    for (name, value) in [
+        ("label:           ", &meta.label),
+        ("owner:           ", &meta.owner),
+        ("notes:           ", &meta.notes),
+        ("hostname:        ", &meta.hostname),
+    ] {
+        if let Some(v) = value {
+            println!("{name} {v}");
+        }
+    }
+    for root in &meta.source_roots {
+        println!("source root:      {root}");
+    }
+    println!(
+        "entries:          {} files, {} dirs, {} symlinks, {} hardlinks",
+        m.files.len(),
+        m.dirs.len(),
+        m.symlinks.len(),
+        m.hardlinks.len()
+    );
+    let encrypted = if opened.sb.flags & FLAG_CHUNK_AEAD != 0 {
+        "per-file"
+    } else if opened.sb.flags & FLAG_ENCRYPTED != 0 {
+        "true"
+    } else {
+        "false"
+    };
+    println!("encrypted:        {encrypted}");
+    Ok(())
+}
+
+pub fn handle_train_dict(out: PathBuf, inputs: Vec<PathBuf>, max_size: usize) -> Result<()> {
+    let mut samples = Vec::new();
+    for input in &inputs {
+        for entry in walkdir::WalkDir::new(input).sort_by_file_name() {
+            let entry = entry.map_err(std::io::Error::from)?;
+            if entry.file_type().is_file() {
+                samples.push(std::fs::read(entry.path())?);
+            }
+        }
+    }
+    let dict = arx_core::codec::zstdc::train_dict(&samples, max_size)?;
+    std::fs::write(&out, &dict)?;
+    eprintln!(
+        "trained {} byte dictionary on {} files → {}",
+        dict.len(),
+        samples.len(),
+        out.display()
+    );
+    Ok(())
+}
+
+pub fn handle_diff(
+    old: PathBuf,
+    new: PathBuf,
+    json: bool,
+    key_hex: Option<String>,
+    password: Option<String>,
+) -> Result<()> {
+    // Each archive derives a password key from its own salt
+    let opts = ExtractOptions {
+        aead_key: key_hex.map(|hex| parse_hex_array::<32>(&hex)).transpose()?,
+        password,
+        ..Default::default()
+    };
+    let changes = arx_core::read::diff::diff(&old, &new, Some(&opts))?;
+    let mut out = std::io::stdout().lock();
+    if json {
+        serde_json::to_writer_pretty(&mut out, &changes).map_err(std::io::Error::other)?;
+        writeln!(out)?;
+        return Ok(());
+    }
+    for c in &changes {
+        write!(out, "{}  {}", c.kind.letter(), c.path)?;
+        if !c.chunks_added.is_empty() || !c.chunks_removed.is_empty() {
+            write!(
+                out,
+                "  (chunks +{} -{})",
+                c.chunks_added.len(),
+                c.chunks_removed.len()
+            )?;
+        }
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+pub fn handle_layout(
     archive: PathBuf,
+    json: bool,
+    key_hex: Option<String>,
+    password: Option<String>,
+) -> Result<()> {
+    let aead_key = resolve_key(&archive, key_hex.as_deref(), password.as_deref())?;
+    let opened = Opened::open(&archive, aead_key, [0u8; 32])?;
+    let layout = arx_core::read::layout::layout(&opened)?;
+    let mut out = std::io::stdout().lock();
+    if json {
+        serde_json::to_writer_pretty(&mut out, &layout).map_err(std::io::Error::other)?;
+        writeln!(out)?;
+        return Ok(());
+    }
+    for f in &layout.files {
+        writeln!(
+            out,
+            "{}  {} bytes, {} chunks",
+            f.path,
+            f.u_size,
+            f.chunks.len()
+        )?;
+        for c in &f.chunks {
+            writeln!(
+                out,
+                "  chunk {:>6}  file_off {:>12}  data_off {:>12}  c_size {:>8}  codec {}{}",
+                c.id,
+                c.file_off,
+                c.data_off,
+                c.c_size,
+                c.codec,
+                if c.parent { "  (parent)" } else { "" }
+            )?;
+        }
+    }
+    Ok(())
+}
+
+pub fn handle_stats(
+    archive: PathBuf,
+    key_hex: Option<String>,
+    password: Option<String>,
+) -> Result<()> {
+    let aead_key = resolve_key(&archive, key_hex.as_deref(), password.as_deref())?;
+    let st = archive_stats(&archive, aead_key)?;
+    println!("files:             {}", st.files);
+    println!("chunk refs:        {}", st.chunk_refs);
+    println!("unique chunks:     {}", st.unique_chunks);
+    println!("logical bytes:     {}", st.logical_bytes);
+    println!("unique bytes:      {}", st.unique_logical_bytes);
+    println!("physical bytes:    {}", st.physical_bytes);
+    println!("dedup ratio:       {:.2}x", st.dedup_ratio);
+    println!("compression ratio: {:.2}x", st.compression_ratio);
+    Ok(())
+}
+
+pub fn handle_checksums(
+    archive: PathBuf,
+    format: &str,
+    key_hex: Option<String>,
+    password: Option<String>,
+) -> Result<()> {
+    let aead_key = resolve_key(&archive, key_hex.as_deref(), password.as_deref())?;
+    let alg = match format {
+        "sha256" => ChecksumAlg::Sha256,
+        _ => ChecksumAlg::Blake3,
+    };
+    let mut out = std::io::stdout().lock();
+    for (hex, path) in file_checksums(&archive, aead_key, alg)? {
+        writeln!(out, "{hex}  {path}")?;
+    }
+    Ok(())
+}
+
+pub fn handle_export_tar(
+    archive: PathBuf,
+    out: PathBuf,
     key_hex: Option<String>,
     password: Option<String>,
 ) -> Result<()> {
@@ -153,12 +653,58 @@ pub fn handle_verify(
         aead_key: Some(k),
         key_salt: [0u8; 32],
         password: None,
+        owner_map: None,
+        parent: None,
+        strip_components: 0,
+        prefix: None,
+        io_buf_size: 0,
+        max_total_uncompressed: None,
+        restore_mtime: true,
+        verify_chunks: false,
+        cancel: None,
     });
-    verify(&archive, opts.as_ref())?;
-    eprintln!("verify: OK");
+    let f = std::io::BufWriter::new(std::fs::File::create(&out)?);
+    export_tar(&archive, f, opts.as_ref())
+}
+
+pub fn handle_rekey(
+    archive: PathBuf,
+    out: PathBuf,
+    key_hex: Option<String>,
+    password: Option<String>,
+    new_key_hex: Option<String>,
+    new_password: Option<String>,
+) -> Result<()> {
+    let old_key =
+        resolve_key(&archive, key_hex.as_deref(), password.as_deref())?.ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "rekey: --key or --password (the current one) is required",
+            )
+        })?;
+    match (new_key_hex, new_password) {
+        (Some(hex), _) => {
+            let new_key = parse_hex_array::<32>(&hex)?;
+            rekey(&archive, old_key, new_key, random_salt(), &out)?;
+        }
+        (None, Some(pw)) => rekey_password(&archive, old_key, &pw, &out)?,
+        (None, None) => unreachable!("clap requires --new-key or --new-password"),
+    }
+    eprintln!("rekeyed {} -> {}", archive.display(), out.display());
     Ok(())
 }
 
+pub fn handle_shell(
+    archive: PathBuf,
+    key_hex: Option<String>,
+    password: Option<String>,
+) -> Result<()> {
+    let aead_key = resolve_key(&archive, key_hex.as_deref(), password.as_deref())?;
+    let opened = Opened::open(&archive, aead_key, [0u8; 32])?;
+    let stdin = std::io::stdin();
+    run_shell(&opened, stdin.lock(), &mut std::io::stdout().lock())
+}
+
 pub fn handle_issue(
     out: PathBuf,
     label: String,
@@ -197,8 +743,8 @@ pub fn handle_chunk_chunks(
     let rows = repo.chunk_map(&path)?;
     for r in rows {
         println!(
-            "#{:<5} id={:<6} codec={} u={} c={} off={}",
-            r.ordinal, r.id, r.codec, r.u_len, r.c_len, r.data_off
+            "#{:<5} id={:<6} codec={} u={} c={} off={} refs={}",
+            r.ordinal, r.id, r.codec, r.u_len, r.c_len, r.data_off, r.refcount
         );
     }
     Ok(())
@@ -213,11 +759,8 @@ pub fn handle_chunk_cat(
     password: Option<String>,
 ) -> Result<()> {
     let repo = repo_from_args(archive, key_hex, password)?;
-    let mut reader: Box<dyn Read + Send> = if let Some(l) = len {
-        repo.open_range(&path, start, l)?
-    } else {
-        repo.open_range(&path, start, u64::MAX / 4)?
-    };
+    // Open-ended ranges seek to `start` and stream to EOF
+    let mut reader = repo.open_range(&path, start, len.unwrap_or(u64::MAX))?;
     let mut out = std::io::stdout().lock();
     let mut buf = [0u8; 64 * 1024];
     loop {
@@ -240,11 +783,7 @@ pub fn handle_chunk_get(
     password: Option<String>,
 ) -> Result<()> {
     let repo = repo_from_args(archive, key_hex, password)?;
-    let mut reader: Box<dyn Read + Send> = if let Some(l) = len {
-        repo.open_range(&path, start, l)?
-    } else {
-        repo.open_reader(&path)?
-    };
+    let mut reader = repo.open_range(&path, start, len.unwrap_or(u64::MAX))?;
     let mut file = std::fs::File::create(&out)?;
     let mut buf = [0u8; 256 * 1024];
     loop {
@@ -327,6 +866,31 @@ pub fn handle_crud_mv(
     Ok(())
 }
 
+pub fn handle_crud_set_policy(
+    archive: PathBuf,
+    policy: Policy,
+    key_hex: Option<String>,
+    password: Option<String>,
+) -> Result<()> {
+    let aead_key = crud_key(&archive, key_hex, password)?;
+    let mut arc = CrudArchive::open_with_crypto(&archive, aead_key, [0u8; 32])?;
+    eprintln!("set-policy: {policy:?}");
+    arc.set_policy(policy)?;
+    Ok(())
+}
+
+pub fn handle_crud_gc(
+    archive: PathBuf,
+    key_hex: Option<String>,
+    password: Option<String>,
+) -> Result<()> {
+    let aead_key = crud_key(&archive, key_hex, password)?;
+    let mut arc = CrudArchive::open_with_crypto(&archive, aead_key, [0u8; 32])?;
+    let (before, after) = arc.compact_delta()?;
+    eprintln!("gc: delta {before} -> {after} bytes");
+    Ok(())
+}
+
 pub fn handle_crud_ls(
     archive: PathBuf,
     prefix: Option<String>,
@@ -336,10 +900,12 @@ pub fn handle_crud_ls(
 ) -> Result<()> {
     let aead_key = crud_key(&archive, key_hex, password)?;
     let arc = CrudArchive::open_with_crypto(&archive, aead_key, [0u8; 32])?;
+    // The index is the merged tree: base entries with the journal replayed on top
+    let prefix = prefix.as_deref().map(normalize_path).transpose()?;
     let iter = arc.index.by_path.iter().filter(|(p, _)| {
         prefix
             .as_ref()
-            .map(|pref| p.starts_with(pref))
+            .map(|pref| p.starts_with(pref.as_str()))
             .unwrap_or(true)
     });
     if long {
@@ -1,7 +1,9 @@
 pub mod handlers;
+pub mod shell;
 
 use crate::presentation::cli::{ChunkCommands, Cli, Commands, CrudCommands};
 use arx_core::error::Result;
+use arx_core::policy::Policy;
 use clap::Parser;
 
 pub fn run() -> Result<()> {
@@ -14,6 +16,26 @@ pub fn run() -> Result<()> {
             min_gain,
             encrypt_raw_hex,
             password,
+            recipients,
+            aes_gcm,
+            chunk_avg,
+            listing,
+            index,
+            record_origin,
+            dedup_window,
+            encrypt_paths,
+            public_manifest,
+            exclude,
+            gitignore,
+            dereference,
+            dry_run,
+            emit_boundaries,
+            parent,
+            stdin,
+            name,
+            tar,
+            files_from,
+            zstd_dict,
         } => handlers::handle_pack(
             out,
             inputs,
@@ -21,26 +43,155 @@ pub fn run() -> Result<()> {
             min_gain,
             encrypt_raw_hex,
             password,
+            recipients,
+            aes_gcm,
+            chunk_avg,
+            listing,
+            index,
+            record_origin,
+            dedup_window,
+            encrypt_paths,
+            public_manifest,
+            exclude,
+            gitignore,
+            dereference,
+            dry_run,
+            emit_boundaries,
+            parent,
+            stdin.then(|| name.unwrap_or_else(|| "stdin".into())),
+            tar,
+            files_from,
+            zstd_dict,
         ),
 
+        Commands::TrainDict {
+            out,
+            inputs,
+            max_size,
+        } => handlers::handle_train_dict(out, inputs, max_size),
+
         Commands::List {
             archive,
+            human,
+            tree,
+            verbose,
+            index,
+            sort,
+            reverse,
+            key_hex,
+            password,
+        } => handlers::handle_list(
+            archive,
+            human,
+            tree,
+            verbose,
+            index,
+            sort.as_deref(),
+            reverse,
             key_hex,
             password,
-        } => handlers::handle_list(archive, key_hex, password),
+        ),
 
         Commands::Extract {
             archive,
             dest,
+            file,
+            numeric_owner,
+            owner_map,
+            parent,
+            strip_components,
+            prefix,
+            max_uncompressed,
+            touch,
+            verify_chunks,
             key_hex,
             password,
-        } => handlers::handle_extract(archive, dest, key_hex, password),
+        } => handlers::handle_extract(
+            archive,
+            dest,
+            file,
+            numeric_owner,
+            owner_map,
+            parent,
+            strip_components,
+            prefix,
+            max_uncompressed,
+            touch,
+            verify_chunks,
+            key_hex,
+            password,
+        ),
 
         Commands::Verify {
             archive,
+            deep,
+            quick,
+            key_hex,
+            password,
+        } => handlers::handle_verify(archive, deep, quick, key_hex, password),
+
+        Commands::FixTail {
+            archive,
+            key_hex,
+            password,
+        } => handlers::handle_fix_tail(archive, key_hex, password),
+
+        Commands::Info {
+            archive,
+            quick,
+            key_hex,
+            password,
+        } => handlers::handle_info(archive, quick, key_hex, password),
+
+        Commands::Diff {
+            old,
+            new,
+            json,
             key_hex,
             password,
-        } => handlers::handle_verify(archive, key_hex, password),
+        } => handlers::handle_diff(old, new, json, key_hex, password),
+
+        Commands::Layout {
+            archive,
+            json,
+            key_hex,
+            password,
+        } => handlers::handle_layout(archive, json, key_hex, password),
+
+        Commands::Stats {
+            archive,
+            key_hex,
+            password,
+        } => handlers::handle_stats(archive, key_hex, password),
+
+        Commands::Checksums {
+            archive,
+            format,
+            key_hex,
+            password,
+        } => handlers::handle_checksums(archive, &format, key_hex, password),
+
+        Commands::ExportTar {
+            archive,
+            out,
+            key_hex,
+            password,
+        } => handlers::handle_export_tar(archive, out, key_hex, password),
+
+        Commands::Rekey {
+            archive,
+            out,
+            key_hex,
+            password,
+            new_key_hex,
+            new_password,
+        } => handlers::handle_rekey(archive, out, key_hex, password, new_key_hex, new_password),
+
+        Commands::Shell {
+            archive,
+            key_hex,
+            password,
+        } => handlers::handle_shell(archive, key_hex, password),
 
         Commands::Issue {
             out,
@@ -113,6 +264,32 @@ pub fn run() -> Result<()> {
                 key_hex,
                 password,
             } => handlers::handle_crud_mv(archive, from, to, key_hex, password),
+            CrudCommands::Gc {
+                archive,
+                key_hex,
+                password,
+            } => handlers::handle_crud_gc(archive, key_hex, password),
+            CrudCommands::SetPolicy {
+                archive,
+                max_entries,
+                max_uncompressed,
+                max_delta_bytes,
+                min_compression_ratio,
+                allow_symlinks,
+                key_hex,
+                password,
+            } => handlers::handle_crud_set_policy(
+                archive,
+                Policy {
+                    max_entries,
+                    max_uncompressed,
+                    max_delta_bytes,
+                    min_compression_ratio,
+                    allow_symlinks,
+                },
+                key_hex,
+                password,
+            ),
             CrudCommands::Ls {
                 archive,
                 prefix,
@@ -0,0 +1,229 @@
+//! `arx shell`: a minimal interactive browser over an opened archive.
+
+use std::collections::BTreeMap;
+use std::io::{BufRead, Write};
+use std::path::Path;
+
+use arx_core::error::Result;
+use arx_core::read::opened::Opened;
+
+const HELP: &str = "commands: ls [dir] | cd <dir> | pwd | cat <file> | stat <path> | get <file> [dest] | help | exit";
+
+/// What an archive path names, for `ls` markers and `stat`.
+enum Node {
+    Dir,
+    File {
+        size: u64,
+        mode: u32,
+        mtime: i64,
+        chunks: usize,
+    },
+    Symlink {
+        target: String,
+    },
+    Hardlink {
+        target: String,
+    },
+}
+
+struct Shell<'a> {
+    arx: &'a Opened,
+    /// Every archive path, including implied parent directories and the root ("").
+    nodes: BTreeMap<String, Node>,
+    /// Current directory; "" is the archive root.
+    cwd: String,
+}
+
+/// Read commands from `input` until EOF or `exit`, writing results to `out`.
+/// Command errors are reported on `out` and do not end the session.
+pub fn run_shell<R: BufRead, W: Write>(arx: &Opened, input: R, out: &mut W) -> Result<()> {
+    let mut sh = Shell::new(arx);
+    write!(out, "{}", sh.prompt())?;
+    out.flush()?;
+    for line in input.lines() {
+        let line = line?;
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            [] => {}
+            ["exit" | "quit"] => return Ok(()),
+            [cmd, args @ ..] => {
+                if let Err(e) = sh.exec(cmd, args, out) {
+                    writeln!(out, "error: {e}")?;
+                }
+            }
+        }
+        write!(out, "{}", sh.prompt())?;
+        out.flush()?;
+    }
+    writeln!(out)?;
+    Ok(())
+}
+
+impl<'a> Shell<'a> {
+    fn new(arx: &'a Opened) -> Self {
+        let m = &arx.manifest;
+        let mut nodes = BTreeMap::new();
+        for d in &m.dirs {
+            nodes.insert(d.path.clone(), Node::Dir);
+        }
+        for f in &m.files {
+            nodes.insert(
+                f.path.clone(),
+                Node::File {
+                    size: f.u_size,
+                    mode: f.mode,
+                    mtime: f.mtime,
+                    chunks: f.chunk_refs.len(),
+                },
+            );
+        }
+        for s in &m.symlinks {
+            nodes.insert(
+                s.path.clone(),
+                Node::Symlink {
+                    target: s.target.clone(),
+                },
+            );
+        }
+        for h in &m.hardlinks {
+            nodes.insert(
+                h.path.clone(),
+                Node::Hardlink {
+                    target: h.target.clone(),
+                },
+            );
+        }
+        // Directories are not always listed explicitly; infer them from paths
+        let implied: Vec<String> = nodes
+            .keys()
+            .flat_map(|p| {
+                p.match_indices('/')
+                    .map(|(i, _)| p[..i].to_string())
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        for d in implied {
+            nodes.entry(d).or_insert(Node::Dir);
+        }
+        nodes.insert(String::new(), Node::Dir);
+        Self {
+            arx,
+            nodes,
+            cwd: String::new(),
+        }
+    }
+
+    fn prompt(&self) -> String {
+        format!("arx:/{}> ", self.cwd)
+    }
+
+    /// Resolve `arg` against the current directory, handling `.`, `..` and `/`.
+    fn resolve(&self, arg: &str) -> String {
+        let mut parts: Vec<&str> = if arg.starts_with('/') {
+            Vec::new()
+        } else {
+            self.cwd.split('/').filter(|s| !s.is_empty()).collect()
+        };
+        for seg in arg.split('/') {
+            match seg {
+                "" | "." => {}
+                ".." => {
+                    parts.pop();
+                }
+                s => parts.push(s),
+            }
+        }
+        parts.join("/")
+    }
+
+    fn lookup(&self, path: &str) -> std::io::Result<&Node> {
+        self.nodes.get(path).ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("no such path: /{path}"),
+            )
+        })
+    }
+
+    fn exec<W: Write>(&mut self, cmd: &str, args: &[&str], out: &mut W) -> Result<()> {
+        match (cmd, args) {
+            ("help", []) => writeln!(out, "{HELP}")?,
+            ("pwd", []) => writeln!(out, "/{}", self.cwd)?,
+            ("ls", [] | [_]) => {
+                let dir = self.resolve(args.first().copied().unwrap_or("."));
+                if !matches!(self.lookup(&dir)?, Node::Dir) {
+                    return Err(not_a_dir(&dir).into());
+                }
+                let prefix = if dir.is_empty() {
+                    String::new()
+                } else {
+                    format!("{dir}/")
+                };
+                for (path, node) in self.nodes.range(prefix.clone()..) {
+                    let Some(name) = path.strip_prefix(&prefix) else {
+                        break;
+                    };
+                    if name.is_empty() || name.contains('/') {
+                        continue;
+                    }
+                    match node {
+                        Node::Dir => writeln!(out, "{name}/")?,
+                        Node::Symlink { target } => writeln!(out, "{name} -> {target}")?,
+                        _ => writeln!(out, "{name}")?,
+                    }
+                }
+            }
+            ("cd", [arg]) => {
+                let dir = self.resolve(arg);
+                if !matches!(self.lookup(&dir)?, Node::Dir) {
+                    return Err(not_a_dir(&dir).into());
+                }
+                self.cwd = dir;
+            }
+            ("cat", [arg]) => {
+                let path = self.resolve(arg);
+                self.lookup(&path)?;
+                let mut r = self.arx.open_reader(&path)?;
+                std::io::copy(&mut r, out)?;
+            }
+            ("get", [arg] | [arg, _]) => {
+                let path = self.resolve(arg);
+                self.lookup(&path)?;
+                let dest = match args.get(1) {
+                    Some(d) => d.to_string(),
+                    None => path.rsplit('/').next().unwrap_or(&path).to_string(),
+                };
+                let mut r = self.arx.open_reader(&path)?;
+                let mut f = std::fs::File::create(Path::new(&dest))?;
+                let n = std::io::copy(&mut r, &mut f)?;
+                writeln!(out, "wrote {n} bytes to {dest}")?;
+            }
+            ("stat", [arg]) => {
+                let path = self.resolve(arg);
+                match self.lookup(&path)? {
+                    Node::Dir => writeln!(out, "/{path}: directory")?,
+                    Node::File {
+                        size,
+                        mode,
+                        mtime,
+                        chunks,
+                    } => writeln!(
+                        out,
+                        "/{path}: file  size={size}  mode={mode:o}  mtime={mtime}  chunks={chunks}"
+                    )?,
+                    Node::Symlink { target } => writeln!(out, "/{path}: symlink -> {target}")?,
+                    Node::Hardlink { target } => writeln!(out, "/{path}: hardlink to /{target}")?,
+                }
+            }
+            _ => writeln!(out, "unknown command or arguments: {cmd}\n{HELP}")?,
+        }
+        Ok(())
+    }
+}
+
+fn not_a_dir(path: &str) -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::NotADirectory,
+        format!("not a directory: /{path}"),
+    )
+}
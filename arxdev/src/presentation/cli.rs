@@ -94,6 +94,29 @@ pub enum CrudCommands {
         password: Option<String>,
     },
 
+    /// Set the limits enforced on later adds; limits left out are cleared.
+    SetPolicy {
+        archive: PathBuf,
+        /// Most paths the overlay may hold.
+        #[arg(long)]
+        max_entries: Option<u64>,
+        /// Most logical bytes across all paths.
+        #[arg(long)]
+        max_uncompressed: Option<u64>,
+        /// Largest the delta store may grow, in bytes.
+        #[arg(long)]
+        max_delta_bytes: Option<u64>,
+        /// Reject adds whose new chunks compress less than this (e.g. 1.05).
+        #[arg(long)]
+        min_compression_ratio: Option<f32>,
+        #[arg(long)]
+        allow_symlinks: bool,
+        #[arg(long = "key")]
+        key_hex: Option<String>,
+        #[arg(long = "password")]
+        password: Option<String>,
+    },
+
     /// List the merged overlay state (base + journal changes).
     Ls {
         archive: PathBuf,
@@ -149,6 +172,15 @@ pub enum CrudCommands {
         seal_base: bool,
     },
 
+    /// Drop overwritten and deleted content from the overlay's delta file.
+    Gc {
+        archive: PathBuf,
+        #[arg(long = "key")]
+        key_hex: Option<String>,
+        #[arg(long = "password")]
+        password: Option<String>,
+    },
+
     /// Stream a file from the overlay to stdout.
     Cat {
         archive: PathBuf,
@@ -176,6 +208,7 @@ pub enum Commands {
     /// Pack files/directories into an ARX archive.
     Pack {
         out: PathBuf,
+        #[arg(required_unless_present_any = ["stdin", "tar", "files_from"])]
         inputs: Vec<PathBuf>,
         #[arg(long)]
         deterministic: bool,
@@ -187,11 +220,116 @@ pub enum Commands {
         /// Derive encryption key from a password (Argon2id).
         #[arg(long)]
         password: Option<String>,
+        /// 32-byte hex key that can open the archive (repeatable); each
+        /// recipient wraps a random data key.
+        #[arg(long = "recipient", conflicts_with_all = ["encrypt_raw_hex", "password"])]
+        recipients: Vec<String>,
+        /// Encrypt with AES-256-GCM instead of XChaCha20-Poly1305 (fast with AES-NI).
+        #[arg(long)]
+        aes_gcm: bool,
+        /// Target average chunk size in bytes (0 = default 256 KiB).
+        #[arg(long, default_value_t = 0)]
+        chunk_avg: usize,
+        /// Also write a catalog of packed files (sizes + blake3) to this path.
+        #[arg(long)]
+        listing: Option<PathBuf>,
+        /// Also write OUT's metadata (no chunk data) to OUT with extension
+        /// `.arxidx`, for `list --index`.
+        #[arg(long)]
+        index: bool,
+        /// Record the absolute input paths and this machine's hostname in the
+        /// archive, for `info` to show.
+        #[arg(long, conflicts_with_all = ["stdin", "tar"])]
+        record_origin: bool,
+        /// Dedup against only this many recently used chunk hashes (0 = all).
+        #[arg(long, default_value_t = 0)]
+        dedup_window: usize,
+        /// Encrypt only the contents of files whose archive path matches this
+        /// glob (repeatable); the rest are stored in plaintext, but listing or
+        /// reading any file still needs the key unless `--public-manifest`.
+        #[arg(long = "encrypt-path", conflicts_with_all = ["stdin", "tar"])]
+        encrypt_paths: Vec<String>,
+        /// With `--encrypt-path`, leave the file list and the unmatched files
+        /// readable without a key (every path and size becomes public).
+        #[arg(long, requires = "encrypt_paths")]
+        public_manifest: bool,
+        /// Skip inputs matching this glob (repeatable). Without a `/` it matches
+        /// file and directory names (`.git`, `*.tmp`); excluded directories are
+        /// skipped entirely.
+        #[arg(long, conflicts_with_all = ["stdin", "tar"])]
+        exclude: Vec<String>,
+        /// Also skip what `.gitignore`/`.ignore` files inside the inputs ignore.
+        #[arg(long, conflicts_with_all = ["stdin", "tar"])]
+        gitignore: bool,
+        /// Follow symlinks and pack what they point to; without it links are skipped.
+        #[arg(long, short = 'L', conflicts_with_all = ["stdin", "tar"])]
+        dereference: bool,
+        /// Report files, chunks, dedup and estimated size without writing OUT.
+        #[arg(long, conflicts_with_all = ["stdin", "tar"])]
+        dry_run: bool,
+        /// Write each input file's chunk cut points (path, end offset, blake3)
+        /// to FILE instead of writing OUT.
+        #[arg(long, value_name = "FILE", conflicts_with_all = ["stdin", "tar", "dry_run"])]
+        emit_boundaries: Option<PathBuf>,
+        /// Store only chunks missing from this parent archive; extracting the
+        /// result then needs `extract --parent`.
+        #[arg(long, conflicts_with_all = ["stdin", "tar"])]
+        parent: Option<PathBuf>,
+        /// Pack standard input as a single file instead of INPUTS.
+        #[arg(long, conflicts_with = "inputs")]
+        stdin: bool,
+        /// Archive path for the `--stdin` entry (default: "stdin").
+        #[arg(long, requires = "stdin")]
+        name: Option<String>,
+        /// Import the entries of this .tar file instead of INPUTS.
+        #[arg(long, conflicts_with_all = ["inputs", "stdin"])]
+        tar: Option<PathBuf>,
+        /// Pack exactly the files listed in LIST instead of walking INPUTS:
+        /// one `SOURCE` or `SOURCE<TAB>ARCHIVE_PATH` per line (or per NUL, as
+        /// from `find -print0`); without an archive path SOURCE is used as one.
+        #[arg(
+            long,
+            value_name = "LIST",
+            conflicts_with_all = ["inputs", "stdin", "tar", "exclude", "gitignore", "dereference", "dry_run", "emit_boundaries"]
+        )]
+        files_from: Option<PathBuf>,
+        /// Compress chunks against this zstd dictionary (see `train-dict`);
+        /// it is stored in the archive.
+        #[arg(long, conflicts_with = "public_manifest")]
+        zstd_dict: Option<PathBuf>,
+    },
+
+    /// Train a zstd dictionary on the files under INPUTS, for `pack --zstd-dict`.
+    TrainDict {
+        out: PathBuf,
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
+        /// Largest dictionary to produce, in bytes.
+        #[arg(long, default_value_t = 112_640)]
+        max_size: usize,
     },
 
     /// List archive contents.
     List {
         archive: PathBuf,
+        /// Print sizes as KiB/MiB/GiB.
+        #[arg(long, short = 'H')]
+        human: bool,
+        /// Print a directory tree with per-directory size rollups.
+        #[arg(long)]
+        tree: bool,
+        /// After each file, print its chunks per codec and min/avg/max chunk size.
+        #[arg(long, short = 'v', conflicts_with = "tree")]
+        verbose: bool,
+        /// ARCHIVE is a `pack --index` sidecar (.arxidx) rather than an archive.
+        #[arg(long)]
+        index: bool,
+        /// Order files by name, size (uncompressed), csize (stored) or chunks.
+        #[arg(long, conflicts_with = "tree", value_parser = ["name", "size", "csize", "chunks"])]
+        sort: Option<String>,
+        /// With `--sort`, largest (or last) first.
+        #[arg(long, requires = "sort")]
+        reverse: bool,
         #[arg(long = "key")]
         key_hex: Option<String>,
         #[arg(long = "password")]
@@ -201,7 +339,35 @@ pub enum Commands {
     /// Extract archive to a destination directory.
     Extract {
         archive: PathBuf,
-        dest: PathBuf,
+        #[arg(required_unless_present = "file")]
+        dest: Option<PathBuf>,
+        /// Write a single archived file to stdout instead of extracting the tree.
+        #[arg(long, visible_alias = "to-stdout")]
+        file: Option<String>,
+        /// Restore the recorded numeric uid/gid of every entry.
+        #[arg(long)]
+        numeric_owner: bool,
+        /// Restore owners, replacing uid SRC with DST (repeatable; implies --numeric-owner).
+        #[arg(long, value_name = "SRC:DST")]
+        owner_map: Vec<String>,
+        /// Parent archive of a differential archive (see `pack --parent`).
+        #[arg(long)]
+        parent: Option<PathBuf>,
+        /// Drop N leading path components from every entry (like tar).
+        #[arg(long, value_name = "N", default_value_t = 0)]
+        strip_components: usize,
+        /// Extract into this subdirectory of DEST.
+        #[arg(long, value_name = "DIR")]
+        prefix: Option<String>,
+        /// Refuse archives that decompress to more than this many bytes.
+        #[arg(long, value_name = "BYTES")]
+        max_uncompressed: Option<u64>,
+        /// Don't restore recorded modification times (like `tar --touch`).
+        #[arg(long, short = 'm')]
+        touch: bool,
+        /// With --file, check each chunk's blake3 before writing any of it.
+        #[arg(long, requires = "file")]
+        verify_chunks: bool,
         #[arg(long = "key")]
         key_hex: Option<String>,
         #[arg(long = "password")]
@@ -210,6 +376,125 @@ pub enum Commands {
 
     /// Verify archive integrity via the Tail Summary.
     Verify {
+        archive: PathBuf,
+        /// Also decompress every chunk and check its size and blake3 hash.
+        #[arg(long)]
+        deep: bool,
+        /// Only check the layout (region bounds, chunk table size, data region
+        /// length, tail totals); hashes nothing, so payload corruption passes.
+        #[arg(long, conflicts_with = "deep")]
+        quick: bool,
+        #[arg(long = "key")]
+        key_hex: Option<String>,
+        #[arg(long = "password")]
+        password: Option<String>,
+    },
+
+    /// Recompute the Tail Summary from the archive's regions and rewrite it,
+    /// replacing a missing or stale one.
+    #[command(visible_alias = "repair-tail")]
+    FixTail {
+        archive: PathBuf,
+        #[arg(long = "key")]
+        key_hex: Option<String>,
+        #[arg(long = "password")]
+        password: Option<String>,
+    },
+
+    /// Print archive metadata: creation time, tool, label, and where it was packed from.
+    Info {
+        archive: PathBuf,
+        /// Print only what the header records (file count, total size); reads
+        /// no manifest and needs no key.
+        #[arg(long)]
+        quick: bool,
+        #[arg(long = "key")]
+        key_hex: Option<String>,
+        #[arg(long = "password")]
+        password: Option<String>,
+    },
+
+    /// Show which files differ between two archives (A added, D removed, M modified).
+    Diff {
+        old: PathBuf,
+        new: PathBuf,
+        /// Emit the changes as JSON, with changed chunk hashes.
+        #[arg(long)]
+        json: bool,
+        #[arg(long = "key")]
+        key_hex: Option<String>,
+        #[arg(long = "password")]
+        password: Option<String>,
+    },
+
+    /// Print where each file's chunks sit in the archive (offsets, sizes, codec).
+    Layout {
+        archive: PathBuf,
+        /// Emit the stable JSON layout for mount/streaming tools.
+        #[arg(long)]
+        json: bool,
+        #[arg(long = "key")]
+        key_hex: Option<String>,
+        #[arg(long = "password")]
+        password: Option<String>,
+    },
+
+    /// Report dedup and compression ratios from the manifest and chunk table.
+    Stats {
+        archive: PathBuf,
+        #[arg(long = "key")]
+        key_hex: Option<String>,
+        #[arg(long = "password")]
+        password: Option<String>,
+    },
+
+    /// Print `<hex>  <path>` per file, like `b3sum` / `sha256sum`.
+    Checksums {
+        archive: PathBuf,
+        /// Digest to print: blake3, or sha256 (needs the `sha256` feature).
+        #[arg(long, default_value = "blake3", value_parser = ["blake3", "sha256"])]
+        format: String,
+        #[arg(long = "key")]
+        key_hex: Option<String>,
+        #[arg(long = "password")]
+        password: Option<String>,
+    },
+
+    /// Write the archive's contents to a .tar file for legacy tooling.
+    ExportTar {
+        archive: PathBuf,
+        out: PathBuf,
+        #[arg(long = "key")]
+        key_hex: Option<String>,
+        #[arg(long = "password")]
+        password: Option<String>,
+    },
+
+    /// Reseal an encrypted archive under a new key without repacking.
+    Rekey {
+        archive: PathBuf,
+        /// Where to write the rekeyed archive (may be ARCHIVE itself).
+        out: PathBuf,
+        /// Current key (hex).
+        #[arg(long = "key")]
+        key_hex: Option<String>,
+        /// Current password.
+        #[arg(long = "password")]
+        password: Option<String>,
+        /// New raw key (hex).
+        #[arg(
+            long = "new-key",
+            required_unless_present = "new_password",
+            conflicts_with = "new_password"
+        )]
+        new_key_hex: Option<String>,
+        /// New password (Argon2id under a fresh salt).
+        #[arg(long)]
+        new_password: Option<String>,
+    },
+
+    /// Browse an archive interactively (ls, cd, cat, stat, get).
+    Shell {
         archive: PathBuf,
         #[arg(long = "key")]
         key_hex: Option<String>,
@@ -137,6 +137,7 @@ fn test_cli_verify() {
         fixtures.to_str().unwrap(),
     ]));
     assert_success(&arx(&["verify", archive.to_str().unwrap()]));
+    assert_success(&arx(&["verify", "--quick", archive.to_str().unwrap()]));
 }
 
 // ── Encrypted pack / extract ─────────────────────────────────────────────────
@@ -252,6 +253,39 @@ fn test_cli_crud_add_ls_diff() {
     assert!(stdout.contains('A'), "diff should show A for added file");
 }
 
+#[test]
+fn test_cli_crud_ls_merges_base_and_overlay() {
+    let tmp = TempDir::new().unwrap();
+    let src = tmp.path().join("src");
+    let archive = tmp.path().join("base.arx");
+    fs::create_dir_all(src.join("docs")).unwrap();
+    fs::write(src.join("docs/keep.txt"), b"keep").unwrap();
+    fs::write(src.join("docs/drop.txt"), b"drop").unwrap();
+    assert_success(&arx(&[
+        "pack",
+        archive.to_str().unwrap(),
+        src.to_str().unwrap(),
+    ]));
+
+    assert_success(&arx(&[
+        "crud",
+        "rm",
+        archive.to_str().unwrap(),
+        "docs/drop.txt",
+    ]));
+
+    for extra in [&[][..], &["--prefix", "/docs"][..], &["--long"][..]] {
+        let mut args = vec!["crud", "ls", archive.to_str().unwrap()];
+        args.extend_from_slice(extra);
+        let out = arx(&args);
+        assert_success(&out);
+        let stdout = String::from_utf8_lossy(&out.stdout);
+        let lines: Vec<&str> = stdout.lines().collect();
+        assert_eq!(lines.len(), 1, "{extra:?}: {stdout}");
+        assert!(lines[0].ends_with("docs/keep.txt"), "{extra:?}: {stdout}");
+    }
+}
+
 #[test]
 fn test_cli_crud_sync() {
     let tmp = TempDir::new().unwrap();
@@ -293,3 +327,359 @@ fn test_cli_crud_sync() {
     ]));
     assert_eq!(fs::read(dest.join("f.txt")).unwrap(), b"sync me");
 }
+
+// ── Shell ────────────────────────────────────────────────────────────────────
+
+#[test]
+fn test_cli_shell_scripted() {
+    use std::io::Write;
+    use std::process::Stdio;
+
+    let tmp = TempDir::new().unwrap();
+    let src = tmp.path().join("src");
+    let archive = tmp.path().join("test.arx");
+    write_fixtures(&src);
+    assert_success(&arx(&[
+        "pack",
+        archive.to_str().unwrap(),
+        src.to_str().unwrap(),
+    ]));
+
+    let script = "ls\ncat hello.txt\ncd sub\npwd\nstat data.bin\nget data.bin copy.bin\ncd ..\ncd nope\nexit\n";
+    let mut child = Command::new(arx_bin())
+        .args(["shell", archive.to_str().unwrap()])
+        .current_dir(tmp.path())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(script.as_bytes())
+        .unwrap();
+    let out = child.wait_with_output().unwrap();
+    assert_success(&out);
+    let stdout = String::from_utf8_lossy(&out.stdout);
+
+    assert!(stdout.contains("hello.txt\n"), "{stdout}");
+    assert!(stdout.contains("sub/\n"), "{stdout}");
+    assert!(stdout.contains("hello world\n"), "{stdout}");
+    assert!(stdout.contains("arx:/sub> "), "{stdout}");
+    assert!(stdout.contains("/sub\n"), "{stdout}");
+    assert!(
+        stdout.contains("/sub/data.bin: file  size=1024"),
+        "{stdout}"
+    );
+    assert!(stdout.contains("wrote 1024 bytes to copy.bin"), "{stdout}");
+    assert!(stdout.contains("error: no such path: /nope"), "{stdout}");
+    assert_eq!(
+        fs::read(tmp.path().join("copy.bin")).unwrap(),
+        [0xAAu8; 1024]
+    );
+}
+
+#[test]
+fn test_cli_export_tar_lists_with_gnu_tar() {
+    let tmp = TempDir::new().unwrap();
+    let src = tmp.path().join("src");
+    let archive = tmp.path().join("test.arx");
+    let tarball = tmp.path().join("out.tar");
+    write_fixtures(&src);
+    assert_success(&arx(&[
+        "pack",
+        "--encrypt-raw",
+        &"ab".repeat(32),
+        archive.to_str().unwrap(),
+        src.to_str().unwrap(),
+    ]));
+    assert_success(&arx(&[
+        "export-tar",
+        archive.to_str().unwrap(),
+        tarball.to_str().unwrap(),
+        "--key",
+        &"ab".repeat(32),
+    ]));
+
+    // Skip the GNU tar half where tar is unavailable
+    let Ok(out) = Command::new("tar")
+        .args(["-tvf", tarball.to_str().unwrap()])
+        .output()
+    else {
+        return;
+    };
+    assert_success(&out);
+    let listing = String::from_utf8_lossy(&out.stdout);
+    assert!(listing.contains("hello.txt"), "{listing}");
+    assert!(listing.contains("sub/data.bin"), "{listing}");
+
+    let dst = tmp.path().join("untarred");
+    fs::create_dir_all(&dst).unwrap();
+    let out = Command::new("tar")
+        .args([
+            "-xf",
+            tarball.to_str().unwrap(),
+            "-C",
+            dst.to_str().unwrap(),
+        ])
+        .output()
+        .unwrap();
+    assert_success(&out);
+    assert_eq!(fs::read(dst.join("hello.txt")).unwrap(), b"hello world\n");
+    assert_eq!(fs::read(dst.join("sub/data.bin")).unwrap(), [0xAAu8; 1024]);
+}
+
+#[test]
+fn test_cli_verify_names_failing_region() {
+    use arx_core::container::superblock::Superblock;
+
+    let tmp = TempDir::new().unwrap();
+    let src = tmp.path().join("src");
+    let archive = tmp.path().join("bad.arx");
+    write_fixtures(&src);
+    assert_success(&arx(&[
+        "pack",
+        archive.to_str().unwrap(),
+        src.to_str().unwrap(),
+    ]));
+
+    // Flip a byte in the middle of the manifest
+    let sb = Superblock::read_from(fs::File::open(&archive).unwrap()).unwrap();
+    let mut bytes = fs::read(&archive).unwrap();
+    bytes[(sb.header_len() + sb.manifest_len / 2) as usize] ^= 0xFF;
+    fs::write(&archive, &bytes).unwrap();
+
+    let out = arx(&["verify", archive.to_str().unwrap()]);
+    assert!(!out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("FAILED manifest"), "{stderr}");
+    assert!(!stderr.contains("FAILED data"), "{stderr}");
+}
+
+#[test]
+fn test_cli_repair_tail_restores_stripped_tail() {
+    use arx_core::container::tail::TAIL_LEN;
+
+    let tmp = TempDir::new().unwrap();
+    let src = tmp.path().join("src");
+    let archive = tmp.path().join("tailless.arx");
+    write_fixtures(&src);
+    assert_success(&arx(&[
+        "pack",
+        archive.to_str().unwrap(),
+        src.to_str().unwrap(),
+    ]));
+    let good_len = fs::metadata(&archive).unwrap().len();
+
+    // Strip the tail, as a crashed writer would leave it
+    let f = fs::OpenOptions::new().write(true).open(&archive).unwrap();
+    f.set_len(good_len - TAIL_LEN).unwrap();
+    drop(f);
+    assert!(!arx(&["verify", archive.to_str().unwrap()]).status.success());
+
+    assert_success(&arx(&["repair-tail", archive.to_str().unwrap()]));
+    assert_eq!(fs::metadata(&archive).unwrap().len(), good_len);
+    assert_success(&arx(&["verify", archive.to_str().unwrap()]));
+}
+
+#[test]
+fn test_cli_pack_files_from_list() {
+    let tmp = TempDir::new().unwrap();
+    let src = tmp.path().join("src");
+    let archive = tmp.path().join("listed.arx");
+    let dest = tmp.path().join("out");
+    write_fixtures(&src);
+    let list = tmp.path().join("files.txt");
+    fs::write(
+        &list,
+        format!(
+            "{}\tnotes/a.txt\n{}\tb.txt\n",
+            src.join("hello.txt").display(),
+            src.join("sub/data.bin").display()
+        ),
+    )
+    .unwrap();
+
+    assert_success(&arx(&[
+        "pack",
+        "--files-from",
+        list.to_str().unwrap(),
+        archive.to_str().unwrap(),
+    ]));
+    assert_success(&arx(&[
+        "extract",
+        archive.to_str().unwrap(),
+        dest.to_str().unwrap(),
+    ]));
+    assert_eq!(
+        fs::read(dest.join("notes/a.txt")).unwrap(),
+        fs::read(src.join("hello.txt")).unwrap()
+    );
+    assert_eq!(
+        fs::read(dest.join("b.txt")).unwrap(),
+        fs::read(src.join("sub/data.bin")).unwrap()
+    );
+    assert_eq!(fs::read_dir(&dest).unwrap().count(), 2);
+}
+
+#[test]
+fn test_cli_info_shows_source_root() {
+    let tmp = TempDir::new().unwrap();
+    let src = tmp.path().join("src");
+    let archive = tmp.path().join("info.arx");
+    write_fixtures(&src);
+    assert_success(&arx(&[
+        "pack",
+        "--record-origin",
+        archive.to_str().unwrap(),
+        src.to_str().unwrap(),
+    ]));
+
+    let out = arx(&["info", archive.to_str().unwrap()]);
+    assert_success(&out);
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    let root = fs::canonicalize(&src).unwrap();
+    assert!(
+        stdout.contains(&format!("source root:      {}", root.display())),
+        "{stdout}"
+    );
+}
+
+#[test]
+fn test_cli_layout_json_ranges_rebuild_files() {
+    use arx_core::codec::get_decoder_u8;
+    use arx_core::read::opened::Opened;
+
+    let tmp = TempDir::new().unwrap();
+    let src = tmp.path().join("src");
+    let archive = tmp.path().join("layout.arx");
+    write_fixtures(&src);
+    let big: Vec<u8> = (0u32..150_000).flat_map(|i| i.to_le_bytes()).collect();
+    fs::write(src.join("big.bin"), &big).unwrap();
+    assert_success(&arx(&[
+        "pack",
+        archive.to_str().unwrap(),
+        src.to_str().unwrap(),
+    ]));
+
+    let out = arx(&["layout", "--json", archive.to_str().unwrap()]);
+    assert_success(&out);
+    let layout: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+    assert_eq!(layout["layout_version"], 2);
+    assert_eq!(layout["encrypted"], false);
+
+    let opened = Opened::open(&archive, None, [0u8; 32]).unwrap();
+    let raw = fs::read(&archive).unwrap();
+    let files = layout["files"].as_array().unwrap();
+    assert_eq!(files.len(), 3);
+    for f in files {
+        let path = f["path"].as_str().unwrap();
+        let mut rebuilt = Vec::new();
+        for c in f["chunks"].as_array().unwrap() {
+            let id = c["id"].as_u64().unwrap() as usize;
+            let (off, len) = (
+                c["data_off"].as_u64().unwrap(),
+                c["c_size"].as_u64().unwrap(),
+            );
+            assert_eq!(off, opened.table[id].data_off, "{path} chunk {id}");
+            assert_eq!(len, opened.table[id].c_size, "{path} chunk {id}");
+            let stored = &raw[off as usize..(off + len) as usize];
+            let codec = get_decoder_u8(c["codec"].as_u64().unwrap() as u8).unwrap();
+            codec.decompress(&mut &stored[..], &mut rebuilt).unwrap();
+        }
+        assert_eq!(rebuilt, fs::read(src.join(path)).unwrap(), "{path}");
+    }
+    assert!(
+        files
+            .iter()
+            .any(|f| f["chunks"].as_array().unwrap().len() > 1),
+        "big.bin should span several chunks"
+    );
+}
+
+#[test]
+fn test_cli_rekey_in_place_to_password() {
+    let tmp = TempDir::new().unwrap();
+    let src = tmp.path().join("src");
+    let archive = tmp.path().join("rekey.arx");
+    let dst = tmp.path().join("dst");
+    write_fixtures(&src);
+    assert_success(&arx(&[
+        "pack",
+        "--encrypt-raw",
+        &"cd".repeat(32),
+        archive.to_str().unwrap(),
+        src.to_str().unwrap(),
+    ]));
+    assert_success(&arx(&[
+        "rekey",
+        archive.to_str().unwrap(),
+        archive.to_str().unwrap(),
+        "--key",
+        &"cd".repeat(32),
+        "--new-password",
+        "rotated",
+    ]));
+
+    let old = arx(&[
+        "extract",
+        archive.to_str().unwrap(),
+        dst.to_str().unwrap(),
+        "--key",
+        &"cd".repeat(32),
+    ]);
+    assert!(!old.status.success(), "old key should no longer open it");
+    assert_success(&arx(&[
+        "extract",
+        archive.to_str().unwrap(),
+        dst.to_str().unwrap(),
+        "--password",
+        "rotated",
+    ]));
+    compare_trees(&src, &dst);
+}
+
+#[test]
+fn test_cli_chunk_cat_open_ended_from_mid_file() {
+    let tmp = TempDir::new().unwrap();
+    let src = tmp.path().join("src");
+    let archive = tmp.path().join("cat.arx");
+    write_fixtures(&src);
+    let big: Vec<u8> = (0u32..400_000).flat_map(|i| i.to_le_bytes()).collect();
+    fs::write(src.join("big.bin"), &big).unwrap();
+    assert_success(&arx(&[
+        "pack",
+        "--chunk-avg",
+        "65536",
+        archive.to_str().unwrap(),
+        src.to_str().unwrap(),
+    ]));
+
+    // Equivalent of `dd bs=1 skip=START` to EOF
+    let start = big.len() / 2 + 12_345;
+    let out = arx(&[
+        "chunk",
+        "cat",
+        archive.to_str().unwrap(),
+        "big.bin",
+        "--start",
+        &start.to_string(),
+    ]);
+    assert_success(&out);
+    assert!(out.stdout == big[start..], "cat from {start} differs");
+
+    // `get` honours --start without --len too
+    let dest = tmp.path().join("tail.bin");
+    assert_success(&arx(&[
+        "chunk",
+        "get",
+        archive.to_str().unwrap(),
+        "big.bin",
+        dest.to_str().unwrap(),
+        "--start",
+        &start.to_string(),
+    ]));
+    assert!(fs::read(&dest).unwrap() == big[start..]);
+}